@@ -0,0 +1,14 @@
+-- Your SQL goes here
+--
+-- Name: idempotency_keys; Type: TABLE; Schema: public; Owner: -
+--
+CREATE TABLE IF NOT EXISTS public.idempotency_keys (
+    key text PRIMARY KEY,
+    response json NOT NULL,
+    created_at timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    created_by text NOT NULL
+);
+--
+-- Name: idempotency_keys_created_at_index; Type: INDEX; Schema: public; Owner: -
+--
+CREATE INDEX IF NOT EXISTS idempotency_keys_created_at_index ON public.idempotency_keys USING btree (created_at);
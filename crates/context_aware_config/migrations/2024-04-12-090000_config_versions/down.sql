@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER IF EXISTS default_configs_config_version ON public.default_configs;
+DROP TRIGGER IF EXISTS contexts_config_version ON public.contexts;
+DROP FUNCTION IF EXISTS public.bump_config_version();
+DROP TABLE IF EXISTS public.config_versions;
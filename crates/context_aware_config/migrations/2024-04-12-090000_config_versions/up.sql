@@ -0,0 +1,32 @@
+-- Your SQL goes here
+-- Name: config_versions; Type: TABLE; Schema: public; Owner: -
+--
+-- A single row counting changes to contexts and default configs. Writers
+-- bump it while holding its row lock until they commit, so versions are
+-- handed out in commit order, unlike the transaction start time stamped on
+-- event_log entries.
+--
+CREATE TABLE IF NOT EXISTS public.config_versions (
+    id boolean PRIMARY KEY DEFAULT true CHECK (id),
+    version bigint NOT NULL
+);
+INSERT INTO public.config_versions (id, version) VALUES (true, 0) ON CONFLICT DO NOTHING;
+--
+-- Name: bump_config_version(); Type: FUNCTION; Schema: public; Owner: -
+--
+CREATE OR REPLACE FUNCTION public.bump_config_version() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+    UPDATE public.config_versions SET version = version + 1;
+    RETURN NULL;
+END;
+$$;
+--
+-- Name: contexts contexts_config_version; Type: TRIGGER; Schema: public; Owner: -
+--
+CREATE TRIGGER contexts_config_version AFTER INSERT OR DELETE OR UPDATE ON public.contexts FOR EACH ROW EXECUTE FUNCTION public.bump_config_version();
+--
+-- Name: default_configs default_configs_config_version; Type: TRIGGER; Schema: public; Owner: -
+--
+CREATE TRIGGER default_configs_config_version AFTER INSERT OR DELETE OR UPDATE ON public.default_configs FOR EACH ROW EXECUTE FUNCTION public.bump_config_version();
@@ -1,8 +1,16 @@
-use actix_web::{get, web::Query, HttpResponse, Scope};
+use actix_web::{
+    get,
+    web::{Data, Query},
+    HttpResponse, Scope,
+};
 use chrono::{Duration, Utc};
 use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
 use serde_json::json;
-use service_utils::{result as superposition, service::types::DbConnection};
+use service_utils::{
+    helpers::resolve_pagination,
+    result as superposition,
+    service::types::{AppState, DbConnection},
+};
 
 use crate::{api::audit_log::types::AuditQueryFilters, db::models::EventLog};
 
@@ -14,6 +22,7 @@ pub fn endpoints() -> Scope {
 
 #[get("")]
 async fn get_audit_logs(
+    state: Data<AppState>,
     filters: Query<AuditQueryFilters>,
     db_conn: DbConnection,
 ) -> superposition::Result<HttpResponse> {
@@ -42,18 +51,18 @@ async fn get_audit_logs(
     let base_query = query_builder(&filters);
     let count_query = query_builder(&filters);
 
-    let limit = filters.count.unwrap_or(10);
-    let offset = (filters.page.unwrap_or(1) - 1) * limit;
+    let pagination =
+        resolve_pagination(filters.page, filters.count, state.max_page_size)?;
     let query = base_query
         .order(event_log::timestamp.desc())
-        .limit(limit)
-        .offset(offset);
+        .limit(pagination.size)
+        .offset(pagination.offset());
 
     let log_count: i64 = count_query.count().get_result(&mut conn)?;
 
     let logs: Vec<EventLog> = query.load(&mut conn)?;
 
-    let total_pages = (log_count as f64 / limit as f64).ceil() as i64;
+    let total_pages = (log_count as f64 / pagination.size as f64).ceil() as i64;
 
     Ok(HttpResponse::Ok().json(json!({
         "total_items": log_count,
@@ -2,15 +2,24 @@ use std::collections::HashSet;
 use std::{collections::HashMap, str::FromStr};
 
 use super::helpers::{
-    filter_config_by_dimensions, filter_config_by_prefix, filter_context,
+    config_etag, etag_matches, filter_config_by_dimensions, filter_config_by_prefix,
+    filter_context, parse_long_poll_wait, LONG_POLL_VERSION_CHECK_INTERVAL,
 };
 
-use super::types::Config;
+use super::types::{Config, ResolveConfigReq, ResolvedConfig};
 use crate::db::schema::{
     contexts::dsl as ctxt, default_configs::dsl as def_conf, event_log::dsl as event_log,
 };
-use actix_http::header::{HeaderName, HeaderValue};
-use actix_web::{get, web::Query, HttpRequest, HttpResponse, Scope};
+use crate::helpers::get_config_version_id;
+use actix_http::header::{self, HeaderName, HeaderValue};
+use actix_web::{
+    get,
+    http::StatusCode,
+    post,
+    rt::time::{timeout, Instant},
+    web::{Data, Json, Query},
+    HttpRequest, HttpResponse, Scope,
+};
 use cac_client::{eval_cac, eval_cac_with_reasoning, MergeStrategy};
 use chrono::{DateTime, NaiveDateTime, TimeZone, Timelike, Utc};
 use diesel::{
@@ -18,17 +27,33 @@ use diesel::{
     r2d2::{ConnectionManager, PooledConnection},
     ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl,
 };
+use experimentation_platform::{
+    api::experiments::{
+        helpers::{assign_experiment_variants, variant_pin_of, VARIANT_DIMENSION},
+        types::VariantType,
+    },
+    db::{
+        models::{Experiment, ExperimentStatusType},
+        schema::experiments::dsl as experiments,
+    },
+};
 use serde_json::{json, Map, Value};
-use service_utils::service::types::DbConnection;
-use service_utils::{bad_argument, db_error, unexpected_error};
+use service_utils::db::pgschema_manager::PgSchemaConnection;
+use service_utils::metrics::{RESOLVE_CACHE_HITS, RESOLVE_CACHE_MISSES};
+use service_utils::resolve_cache::ResolveCacheKey;
+use service_utils::service::types::{
+    execution_namespace, AppExecutionNamespace, AppScope, AppState, DbConnection, Tenant,
+};
+use service_utils::{bad_argument, db_error, response_error, unexpected_error};
+use std::time::Duration;
 
 use service_utils::result as superposition;
-use uuid::Uuid;
 
 pub fn endpoints() -> Scope {
     Scope::new("")
         .service(get)
         .service(get_resolved_config)
+        .service(resolve_config_for_context)
         .service(get_filtered_config)
 }
 
@@ -37,20 +62,12 @@ pub fn add_audit_header(
     mut res: HttpResponse,
 ) -> superposition::Result<HttpResponse> {
     let header_name = HeaderName::from_static("x-audit-id");
-    if let Ok(uuid) = event_log::event_log
-        .select(event_log::id)
-        .filter(event_log::table_name.eq("contexts"))
-        .order_by(event_log::timestamp.desc())
-        .first::<Uuid>(conn)
-    {
-        let uuid_string = uuid.to_string();
-        if let Ok(header_value) = HeaderValue::from_str(&uuid_string) {
+    if let Some(version_id) = get_config_version_id(conn) {
+        if let Ok(header_value) = HeaderValue::from_str(&version_id) {
             res.headers_mut().insert(header_name, header_value);
         } else {
             log::error!("Failed to convert UUID to string");
         }
-    } else {
-        log::error!("Failed to fetch contexts from event_log");
     }
     Ok(res)
 }
@@ -81,7 +98,44 @@ fn get_max_created_at(
         .and_then(|res| res.ok_or(diesel::result::Error::NotFound))
 }
 
-fn is_not_modified(max_created_at: Option<NaiveDateTime>, req: &HttpRequest) -> bool {
+fn get_config_etag(
+    conn: &mut PooledConnection<ConnectionManager<PgConnection>>,
+    req: &HttpRequest,
+) -> Option<String> {
+    let merge_strategy = req
+        .headers()
+        .get("x-merge-strategy")
+        .and_then(|header_value| header_value.to_str().ok())
+        .unwrap_or_default();
+    get_config_version_id(conn).map(|version| {
+        config_etag(&version, req.path(), req.query_string(), merge_strategy)
+    })
+}
+
+fn add_etag_header(etag: Option<String>, mut res: HttpResponse) -> HttpResponse {
+    if let Some(header_value) = etag.and_then(|etag| HeaderValue::from_str(&etag).ok()) {
+        res.headers_mut().insert(header::ETAG, header_value);
+    }
+    res
+}
+
+fn not_modified_response(etag: Option<String>) -> HttpResponse {
+    add_etag_header(etag, HttpResponse::NotModified().finish())
+}
+
+// If-None-Match takes precedence over If-Modified-Since when both are sent
+fn is_not_modified(
+    max_created_at: Option<NaiveDateTime>,
+    etag: Option<&str>,
+    req: &HttpRequest,
+) -> bool {
+    if let Some(if_none_match) = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|header_val| header_val.to_str().ok())
+    {
+        return etag.map_or(false, |etag| etag_matches(if_none_match, etag));
+    }
     let nanosecond_erasure = |t: NaiveDateTime| t.with_nanosecond(0);
     let last_modified = req
         .headers()
@@ -147,33 +201,99 @@ async fn generate_cac(
     })
 }
 
+// Waits for the config to move past `since_version`, returning a connection
+// to serve it with, or `None` once `wait` elapses without a change. Changes
+// notified on this server wake it, and the version is re-read every
+// `LONG_POLL_VERSION_CHECK_INTERVAL` and once more at the deadline for the
+// ones made on other servers. No pooled connection is held while waiting.
+async fn wait_for_config_change(
+    req: &HttpRequest,
+    state: &Data<AppState>,
+    conn: PgSchemaConnection,
+    since_version: &str,
+    wait: Duration,
+) -> superposition::Result<Option<PgSchemaConnection>> {
+    let namespace = AppExecutionNamespace::from_request_sync(req).map_err(|_| {
+        unexpected_error!("Something went wrong, failed to wait for config")
+    })?;
+    let subscription = state
+        .config_change_notifier
+        .subscribe(namespace.as_str())
+        .ok_or_else(|| {
+            response_error!(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "too many requests are waiting for config changes, retry after some time"
+            )
+        })?;
+
+    let deadline = Instant::now() + wait;
+    let mut idle_conn = Some(conn);
+    loop {
+        // subscribed before checking, so a change right after the check wakes it
+        let change = subscription.next_change();
+        let mut conn = match idle_conn.take() {
+            Some(conn) => conn,
+            None => state
+                .db_pool
+                .get_conn(namespace.as_str().to_string())
+                .map_err(|err| {
+                    log::error!("failed to get a db connection after waiting: {err}");
+                    unexpected_error!("Something went wrong, failed to fetch config")
+                })?,
+        };
+        if get_config_version_id(&mut conn).as_deref() != Some(since_version) {
+            return Ok(Some(conn));
+        }
+        drop(conn);
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(None);
+        }
+        // re-checked above whether it was a change, the interval or the deadline
+        let _ = timeout(remaining.min(LONG_POLL_VERSION_CHECK_INTERVAL), change).await;
+    }
+}
+
 #[get("")]
 async fn get(
     req: HttpRequest,
+    state: Data<AppState>,
     db_conn: DbConnection,
 ) -> superposition::Result<HttpResponse> {
     let DbConnection(mut conn) = db_conn;
 
+    let mut params = Query::<HashMap<String, String>>::from_query(req.query_string())
+        .map_err(|err| {
+            log::error!("Failed to parse query params with err: {}", err);
+            bad_argument!("Unable to retrieve query parameters.")
+        })?
+        .into_inner();
+
+    if let Some(since_version) = params.remove("since_version") {
+        let wait = parse_long_poll_wait(params.remove("wait").as_deref())?;
+        conn = match wait_for_config_change(&req, &state, conn, &since_version, wait)
+            .await?
+        {
+            Some(conn) => conn,
+            None => return Ok(HttpResponse::NotModified().finish()),
+        };
+    }
+
     let max_created_at = get_max_created_at(&mut conn)
         .map_err(|e| log::error!("failed to fetch max timestamp from event_log: {e}"))
         .ok();
 
     log::info!("Max created at: {max_created_at:?}");
 
-    let is_not_modified = is_not_modified(max_created_at, &req);
-
-    if is_not_modified {
-        return Ok(HttpResponse::NotModified().finish());
+    let etag = get_config_etag(&mut conn, &req);
+    if is_not_modified(max_created_at, etag.as_deref(), &req) {
+        return Ok(not_modified_response(etag));
     }
 
-    let params = Query::<HashMap<String, String>>::from_query(req.query_string())
-        .map_err(|err| {
-            log::error!("Failed to parse query params with err: {}", err);
-            bad_argument!("Unable to retrieve query parameters.")
-        })?;
     let mut query_params_map: serde_json::Map<String, Value> = Map::new();
 
-    for (key, value) in params.0.into_iter() {
+    for (key, value) in params.into_iter() {
         query_params_map.insert(
             key,
             value
@@ -201,7 +321,14 @@ async fn get(
         config = filter_config_by_dimensions(&config, &query_params_map)?
     }
 
-    let resp = HttpResponse::Ok().json(config);
+    let mut resp = add_etag_header(etag, HttpResponse::Ok().json(config));
+    // long-polling clients send this back as since_version
+    if let Some(version) = get_config_version_id(&mut conn)
+        .and_then(|version| HeaderValue::from_str(&version).ok())
+    {
+        resp.headers_mut()
+            .insert(HeaderName::from_static("x-config-version"), version);
+    }
     let audit_resp = add_audit_header(&mut conn, resp)?;
 
     add_last_modified_header(max_created_at, audit_resp)
@@ -210,6 +337,7 @@ async fn get(
 #[get("/resolve")]
 async fn get_resolved_config(
     req: HttpRequest,
+    state: Data<AppState>,
     db_conn: DbConnection,
 ) -> superposition::Result<HttpResponse> {
     let DbConnection(mut conn) = db_conn;
@@ -234,15 +362,157 @@ async fn get_resolved_config(
         .map_err(|e| log::error!("failed to fetch max timestamp from event_log : {e}"))
         .ok();
 
-    let is_not_modified = is_not_modified(max_created_at, &req);
+    let etag = get_config_etag(&mut conn, &req);
+    if is_not_modified(max_created_at, etag.as_deref(), &req) {
+        return Ok(not_modified_response(etag));
+    }
 
-    if is_not_modified {
-        return Ok(HttpResponse::NotModified().finish());
+    let show_reasoning = matches!(
+        query_params_map.get("show_reasoning"),
+        Some(Value::String(_))
+    );
+    let resolved =
+        resolve_cached(&req, &state, &mut conn, &query_params_map, show_reasoning)
+            .await?;
+    let response = HttpResponse::Ok().json(resolved);
+    let audit_resp = add_audit_header(&mut conn, add_etag_header(etag, response))?;
+    add_last_modified_header(max_created_at, audit_resp)
+}
+
+#[post("/resolve")]
+async fn resolve_config_for_context(
+    req: HttpRequest,
+    state: Data<AppState>,
+    body: Json<ResolveConfigReq>,
+    tenant: Tenant,
+    db_conn: DbConnection,
+) -> superposition::Result<HttpResponse> {
+    let DbConnection(mut conn) = db_conn;
+    let ResolveConfigReq {
+        mut context,
+        show_reasoning,
+        toss,
+        toss_key,
+    } = body.into_inner();
+    if context.contains_key(VARIANT_DIMENSION) {
+        return Err(bad_argument!(
+            "{} is assigned from the running experiments, remove it from the context",
+            VARIANT_DIMENSION
+        ));
     }
+    let pin = variant_pin_of(&req)?;
+
+    let running_experiments = {
+        // experiments of a tenant live in its experimentation schema
+        let namespace = execution_namespace(
+            state.enable_tenant_and_scope,
+            Some(&tenant),
+            Some(AppScope::EXPERIMENTATION),
+        )?;
+        let mut exp_conn = state.db_pool.get_conn(namespace).map_err(|err| {
+            log::error!("failed to get a db connection for experiments: {err}");
+            unexpected_error!("Something went wrong, failed to resolve config")
+        })?;
+        experiments::experiments
+            .filter(experiments::status.eq(ExperimentStatusType::INPROGRESS))
+            .order(experiments::created_at.asc())
+            .load::<Experiment>(&mut exp_conn)?
+    };
+    let served_variants = assign_experiment_variants(
+        &running_experiments,
+        &context,
+        toss,
+        &toss_key,
+        pin.as_ref(),
+    )?;
+    // control variants are served the base config, only experimental ones
+    // select their variant contexts
+    let experimental_variant_ids = served_variants
+        .iter()
+        .filter(|served| served.variant_type == VariantType::EXPERIMENTAL)
+        .map(|served| json!(served.variant_id))
+        .collect::<Vec<Value>>();
+    if !experimental_variant_ids.is_empty() {
+        context.insert(
+            VARIANT_DIMENSION.to_string(),
+            Value::Array(experimental_variant_ids),
+        );
+    }
+
+    let max_created_at = get_max_created_at(&mut conn)
+        .map_err(|e| log::error!("failed to fetch max timestamp from event_log : {e}"))
+        .ok();
+    let resolved =
+        resolve_cached(&req, &state, &mut conn, &context, show_reasoning).await?;
+
+    let response = HttpResponse::Ok().json(ResolvedConfig {
+        config: resolved,
+        experiments: served_variants,
+        toss_key,
+    });
+    let audit_resp = add_audit_header(&mut conn, response)?;
+    add_last_modified_header(max_created_at, audit_resp)
+}
+
+fn merge_strategy_of(req: &HttpRequest) -> MergeStrategy {
+    req.headers()
+        .get("x-merge-strategy")
+        .and_then(|header_value: &HeaderValue| header_value.to_str().ok())
+        .and_then(|val| MergeStrategy::from_str(val).ok())
+        .unwrap_or_default()
+}
 
-    let res = generate_cac(&mut conn).await?;
+// resolves `context` against the current config version, the config is only
+// loaded and evaluated when the resolve cache has no result for it
+async fn resolve_cached(
+    req: &HttpRequest,
+    state: &Data<AppState>,
+    conn: &mut PooledConnection<ConnectionManager<PgConnection>>,
+    context: &Map<String, Value>,
+    show_reasoning: bool,
+) -> superposition::Result<Map<String, Value>> {
+    let merge_strategy = merge_strategy_of(req);
+    let cache_key = AppExecutionNamespace::from_request_sync(req)
+        .ok()
+        .zip(get_config_version_id(conn))
+        .map(|(namespace, version)| {
+            let merge = match merge_strategy {
+                MergeStrategy::MERGE => "merge",
+                MergeStrategy::REPLACE => "replace",
+            };
+            ResolveCacheKey::new(
+                namespace.as_str(),
+                &version,
+                &format!("{merge}|{show_reasoning}"),
+                context,
+            )
+        });
+    let Some(cache_key) = cache_key else {
+        let config = generate_cac(conn).await?;
+        return resolve_config(config, context, merge_strategy, show_reasoning);
+    };
 
-    let cac_client_contexts = res
+    let labels = [("namespace", cache_key.namespace.as_str())];
+    if let Some(resolved) = state.resolve_cache.get(&cache_key) {
+        state.metrics.inc_counter(&RESOLVE_CACHE_HITS, &labels);
+        return Ok(resolved);
+    }
+    state.metrics.inc_counter(&RESOLVE_CACHE_MISSES, &labels);
+    let config = generate_cac(conn).await?;
+    let resolved = resolve_config(config, context, merge_strategy, show_reasoning)?;
+    state.resolve_cache.insert(cache_key, resolved.clone());
+    Ok(resolved)
+}
+
+// applies the overrides of the contexts matching `context` over the default
+// configs, in the priority order the contexts were loaded in
+fn resolve_config(
+    config: Config,
+    context: &Map<String, Value>,
+    merge_strategy: MergeStrategy,
+    show_reasoning: bool,
+) -> superposition::Result<Map<String, Value>> {
+    let cac_client_contexts = config
         .contexts
         .into_iter()
         .map(|val| cac_client::Context {
@@ -250,46 +520,22 @@ async fn get_resolved_config(
             override_with_keys: val.override_with_keys,
         })
         .collect();
-
-    let merge_strategy = req
-        .headers()
-        .get("x-merge-strategy")
-        .and_then(|header_value: &HeaderValue| header_value.to_str().ok())
-        .and_then(|val| MergeStrategy::from_str(val).ok())
-        .unwrap_or(MergeStrategy::default());
-
-    let response = if let Some(Value::String(_)) = query_params_map.get("show_reasoning")
-    {
-        HttpResponse::Ok().json(
-            eval_cac_with_reasoning(
-                res.default_configs,
-                &cac_client_contexts,
-                &res.overrides,
-                &query_params_map,
-                merge_strategy,
-            )
-            .map_err(|err| {
-                log::error!("failed to eval cac with err: {}", err);
-                unexpected_error!("cac eval failed")
-            })?,
-        )
+    let eval = if show_reasoning {
+        eval_cac_with_reasoning
     } else {
-        HttpResponse::Ok().json(
-            eval_cac(
-                res.default_configs,
-                &cac_client_contexts,
-                &res.overrides,
-                &query_params_map,
-                merge_strategy,
-            )
-            .map_err(|err| {
-                log::error!("failed to eval cac with err: {}", err);
-                unexpected_error!("cac eval failed")
-            })?,
-        )
+        eval_cac
     };
-    let audit_resp = add_audit_header(&mut conn, response)?;
-    add_last_modified_header(max_created_at, audit_resp)
+    eval(
+        config.default_configs,
+        &cac_client_contexts,
+        &config.overrides,
+        context,
+        merge_strategy,
+    )
+    .map_err(|err| {
+        log::error!("failed to eval cac with err: {}", err);
+        unexpected_error!("cac eval failed")
+    })
 }
 
 #[get("/filter")]
@@ -313,6 +559,12 @@ async fn get_filtered_config(
                 .map_or_else(|_| json!(value), |int_val| json!(int_val)),
         );
     }
+
+    let etag = get_config_etag(&mut conn, &req);
+    if is_not_modified(None, etag.as_deref(), &req) {
+        return Ok(not_modified_response(etag));
+    }
+
     let config = generate_cac(&mut conn).await?;
     let contexts = config.contexts;
 
@@ -339,5 +591,81 @@ async fn get_filtered_config(
         default_configs: config.default_configs,
     };
 
-    add_audit_header(&mut conn, HttpResponse::Ok().json(filtered_config))
+    add_audit_header(
+        &mut conn,
+        add_etag_header(etag, HttpResponse::Ok().json(filtered_config)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::config::types::Context;
+
+    fn test_config() -> Config {
+        Config {
+            contexts: vec![
+                Context {
+                    id: "ctx-ios".to_string(),
+                    condition: json!({ "==": [{ "var": "os" }, "ios"] }),
+                    override_with_keys: ["override-ios".to_string()],
+                },
+                Context {
+                    id: "ctx-bangalore".to_string(),
+                    condition: json!({ "==": [{ "var": "city" }, "Bangalore"] }),
+                    override_with_keys: ["override-bangalore".to_string()],
+                },
+            ],
+            overrides: Map::from_iter([
+                (
+                    "override-ios".to_string(),
+                    json!({ "theme": { "color": "red" } }),
+                ),
+                ("override-bangalore".to_string(), json!({ "door_count": 2 })),
+            ]),
+            default_configs: Map::from_iter([
+                (
+                    "theme".to_string(),
+                    json!({ "color": "blue", "font": "sans" }),
+                ),
+                ("door_count".to_string(), json!(4)),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_resolve_config_merge_strategies() {
+        let context = Map::from_iter([("os".to_string(), json!("ios"))]);
+
+        let merged =
+            resolve_config(test_config(), &context, MergeStrategy::MERGE, false).unwrap();
+        assert_eq!(
+            Value::Object(merged),
+            json!({ "theme": { "color": "red", "font": "sans" }, "door_count": 4 })
+        );
+
+        let replaced =
+            resolve_config(test_config(), &context, MergeStrategy::REPLACE, false)
+                .unwrap();
+        assert_eq!(
+            Value::Object(replaced),
+            json!({ "theme": { "color": "red" }, "door_count": 4 })
+        );
+    }
+
+    #[test]
+    fn test_resolve_config_show_reasoning() {
+        let context = Map::from_iter([("os".to_string(), json!("ios"))]);
+
+        let resolved =
+            resolve_config(test_config(), &context, MergeStrategy::MERGE, true).unwrap();
+        assert_eq!(
+            resolved.get("metadata"),
+            Some(&json!([{
+                "context": { "==": [{ "var": "os" }, "ios"] },
+                "override": ["override-ios"]
+            }]))
+        );
+        assert_eq!(resolved.get("door_count"), Some(&json!(4)));
+    }
 }
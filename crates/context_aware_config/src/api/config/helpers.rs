@@ -1,10 +1,11 @@
 use std::collections::HashSet;
+use std::time::Duration;
 
 use super::types::{Config, Context};
 
 use serde_json::{Map, Value};
 use service_utils::{
-    helpers::extract_dimensions, result as superposition, unexpected_error,
+    bad_argument, helpers::extract_dimensions, result as superposition, unexpected_error,
 };
 
 pub fn filter_context(
@@ -108,3 +109,101 @@ pub fn filter_config_by_dimensions(
 
     Ok(filtered_config)
 }
+
+pub const DEFAULT_LONG_POLL_WAIT: Duration = Duration::from_secs(30);
+pub const MAX_LONG_POLL_WAIT: Duration = Duration::from_secs(60);
+/// How often a long-poll request re-reads the config version. Changes made
+/// through this server wake it right away, this catches the ones made
+/// through other servers.
+pub const LONG_POLL_VERSION_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Parses the `wait` of a long-poll request, in seconds (`30`, `30s`) or in
+/// milliseconds (`500ms`), capped at [`MAX_LONG_POLL_WAIT`].
+pub fn parse_long_poll_wait(wait: Option<&str>) -> superposition::Result<Duration> {
+    let Some(wait) = wait else {
+        return Ok(DEFAULT_LONG_POLL_WAIT);
+    };
+    let parsed = match wait.trim() {
+        millis if millis.ends_with("ms") => millis
+            .trim_end_matches("ms")
+            .parse::<u64>()
+            .map(Duration::from_millis),
+        secs => secs
+            .trim_end_matches('s')
+            .parse::<u64>()
+            .map(Duration::from_secs),
+    };
+    parsed
+        .map(|wait| wait.min(MAX_LONG_POLL_WAIT))
+        .map_err(|_| bad_argument!("wait should look like 30s or 500ms, got {}", wait))
+}
+
+/// Strong ETag of a config response: the config version along with everything
+/// else of the request that shapes the response.
+pub fn config_etag(
+    version: &str,
+    path: &str,
+    query: &str,
+    merge_strategy: &str,
+) -> String {
+    let key = [version, path, query, merge_strategy].join("\n");
+    format!("\"{}\"", blake3::hash(key.as_bytes()))
+}
+
+/// Whether an `If-None-Match` header value names `etag`, weak ETags match on
+/// their opaque part as GET requests use weak comparison.
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_long_poll_wait() {
+        assert_eq!(parse_long_poll_wait(None).unwrap(), DEFAULT_LONG_POLL_WAIT);
+        assert_eq!(
+            parse_long_poll_wait(Some("30s")).unwrap(),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            parse_long_poll_wait(Some("15")).unwrap(),
+            Duration::from_secs(15)
+        );
+        assert_eq!(
+            parse_long_poll_wait(Some("500ms")).unwrap(),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            parse_long_poll_wait(Some("3600s")).unwrap(),
+            MAX_LONG_POLL_WAIT
+        );
+        assert!(parse_long_poll_wait(Some("soon")).is_err());
+        assert!(parse_long_poll_wait(Some("-1s")).is_err());
+    }
+
+    #[test]
+    fn test_config_etag() {
+        let etag = config_etag("version-1", "/config", "os=ios", "MERGE");
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        assert_eq!(etag, config_etag("version-1", "/config", "os=ios", "MERGE"));
+        assert_ne!(etag, config_etag("version-2", "/config", "os=ios", "MERGE"));
+        assert_ne!(
+            etag,
+            config_etag("version-1", "/config", "os=android", "MERGE")
+        );
+        assert_ne!(
+            etag,
+            config_etag("version-1", "/config", "os=ios", "REPLACE")
+        );
+
+        assert!(etag_matches(&etag, &etag));
+        assert!(etag_matches(&format!("\"stale\", W/{etag}"), &etag));
+        assert!(etag_matches("*", &etag));
+        assert!(!etag_matches("\"stale\"", &etag));
+    }
+}
@@ -1,4 +1,4 @@
 mod handlers;
 mod types;
-pub use handlers::endpoints;
+pub use handlers::{add_audit_header, endpoints};
 mod helpers;
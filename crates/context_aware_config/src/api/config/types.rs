@@ -1,4 +1,7 @@
-use serde::Serialize;
+use experimentation_platform::api::experiments::types::{
+    default_toss_key, ServedVariant,
+};
+use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
 #[derive(Serialize)]
@@ -14,3 +17,22 @@ pub struct Context {
     pub condition: Value,
     pub override_with_keys: [String; 1],
 }
+
+#[derive(Deserialize)]
+pub struct ResolveConfigReq {
+    pub context: Map<String, Value>,
+    #[serde(default)]
+    pub show_reasoning: bool,
+    // buckets the context into the variants of running experiments, see
+    // `POST /experiments/{id}/resolve`
+    pub toss: Option<i8>,
+    #[serde(default = "default_toss_key")]
+    pub toss_key: String,
+}
+
+#[derive(Serialize)]
+pub struct ResolvedConfig {
+    pub config: Map<String, Value>,
+    pub experiments: Vec<ServedVariant>,
+    pub toss_key: String,
+}
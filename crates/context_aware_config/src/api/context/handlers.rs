@@ -1,43 +1,56 @@
 extern crate base64;
 use std::str;
 
-use crate::helpers::{json_to_sorted_string, validate_context_jsonschema};
+use crate::helpers::{
+    get_config_version_id, json_to_sorted_string, validate_context_jsonschema,
+};
 use crate::{
     api::{
         context::types::{
-            ContextAction, ContextBulkResponse, DimensionCondition, MoveReq,
-            PaginationParams, PutReq, PutResp,
+            BulkOperationParams, ContextAction, ContextBulkResponse, ContextFilters,
+            DimensionCondition, DryRunBulkResponse, MoveReq, PaginatedContexts,
+            PaginationParams, PutReq, PutResp, ValidateContextReq, ValidateContextResp,
         },
         dimension::get_all_dimension_schema_map,
     },
     db::{
-        models::Context,
+        models::{Context, IdempotencyKey},
         schema::{
             contexts::{self, id},
             default_configs::dsl,
+            idempotency_keys,
         },
     },
 };
 use actix_web::{
-    delete, get, put,
-    web::{Json, Path, Query},
-    HttpResponse, Responder, Scope,
+    delete, get,
+    http::header::{HeaderName, HeaderValue},
+    post, put,
+    web::{Data, Json, Path, Query},
+    HttpRequest, HttpResponse, Responder, Scope,
 };
-use chrono::Utc;
+use chrono::{Duration, Utc};
 use diesel::{
     delete,
     r2d2::{ConnectionManager, PooledConnection},
-    result::{DatabaseErrorKind::*, Error::DatabaseError},
-    Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl,
+    result::{
+        DatabaseErrorKind::*,
+        Error::{DatabaseError, RollbackTransaction},
+    },
+    Connection, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl,
+    RunQueryDsl,
 };
 use jsonschema::{Draft, JSONSchema, ValidationError};
 use serde_json::{from_value, json, Map, Value};
-use service_utils::service::types::DbConnection;
+use service_utils::helpers::{extract_dimensions, resolve_pagination};
+use service_utils::middlewares::config_change::config_changed;
+use service_utils::service::types::{AppState, DbConnection};
 use service_utils::{db_error, not_found, unexpected_error, validation_error};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use superposition_types::{SuperpositionUser, User};
 
 use super::helpers::{
+    canonicalize_condition, context_has_dimension, find_missing_context_ids,
     validate_condition_with_functions, validate_override_with_functions,
 };
 
@@ -46,16 +59,20 @@ use service_utils::{bad_argument, result as superposition};
 pub fn endpoints() -> Scope {
     Scope::new("")
         .service(put_handler)
+        .service(validate_handler)
         .service(move_handler)
         .service(delete_context)
         .service(bulk_operations)
         .service(list_contexts)
+        .service(list_contexts_paginated)
         .service(get_context)
 }
 
 type DBConnection = PooledConnection<ConnectionManager<PgConnection>>;
 
-fn validate_dimensions_and_calculate_priority(
+/// Priority of a context condition, the sum of the priorities of every dimension
+/// it references, checking the values compared against them on the way.
+pub fn validate_dimensions_and_calculate_priority(
     object_key: &str,
     cond: &Value,
     dimension_schema_map: &HashMap<String, (JSONSchema, i32)>,
@@ -178,24 +195,12 @@ fn create_ctx_from_put_req(
     conn: &mut DBConnection,
     user: &User,
 ) -> superposition::Result<Context> {
-    let ctx_condition = Value::Object(req.context.to_owned());
     let ctx_override: Value = req.r#override.to_owned().into();
     validate_override_with_default_configs(conn, &req.r#override)?;
-    validate_condition_with_functions(conn, &ctx_condition)?;
+    let (ctx_condition, priority) =
+        validate_condition(conn, Value::Object(req.context.to_owned()))?;
     validate_override_with_functions(conn, &req.r#override)?;
 
-    let dimension_schema_map = get_all_dimension_schema_map(conn)?;
-
-    let priority = validate_dimensions_and_calculate_priority(
-        "context",
-        &ctx_condition,
-        &dimension_schema_map,
-    )?;
-
-    if priority == 0 {
-        return Err(bad_argument!("No dimension found in context"));
-    }
-
     let context_id = hash(&ctx_condition);
     let override_id = hash(&ctx_override);
     Ok(Context {
@@ -209,6 +214,28 @@ fn create_ctx_from_put_req(
     })
 }
 
+// canonical form and priority of the condition of a context being created
+fn validate_condition(
+    conn: &mut DBConnection,
+    condition: Value,
+) -> superposition::Result<(Value, i32)> {
+    let ctx_condition = canonicalize_condition(&condition);
+    validate_condition_with_functions(conn, &ctx_condition)?;
+
+    let dimension_schema_map = get_all_dimension_schema_map(conn)?;
+
+    let priority = validate_dimensions_and_calculate_priority(
+        "context",
+        &ctx_condition,
+        &dimension_schema_map,
+    )?;
+
+    if priority == 0 {
+        return Err(bad_argument!("No dimension found in context"));
+    }
+    Ok((ctx_condition, priority))
+}
+
 fn hash(val: &Value) -> String {
     let sorted_str: String = json_to_sorted_string(val);
     blake3::hash(sorted_str.as_bytes()).to_string()
@@ -242,6 +269,7 @@ fn get_put_resp(ctx: Context) -> PutResp {
         context_id: ctx.id,
         override_id: ctx.override_id,
         priority: ctx.priority,
+        version_id: None,
     }
 }
 
@@ -279,25 +307,49 @@ async fn put_handler(
     req: Json<PutReq>,
     mut db_conn: DbConnection,
     user: User,
-) -> superposition::Result<Json<PutResp>> {
+) -> superposition::Result<HttpResponse> {
     put(req, &mut db_conn, false, &user)
-        .map(|resp| Json(resp))
+        .map(|resp| {
+            config_changed(HttpResponse::Ok().json(PutResp {
+                version_id: get_config_version_id(&mut db_conn),
+                ..resp
+            }))
+        })
         .map_err(|err: superposition::AppError| {
             log::info!("context put failed with error: {:?}", err);
             err
         })
 }
 
-fn r#move(
-    old_ctx_id: String,
-    req: Json<MoveReq>,
-    conn: &mut PooledConnection<ConnectionManager<PgConnection>>,
-    already_under_txn: bool,
-    user: &User,
-) -> superposition::Result<PutResp> {
-    use contexts::dsl;
-    let req = req.into_inner();
-    let ctx_condition = Value::Object(req.context);
+// checks a context the way creating it would, without storing anything
+fn validate_context(
+    conn: &mut DBConnection,
+    context: Map<String, Value>,
+) -> superposition::Result<ValidateContextResp> {
+    let condition = Value::Object(context);
+    // parsed before canonicalizing, so that errors point at the node as sent
+    let dimensions = extract_dimensions(&condition)?;
+    let (context, priority) = validate_condition(conn, condition)?;
+    Ok(ValidateContextResp {
+        context,
+        dimensions,
+        priority,
+    })
+}
+
+#[post("/validate")]
+async fn validate_handler(
+    req: Json<ValidateContextReq>,
+    mut db_conn: DbConnection,
+) -> superposition::Result<Json<ValidateContextResp>> {
+    validate_context(&mut db_conn, req.into_inner().context).map(Json)
+}
+
+fn validate_move_req(
+    req: MoveReq,
+    conn: &mut DBConnection,
+) -> superposition::Result<(String, Value, i32)> {
+    let ctx_condition = canonicalize_condition(&Value::Object(req.context));
     let new_ctx_id = hash(&ctx_condition);
     let dimension_schema_map = get_all_dimension_schema_map(conn)?;
     let priority = validate_dimensions_and_calculate_priority(
@@ -309,6 +361,19 @@ fn r#move(
     if priority == 0 {
         return Err(bad_argument!("no dimension found in context"));
     }
+    Ok((new_ctx_id, ctx_condition, priority))
+}
+
+fn r#move(
+    old_ctx_id: String,
+    req: Json<MoveReq>,
+    conn: &mut PooledConnection<ConnectionManager<PgConnection>>,
+    already_under_txn: bool,
+    user: &User,
+) -> superposition::Result<PutResp> {
+    use contexts::dsl;
+    let (new_ctx_id, ctx_condition, priority) =
+        validate_move_req(req.into_inner(), conn)?;
 
     if already_under_txn {
         diesel::sql_query("SAVEPOINT update_ctx_savepoint").execute(conn)?;
@@ -374,9 +439,14 @@ async fn move_handler(
     req: Json<MoveReq>,
     mut db_conn: DbConnection,
     user: User,
-) -> superposition::Result<Json<PutResp>> {
+) -> superposition::Result<HttpResponse> {
     r#move(path.into_inner(), req, &mut db_conn, false, &user)
-        .map(|resp| Json(resp))
+        .map(|resp| {
+            config_changed(HttpResponse::Ok().json(PutResp {
+                version_id: get_config_version_id(&mut db_conn),
+                ..resp
+            }))
+        })
         .map_err(|err| {
             log::info!("move api failed with error: {:?}", err);
             err
@@ -400,33 +470,83 @@ async fn get_context(
     Ok(Json(ctx))
 }
 
+#[get("")]
+async fn list_contexts_paginated(
+    state: Data<AppState>,
+    qparams: Query<PaginationParams>,
+    filters: Query<ContextFilters>,
+    db_conn: DbConnection,
+) -> superposition::Result<Json<PaginatedContexts>> {
+    let DbConnection(mut conn) = db_conn;
+
+    let pagination = resolve_pagination(
+        qparams.page.map(i64::from),
+        qparams.size.map(i64::from),
+        state.max_page_size,
+    )?;
+
+    let (total_items, data) = match &filters.dimension {
+        // conditions are matched on their parsed dimensions, which SQL cannot do
+        Some(dimension) => {
+            let matching_contexts = contexts::table
+                .order(contexts::created_at)
+                .load::<Context>(&mut conn)?
+                .into_iter()
+                .filter(|context| {
+                    context_has_dimension(
+                        &context.value,
+                        dimension,
+                        filters.value.as_deref(),
+                    )
+                })
+                .collect::<Vec<Context>>();
+            let total_items = matching_contexts.len() as i64;
+            let data = matching_contexts
+                .into_iter()
+                .skip(pagination.offset() as usize)
+                .take(pagination.size as usize)
+                .collect();
+            (total_items, data)
+        }
+        None => {
+            let total_items = contexts::table.count().get_result(&mut conn)?;
+            let data = contexts::table
+                .order(contexts::created_at)
+                .limit(pagination.size)
+                .offset(pagination.offset())
+                .load::<Context>(&mut conn)?;
+            (total_items, data)
+        }
+    };
+
+    Ok(Json(PaginatedContexts {
+        total_items,
+        total_pages: (total_items + pagination.size - 1) / pagination.size,
+        page: pagination.page,
+        count: data.len() as i64,
+        data,
+    }))
+}
+
 #[get("/list")]
 async fn list_contexts(
+    state: Data<AppState>,
     qparams: Query<PaginationParams>,
     db_conn: DbConnection,
 ) -> superposition::Result<impl Responder> {
     use crate::db::schema::contexts::dsl::*;
     let DbConnection(mut conn) = db_conn;
 
-    let PaginationParams {
-        page: opt_page,
-        size: opt_size,
-    } = qparams.into_inner();
-    let default_page = 1;
-    let page = opt_page.unwrap_or(default_page);
-    let default_size = 20;
-    let size = opt_size.unwrap_or(default_size);
-
-    if page < 1 {
-        return Err(bad_argument!("Param 'page' has to be at least 1."));
-    } else if size < 1 {
-        return Err(bad_argument!("Param 'size' has to be at least 1."));
-    }
+    let pagination = resolve_pagination(
+        qparams.page.map(i64::from),
+        qparams.size.map(i64::from),
+        state.max_page_size,
+    )?;
 
     let result: Vec<Context> = contexts
         .order(created_at)
-        .limit(i64::from(size))
-        .offset(i64::from(size * (page - 1)))
+        .limit(pagination.size)
+        .offset(pagination.offset())
         .load(&mut conn)?;
 
     Ok(Json(result))
@@ -448,7 +568,7 @@ async fn delete_context(
         Ok(0) => Err(not_found!("Context Id `{}` doesn't exists", ctx_id)),
         Ok(_) => {
             log::info!("{ctx_id} context deleted by {}", user.get_email());
-            Ok(HttpResponse::NoContent().finish())
+            Ok(config_changed(HttpResponse::NoContent().finish()))
         }
         Err(e) => {
             log::error!("context delete query failed with error: {e}");
@@ -457,69 +577,367 @@ async fn delete_context(
     }
 }
 
+// replays of a bulk operation with the same Idempotency-Key within this window
+// get the original response instead of being applied again
+const IDEMPOTENCY_KEY_TTL_HOURS: i64 = 24;
+
+fn fetch_processed_bulk_operation(
+    key: &str,
+    conn: &mut PgConnection,
+) -> superposition::Result<Option<Value>> {
+    use idempotency_keys::dsl;
+    let expiry = Utc::now() - Duration::hours(IDEMPOTENCY_KEY_TTL_HOURS);
+
+    diesel::delete(dsl::idempotency_keys.filter(dsl::created_at.lt(expiry)))
+        .execute(conn)?;
+    let response = dsl::idempotency_keys
+        .filter(dsl::key.eq(key))
+        .select(dsl::response)
+        .first::<Value>(conn)
+        .optional()?;
+    Ok(response)
+}
+
+fn replayed_bulk_response(response: Value) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("x-idempotent-replay", "true"))
+        .json(response)
+}
+
+// applies a batch of bulk operations on `conn`, which the caller runs in a
+// transaction so that a failed operation leaves none of the batch behind
+fn apply_bulk_operations(
+    reqs: Vec<ContextAction>,
+    conn: &mut DBConnection,
+    user: &User,
+) -> superposition::Result<Vec<ContextBulkResponse>> {
+    use contexts::dsl::contexts;
+    let mut response = Vec::<ContextBulkResponse>::new();
+    for action in reqs.into_iter() {
+        match action {
+            ContextAction::PUT(put_req) => {
+                let put_resp = put(Json(put_req), conn, true, user).map_err(|err| {
+                    log::error!("Failed at insert into contexts due to {:?}", err);
+                    err
+                })?;
+                response.push(ContextBulkResponse::PUT(put_resp));
+            }
+            ContextAction::DELETE(ctx_id) => {
+                let deleted_row = delete(contexts.filter(id.eq(&ctx_id))).execute(conn);
+                let email: String = user.get_email();
+                match deleted_row {
+                    // Any kind of error would rollback the tranction but explicitly returning rollback tranction allows you to rollback from any point in transaction.
+                    Ok(0) => {
+                        return Err(bad_argument!("context with id {} not found", ctx_id))
+                    }
+                    Ok(_) => {
+                        log::info!("{ctx_id} context deleted by {email}");
+                        response.push(ContextBulkResponse::DELETE(format!(
+                            "{ctx_id} deleted succesfully"
+                        )))
+                    }
+                    Err(e) => {
+                        log::error!("Delete context failed due to {:?}", e);
+                        return Err(db_error!(e));
+                    }
+                };
+            }
+            ContextAction::MOVE((old_ctx_id, move_req)) => {
+                let move_context_resp =
+                    r#move(old_ctx_id, Json(move_req), conn, true, user).map_err(
+                        |err| {
+                            log::error!(
+                                "Failed at moving context reponse due to {:?}",
+                                err
+                            );
+                            err
+                        },
+                    )?;
+                response.push(ContextBulkResponse::MOVE(move_context_resp));
+            }
+        }
+    }
+    Ok(response)
+}
+
+// MOVEs of contexts that do not exist fail the whole batch before any of it
+// is applied
+fn validate_move_targets(
+    reqs: &[ContextAction],
+    conn: &mut DBConnection,
+) -> superposition::Result<()> {
+    use contexts::dsl::contexts;
+    let move_ctx_ids: Vec<String> = reqs
+        .iter()
+        .filter_map(|action| match action {
+            ContextAction::MOVE((old_ctx_id, _)) => Some(old_ctx_id.clone()),
+            _ => None,
+        })
+        .collect();
+    if !move_ctx_ids.is_empty() {
+        let existing_ctx_ids: HashSet<String> = contexts
+            .filter(id.eq_any(&move_ctx_ids))
+            .select(id)
+            .load::<String>(conn)?
+            .into_iter()
+            .collect();
+        let missing_ctx_ids = find_missing_context_ids(&move_ctx_ids, &existing_ctx_ids);
+        if !missing_ctx_ids.is_empty() {
+            log::error!(
+                "bulk operation has MOVE on missing contexts {missing_ctx_ids:?}"
+            );
+            return Err(bad_argument!(
+                "contexts with ids {} not found for MOVE",
+                missing_ctx_ids.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
 #[put("/bulk-operations")]
 async fn bulk_operations(
+    http_req: HttpRequest,
     reqs: Json<Vec<ContextAction>>,
+    qparams: Query<BulkOperationParams>,
     db_conn: DbConnection,
     user: User,
-) -> superposition::Result<Json<Vec<ContextBulkResponse>>> {
-    use contexts::dsl::contexts;
+) -> superposition::Result<HttpResponse> {
     let DbConnection(mut conn) = db_conn;
+    let dry_run = qparams.dry_run.unwrap_or(false);
+
+    let idempotency_key = match http_req.headers().get("Idempotency-Key") {
+        Some(header_value) => Some(
+            header_value
+                .to_str()
+                .map_err(|_| {
+                    bad_argument!("Idempotency-Key header should be a valid ASCII string")
+                })?
+                .to_string(),
+        ),
+        None => None,
+    };
+    if let Some(key) = idempotency_key.as_ref().filter(|_| !dry_run) {
+        if let Some(response) = fetch_processed_bulk_operation(key, &mut conn)? {
+            log::info!("replaying bulk operation for Idempotency-Key {key}");
+            return Ok(replayed_bulk_response(response));
+        }
+    }
+
+    let reqs = reqs.into_inner();
+    validate_move_targets(&reqs, &mut conn)?;
+
+    if dry_run {
+        // the batch goes through the same path as a real one, in a transaction
+        // that is always rolled back
+        let mut dry_run_response = None;
+        let rolled_back =
+            conn.transaction::<(), superposition::AppError, _>(|transaction_conn| {
+                dry_run_response =
+                    Some(apply_bulk_operations(reqs, transaction_conn, &user)?);
+                Err(superposition::AppError::DbError(RollbackTransaction))
+            });
+        let operations = match (dry_run_response, rolled_back) {
+            (Some(operations), _) => operations,
+            (None, Err(err)) => return Err(err),
+            (None, Ok(())) => {
+                return Err(unexpected_error!("Something went wrong."));
+            }
+        };
+        return Ok(HttpResponse::Ok().json(DryRunBulkResponse {
+            dry_run: true,
+            operations,
+        }));
+    }
 
     let mut response = Vec::<ContextBulkResponse>::new();
-    conn.transaction::<_, superposition::AppError, _>(|transaction_conn| {
-        for action in reqs.into_inner().into_iter() {
-            match action {
-                ContextAction::PUT(put_req) => {
-                    let put_resp = put(Json(put_req), transaction_conn, true, &user)
-                        .map_err(|err| {
-                            log::error!(
-                                "Failed at insert into contexts due to {:?}",
-                                err
-                            );
-                            err
-                        })?;
-                    response.push(ContextBulkResponse::PUT(put_resp));
-                }
-                ContextAction::DELETE(ctx_id) => {
-                    let deleted_row =
-                        delete(contexts.filter(id.eq(&ctx_id))).execute(transaction_conn);
-                    let email: String = user.get_email();
-                    match deleted_row {
-                        // Any kind of error would rollback the tranction but explicitly returning rollback tranction allows you to rollback from any point in transaction.
-                        Ok(0) => {
-                            return Err(bad_argument!(
-                                "context with id {} not found",
-                                ctx_id
-                            ))
-                        }
-                        Ok(_) => {
-                            log::info!("{ctx_id} context deleted by {email}");
-                            response.push(ContextBulkResponse::DELETE(format!(
-                                "{ctx_id} deleted succesfully"
-                            )))
-                        }
-                        Err(e) => {
-                            log::error!("Delete context failed due to {:?}", e);
-                            return Err(db_error!(e));
-                        }
-                    };
-                }
-                ContextAction::MOVE((old_ctx_id, move_req)) => {
-                    let move_context_resp =
-                        r#move(old_ctx_id, Json(move_req), transaction_conn, true, &user)
-                            .map_err(|err| {
-                                log::error!(
-                                    "Failed at moving context reponse due to {:?}",
-                                    err
-                                );
-                                err
-                            })?;
-                    response.push(ContextBulkResponse::MOVE(move_context_resp));
-                }
+    let transaction_result =
+        conn.transaction::<_, superposition::AppError, _>(|transaction_conn| {
+            response = apply_bulk_operations(reqs, transaction_conn, &user)?;
+            // the key is stored with the batch so that a replay can never see a
+            // partially applied batch
+            if let Some(key) = &idempotency_key {
+                diesel::insert_into(idempotency_keys::table)
+                    .values(&IdempotencyKey {
+                        key: key.to_string(),
+                        response: json!(response),
+                        created_at: Utc::now(),
+                        created_by: user.get_email(),
+                    })
+                    .execute(transaction_conn)?;
+            }
+            Ok(()) // Commit the transaction
+        });
+
+    match (transaction_result, &idempotency_key) {
+        (Ok(()), _) => (),
+        // a concurrent request with the same key committed first
+        (
+            Err(superposition::AppError::DbError(DatabaseError(UniqueViolation, _))),
+            Some(key),
+        ) => {
+            if let Some(response) = fetch_processed_bulk_operation(key, &mut conn)? {
+                return Ok(replayed_bulk_response(response));
             }
+            return Err(unexpected_error!("Something went wrong."));
         }
-        Ok(()) // Commit the transaction
-    })?;
-    Ok(Json(response))
+        (Err(err), _) => return Err(err),
+    }
+
+    // versions are only known once the whole batch is committed
+    let version_id = get_config_version_id(&mut conn);
+    for resp in response.iter_mut() {
+        if let ContextBulkResponse::PUT(put_resp) | ContextBulkResponse::MOVE(put_resp) =
+            resp
+        {
+            put_resp.version_id = version_id.clone();
+        }
+    }
+    if let Some(key) = &idempotency_key {
+        let stored_response = diesel::update(
+            idempotency_keys::dsl::idempotency_keys
+                .filter(idempotency_keys::dsl::key.eq(key)),
+        )
+        .set(idempotency_keys::dsl::response.eq(json!(response)))
+        .execute(&mut conn);
+        if let Err(err) = stored_response {
+            log::error!("failed to store versions for Idempotency-Key {key}: {err}");
+        }
+    }
+
+    let mut http_resp = config_changed(HttpResponse::Ok().json(response));
+    if let Some(header_value) = version_id
+        .as_deref()
+        .and_then(|version_id| HeaderValue::from_str(version_id).ok())
+    {
+        http_resp
+            .headers_mut()
+            .insert(HeaderName::from_static("x-audit-id"), header_value);
+    }
+    Ok(http_resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_connection;
+    use diesel::connection::SimpleConnection;
+
+    #[test]
+    #[ignore = "needs a database, run with DATABASE_URL set and --ignored"]
+    fn test_bulk_operations_with_a_bad_move_leave_contexts_untouched() {
+        let mut conn = test_connection("cac_test_bulk_move");
+        conn.batch_execute(
+            r#"INSERT INTO dimensions (dimension, priority, created_at, created_by, schema, function_name)
+                VALUES ('os', 1, now(), 'test', '{"type": "string"}', NULL);
+            INSERT INTO contexts (id, value, override_id, created_at, created_by, priority, override)
+                VALUES ('ctx-1', '{"==": [{"var": "os"}, "ios"]}', 'o-1', now(), 'test', 1, '{"key": 1}'),
+                    ('ctx-2', '{"==": [{"var": "os"}, "android"]}', 'o-2', now(), 'test', 1, '{"key": 2}');"#,
+        )
+        .unwrap();
+        let move_req = |os: &str| MoveReq {
+            context: Map::from_iter([("==".to_string(), json!([{"var": "os"}, os]))]),
+        };
+        let reqs = vec![
+            ContextAction::DELETE("ctx-1".to_string()),
+            ContextAction::MOVE(("ctx-2".to_string(), move_req("web"))),
+            ContextAction::MOVE(("missing".to_string(), move_req("tv"))),
+        ];
+        let contexts_of = |conn: &mut DBConnection| {
+            contexts::table
+                .order(contexts::id)
+                .select((contexts::id, contexts::value))
+                .load::<(String, Value)>(conn)
+                .unwrap()
+        };
+        let before = contexts_of(&mut conn);
+
+        assert!(matches!(
+            validate_move_targets(&reqs, &mut conn),
+            Err(superposition::AppError::BadArgument(msg)) if msg.contains("missing")
+        ));
+        // the batch is atomic even past the check, an operation failing
+        // undoes the ones applied before it
+        let applied = conn.transaction(|transaction_conn| {
+            apply_bulk_operations(reqs, transaction_conn, &User::default())
+        });
+        assert!(applied.is_err());
+        assert_eq!(contexts_of(&mut conn), before);
+        assert_eq!(before.len(), 2);
+    }
+
+    #[test]
+    #[ignore = "needs a database, run with DATABASE_URL set and --ignored"]
+    fn test_validate_context() {
+        let mut conn = test_connection("cac_test_validate_context");
+        conn.batch_execute(
+            r#"INSERT INTO dimensions (dimension, priority, created_at, created_by, schema, function_name)
+                VALUES ('os', 2, now(), 'test', '{"type": "string"}', NULL),
+                    ('city', 4, now(), 'test', '{"type": "string"}', NULL);"#,
+        )
+        .unwrap();
+        let context_of = |condition: Value| condition.as_object().unwrap().clone();
+
+        let malformed = context_of(json!({
+            "and": [
+                { "==": [{ "var": "os" }, "ios"] },
+                { "==": "Bangalore" }
+            ]
+        }));
+        assert!(matches!(
+            validate_context(&mut conn, malformed),
+            Err(superposition::AppError::BadArgument(msg)) if msg.ends_with("(at `and[1].==`)")
+        ));
+
+        let valid = context_of(json!({
+            "and": [
+                { "==": ["ios", { "var": "os" }] },
+                { "==": [{ "var": "city" }, "Bangalore"] }
+            ]
+        }));
+        let resp = validate_context(&mut conn, valid).unwrap();
+        assert_eq!(
+            resp.context,
+            json!({
+                "and": [
+                    { "==": [{ "var": "city" }, "Bangalore"] },
+                    { "==": [{ "var": "os" }, "ios"] }
+                ]
+            })
+        );
+        assert_eq!(
+            Value::Object(resp.dimensions),
+            json!({ "city": "Bangalore", "os": "ios" })
+        );
+        assert_eq!(resp.priority, 6);
+    }
+
+    #[test]
+    fn test_validate_dimensions_and_calculate_priority() {
+        let string_schema = || JSONSchema::compile(&json!({ "type": "string" })).unwrap();
+        let dimension_schema_map = HashMap::from([
+            ("city".to_string(), (string_schema(), 4)),
+            ("os".to_string(), (string_schema(), 2)),
+        ]);
+        let condition = json!({
+            "and": [
+                { "==": [{ "var": "city" }, "Bangalore"] },
+                { "==": [{ "var": "os" }, "android"] }
+            ]
+        });
+        let priority = validate_dimensions_and_calculate_priority(
+            "context",
+            &condition,
+            &dimension_schema_map,
+        );
+        assert_eq!(priority.unwrap(), 6);
+
+        let unknown = json!({ "==": [{ "var": "tier" }, "gold"] });
+        assert!(validate_dimensions_and_calculate_priority(
+            "context",
+            &unknown,
+            &dimension_schema_map
+        )
+        .is_err());
+    }
 }
@@ -5,6 +5,7 @@ use service_utils::{result as superposition, unexpected_error, validation_error}
 use std::str;
 
 use crate::api::functions::helpers::get_published_functions_by_names;
+use crate::helpers::json_to_sorted_string;
 use crate::validation_functions::execute_fn;
 use crate::{
     api::context::types::FunctionsInfo,
@@ -17,8 +18,8 @@ use diesel::{
     r2d2::{ConnectionManager, PooledConnection},
     ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl,
 };
-use serde_json::{Map, Value};
-use std::collections::HashMap;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
 type DBConnection = PooledConnection<ConnectionManager<PgConnection>>;
 
 pub fn validate_condition_with_functions(
@@ -134,3 +135,188 @@ pub fn validate_value_with_function(
     }
     Ok(())
 }
+
+/// Returns the requested context ids that are absent from `existing`, in the
+/// order they were requested and without duplicates.
+pub fn find_missing_context_ids(
+    requested: &[String],
+    existing: &HashSet<String>,
+) -> Vec<String> {
+    let mut seen = HashSet::new();
+    requested
+        .iter()
+        .filter(|ctx_id| !existing.contains(*ctx_id) && seen.insert(*ctx_id))
+        .cloned()
+        .collect()
+}
+
+/// Whether a context condition references `dimension`, and when `value` is
+/// given whether it compares the dimension against it. Values are matched on
+/// their text, and against each of them when a condition like `in` has many.
+pub fn context_has_dimension(
+    condition: &Value,
+    dimension: &str,
+    value: Option<&str>,
+) -> bool {
+    fn matches(operand: &Value, value: &str) -> bool {
+        match operand {
+            Value::String(operand) => operand == value,
+            Value::Array(items) => items.iter().any(|item| matches(item, value)),
+            operand => operand.to_string() == value,
+        }
+    }
+    let Ok(dimensions) = extract_dimensions(condition) else {
+        return false;
+    };
+    match (dimensions.get(dimension), value) {
+        (None, _) => false,
+        (Some(_), None) => true,
+        (Some(operand), Some(value)) => matches(operand, value),
+    }
+}
+
+/// Canonical form of a context condition, so that conditions meaning the same
+/// compare and hash equal. Operands of `and`/`or` are flattened into a single
+/// level, deduplicated and sorted by the dimension they reference, and
+/// equality comparisons are written with the `var` first.
+pub fn canonicalize_condition(condition: &Value) -> Value {
+    let Some((operator, operands)) = condition
+        .as_object()
+        .filter(|condition| condition.len() == 1)
+        .and_then(|condition| condition.iter().next())
+    else {
+        return condition.clone();
+    };
+    let Some(operands) = operands.as_array() else {
+        return condition.clone();
+    };
+
+    match operator.as_str() {
+        "and" | "or" => {
+            let mut conditions: Vec<Value> = Vec::new();
+            for operand in operands.iter().map(canonicalize_condition) {
+                match operand.get(operator).and_then(Value::as_array) {
+                    Some(nested)
+                        if operand.as_object().map_or(false, |o| o.len() == 1) =>
+                    {
+                        conditions.extend(nested.iter().cloned())
+                    }
+                    _ => conditions.push(operand),
+                }
+            }
+            conditions.sort_by_cached_key(|condition| {
+                (
+                    first_variable(condition).unwrap_or_default(),
+                    json_to_sorted_string(condition),
+                )
+            });
+            conditions.dedup();
+            json!({ operator: conditions })
+        }
+        "==" | "!=" | "===" | "!==" => match operands.as_slice() {
+            [value, variable] if is_variable(variable) && !is_variable(value) => {
+                json!({ operator: [variable, value] })
+            }
+            _ => condition.clone(),
+        },
+        _ => condition.clone(),
+    }
+}
+
+fn is_variable(operand: &Value) -> bool {
+    operand.get("var").is_some()
+}
+
+// name of the first dimension a condition references, depth first
+fn first_variable(condition: &Value) -> Option<String> {
+    match condition {
+        Value::Object(map) => {
+            map.iter().find_map(|(key, val)| match (key.as_str(), val) {
+                ("var", Value::String(name)) => Some(name.clone()),
+                _ => first_variable(val),
+            })
+        }
+        Value::Array(items) => items.iter().find_map(first_variable),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_context_has_dimension() {
+        let condition = json!({"and": [
+            {"==": [{"var": "os"}, "ios"]},
+            {"in": ["1.2", {"var": "appVersion"}]},
+            {"<=": [1, {"var": "buildNumber"}, 5]}
+        ]});
+        assert!(context_has_dimension(&condition, "os", None));
+        assert!(context_has_dimension(&condition, "os", Some("ios")));
+        assert!(!context_has_dimension(&condition, "os", Some("android")));
+        assert!(context_has_dimension(&condition, "appVersion", Some("1.2")));
+        assert!(context_has_dimension(&condition, "buildNumber", Some("5")));
+        assert!(!context_has_dimension(&condition, "buildNumber", Some("3")));
+        assert!(!context_has_dimension(&condition, "city", None));
+        assert!(!context_has_dimension(
+            &json!("not a condition"),
+            "os",
+            None
+        ));
+    }
+
+    #[test]
+    fn test_canonicalize_condition() {
+        let os = json!({"==": [{"var": "os"}, "ios"]});
+        let city = json!({"==": [{"var": "city"}, "Bangalore"]});
+        let canonical = json!({"and": [city, os]});
+
+        assert_eq!(
+            canonicalize_condition(&json!({"and": [os, city]})),
+            canonical
+        );
+        // nested and repeated conditions collapse into one level
+        assert_eq!(
+            canonicalize_condition(&json!({"and": [os, {"and": [city, os]}]})),
+            canonical
+        );
+        // equality is written with the variable first
+        assert_eq!(
+            canonicalize_condition(&json!({"and": [
+                {"==": ["ios", {"var": "os"}]},
+                {"==": ["Bangalore", {"var": "city"}]}
+            ]})),
+            canonical
+        );
+        // other operators keep their operand order
+        let membership = json!({"in": ["1.2", {"var": "appVersion"}]});
+        assert_eq!(canonicalize_condition(&membership), membership);
+        // or branches are sorted but never merged into an and
+        assert_eq!(
+            canonicalize_condition(&json!({"or": [os, {"and": [city]}]})),
+            json!({"or": [{"and": [city]}, os]})
+        );
+    }
+
+    #[test]
+    fn test_find_missing_context_ids() {
+        let existing: HashSet<String> =
+            HashSet::from(["ctx-1".to_string(), "ctx-2".to_string()]);
+
+        let all_present = vec!["ctx-1".to_string(), "ctx-2".to_string()];
+        assert!(find_missing_context_ids(&all_present, &existing).is_empty());
+
+        let with_missing = vec![
+            "ctx-3".to_string(),
+            "ctx-1".to_string(),
+            "ctx-4".to_string(),
+            "ctx-3".to_string(),
+        ];
+        assert_eq!(
+            find_missing_context_ids(&with_missing, &existing),
+            vec!["ctx-3".to_string(), "ctx-4".to_string()]
+        );
+    }
+}
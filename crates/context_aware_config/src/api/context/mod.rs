@@ -1,4 +1,4 @@
 mod handlers;
 pub mod helpers;
 mod types;
-pub use handlers::endpoints;
+pub use handlers::{endpoints, validate_dimensions_and_calculate_priority};
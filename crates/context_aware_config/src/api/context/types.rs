@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
+use crate::db::models::Context;
+
 #[derive(Deserialize, Clone)]
 pub struct PutReq {
     pub context: Map<String, Value>,
@@ -12,6 +14,19 @@ pub struct MoveReq {
     pub context: Map<String, Value>,
 }
 
+#[derive(Deserialize)]
+pub struct ValidateContextReq {
+    pub context: Map<String, Value>,
+}
+
+#[derive(Serialize)]
+pub struct ValidateContextResp {
+    // the context as it would be stored
+    pub context: Value,
+    pub dimensions: Map<String, Value>,
+    pub priority: i32,
+}
+
 #[derive(Deserialize, Clone)]
 pub struct DimensionCondition {
     pub var: String,
@@ -22,6 +37,7 @@ pub struct PutResp {
     pub context_id: String,
     pub override_id: String,
     pub priority: i32,
+    pub version_id: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -30,6 +46,22 @@ pub struct PaginationParams {
     pub size: Option<u32>,
 }
 
+#[derive(Deserialize)]
+pub struct ContextFilters {
+    pub dimension: Option<String>,
+    // only applies along with a dimension
+    pub value: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PaginatedContexts {
+    pub total_items: i64,
+    pub total_pages: i64,
+    pub page: i64,
+    pub count: i64,
+    pub data: Vec<Context>,
+}
+
 #[derive(serde::Deserialize)]
 pub enum ContextAction {
     PUT(PutReq),
@@ -44,6 +76,17 @@ pub enum ContextBulkResponse {
     MOVE(PutResp),
 }
 
+#[derive(Deserialize)]
+pub struct BulkOperationParams {
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct DryRunBulkResponse {
+    pub dry_run: bool,
+    pub operations: Vec<ContextBulkResponse>,
+}
+
 #[derive(Deserialize, Clone)]
 pub struct FunctionsInfo {
     pub name: String,
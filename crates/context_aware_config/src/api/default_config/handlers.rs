@@ -1,34 +1,50 @@
 extern crate base64;
-use super::types::CreateReq;
-use service_utils::{bad_argument, unexpected_error, validation_error};
+use super::types::{BulkCreateAction, BulkCreateReq, BulkCreateResp, CreateReq, Patch};
+use service_utils::{
+    bad_argument, helpers::extract_dimensions, not_found, response_error,
+    unexpected_error, validation_error,
+};
+use std::collections::{BTreeSet, HashSet};
 
 use superposition_types::{SuperpositionUser, User};
 
+use crate::api::config::add_audit_header;
 use crate::api::context::helpers::validate_value_with_function;
 use crate::{
     api::functions::helpers::get_published_function_code,
-    db::{self, models::DefaultConfig, schema::default_configs::dsl::default_configs},
-    helpers::validate_jsonschema,
+    db::{
+        self,
+        models::DefaultConfig,
+        schema::{contexts::dsl as contexts, default_configs::dsl::default_configs},
+    },
+    helpers::{format_validation_errors, validate_jsonschema},
 };
 use actix_web::{
-    get, put,
+    delete, get,
+    http::StatusCode,
+    put,
     web::{self, Data, Json},
     HttpResponse, Scope,
 };
 use chrono::Utc;
 use diesel::{
     r2d2::{ConnectionManager, PooledConnection},
-    ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl,
+    Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl,
 };
 use jsonschema::{Draft, JSONSchema, ValidationError};
 use serde_json::{json, Value};
 use service_utils::{
+    middlewares::config_change::config_changed,
     result as superposition,
     service::types::{AppState, DbConnection},
 };
 
 pub fn endpoints() -> Scope {
-    Scope::new("").service(create).service(get)
+    Scope::new("")
+        .service(bulk_create)
+        .service(create)
+        .service(get)
+        .service(delete_default_config)
 }
 
 #[put("/{key}")]
@@ -40,40 +56,146 @@ async fn create(
     user: User,
 ) -> superposition::Result<HttpResponse> {
     let DbConnection(mut conn) = db_conn;
-    let req = request.into_inner();
-    let key = key.into_inner();
+    let (default_config, _) = resolve_default_config(
+        &state,
+        key.into_inner(),
+        request.into_inner(),
+        &user,
+        &mut conn,
+    )?;
+
+    let upsert = diesel::insert_into(default_configs)
+        .values(&default_config)
+        .on_conflict(db::schema::default_configs::key)
+        .do_update()
+        .set(&default_config)
+        .execute(&mut conn);
 
-    if req.value.is_none() && req.schema.is_none() && req.function_name.is_none() {
+    match upsert {
+        Ok(_) => Ok(config_changed(HttpResponse::Ok().json(json!({
+            "message": "DefaultConfig created/updated successfully."
+        })))),
+        Err(e) => {
+            log::info!("DefaultConfig creation failed with error: {e}");
+            Err(unexpected_error!(
+                "Something went wrong, failed to create DefaultConfig"
+            ))
+        }
+    }
+}
+
+#[put("/bulk")]
+async fn bulk_create(
+    state: Data<AppState>,
+    request: web::Json<Vec<BulkCreateReq>>,
+    db_conn: DbConnection,
+    user: User,
+) -> superposition::Result<HttpResponse> {
+    let DbConnection(mut conn) = db_conn;
+    let requests = request.into_inner();
+
+    if requests.is_empty() {
+        return Err(bad_argument!(
+            "Provide at least one default config in the request body."
+        ));
+    }
+    let mut keys = HashSet::new();
+    if let Some(duplicate) = requests.iter().find(|req| !keys.insert(&req.key)) {
+        return Err(bad_argument!(
+            "default config key {} is present more than once in the request",
+            duplicate.key
+        ));
+    }
+
+    // the batch is applied as a whole, any invalid entry rolls back the others
+    let results =
+        conn.transaction::<_, superposition::AppError, _>(|transaction_conn| {
+            requests
+                .into_iter()
+                .map(|BulkCreateReq { key, request }| {
+                    let (default_config, existed) = resolve_default_config(
+                        &state,
+                        key,
+                        request,
+                        &user,
+                        transaction_conn,
+                    )?;
+                    diesel::insert_into(default_configs)
+                        .values(&default_config)
+                        .on_conflict(db::schema::default_configs::key)
+                        .do_update()
+                        .set(&default_config)
+                        .execute(transaction_conn)?;
+                    Ok(BulkCreateResp {
+                        key: default_config.key,
+                        action: if existed {
+                            BulkCreateAction::Updated
+                        } else {
+                            BulkCreateAction::Created
+                        },
+                    })
+                })
+                .collect::<superposition::Result<Vec<BulkCreateResp>>>()
+        })?;
+
+    Ok(config_changed(HttpResponse::Ok().json(results)))
+}
+
+// The default config `req` makes of `key`, merged with the stored one if
+// any, after validating it. The flag tells whether `key` already existed.
+fn resolve_default_config(
+    state: &AppState,
+    key: String,
+    req: CreateReq,
+    user: &User,
+    conn: &mut PooledConnection<ConnectionManager<PgConnection>>,
+) -> superposition::Result<(DefaultConfig, bool)> {
+    if req.value.is_absent() && req.schema.is_absent() && req.function_name.is_absent() {
         log::error!("No data provided in the request body for {key}");
         return Err(bad_argument!("Please provide data in the request body."));
     }
 
-    let func_name = match &req.function_name {
-        Some(Value::String(s)) => Some(s.clone()),
-        Some(Value::Null) | None => None,
-        Some(_) => {
+    // a null value is stored as is, like any other value
+    let req_value = match req.value {
+        Patch::Absent => None,
+        Patch::Null => Some(Value::Null),
+        Patch::Value(value) => Some(value),
+    };
+    let req_schema = match req.schema {
+        Patch::Absent => None,
+        Patch::Null => {
             return Err(bad_argument!(
-                "Expected a string or null as the function name.",
+                "schema of {} cannot be removed, provide a schema or leave it out",
+                key
             ))
         }
+        Patch::Value(schema) => Some(Value::Object(schema)),
     };
 
-    let result = fetch_default_key(&key, &mut conn);
+    let result = fetch_default_key(&key, conn);
 
+    let existed = result.is_ok();
     let (value, schema, function_name) = match result {
         Ok((val, schema, f_name)) => {
-            let val = req.value.unwrap_or_else(|| val);
-            let schema = req.schema.map_or_else(|| schema, Value::Object);
-            let f_name = if req.function_name == Some(Value::Null) {
-                None
-            } else {
-                func_name.or(f_name)
+            let val = req_value.unwrap_or(val);
+            let schema = req_schema.unwrap_or(schema);
+            // an absent function name keeps the stored one, null removes it
+            let f_name = match req.function_name {
+                Patch::Absent => f_name,
+                Patch::Null => None,
+                Patch::Value(f_name) => Some(f_name),
             };
             (val, schema, f_name)
         }
         Err(superposition::AppError::DbError(diesel::NotFound)) => {
-            match (req.value, req.schema) {
-                (Some(val), Some(schema)) => (val, Value::Object(schema), func_name),
+            match (req_value, req_schema) {
+                (Some(val), Some(schema)) => {
+                    let f_name = match req.function_name {
+                        Patch::Value(f_name) => Some(f_name),
+                        Patch::Absent | Patch::Null => None,
+                    };
+                    (val, schema, f_name)
+                }
                 _ => {
                     log::error!("No record found for {key}.");
                     return Err(bad_argument!("No record found for {}", key));
@@ -107,7 +229,10 @@ async fn create(
         Ok(jschema) => jschema,
         Err(e) => {
             log::info!("Failed to compile as a Draft-7 JSON schema: {e}");
-            return Err(bad_argument!("Invalid JSON schema (failed to compile)"));
+            return Err(bad_argument!(
+                "Invalid JSON schema (failed to compile): {}",
+                e
+            ));
         }
     };
 
@@ -118,46 +243,35 @@ async fn create(
             verrors
         );
         return Err(validation_error!(
-            "Schema validation failed for key {} with error {:?}",
+            "Schema validation failed for key {} with error {}",
             default_config.key,
-            verrors
+            format_validation_errors(&verrors).join("; ")
         ));
     }
 
     if let Some(f_name) = &default_config.function_name {
-        let function_code = get_published_function_code(&mut conn, f_name.to_string())
+        let function_code = get_published_function_code(conn, f_name.to_string())
             .map_err(|e| {
                 log::info!("Function not found with error : {e}");
                 bad_argument!("Function {} doesn't exists.", f_name)
             })?;
-        if let Some(f_code) = function_code {
-            validate_value_with_function(
+        let f_code = function_code.ok_or_else(|| {
+            log::info!("Function {f_name} has no published code");
+            bad_argument!(
+                "Function {} is not published yet. Publish the function before using it to validate {}",
                 f_name,
-                &f_code,
-                &default_config.key,
-                &default_config.value,
-            )?;
-        }
+                default_config.key
+            )
+        })?;
+        validate_value_with_function(
+            f_name,
+            &f_code,
+            &default_config.key,
+            &default_config.value,
+        )?;
     }
 
-    let upsert = diesel::insert_into(default_configs)
-        .values(&default_config)
-        .on_conflict(db::schema::default_configs::key)
-        .do_update()
-        .set(&default_config)
-        .execute(&mut conn);
-
-    match upsert {
-        Ok(_) => Ok(HttpResponse::Ok().json(json!({
-            "message": "DefaultConfig created/updated successfully."
-        }))),
-        Err(e) => {
-            log::info!("DefaultConfig creation failed with error: {e}");
-            Err(unexpected_error!(
-                "Something went wrong, failed to create DefaultConfig"
-            ))
-        }
-    }
+    Ok((default_config, existed))
 }
 
 fn fetch_default_key(
@@ -182,3 +296,130 @@ async fn get(db_conn: DbConnection) -> superposition::Result<Json<Vec<DefaultCon
     let result: Vec<DefaultConfig> = default_configs.get_results(&mut conn)?;
     Ok(Json(result))
 }
+
+// experiment variant contexts carry a `variantIds` condition holding
+// `<experiment_id>-<variant_id>`
+fn experiment_id_of_context(context: &Value) -> Option<String> {
+    let dimensions = extract_dimensions(context).ok()?;
+    let variant_id = dimensions.get("variantIds")?.as_str()?;
+    variant_id.split('-').next().map(String::from)
+}
+
+// removes `key` unless contexts override it, the event_log entry of the
+// delete moves the config version
+fn delete_default_config_key(
+    key: &str,
+    conn: &mut PgConnection,
+) -> superposition::Result<()> {
+    // running experiments override their keys through variant contexts, so
+    // the contexts alone tell us about both plain overrides and experiments
+    let context_overrides: Vec<(String, Value, Value)> = contexts::contexts
+        .select((contexts::id, contexts::value, contexts::override_))
+        .load(conn)?;
+
+    let mut blocking_experiments = BTreeSet::new();
+    let mut blocking_contexts = BTreeSet::new();
+    for (context_id, context, override_) in context_overrides {
+        let overrides_key = override_
+            .as_object()
+            .map_or(false, |override_| override_.contains_key(key));
+        if !overrides_key {
+            continue;
+        }
+        match experiment_id_of_context(&context) {
+            Some(experiment_id) => blocking_experiments.insert(experiment_id),
+            None => blocking_contexts.insert(context_id),
+        };
+    }
+
+    if !blocking_experiments.is_empty() || !blocking_contexts.is_empty() {
+        return Err(response_error!(
+            StatusCode::CONFLICT,
+            format!(
+                "default config key {} is still in use by experiments [{}] and contexts [{}]. Conclude the experiments and remove the overrides before deleting it",
+                key,
+                blocking_experiments.into_iter().collect::<Vec<String>>().join(","),
+                blocking_contexts.into_iter().collect::<Vec<String>>().join(",")
+            )
+        ));
+    }
+
+    let deleted_rows =
+        diesel::delete(default_configs.filter(db::schema::default_configs::key.eq(key)))
+            .execute(conn)?;
+    if deleted_rows == 0 {
+        return Err(not_found!("default config key `{}` doesn't exists", key));
+    }
+    Ok(())
+}
+
+#[delete("/{key}")]
+async fn delete_default_config(
+    key: web::Path<String>,
+    db_conn: DbConnection,
+    user: User,
+) -> superposition::Result<HttpResponse> {
+    let DbConnection(mut conn) = db_conn;
+    let key = key.into_inner();
+
+    // an override added between the check and the delete would be orphaned
+    conn.transaction::<_, superposition::AppError, _>(|transaction_conn| {
+        delete_default_config_key(&key, transaction_conn)
+    })?;
+
+    log::info!("default config key {key} deleted by {}", user.get_email());
+    add_audit_header(
+        &mut conn,
+        config_changed(HttpResponse::NoContent().finish()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_connection;
+    use crate::helpers::get_config_version_id;
+    use diesel::connection::SimpleConnection;
+
+    #[test]
+    #[ignore = "needs a database, run with DATABASE_URL set and --ignored"]
+    fn test_delete_default_config_key_moves_the_config_version() {
+        let mut conn = test_connection("cac_test_delete_default_config");
+        conn.batch_execute(
+            r#"INSERT INTO default_configs (key, value, created_at, created_by, schema, function_name)
+                VALUES ('unused', '1', now(), 'test', '{"type": "number"}', NULL),
+                    ('overridden', '1', now(), 'test', '{"type": "number"}', NULL);
+            INSERT INTO contexts (id, value, override_id, created_at, created_by, priority, override)
+                VALUES ('ctx-1', '{"==": [{"var": "os"}, "ios"]}', 'o-1', now(), 'test', 1, '{"overridden": 2}');"#,
+        )
+        .unwrap();
+        let version = get_config_version_id(&mut conn);
+        assert!(version.is_some());
+
+        assert!(matches!(
+            delete_default_config_key("overridden", &mut conn),
+            Err(superposition::AppError::ResponseError(err))
+                if err.status_code == StatusCode::CONFLICT && err.message.contains("ctx-1")
+        ));
+        assert_eq!(get_config_version_id(&mut conn), version);
+
+        delete_default_config_key("unused", &mut conn).unwrap();
+        let new_version = get_config_version_id(&mut conn);
+        assert!(
+            new_version.as_ref().unwrap().parse::<i64>().unwrap()
+                > version.unwrap().parse::<i64>().unwrap()
+        );
+        let original_data: Option<Value> = db::schema::event_log::table
+            .filter(db::schema::event_log::table_name.eq("default_configs"))
+            .filter(db::schema::event_log::action.eq("DELETE"))
+            .select(db::schema::event_log::original_data)
+            .first(&mut conn)
+            .unwrap();
+        assert_eq!(original_data.unwrap()["key"], json!("unused"));
+
+        assert!(matches!(
+            delete_default_config_key("unused", &mut conn),
+            Err(superposition::AppError::NotFound(_))
+        ));
+    }
+}
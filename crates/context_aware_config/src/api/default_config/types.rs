@@ -1,19 +1,109 @@
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::{Map, Value};
 
 #[derive(Debug, Deserialize)]
 pub struct CreateReq {
-    #[serde(default, deserialize_with = "deserialize_option")]
-    pub value: Option<Value>,
-    pub schema: Option<Map<String, Value>>,
-    #[serde(default, deserialize_with = "deserialize_option")]
-    pub function_name: Option<Value>,
-}
-
-fn deserialize_option<'de, D>(deserializer: D) -> Result<Option<Value>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let value: Value = Deserialize::deserialize(deserializer)?;
-    Ok(Some(value))
+    #[serde(default)]
+    pub value: Patch<Value>,
+    #[serde(default)]
+    pub schema: Patch<Map<String, Value>>,
+    #[serde(default)]
+    pub function_name: Patch<String>,
+}
+
+/// Field of a request updating only what it mentions: left out of the request,
+/// explicitly `null`, or set to a value. Needs `#[serde(default)]` on the
+/// field for a missing field to be `Absent`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Patch<T> {
+    #[default]
+    Absent,
+    Null,
+    Value(T),
+}
+
+impl<T> Patch<T> {
+    pub fn is_absent(&self) -> bool {
+        matches!(self, Patch::Absent)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Patch<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // only called for fields present in the request
+        Option::<T>::deserialize(deserializer)
+            .map(|value| value.map_or(Patch::Null, Patch::Value))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkCreateReq {
+    pub key: String,
+    #[serde(flatten)]
+    pub request: CreateReq,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BulkCreateAction {
+    Created,
+    Updated,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkCreateResp {
+    pub key: String,
+    pub action: BulkCreateAction,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_bulk_create_req() {
+        let requests: Vec<BulkCreateReq> = serde_json::from_value(json!([
+            {"key": "pmTestKey1", "value": 1, "schema": {"type": "number"}},
+            {"key": "pmTestKey2", "function_name": null}
+        ]))
+        .unwrap();
+
+        assert_eq!(requests[0].key, "pmTestKey1");
+        assert_eq!(requests[0].request.value, Patch::Value(json!(1)));
+        assert!(matches!(requests[0].request.schema, Patch::Value(_)));
+        assert_eq!(requests[0].request.function_name, Patch::Absent);
+        assert_eq!(requests[1].request.value, Patch::Absent);
+        assert_eq!(requests[1].request.function_name, Patch::Null);
+
+        assert!(
+            serde_json::from_value::<Vec<BulkCreateReq>>(json!([{"value": 1}])).is_err()
+        );
+    }
+
+    #[test]
+    fn test_create_req_patch_fields() {
+        let request = |body: Value| serde_json::from_value::<CreateReq>(body).unwrap();
+
+        let absent = request(json!({}));
+        assert!(absent.value.is_absent());
+        assert!(absent.schema.is_absent());
+        assert!(absent.function_name.is_absent());
+
+        let cleared = request(json!({"value": null, "function_name": null}));
+        assert_eq!(cleared.value, Patch::Null);
+        assert_eq!(cleared.function_name, Patch::Null);
+
+        let set = request(json!({"function_name": "validate_door_count"}));
+        assert_eq!(
+            set.function_name,
+            Patch::Value("validate_door_count".to_string())
+        );
+        assert!(
+            serde_json::from_value::<CreateReq>(json!({"function_name": 1})).is_err()
+        );
+    }
 }
@@ -1,19 +1,30 @@
 use crate::{
-    api::dimension::types::CreateReq,
-    db::{models::Dimension, schema::dimensions::dsl::*},
+    api::{
+        context::validate_dimensions_and_calculate_priority,
+        dimension::{
+            get_all_dimension_schema_map,
+            types::{CreateReq, PriorityUpdateReq, PriorityUpdateResp},
+        },
+    },
+    db::{
+        models::{Context, Dimension},
+        schema::{contexts, dimensions::dsl::*},
+    },
     helpers::validate_jsonschema,
 };
 use actix_web::{
     get, put,
-    web::{self, Data, Json},
+    web::{self, Data, Json, Path},
     HttpResponse, Scope,
 };
 use chrono::Utc;
-use diesel::RunQueryDsl;
+use diesel::{Connection, ExpressionMethods, RunQueryDsl};
 use jsonschema::{Draft, JSONSchema};
 use serde_json::Value;
 use service_utils::{
-    bad_argument, result as superposition,
+    bad_argument,
+    middlewares::config_change::config_changed,
+    result as superposition,
     service::types::{AppState, DbConnection},
     unexpected_error,
 };
@@ -21,7 +32,10 @@ use service_utils::{
 use superposition_types::{SuperpositionUser, User};
 
 pub fn endpoints() -> Scope {
-    Scope::new("").service(create).service(get)
+    Scope::new("")
+        .service(create)
+        .service(get)
+        .service(update_priority)
 }
 
 #[put("")]
@@ -110,3 +124,69 @@ async fn get(db_conn: DbConnection) -> superposition::Result<Json<Vec<Dimension>
     let result: Vec<Dimension> = dimensions.get_results(&mut conn)?;
     Ok(Json(result))
 }
+
+#[put("/{name}/priority")]
+async fn update_priority(
+    path: Path<String>,
+    req: Json<PriorityUpdateReq>,
+    db_conn: DbConnection,
+) -> superposition::Result<HttpResponse> {
+    let DbConnection(mut conn) = db_conn;
+    let dimension_name = path.into_inner();
+    let new_priority = i32::from(req.priority);
+
+    if new_priority <= 0 {
+        return Err(bad_argument!("Priority should be greater than 0"));
+    }
+
+    // context priorities are derived from dimension priorities, both move together
+    let resp = conn.transaction::<_, superposition::AppError, _>(|transaction_conn| {
+        let updated_dimension = diesel::update(dimensions)
+            .filter(dimension.eq(&dimension_name))
+            .set(priority.eq(new_priority))
+            .get_result::<Dimension>(transaction_conn)?;
+
+        let dimension_schema_map = get_all_dimension_schema_map(transaction_conn)?;
+
+        let mut updated_context_ids = Vec::new();
+        for context in contexts::table.load::<Context>(transaction_conn)? {
+            let context_priority = validate_dimensions_and_calculate_priority(
+                "context",
+                &context.value,
+                &dimension_schema_map,
+            )
+            .map_err(|err| {
+                log::error!(
+                    "failed to recompute priority of context {}: {err}",
+                    context.id
+                );
+                unexpected_error!("Failed to recompute priority of context {}", context.id)
+            })?;
+            if context_priority != context.priority {
+                diesel::update(contexts::table)
+                    .filter(contexts::id.eq(&context.id))
+                    .set(contexts::priority.eq(context_priority))
+                    .execute(transaction_conn)?;
+                updated_context_ids.push(context.id);
+            }
+        }
+
+        log::info!(
+            "priority of dimension {dimension_name} set to {new_priority}, updated {} contexts",
+            updated_context_ids.len()
+        );
+        Ok(PriorityUpdateResp {
+            dimension: updated_dimension,
+            updated_context_ids,
+        })
+    })?;
+
+    // only context priorities are part of the config
+    let contexts_changed = !resp.updated_context_ids.is_empty();
+    let http_resp = HttpResponse::Ok().json(resp);
+    Ok(if contexts_changed {
+        config_changed(http_resp)
+    } else {
+        http_resp
+    })
+}
@@ -1,6 +1,8 @@
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 
+use crate::db::models::Dimension;
+
 #[derive(Debug, Deserialize)]
 pub struct CreateReq {
     pub dimension: String,
@@ -10,6 +12,18 @@ pub struct CreateReq {
     pub function_name: Option<Value>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PriorityUpdateReq {
+    pub priority: u16,
+}
+
+#[derive(Serialize)]
+pub struct PriorityUpdateResp {
+    #[serde(flatten)]
+    pub dimension: Dimension,
+    pub updated_context_ids: Vec<String>,
+}
+
 fn deserialize_option<'de, D>(deserializer: D) -> Result<Option<Value>, D::Error>
 where
     D: Deserializer<'de>,
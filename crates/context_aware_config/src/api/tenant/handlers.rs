@@ -0,0 +1,170 @@
+use actix_web::{
+    http::StatusCode,
+    post,
+    web::{Data, Json},
+    HttpRequest, HttpResponse, Scope,
+};
+use diesel::{
+    connection::SimpleConnection,
+    sql_query,
+    sql_types::{Json as SqlJson, Text},
+    PgConnection, QueryableByName, RunQueryDsl,
+};
+use serde_json::json;
+use service_utils::{
+    admin::authorize_admin_request,
+    bad_argument,
+    db::tenants::record_provisioned_tenant,
+    response_error, result as superposition,
+    service::types::{AppScope, AppState},
+    unexpected_error,
+};
+use superposition_types::{SuperpositionUser, User};
+
+use super::{
+    helpers::{tenant_schema_sql, validate_tenant_id},
+    types::{CreateTenantRequest, CreateTenantResponse},
+};
+use crate::db::TENANT_MIGRATIONS as CAC_MIGRATIONS;
+use experimentation_platform::db::TENANT_MIGRATIONS as EXPERIMENTATION_MIGRATIONS;
+
+pub fn endpoints() -> Scope {
+    Scope::new("").service(create_tenant)
+}
+
+#[derive(QueryableByName)]
+struct SchemaName {
+    #[diesel(sql_type = Text)]
+    schema_name: String,
+}
+
+#[post("")]
+async fn create_tenant(
+    req: HttpRequest,
+    state: Data<AppState>,
+    body: Json<CreateTenantRequest>,
+    user: User,
+) -> superposition::Result<HttpResponse> {
+    authorize_admin_request(&state, &req)?;
+
+    if !state.enable_tenant_and_scope {
+        return Err(bad_argument!(
+            "tenants are disabled, set ENABLE_TENANT_AND_SCOPE to provision them"
+        ));
+    }
+    let tenant = body.into_inner().tenant;
+    validate_tenant_id(&tenant)?;
+    if state.serves_tenant(&tenant) {
+        return Err(response_error!(
+            StatusCode::CONFLICT,
+            format!("tenant {tenant} already exists")
+        ));
+    }
+
+    let cac_schema = format!("{tenant}_{}", AppScope::CAC);
+    let experimentation_schema = format!("{tenant}_{}", AppScope::EXPERIMENTATION);
+    let mut conn = state.db_pool.establish().map_err(|err| {
+        log::error!("failed to connect to the database to provision {tenant}: {err}");
+        unexpected_error!("Something went wrong, failed to provision tenant")
+    })?;
+    provision_schemas(
+        &mut conn,
+        &tenant,
+        &cac_schema,
+        &experimentation_schema,
+        &user,
+    )?;
+
+    if let Ok(mut tenants) = state.variant_dimension_tenants.lock() {
+        tenants.insert(tenant.clone());
+    }
+    state.register_tenant(&tenant).map_err(|err| {
+        log::error!("failed to create connection pools for {tenant}: {err}");
+        unexpected_error!(
+            "tenant {} was provisioned but could not be connected to, it is served once the database is reachable",
+            tenant
+        )
+    })?;
+    log::info!("provisioned tenant {tenant}");
+
+    Ok(HttpResponse::Created().json(CreateTenantResponse { tenant }))
+}
+
+// Runs the migrations of both schemas, registers the variant dimension and
+// records the tenant as provisioned. Migrations are run one at a time like
+// `scripts/create-tenant.sh` does, as enum values they add cannot be used in
+// the transaction adding them, so the schemas are dropped again if any of it
+// fails.
+fn provision_schemas(
+    conn: &mut PgConnection,
+    tenant: &str,
+    cac_schema: &str,
+    experimentation_schema: &str,
+    user: &User,
+) -> superposition::Result<()> {
+    // held until the connection is closed, serializes provisioning the same
+    // tenant from several requests or servers
+    sql_query("SELECT pg_advisory_lock(hashtext($1))")
+        .bind::<Text, _>(cac_schema)
+        .execute(conn)?;
+
+    let existing = sql_query(
+        "SELECT schema_name::text AS schema_name FROM information_schema.schemata WHERE schema_name IN ($1, $2)",
+    )
+    .bind::<Text, _>(cac_schema)
+    .bind::<Text, _>(experimentation_schema)
+    .load::<SchemaName>(conn)?;
+    if let Some(existing) = existing.first() {
+        return Err(response_error!(
+            StatusCode::CONFLICT,
+            format!(
+                "schema {} already exists, add its tenant to TENANTS instead of provisioning it",
+                existing.schema_name
+            )
+        ));
+    }
+
+    let result = run_tenant_migrations(conn, cac_schema, experimentation_schema, user)
+        .and_then(|_| {
+            record_provisioned_tenant(conn, tenant, &user.get_email())
+                .map_err(superposition::AppError::from)
+        });
+    if let Err(err) = &result {
+        log::error!(
+            "failed to provision schemas {cac_schema}, {experimentation_schema}: {err}"
+        );
+        let cleanup = format!(
+            "DROP SCHEMA IF EXISTS {cac_schema} CASCADE; DROP SCHEMA IF EXISTS {experimentation_schema} CASCADE;"
+        );
+        if let Err(err) = conn.batch_execute(&cleanup) {
+            log::error!("failed to drop partially provisioned schemas: {err}");
+        }
+    }
+    result
+}
+
+fn run_tenant_migrations(
+    conn: &mut PgConnection,
+    cac_schema: &str,
+    experimentation_schema: &str,
+    user: &User,
+) -> superposition::Result<()> {
+    let migrations = [
+        (cac_schema, CAC_MIGRATIONS),
+        (experimentation_schema, EXPERIMENTATION_MIGRATIONS),
+    ];
+    for (schema, schema_migrations) in migrations {
+        for migration in schema_migrations {
+            conn.batch_execute(&tenant_schema_sql(migration, schema))?;
+        }
+    }
+
+    // experiments need it to serve their variants, see ensure_variant_dimension
+    sql_query(format!(
+        "INSERT INTO {cac_schema}.dimensions (dimension, priority, created_at, created_by, schema, function_name) VALUES ('variantIds', 1, CURRENT_TIMESTAMP, $1, $2, NULL)"
+    ))
+    .bind::<Text, _>(user.get_email())
+    .bind::<SqlJson, _>(json!({ "type": "string", "pattern": ".*" }))
+    .execute(conn)?;
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use service_utils::{bad_argument, result as superposition};
+
+// schema names are at most 63 bytes, leaving room for `_experimentation`
+pub const MAX_TENANT_LENGTH: usize = 40;
+
+/// Tenant ids become part of schema names and of SQL run to create them, so
+/// only lowercase letters, digits and underscores are allowed, starting with a
+/// letter.
+pub fn validate_tenant_id(tenant: &str) -> superposition::Result<()> {
+    let starts_with_letter = tenant
+        .chars()
+        .next()
+        .map_or(false, |first| first.is_ascii_lowercase());
+    let valid_chars = tenant
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !starts_with_letter || !valid_chars || tenant.len() > MAX_TENANT_LENGTH {
+        return Err(bad_argument!(
+            "tenant {} is invalid, it should start with a lowercase letter and have at most {} lowercase letters, digits or underscores",
+            tenant,
+            MAX_TENANT_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+/// A migration written against the `public` schema, rewritten to set up
+/// `schema` instead.
+pub fn tenant_schema_sql(migration: &str, schema: &str) -> String {
+    migration.replace("public", schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_tenant_id() {
+        assert!(validate_tenant_id("acme").is_ok());
+        assert!(validate_tenant_id("acme_2").is_ok());
+        assert!(validate_tenant_id("").is_err());
+        assert!(validate_tenant_id("2acme").is_err());
+        assert!(validate_tenant_id("Acme").is_err());
+        assert!(validate_tenant_id("acme; drop schema public").is_err());
+        assert!(validate_tenant_id(&"a".repeat(MAX_TENANT_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_tenant_schema_sql() {
+        let migration =
+            "CREATE SCHEMA IF NOT EXISTS public;\nCREATE TABLE public.contexts ();";
+        assert_eq!(
+            tenant_schema_sql(migration, "acme_cac"),
+            "CREATE SCHEMA IF NOT EXISTS acme_cac;\nCREATE TABLE acme_cac.contexts ();"
+        );
+    }
+
+    #[test]
+    fn test_tenant_migrations_are_embedded() {
+        let migrations_dir =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let migration_count = std::fs::read_dir(migrations_dir)
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name() != "00000000000000_diesel_initial_setup")
+            .count();
+        assert_eq!(crate::db::TENANT_MIGRATIONS.len(), migration_count);
+    }
+}
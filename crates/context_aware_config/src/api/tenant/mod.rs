@@ -0,0 +1,4 @@
+mod handlers;
+pub use handlers::endpoints;
+pub mod helpers;
+mod types;
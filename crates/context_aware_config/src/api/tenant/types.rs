@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTenantRequest {
+    pub tenant: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateTenantResponse {
+    pub tenant: String,
+}
@@ -1,2 +1,46 @@
 pub mod models;
 pub mod schema;
+
+/// `up.sql` of every CAC migration in the order they are applied, setting
+/// up the `public` schema. Tenants are provisioned by running them with
+/// `public` replaced by the tenant's schema, like `scripts/create-tenant.sh`.
+pub const TENANT_MIGRATIONS: &[&str] = &[
+    include_str!("../../migrations/2023-10-16-133815_context-aware-config-init/up.sql"),
+    include_str!("../../migrations/2024-01-23-123559_audit_log_partitions/up.sql"),
+    include_str!("../../migrations/2024-02-19-125126_functions/up.sql"),
+    include_str!("../../migrations/2024-03-05-122806_dimensions_functions_ref/up.sql"),
+    include_str!("../../migrations/2024-03-18-094500_idempotency_keys/up.sql"),
+    include_str!("../../migrations/2024-04-12-090000_config_versions/up.sql"),
+];
+
+/// Connection to the database of `DATABASE_URL` for tests that need one, with
+/// the CAC tables set up in `schema`. Nothing done on it is committed. Tests
+/// using it are `#[ignore]`d, and panic when run without `DATABASE_URL`.
+#[cfg(test)]
+pub fn test_connection(
+    schema: &str,
+) -> diesel::r2d2::PooledConnection<diesel::r2d2::ConnectionManager<diesel::PgConnection>>
+{
+    use diesel::{connection::SimpleConnection, Connection};
+
+    let database_url = std::env::var("DATABASE_URL")
+        .expect("DATABASE_URL is not set, tests that need a database cannot run");
+    let pool = diesel::r2d2::Pool::builder()
+        .max_size(1)
+        .build(diesel::r2d2::ConnectionManager::<diesel::PgConnection>::new(database_url))
+        .expect("failed to connect to DATABASE_URL");
+    let mut conn = pool.get().expect("failed to connect to DATABASE_URL");
+    conn.begin_test_transaction()
+        .expect("failed to start a test transaction");
+    for migration in TENANT_MIGRATIONS {
+        conn.batch_execute(&migration.replace("public", schema))
+            .expect("failed to run the CAC migrations");
+    }
+    // the event_log partitions run out, changes made after them go here
+    conn.batch_execute(&format!(
+        "CREATE TABLE {schema}.event_log_default PARTITION OF {schema}.event_log DEFAULT;
+        SET LOCAL search_path TO {schema};"
+    ))
+    .expect("failed to set up the test schema");
+    conn
+}
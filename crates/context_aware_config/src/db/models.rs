@@ -1,4 +1,6 @@
-use crate::db::schema::{contexts, default_configs, dimensions, event_log, functions};
+use crate::db::schema::{
+    contexts, default_configs, dimensions, event_log, functions, idempotency_keys,
+};
 use chrono::{offset::Utc, DateTime, NaiveDateTime};
 use diesel::{AsChangeset, Insertable, Queryable, Selectable};
 use serde::Serialize;
@@ -74,3 +76,13 @@ pub struct EventLog {
     pub new_data: Option<Value>,
     pub query: String,
 }
+
+#[derive(Queryable, Selectable, Insertable, Clone, Debug)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(primary_key(key))]
+pub struct IdempotencyKey {
+    pub key: String,
+    pub response: Value,
+    pub created_at: DateTime<Utc>,
+    pub created_by: String,
+}
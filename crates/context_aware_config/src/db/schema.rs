@@ -1,5 +1,12 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    config_versions (id) {
+        id -> Bool,
+        version -> Int8,
+    }
+}
+
 diesel::table! {
     contexts (id) {
         id -> Varchar,
@@ -598,10 +605,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    idempotency_keys (key) {
+        key -> Text,
+        response -> Json,
+        created_at -> Timestamptz,
+        created_by -> Text,
+    }
+}
+
 diesel::joinable!(default_configs -> functions (function_name));
 diesel::joinable!(dimensions -> functions (function_name));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    config_versions,
     contexts,
     default_configs,
     dimensions,
@@ -648,4 +665,5 @@ diesel::allow_tables_to_appear_in_same_query!(
     event_log_y2026m11,
     event_log_y2026m12,
     functions,
+    idempotency_keys,
 );
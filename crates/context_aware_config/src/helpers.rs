@@ -1,10 +1,28 @@
+use crate::db::schema::config_versions::dsl as config_versions;
 use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use diesel::{PgConnection, QueryDsl, RunQueryDsl};
 use itertools::{self, Itertools};
 use jsonschema::{Draft, JSONSchema, ValidationError};
 use serde_json::{json, Value};
 use service_utils::{result as superposition, validation_error};
 use std::collections::HashMap;
 
+// the count of changes to contexts and default configs, deletes included,
+// kept in `config_versions`. It is bumped under a row lock held until commit,
+// so a later version is never committed before an earlier one. Config reads
+// return it in the `x-audit-id` and `x-config-version` headers and derive
+// their ETags from it, and the resolve cache is keyed on it
+pub fn get_config_version_id(conn: &mut PgConnection) -> Option<String> {
+    config_versions::config_versions
+        .select(config_versions::version)
+        .first::<i64>(conn)
+        .map(|version| version.to_string())
+        .map_err(|err| {
+            log::error!("failed to fetch config version from config_versions: {err}")
+        })
+        .ok()
+}
+
 pub fn get_default_config_validation_schema() -> JSONSchema {
     let my_schema = json!(
     {
@@ -199,6 +217,23 @@ pub fn validate_context_jsonschema(
 */
 // TODO: Recursive validation.
 
+// renders jsonschema errors as `<instance path>: <reason>`, the root instance
+// has an empty path
+pub fn format_validation_errors(verrors: &[ValidationError]) -> Vec<String> {
+    verrors
+        .iter()
+        .map(|error| {
+            let instance_path = error.instance_path.to_string();
+            let instance_path = if instance_path.is_empty() {
+                "/".to_string()
+            } else {
+                instance_path
+            };
+            format!("{instance_path}: {error}")
+        })
+        .collect()
+}
+
 pub fn validate_jsonschema(
     validation_schema: &JSONSchema,
     schema: &Value,
@@ -209,8 +244,8 @@ pub fn validate_jsonschema(
             //TODO: Try & render as json.
             let verrors = e.collect::<Vec<ValidationError>>();
             Err(validation_error!(
-                "schema validation failed: {:?}",
-                verrors.as_slice()
+                "schema validation failed: {}",
+                format_validation_errors(&verrors).join("; ")
             ))
         }
     };
@@ -250,6 +285,36 @@ pub fn json_to_sorted_string(v: &Value) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn test_format_validation_errors() {
+        let schema = JSONSchema::compile(&json!({
+            "type": "object",
+            "properties": { "port": { "type": "integer" } }
+        }))
+        .unwrap();
+
+        let instance = json!({ "port": "eight" });
+        let verrors = schema
+            .validate(&instance)
+            .unwrap_err()
+            .collect::<Vec<ValidationError>>();
+        assert_eq!(
+            format_validation_errors(&verrors),
+            vec![r#"/port: "eight" is not of type "integer""#.to_string()]
+        );
+
+        let instance = json!("eight");
+        let verrors = schema
+            .validate(&instance)
+            .unwrap_err()
+            .collect::<Vec<ValidationError>>();
+        assert_eq!(
+            format_validation_errors(&verrors),
+            vec![r#"/: "eight" is not of type "object""#.to_string()]
+        );
+    }
+
     #[test]
     fn test_get_meta_schema() {
         let x = get_meta_schema();
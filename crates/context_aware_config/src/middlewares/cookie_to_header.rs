@@ -48,7 +48,9 @@ where
     fn call(&self, mut req: ServiceRequest) -> Self::Future {
         let srv = self.service.clone();
         Box::pin(async move {
-            if let Some(cookie) = req.cookie("token") {
+            // an explicit Authorization header, like an admin token, wins
+            let has_authorization = req.headers().contains_key("authorization");
+            if let Some(cookie) = req.cookie("token").filter(|_| !has_authorization) {
                 let token_value = cookie.value().to_string();
                 req.headers_mut().insert(
                     HeaderName::from_static("authorization"),
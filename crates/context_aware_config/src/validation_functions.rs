@@ -24,6 +24,10 @@ fn type_check_validate(code_str: &str) -> String {
     )
 }
 
+// Contract for validation functions: `validate` is called with
+// `{ "key": <config key>, "value": <value> }` and may return a promise.
+// Returning `true` accepts the value, returning a string rejects it with that
+// string as the reason, any other output or a thrown error rejects it as well.
 fn execute_validate_fun(code_str: &str, val: Value) -> String {
     format!(
         r#"
@@ -34,10 +38,13 @@ fn execute_validate_fun(code_str: &str, val: Value) -> String {
         {}
         Promise.resolve(validate({})).then((output) => {{
 
-            if(output!=true){{
-                throw new Error("The function did not return true as expected. Check the conditions or logic inside the function.")
+            if(output===true){{
+                return output;
             }}
-            return output;
+            if(typeof(output)=="string"){{
+                throw new Error(output)
+            }}
+            throw new Error("The function did not return true as expected. Check the conditions or logic inside the function.")
         }}).catch((err)=> {{
             throw new Error(err)
         }});\`);
@@ -57,7 +57,8 @@ impl Client {
                 let mut exp_store = self.experiments.write().await;
                 for (exp_id, experiment) in experiments.into_iter() {
                     match experiment.status {
-                        types::ExperimentStatusType::CONCLUDED => {
+                        types::ExperimentStatusType::CONCLUDED
+                        | types::ExperimentStatusType::PAUSED => {
                             exp_store.remove(&exp_id)
                         }
                         _ => exp_store.insert(exp_id, experiment),
@@ -113,16 +114,23 @@ impl Client {
                 }
             }
         }
-        let variant_count = applicable_variants.len() as u8;
-        let range = (traffic * variant_count) as i32;
-        if (toss as i32) >= range {
-            return None;
+        // weighted variants get their share of the experiment's traffic,
+        // otherwise every variant gets `traffic` buckets
+        let weighted = applicable_variants
+            .iter()
+            .any(|variant| variant.traffic_percentage.is_some());
+        let mut cumulative_percentage = 0;
+        for variant in applicable_variants.iter() {
+            cumulative_percentage += match variant.traffic_percentage {
+                Some(weight) if weighted => weight * traffic as i32 / 100,
+                _ if weighted => 0,
+                _ => traffic as i32,
+            };
+            if (toss as i32) < cumulative_percentage {
+                return Some(variant.clone());
+            }
         }
-        let buckets = (1..=variant_count)
-            .map(|i| (traffic * i) as i8)
-            .collect::<Vec<i8>>();
-        let index = buckets.into_iter().position(|x| toss < x);
-        applicable_variants.get(index.unwrap()).map(Variant::clone)
+        None
     }
 }
 
@@ -141,7 +149,9 @@ async fn get_experiments(
             "{hostname}/experiments?from_date={start_date}&to_date={now}&page={page}&count={requesting_count}"
         );
         let list_experiments_response = http_client
-            .get(format!("{endpoint}&status=CREATED,INPROGRESS,CONCLUDED"))
+            .get(format!(
+                "{endpoint}&status=CREATED,INPROGRESS,CONCLUDED,PAUSED"
+            ))
             .header("x-tenant", tenant.to_string())
             .send()
             .await
@@ -15,6 +15,7 @@ pub(crate) enum ExperimentStatusType {
     CREATED,
     INPROGRESS,
     CONCLUDED,
+    PAUSED,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
@@ -29,6 +30,8 @@ pub struct Variant {
     pub id: String,
     pub overrides: Value,
     pub(crate) variant_type: VariantType,
+    #[serde(default)]
+    pub(crate) traffic_percentage: Option<i32>,
 }
 
 pub type Variants = Vec<Variant>;
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+--
+-- Name: experiment_audit; Type: TABLE; Schema: public; Owner: -
+--
+CREATE TABLE IF NOT EXISTS public.experiment_audit (
+    id uuid DEFAULT uuid_generate_v4() PRIMARY KEY,
+    experiment_id bigint NOT NULL REFERENCES public.experiments(id),
+    action text NOT NULL,
+    previous_status public.experiment_status_type,
+    new_status public.experiment_status_type NOT NULL,
+    actor_email text NOT NULL,
+    "timestamp" timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+--
+-- Name: experiment_audit_experiment_id_index; Type: INDEX; Schema: public; Owner: -
+--
+CREATE INDEX IF NOT EXISTS experiment_audit_experiment_id_index ON public.experiment_audit USING btree (experiment_id) INCLUDE ("timestamp");
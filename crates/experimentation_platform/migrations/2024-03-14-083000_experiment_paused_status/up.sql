@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TYPE public.experiment_status_type ADD VALUE IF NOT EXISTS 'PAUSED';
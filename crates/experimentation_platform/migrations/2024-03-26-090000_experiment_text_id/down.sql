@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+-- fails if any experiment has a non numeric (ULID) id
+ALTER TABLE public.experiment_audit DROP CONSTRAINT IF EXISTS experiment_audit_experiment_id_fkey;
+ALTER TABLE public.experiment_audit ALTER COLUMN experiment_id TYPE bigint USING experiment_id::bigint;
+ALTER TABLE public.experiments ALTER COLUMN id TYPE bigint USING id::bigint;
+ALTER TABLE public.experiment_audit ADD CONSTRAINT experiment_audit_experiment_id_fkey FOREIGN KEY (experiment_id) REFERENCES public.experiments(id);
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- experiment ids are stored as text so that both snowflake ids and ULIDs fit,
+-- existing snowflake ids keep their decimal representation
+ALTER TABLE public.experiment_audit DROP CONSTRAINT IF EXISTS experiment_audit_experiment_id_fkey;
+ALTER TABLE public.experiments ALTER COLUMN id TYPE text USING id::text;
+ALTER TABLE public.experiment_audit ALTER COLUMN experiment_id TYPE text USING experiment_id::text;
+ALTER TABLE public.experiment_audit ADD CONSTRAINT experiment_audit_experiment_id_fkey FOREIGN KEY (experiment_id) REFERENCES public.experiments(id);
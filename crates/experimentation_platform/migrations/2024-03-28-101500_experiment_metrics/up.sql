@@ -0,0 +1,16 @@
+-- Your SQL goes here
+--
+-- Name: experiment_metrics; Type: TABLE; Schema: public; Owner: -
+--
+CREATE TABLE IF NOT EXISTS public.experiment_metrics (
+    id uuid DEFAULT uuid_generate_v4() PRIMARY KEY,
+    experiment_id text NOT NULL REFERENCES public.experiments(id),
+    variant_id text NOT NULL,
+    metric_name text NOT NULL,
+    value double precision NOT NULL,
+    recorded_at timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+--
+-- Name: experiment_metrics_experiment_id_index; Type: INDEX; Schema: public; Owner: -
+--
+CREATE INDEX IF NOT EXISTS experiment_metrics_experiment_id_index ON public.experiment_metrics USING btree (experiment_id, variant_id, metric_name);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE public.experiments DROP COLUMN IF EXISTS ends_at;
+ALTER TABLE public.experiments DROP COLUMN IF EXISTS starts_at;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE public.experiments ADD COLUMN IF NOT EXISTS starts_at TIMESTAMPTZ;
+ALTER TABLE public.experiments ADD COLUMN IF NOT EXISTS ends_at TIMESTAMPTZ;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE public.experiments DROP COLUMN IF EXISTS rollout_step_started_at;
+ALTER TABLE public.experiments DROP COLUMN IF EXISTS rollout_step;
+ALTER TABLE public.experiments DROP COLUMN IF EXISTS rollout_plan;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE public.experiments ADD COLUMN IF NOT EXISTS rollout_plan JSON;
+ALTER TABLE public.experiments ADD COLUMN IF NOT EXISTS rollout_step INTEGER;
+ALTER TABLE public.experiments ADD COLUMN IF NOT EXISTS rollout_step_started_at TIMESTAMPTZ;
@@ -0,0 +1,24 @@
+-- Your SQL goes here
+-- active experiments sharing a name have to be renamed or archived by hand
+-- before the index can be built, names are what people know experiments by
+-- so they are not changed here
+DO $$
+DECLARE
+    duplicates text;
+BEGIN
+    SELECT string_agg(format('%L (ids %s)', name, ids), ', ' ORDER BY name)
+    INTO duplicates
+    FROM (
+        SELECT name, string_agg(id::text, ', ' ORDER BY created_at, id) AS ids
+        FROM public.experiments
+        WHERE archived_at IS NULL
+        GROUP BY name
+        HAVING count(*) > 1
+    ) AS duplicate_names;
+    IF duplicates IS NOT NULL THEN
+        RAISE EXCEPTION 'active experiments share names, rename or archive all but one of each before running this migration: %', duplicates;
+    END IF;
+END
+$$;
+CREATE UNIQUE INDEX IF NOT EXISTS experiments_active_name_key ON public.experiments (name)
+WHERE archived_at IS NULL;
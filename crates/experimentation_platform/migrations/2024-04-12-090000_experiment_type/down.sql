@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE public.experiments DROP COLUMN IF EXISTS experiment_type;
+DROP TYPE IF EXISTS public.experiment_type;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+CREATE TYPE public.experiment_type AS ENUM (
+    'DEFAULT',
+    'FLAG'
+);
+ALTER TABLE public.experiments ADD COLUMN IF NOT EXISTS experiment_type public.experiment_type NOT NULL DEFAULT 'DEFAULT';
@@ -1,4 +1,5 @@
 use std::collections::{HashMap, HashSet};
+use std::time::Instant;
 
 use actix_web::{
     get, patch, post, put,
@@ -7,36 +8,79 @@ use actix_web::{
 };
 use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use diesel::{
+    dsl::{avg, count, sql},
     r2d2::{ConnectionManager, PooledConnection},
-    ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl,
+    result::DatabaseErrorKind,
+    sql_types::{Bool, Float8, Nullable},
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension,
+    PgConnection, PgTextExpressionMethods, QueryDsl, RunQueryDsl,
 };
 
 use service_utils::{
-    bad_argument, response_error, result as superposition, unexpected_error,
+    bad_argument, db_error, not_found, response_error, result as superposition,
+    unexpected_error,
 };
 
 use superposition_types::{SuperpositionUser, User};
 
 use reqwest::{Response, StatusCode};
+use service_utils::helpers::resolve_pagination;
+use service_utils::metrics::{
+    MetricDesc, CAC_BULK_OPERATIONS_DURATION, CAC_BULK_OPERATIONS_RETRIES,
+    EXPERIMENTS_CONCLUDED, EXPERIMENTS_CREATED, EXPERIMENTS_RAMPED,
+};
+use service_utils::result::{check_field, field_errors_result, FieldErrors};
+use service_utils::retry::RetryPolicy;
 use service_utils::service::types::{AppState, DbConnection, Tenant};
 
 use super::{
     helpers::{
-        add_variant_dimension_to_ctx, check_variant_types,
-        check_variants_override_coverage, extract_override_keys, validate_experiment,
-        validate_override_keys,
+        add_variant_dimension_to_ctx, apply_default_traffic_percentage,
+        apply_flag_variants, assign_control_traffic_percentage, assign_created_contexts,
+        calculate_total_pages, check_experiment_precondition, check_variant_types,
+        check_variants_override_coverage, choose_variant, clone_variants,
+        created_contexts_of, decode_cursor, diff_overrides, end_experiment_change,
+        ensure_experiment_names_available, experiment_create_warnings,
+        experiment_import_request, experiment_modified_error,
+        experiment_name_taken_error, extract_override_keys, fetch_active_experiments,
+        is_valid_experiment, listing_modified_window, lock_experiment,
+        matching_experiments, merge_variant_overrides, next_rollout_step,
+        parse_experiment_precondition, parse_rollout_plan, pinned_variant,
+        prefix_bulk_error, reconcile_variant, record_experiment_audit, split_cursor_page,
+        validate_context_is_conjunction, validate_experiment, validate_global_context,
+        validate_metric_observations, validate_override_keys,
+        validate_override_keys_exist, validate_rollout_plan, validate_schedule,
+        validate_traffic_percentage, validate_variant_count, validate_variant_ids,
+        validate_variant_override_types, validate_variant_traffic_percentages,
+        variant_context_id, variant_pin_of, ExperimentPrecondition,
+        EXPERIMENT_NAME_INDEX, VARIANT_DIMENSION,
     },
+    statistics::compare_with_control,
     types::{
-        AuditQueryFilters, ConcludeExperimentRequest, ContextAction, ContextBulkResponse,
-        ContextMoveReq, ContextPutReq, ExperimentCreateRequest, ExperimentCreateResponse,
-        ExperimentResponse, ExperimentsResponse, ListFilters, OverrideKeysUpdateRequest,
-        RampRequest, Variant,
+        AuditQueryFilters, CacContext, CacContextPage, ConcludeExperimentRequest,
+        ContextAction, ContextBulkResponse, ContextGetResp, ContextMoveReq,
+        ContextPutReq, ContextUpdateRequest, DefaultConfigKey, DiffQuery, DimensionName,
+        ExperimentCloneRequest, ExperimentCreateRequest, ExperimentCreateResponse,
+        ExperimentDetailResponse, ExperimentDiffResponse, ExperimentEvent,
+        ExperimentExport, ExperimentResponse, ExperimentResultsResponse,
+        ExperimentSortOn, ExperimentVerifyResponse, ExperimentWebhookPayload,
+        ExperimentsCursorResponse, ExperimentsResponse, ListFilters,
+        MatchExperimentsRequest, MetricsIngestRequest, MetricsIngestResponse,
+        OverrideKeysUpdateRequest, PaginationMode, RampRequest, ReconciliationStatus,
+        ResolveVariantRequest, ResolveVariantResponse, ResultsQuery, SortOrder, Variant,
+        VariantDiff, VariantMetricSummary, VariantType, VariantUpdateRequest,
     },
 };
 
 use crate::{
-    db::models::{EventLog, Experiment, ExperimentStatusType},
-    db::schema::{event_log::dsl as event_log, experiments::dsl as experiments},
+    db::models::{
+        EventLog, Experiment, ExperimentAudit, ExperimentMetric, ExperimentStatusType,
+        ExperimentType,
+    },
+    db::schema::{
+        event_log::dsl as event_log, experiment_audit::dsl as experiment_audit,
+        experiment_metrics::dsl as experiment_metrics, experiments::dsl as experiments,
+    },
 };
 
 use serde_json::{json, Map, Value};
@@ -45,85 +89,577 @@ pub fn endpoints(scope: Scope) -> Scope {
     scope
         .service(get_audit_logs)
         .service(create)
+        .service(bulk_create)
+        .service(match_experiments)
+        .service(import_experiment)
+        .service(clone_experiment)
         .service(conclude_handler)
         .service(list_experiments)
+        .service(get_create_request_schema)
         .service(get_experiment_handler)
+        .service(get_experiment_audit)
+        .service(export_experiment)
+        .service(verify_experiment)
+        .service(record_metrics)
+        .service(get_results)
+        .service(get_diff)
+        .service(resolve_variant)
         .service(ramp)
+        .service(advance_handler)
+        .service(pause)
+        .service(resume)
+        .service(archive)
         .service(update_overrides)
+        .service(patch_overrides)
+        .service(update_context)
+}
+
+// precondition of a change to an experiment, sent in its If-Match or
+// If-Unmodified-Since header
+fn experiment_precondition(
+    req: &HttpRequest,
+) -> superposition::Result<Option<ExperimentPrecondition>> {
+    let header = |name: &str| -> superposition::Result<Option<&str>> {
+        req.headers()
+            .get(name)
+            .map(|value| {
+                value
+                    .to_str()
+                    .map_err(|_| bad_argument!("{} header is not valid text", name))
+            })
+            .transpose()
+    };
+    parse_experiment_precondition(header("If-Match")?, header("If-Unmodified-Since")?)
+}
+
+fn generate_experiment_id(state: &AppState) -> superposition::Result<String> {
+    state.id_generator.generate_id().map_err(|err| {
+        log::error!("failed to generate an experiment id: {err}");
+        response_error!(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "could not generate an experiment id, retry after some time"
+        )
+    })
+}
+
+// upstream error bodies are echoed back to clients, cap them to keep responses small
+const CAC_ERROR_BODY_LIMIT: usize = 512;
+
+fn truncate_upstream_body(body: &str) -> String {
+    if body.chars().count() > CAC_ERROR_BODY_LIMIT {
+        let truncated: String = body.chars().take(CAC_ERROR_BODY_LIMIT).collect();
+        format!("{truncated}...")
+    } else {
+        body.to_string()
+    }
 }
 
 async fn parse_error_response(
     response: reqwest::Response,
 ) -> superposition::Result<(StatusCode, superposition::ErrorResponse)> {
     let status_code = response.status();
-    let error_response = response
-        .json::<superposition::ErrorResponse>()
-        .await
-        .map_err(|err: reqwest::Error| {
-            log::error!("failed to parse error response: {}", err);
-            unexpected_error!("Something went wrong")
-        })?;
+    let body = response.text().await.map_err(|err: reqwest::Error| {
+        log::error!("failed to read error response body: {}", err);
+        response_error!(
+            StatusCode::BAD_GATEWAY,
+            format!("CAC responded with status {status_code} and an unreadable body")
+        )
+    })?;
+    let error_response = serde_json::from_str::<superposition::ErrorResponse>(&body)
+        .unwrap_or_else(|_| {
+            superposition::ErrorResponse::new(truncate_upstream_body(&body))
+        });
     log::error!("http call to CAC failed with err {:?}", error_response);
 
     Ok((status_code, error_response))
 }
 
-async fn process_cac_http_response(
+fn cac_request_error(err: reqwest::Error) -> superposition::AppError {
+    if err.is_timeout() {
+        response_error!(
+            StatusCode::GATEWAY_TIMEOUT,
+            format!("request to CAC timed out: {err}")
+        )
+    } else {
+        response_error!(
+            StatusCode::BAD_GATEWAY,
+            format!("failed to reach CAC: {err}")
+        )
+    }
+}
+
+pub async fn process_cac_http_response(
     response: Result<Response, reqwest::Error>,
 ) -> superposition::Result<Vec<ContextBulkResponse>> {
-    let internal_server_error = unexpected_error!("Something went wrong.");
     match response {
         Ok(res) if res.status().is_success() => {
             res.json::<Vec<ContextBulkResponse>>().await.map_err(|err| {
                 log::error!("failed to parse JSON response with error: {}", err);
-                internal_server_error
+                response_error!(
+                    StatusCode::BAD_GATEWAY,
+                    format!("failed to parse the response from CAC: {err}")
+                )
             })
         }
         Ok(res) => {
             log::error!("http call to CAC failed with status_code {}", res.status());
 
-            if res.status().is_client_error() {
-                let (status_code, error_response) = parse_error_response(res).await?;
+            let (status_code, error_response) = parse_error_response(res).await?;
+            if status_code.is_client_error() {
                 Err(response_error!(status_code, error_response.message))
             } else {
-                Err(internal_server_error)
+                Err(response_error!(
+                    status_code,
+                    format!(
+                        "CAC bulk operation failed with status {}: {}",
+                        status_code, error_response.message
+                    )
+                ))
             }
         }
         Err(err) => {
             log::error!("reqwest failed to send request to CAC with error: {}", err);
-            Err(internal_server_error)
+            Err(cac_request_error(err))
         }
     }
 }
 
-#[post("")]
-async fn create(
-    state: Data<AppState>,
-    req: web::Json<ExperimentCreateRequest>,
-    db_conn: DbConnection,
-    tenant: Tenant,
-    user: User,
-) -> superposition::Result<Json<ExperimentCreateResponse>> {
-    use crate::db::schema::experiments::dsl::experiments;
-    let mut variants = req.variants.to_vec();
-    let DbConnection(mut conn) = db_conn;
+pub async fn call_cac_bulk_operations(
+    state: &Data<AppState>,
+    tenant: &Tenant,
+    user: &User,
+    operations: &Vec<ContextAction>,
+    idempotency_key: Option<String>,
+) -> Result<Response, reqwest::Error> {
+    let url = state.cac_url("context/bulk-operations");
+    let request = state
+        .http_client
+        .put(url)
+        .header("x-tenant", tenant.as_str())
+        .header(
+            "Authorization",
+            format!("{} {}", user.get_auth_type(), user.get_auth_token()),
+        );
+    // only operations carrying an idempotency key are safe to send again
+    let retry_policy = match &idempotency_key {
+        Some(_) => state.cac_retry_policy,
+        None => RetryPolicy::no_retry(),
+    };
+    let request = match idempotency_key {
+        Some(key) => request.header("Idempotency-Key", key),
+        None => request,
+    };
+
+    let mut attempt = 1;
+    loop {
+        let attempt_request = request
+            .try_clone()
+            .expect("requests without a streaming body can always be cloned");
+        let started_at = Instant::now();
+        let response = attempt_request.json(operations).send().await;
+        let status = match &response {
+            Ok(res) => res.status().as_u16().to_string(),
+            Err(_) => String::from("error"),
+        };
+        state.metrics.observe(
+            &CAC_BULK_OPERATIONS_DURATION,
+            &[("status", &status), ("tenant", tenant.as_str())],
+            started_at.elapsed().as_secs_f64(),
+        );
+
+        let retryable = match &response {
+            Ok(res) => res.status().is_server_error(),
+            Err(err) => err.is_connect() || err.is_timeout(),
+        };
+        if !retryable || attempt >= retry_policy.max_attempts {
+            return response;
+        }
+
+        let delay = retry_policy.delay_after(attempt);
+        log::warn!(
+            "CAC bulk operations attempt {attempt} failed with {status}, retrying in {}ms",
+            delay.as_millis()
+        );
+        state.metrics.inc_counter(
+            &CAC_BULK_OPERATIONS_RETRIES,
+            &[("status", &status), ("tenant", tenant.as_str())],
+        );
+        actix_web::rt::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+fn record_experiment_metric(
+    state: &Data<AppState>,
+    metric: &MetricDesc,
+    experiment: &Experiment,
+    tenant: &Tenant,
+) {
+    state.metrics.inc_counter(
+        metric,
+        &[
+            ("status", &format!("{:?}", experiment.status)),
+            ("tenant", tenant.as_str()),
+        ],
+    );
+}
+
+fn notify_experiment_event(
+    state: &Data<AppState>,
+    tenant: &Tenant,
+    experiment: &Experiment,
+    event: ExperimentEvent,
+    user: &User,
+) {
+    let payload = ExperimentWebhookPayload {
+        experiment_id: experiment.id.clone(),
+        name: experiment.name.clone(),
+        event,
+        actor: user.get_email(),
+        timestamp: Utc::now(),
+    };
+    state
+        .webhooks
+        .notify(&state.http_client, tenant.as_str(), &payload);
+}
+
+// schemas of the default config keys, keyed on the key
+async fn fetch_default_config_schemas(
+    state: &Data<AppState>,
+    tenant: &Tenant,
+    user: &User,
+) -> superposition::Result<HashMap<String, Value>> {
+    let default_configs = fetch_default_configs(state, tenant, user).await?;
+    Ok(default_configs
+        .into_iter()
+        .map(|config| (config.key, config.schema))
+        .collect())
+}
+
+async fn fetch_default_configs(
+    state: &Data<AppState>,
+    tenant: &Tenant,
+    user: &User,
+) -> superposition::Result<Vec<DefaultConfigKey>> {
+    let url = state.cac_url("default-config");
+    let response = state
+        .http_client
+        .get(url)
+        .header("x-tenant", tenant.as_str())
+        .header(
+            "Authorization",
+            format!("{} {}", user.get_auth_type(), user.get_auth_token()),
+        )
+        .send()
+        .await;
+
+    match response {
+        Ok(res) if res.status().is_success() => {
+            res.json::<Vec<DefaultConfigKey>>().await.map_err(|err| {
+                log::error!("failed to parse default config response: {}", err);
+                response_error!(
+                    StatusCode::BAD_GATEWAY,
+                    format!("failed to parse default config from CAC: {err}")
+                )
+            })
+        }
+        Ok(res) => {
+            let (status_code, error_response) = parse_error_response(res).await?;
+            Err(response_error!(
+                status_code,
+                format!(
+                    "failed to fetch default config from CAC: {}",
+                    error_response.message
+                )
+            ))
+        }
+        Err(err) => {
+            log::error!("reqwest failed to fetch default config with error: {}", err);
+            Err(cac_request_error(err))
+        }
+    }
+}
+
+async fn fetch_dimension_names(
+    state: &Data<AppState>,
+    tenant: &Tenant,
+    user: &User,
+) -> superposition::Result<HashSet<String>> {
+    let url = state.cac_url("dimension");
+    let response = state
+        .http_client
+        .get(url)
+        .header("x-tenant", tenant.as_str())
+        .header(
+            "Authorization",
+            format!("{} {}", user.get_auth_type(), user.get_auth_token()),
+        )
+        .send()
+        .await;
+
+    match response {
+        Ok(res) if res.status().is_success() => res
+            .json::<Vec<DimensionName>>()
+            .await
+            .map(|dimensions| {
+                dimensions
+                    .into_iter()
+                    .map(|dimension| dimension.dimension)
+                    .collect()
+            })
+            .map_err(|err| {
+                log::error!("failed to parse dimensions response: {}", err);
+                response_error!(
+                    StatusCode::BAD_GATEWAY,
+                    format!("failed to parse dimensions from CAC: {err}")
+                )
+            }),
+        Ok(res) => {
+            let (status_code, error_response) = parse_error_response(res).await?;
+            Err(response_error!(
+                status_code,
+                format!(
+                    "failed to fetch dimensions from CAC: {}",
+                    error_response.message
+                )
+            ))
+        }
+        Err(err) => {
+            log::error!("reqwest failed to fetch dimensions with error: {}", err);
+            Err(cac_request_error(err))
+        }
+    }
+}
+
+// contexts conditioned on a dimension CAC does not know never resolve, so the
+// variant dimension is registered before the first experiment of a tenant
+async fn ensure_variant_dimension(
+    state: &Data<AppState>,
+    tenant: &Tenant,
+    user: &User,
+) -> superposition::Result<()> {
+    let is_known = state
+        .variant_dimension_tenants
+        .lock()
+        .map_or(false, |tenants| tenants.contains(tenant.as_str()));
+    if is_known {
+        return Ok(());
+    }
+
+    let dimensions = fetch_dimension_names(state, tenant, user).await?;
+    if !dimensions.contains(VARIANT_DIMENSION) {
+        log::info!(
+            "{VARIANT_DIMENSION} dimension missing in CAC for tenant {}, creating it",
+            tenant.as_str()
+        );
+        let url = state.cac_url("dimension");
+        let response = state
+            .http_client
+            .put(url)
+            .header("x-tenant", tenant.as_str())
+            .header(
+                "Authorization",
+                format!("{} {}", user.get_auth_type(), user.get_auth_token()),
+            )
+            .json(&json!({
+                "dimension": VARIANT_DIMENSION,
+                "priority": 1,
+                "schema": { "type": "string", "pattern": ".*" }
+            }))
+            .send()
+            .await;
+
+        match response {
+            Ok(res) if res.status().is_success() => (),
+            Ok(res) => {
+                let (status_code, error_response) = parse_error_response(res).await?;
+                return Err(response_error!(
+                    status_code,
+                    format!(
+                        "{VARIANT_DIMENSION} dimension is not registered in CAC and could not be created: {}",
+                        error_response.message
+                    )
+                ));
+            }
+            Err(err) => {
+                log::error!(
+                    "reqwest failed to create {VARIANT_DIMENSION} dimension with error: {}",
+                    err
+                );
+                return Err(cac_request_error(err));
+            }
+        }
+    }
+
+    if let Ok(mut tenants) = state.variant_dimension_tenants.lock() {
+        tenants.insert(tenant.as_str().to_string());
+    }
+    Ok(())
+}
+
+async fn fetch_cac_context(
+    state: &Data<AppState>,
+    tenant: &Tenant,
+    user: &User,
+    context_id: &str,
+) -> superposition::Result<Option<ContextGetResp>> {
+    let url = state.cac_url(&format!("context/{context_id}"));
+    let response = state
+        .http_client
+        .get(url)
+        .header("x-tenant", tenant.as_str())
+        .header(
+            "Authorization",
+            format!("{} {}", user.get_auth_type(), user.get_auth_token()),
+        )
+        .send()
+        .await;
 
+    match response {
+        Ok(res) if res.status() == StatusCode::NOT_FOUND => Ok(None),
+        Ok(res) if res.status().is_success() => {
+            res.json::<ContextGetResp>().await.map(Some).map_err(|err| {
+                log::error!("failed to parse context response: {}", err);
+                response_error!(
+                    StatusCode::BAD_GATEWAY,
+                    format!("failed to parse context {context_id} from CAC: {err}")
+                )
+            })
+        }
+        Ok(res) => {
+            let (status_code, error_response) = parse_error_response(res).await?;
+            Err(response_error!(
+                status_code,
+                format!(
+                    "failed to fetch context {context_id} from CAC: {}",
+                    error_response.message
+                )
+            ))
+        }
+        Err(err) => {
+            log::error!("reqwest failed to fetch context with error: {}", err);
+            Err(cac_request_error(err))
+        }
+    }
+}
+
+/// Every CAC context conditioned on the variant dimension, read page by page.
+pub async fn fetch_variant_contexts(
+    state: &Data<AppState>,
+    tenant: &Tenant,
+    user: &User,
+) -> superposition::Result<Vec<CacContext>> {
+    let url = state.cac_url("context");
+    let mut contexts = Vec::new();
+    let mut page = 1;
+    loop {
+        let response = state
+            .http_client
+            .get(url.clone())
+            .query(&[
+                ("dimension", VARIANT_DIMENSION.to_string()),
+                ("page", page.to_string()),
+                ("size", state.max_page_size.to_string()),
+            ])
+            .header("x-tenant", tenant.as_str())
+            .header(
+                "Authorization",
+                format!("{} {}", user.get_auth_type(), user.get_auth_token()),
+            )
+            .send()
+            .await;
+        let context_page = match response {
+            Ok(res) if res.status().is_success() => {
+                res.json::<CacContextPage>().await.map_err(|err| {
+                    log::error!("failed to parse contexts response: {}", err);
+                    response_error!(
+                        StatusCode::BAD_GATEWAY,
+                        format!("failed to parse contexts from CAC: {err}")
+                    )
+                })?
+            }
+            Ok(res) => {
+                let (status_code, error_response) = parse_error_response(res).await?;
+                return Err(response_error!(
+                    status_code,
+                    format!(
+                        "failed to list contexts from CAC: {}",
+                        error_response.message
+                    )
+                ));
+            }
+            Err(err) => {
+                log::error!("reqwest failed to list contexts with error: {}", err);
+                return Err(cac_request_error(err));
+            }
+        };
+        contexts.extend(context_page.data);
+        if page >= context_page.total_pages {
+            return Ok(contexts);
+        }
+        page += 1;
+    }
+}
+
+fn validate_create_request(
+    req: &ExperimentCreateRequest,
+    default_config_schemas: &HashMap<String, Value>,
+) -> superposition::Result<Vec<String>> {
+    let mut field_errors = FieldErrors::new();
+    let variants = &req.variants;
+
+    let override_keys = check_field(
+        &mut field_errors,
+        "variants",
+        validate_variants(variants, req.experiment_type, default_config_schemas),
+    )?;
+    // the traffic checks split it between the variants, so they need valid ones
+    if override_keys.is_some() {
+        check_field(
+            &mut field_errors,
+            "traffic_percentage",
+            validate_traffic_percentage(
+                req.traffic_percentage.unwrap_or_default(),
+                variants,
+            ),
+        )?;
+        check_field(
+            &mut field_errors,
+            "rollout_plan",
+            validate_rollout_plan(&req.rollout_plan, variants),
+        )?;
+    }
+    check_field(
+        &mut field_errors,
+        "context",
+        validate_experiment_context(&req.context, req.allow_global),
+    )?;
+    check_field(
+        &mut field_errors,
+        "schedule",
+        validate_schedule(req.starts_at, req.ends_at, Utc::now()),
+    )?;
+
+    field_errors_result(field_errors)?;
+    Ok(override_keys.unwrap_or_default())
+}
+
+fn validate_variants(
+    variants: &Vec<Variant>,
+    experiment_type: ExperimentType,
+    default_config_schemas: &HashMap<String, Value>,
+) -> superposition::Result<Vec<String>> {
     // Checking if experiment has exactly 1 control variant, and
     // atleast 1 experimental variant
-    check_variant_types(&variants)?;
+    check_variant_types(variants, experiment_type)?;
+    validate_variant_ids(variants)?;
     let unique_override_keys: Vec<String> = extract_override_keys(&variants[0].overrides)
         .into_iter()
         .collect();
 
-    let unique_ids_of_variants_from_req: HashSet<&str> =
-        HashSet::from_iter(variants.iter().map(|v| v.id.as_str()));
-
-    if unique_ids_of_variants_from_req.len() != variants.len() {
-        return Err(bad_argument!(
-            "Variant ids are expected to be unique. Provide unqiue variant IDs"
-        ));
-    }
     validate_override_keys(&unique_override_keys)?;
+    let default_config_keys = default_config_schemas.keys().cloned().collect();
+    validate_override_keys_exist(&unique_override_keys, &default_config_keys)?;
 
     // Checking if all the variants are overriding the mentioned keys
     let variant_overrides = variants
@@ -139,39 +675,37 @@ async fn create(
             )
         );
     }
+    validate_variant_override_types(variants, default_config_schemas)?;
+    validate_variant_traffic_percentages(variants)?;
+
+    Ok(unique_override_keys)
+}
 
+fn validate_experiment_context(
+    context: &Value,
+    allow_global: bool,
+) -> superposition::Result<()> {
     // Checking if context is a key-value pair map
-    if !req.context.is_object() {
+    if !context.is_object() {
         return Err(bad_argument!("Context should be map of key value pairs."));
     }
+    validate_global_context(context, allow_global)?;
+    validate_context_is_conjunction(context)
+}
 
-    // validating experiment against other active experiments based on permission flags
-    let flags = &state.experimentation_flags;
-    let (valid, reason) = validate_experiment(
-        &req.context,
-        &unique_override_keys,
-        None,
-        &flags,
-        &mut conn,
-    )?;
-    if !valid {
-        return Err(bad_argument!(reason));
-    }
-
-    // generating snowflake id for experiment
-    let mut snowflake_generator = state.snowflake_generator.lock().unwrap();
-    let experiment_id = snowflake_generator.real_time_generate();
-
-    //create overrides in CAC, if successfull then create experiment in DB
+fn build_variant_context_operations(
+    experiment_id: &str,
+    context: &Value,
+    variants: &mut Vec<Variant>,
+) -> superposition::Result<Vec<ContextAction>> {
     let mut cac_operations: Vec<ContextAction> = vec![];
-    for variant in &mut variants {
-        let variant_id = experiment_id.to_string() + "-" + &variant.id;
+    for variant in variants {
+        let variant_id = variant_context_id(experiment_id, &variant.id);
 
         // updating variant.id to => experiment_id + variant.id
         variant.id = variant_id.to_string();
 
-        let updated_cacccontext =
-            add_variant_dimension_to_ctx(&req.context, variant_id.to_string())?;
+        let updated_cacccontext = add_variant_dimension_to_ctx(context, variant_id)?;
 
         let payload = ContextPutReq {
             context: updated_cacccontext
@@ -187,81 +721,548 @@ async fn create(
         };
         cac_operations.push(ContextAction::PUT(payload));
     }
+    Ok(cac_operations)
+}
 
-    // creating variants' context in CAC
-    let http_client = reqwest::Client::new();
-    let url = state.cac_host.clone() + "/context/bulk-operations";
-
-    // Step 1: Perform the HTTP request and handle errors
-    let response = http_client
-        .put(&url)
-        .header("x-tenant", tenant.as_str())
-        .header(
-            "Authorization",
-            format!("{} {}", user.get_auth_type(), user.get_auth_token()),
-        )
-        .json(&cac_operations)
-        .send()
-        .await;
+// deletes the variant contexts created in CAC for experiments that could not be
+// stored, so that they are not left behind as orphans
+async fn rollback_created_contexts(
+    state: &Data<AppState>,
+    tenant: &Tenant,
+    user: &User,
+    context_ids: Vec<String>,
+) {
+    if context_ids.is_empty() {
+        return;
+    }
+    let rollback_operations = context_ids
+        .iter()
+        .cloned()
+        .map(ContextAction::DELETE)
+        .collect::<Vec<ContextAction>>();
+    let response =
+        call_cac_bulk_operations(state, tenant, user, &rollback_operations, None).await;
+    match process_cac_http_response(response).await {
+        Ok(_) => log::info!(
+            "rolled back CAC contexts {} after experiment creation failed",
+            context_ids.join(",")
+        ),
+        Err(rollback_err) => log::error!(
+            "failed to rollback CAC contexts {} after experiment creation failed, they have to be deleted manually: {}",
+            context_ids.join(","),
+            rollback_err
+        ),
+    }
+}
 
-    // directly return an error response if not a 200 response
-    let created_contexts = process_cac_http_response(response).await?.into_iter().fold(
-        Vec::new(),
-        |mut acc, item| {
-            if let ContextBulkResponse::PUT(context) = item {
-                acc.push(context);
-            } else {
-                log::error!("Unexpected response item: {:?}", item);
+// contexts created by a bulk call, to roll back whatever else goes wrong
+fn created_context_ids(responses: &[ContextBulkResponse]) -> Vec<String> {
+    responses
+        .iter()
+        .filter_map(|response| match response {
+            ContextBulkResponse::PUT(created_context) => {
+                Some(created_context.context_id.clone())
             }
-            acc
-        },
-    );
-    for i in 0..created_contexts.len() {
-        let created_context = &created_contexts[i];
-        variants[i].context_id = Some(created_context.context_id.clone());
-        variants[i].override_id = Some(created_context.override_id.clone());
-    }
+            _ => None,
+        })
+        .collect()
+}
 
-    // inserting experiment in db
-    let new_experiment = Experiment {
+fn new_experiment(
+    experiment_id: String,
+    req: &ExperimentCreateRequest,
+    override_keys: Vec<String>,
+    variants: Vec<Variant>,
+    user: &User,
+) -> superposition::Result<Experiment> {
+    Ok(Experiment {
         id: experiment_id,
         created_by: user.get_email(),
         created_at: Utc::now(),
         last_modified: Utc::now(),
         name: req.name.to_string(),
-        override_keys: unique_override_keys.to_vec(),
-        traffic_percentage: 0,
+        override_keys,
+        traffic_percentage: req.traffic_percentage.unwrap_or_default() as i32,
         status: ExperimentStatusType::CREATED,
         context: req.context.clone(),
-        variants: serde_json::to_value(variants).unwrap(),
+        variants: serde_json::to_value(variants).inspect_err(|e| {
+            log::error!("failed to serialize variants to json with error: {e}")
+        })?,
         last_modified_by: user.get_email(),
         chosen_variant: None,
+        archived_at: None,
+        starts_at: req.starts_at,
+        ends_at: req.ends_at,
+        rollout_plan: match req.rollout_plan.is_empty() {
+            true => None,
+            false => Some(serde_json::to_value(&req.rollout_plan).inspect_err(|e| {
+                log::error!("failed to serialize rollout plan to json with error: {e}")
+            })?),
+        },
+        rollout_step: None,
+        rollout_step_started_at: None,
+        experiment_type: req.experiment_type,
+    })
+}
+
+fn insert_experiments(
+    new_experiments: &Vec<Experiment>,
+    user: &User,
+    conn: &mut PgConnection,
+) -> superposition::Result<Vec<Experiment>> {
+    use crate::db::schema::experiments::dsl::experiments;
+
+    conn.transaction::<_, superposition::AppError, _>(|transaction_conn| {
+        let mut inserted_experiments = Vec::with_capacity(new_experiments.len());
+        for new_experiment in new_experiments {
+            // a name taken by a concurrent create only shows up on insert
+            let inserted_experiment: Experiment = diesel::insert_into(experiments)
+                .values(new_experiment)
+                .get_result(transaction_conn)
+                .map_err(|err| match err {
+                    diesel::result::Error::DatabaseError(
+                        DatabaseErrorKind::UniqueViolation,
+                        ref info,
+                    ) if info.constraint_name() == Some(EXPERIMENT_NAME_INDEX) => {
+                        experiment_name_taken_error(
+                            &new_experiment.name,
+                            "created concurrently",
+                        )
+                    }
+                    err => db_error!(err),
+                })?;
+            record_experiment_audit(
+                &inserted_experiment.id,
+                "CREATE",
+                None,
+                inserted_experiment.status,
+                user.get_email(),
+                transaction_conn,
+            )?;
+            inserted_experiments.push(inserted_experiment);
+        }
+        Ok(inserted_experiments)
+    })
+}
+
+// every create fans out to the CAC bulk-operations endpoint
+fn rate_limited_response(state: &AppState, tenant: &Tenant) -> Option<HttpResponse> {
+    let retry_after = state
+        .experiment_create_rate_limiter
+        .try_acquire(tenant.as_str())
+        .err()?;
+    log::warn!(
+        "experiment creation rate limit exceeded for {}",
+        tenant.as_str()
+    );
+    Some(
+        HttpResponse::TooManyRequests()
+            .insert_header((
+                "Retry-After",
+                (retry_after.as_secs_f64().ceil() as u64).max(1).to_string(),
+            ))
+            .json(superposition::ErrorResponse::new(
+                "Too many experiments created, retry after some time",
+            )),
+    )
+}
+
+#[post("")]
+async fn create(
+    state: Data<AppState>,
+    req: web::Json<ExperimentCreateRequest>,
+    db_conn: DbConnection,
+    tenant: Tenant,
+    user: User,
+) -> superposition::Result<HttpResponse> {
+    let DbConnection(mut conn) = db_conn;
+
+    if let Some(response) = rate_limited_response(&state, &tenant) {
+        return Ok(response);
+    }
+
+    let mut req = req.into_inner();
+    apply_flag_variants(&mut req)?;
+    let default_traffic_percentage =
+        apply_default_traffic_percentage(&mut req, state.default_traffic_percentage);
+    let inserted_experiment =
+        create_experiment(&state, &req, &tenant, &user, &mut conn).await?;
+    let mut response = ExperimentCreateResponse::from(inserted_experiment);
+    response.warnings = experiment_create_warnings(&req);
+    response.default_traffic_percentage = default_traffic_percentage;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+async fn create_experiment(
+    state: &Data<AppState>,
+    req: &ExperimentCreateRequest,
+    tenant: &Tenant,
+    user: &User,
+    conn: &mut PgConnection,
+) -> superposition::Result<Experiment> {
+    // checked before anything is asked of CAC
+    validate_variant_count(&req.variants, state.max_variants_per_experiment)?;
+    let default_config_schemas =
+        fetch_default_config_schemas(state, tenant, user).await?;
+    let unique_override_keys = validate_create_request(req, &default_config_schemas)?;
+    ensure_experiment_names_available(&[req.name.as_str()], conn)?;
+    ensure_variant_dimension(state, tenant, user).await?;
+
+    // validating experiment against other active experiments based on permission flags
+    let flags = &state.experimentation_flags;
+    let (valid, reason) =
+        validate_experiment(&req.context, &unique_override_keys, None, flags, conn)?;
+    if !valid {
+        return Err(bad_argument!(reason));
+    }
+
+    // generating id for experiment
+    let experiment_id = generate_experiment_id(state)?;
+
+    //create overrides in CAC, if successfull then create experiment in DB
+    let mut variants = req.variants.to_vec();
+    assign_control_traffic_percentage(&mut variants);
+    let cac_operations =
+        build_variant_context_operations(&experiment_id, &req.context, &mut variants)?;
+
+    // creating variants' context in CAC, keyed on the experiment id so that
+    // retrying this call cannot create the contexts twice
+    let response = call_cac_bulk_operations(
+        state,
+        tenant,
+        user,
+        &cac_operations,
+        Some(format!("experiment-{experiment_id}-create")),
+    )
+    .await;
+
+    // directly return an error response if not a 200 response
+    let responses = process_cac_http_response(response).await?;
+    let created_context_ids = created_context_ids(&responses);
+    if let Err(err) =
+        created_contexts_of(&cac_operations, responses).and_then(|created_contexts| {
+            assign_created_contexts(&mut variants, &created_contexts)
+        })
+    {
+        rollback_created_contexts(state, tenant, user, created_context_ids).await;
+        return Err(err);
+    }
+
+    // inserting experiment in db
+    let inserted_experiment =
+        match new_experiment(experiment_id, req, unique_override_keys, variants, user)
+            .and_then(|new_experiment| {
+                insert_experiments(&vec![new_experiment], user, conn)
+            }) {
+            Ok(mut inserted_experiments) => inserted_experiments.remove(0),
+            Err(err) => {
+                rollback_created_contexts(state, tenant, user, created_context_ids).await;
+                return Err(err);
+            }
+        };
+    record_experiment_metric(state, &EXPERIMENTS_CREATED, &inserted_experiment, tenant);
+    notify_experiment_event(
+        state,
+        tenant,
+        &inserted_experiment,
+        ExperimentEvent::Created,
+        user,
+    );
+
+    Ok(inserted_experiment)
+}
+
+// previews the experiments a new one on this context would conflict with
+#[post("/match")]
+async fn match_experiments(
+    req: web::Json<MatchExperimentsRequest>,
+    db_conn: DbConnection,
+) -> superposition::Result<Json<Vec<ExperimentResponse>>> {
+    let DbConnection(mut conn) = db_conn;
+    let MatchExperimentsRequest { context } = req.into_inner();
+
+    let active_experiments = fetch_active_experiments(None, &mut conn)?;
+    let matching = matching_experiments(active_experiments, &context)?;
+
+    Ok(Json(
+        matching.into_iter().map(ExperimentResponse::from).collect(),
+    ))
+}
+
+#[get("/{id}/export")]
+async fn export_experiment(
+    params: web::Path<String>,
+    db_conn: DbConnection,
+) -> superposition::Result<Json<ExperimentExport>> {
+    let DbConnection(mut conn) = db_conn;
+    let experiment_id = params.into_inner();
+
+    let experiment = get_experiment(&experiment_id, &mut conn)?;
+    let rollout_plan = parse_rollout_plan(&experiment)?;
+    let variants: Vec<Variant> =
+        serde_json::from_value(experiment.variants).map_err(|e| {
+            log::error!("failed to parse variants of experiment {experiment_id}: {e}");
+            unexpected_error!("Something went wrong, failed to export experiment")
+        })?;
+
+    Ok(Json(ExperimentExport {
+        name: experiment.name,
+        context: experiment.context,
+        override_keys: experiment.override_keys,
+        traffic_percentage: experiment.traffic_percentage as i64,
+        variants: clone_variants(&experiment_id, variants),
+        rollout_plan,
+        experiment_type: experiment.experiment_type,
+    }))
+}
+
+// recreates an exported experiment under a new id, with its own CAC contexts in
+// this tenant
+#[post("/import")]
+async fn import_experiment(
+    state: Data<AppState>,
+    req: web::Json<ExperimentExport>,
+    db_conn: DbConnection,
+    tenant: Tenant,
+    user: User,
+) -> superposition::Result<HttpResponse> {
+    let DbConnection(mut conn) = db_conn;
+
+    if let Some(response) = rate_limited_response(&state, &tenant) {
+        return Ok(response);
+    }
+
+    let import_request = experiment_import_request(req.into_inner())?;
+
+    let inserted_experiment =
+        create_experiment(&state, &import_request, &tenant, &user, &mut conn).await?;
+    let mut response = ExperimentCreateResponse::from(inserted_experiment);
+    response.warnings = experiment_create_warnings(&import_request);
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[post("/{id}/clone")]
+async fn clone_experiment(
+    state: Data<AppState>,
+    params: web::Path<String>,
+    req: web::Json<ExperimentCloneRequest>,
+    db_conn: DbConnection,
+    tenant: Tenant,
+    user: User,
+) -> superposition::Result<HttpResponse> {
+    let DbConnection(mut conn) = db_conn;
+    let experiment_id = params.into_inner();
+
+    if let Some(response) = rate_limited_response(&state, &tenant) {
+        return Ok(response);
+    }
+
+    let source_experiment = get_experiment(&experiment_id, &mut conn)?;
+    let rollout_plan = parse_rollout_plan(&source_experiment)?;
+    let source_variants: Vec<Variant> =
+        serde_json::from_value(source_experiment.variants).map_err(|e| {
+            log::error!("failed to parse variants of experiment {experiment_id}: {e}");
+            unexpected_error!("Something went wrong, failed to clone experiment")
+        })?;
+
+    // the clone goes through the same validations as a new experiment and gets
+    // its own CAC contexts, traffic starts from zero as with any CREATED experiment
+    let clone_request = ExperimentCreateRequest {
+        name: req.into_inner().name,
+        traffic_percentage: Some(0),
+        allow_global: source_experiment
+            .context
+            .as_object()
+            .map_or(false, |context| context.is_empty()),
+        context: source_experiment.context,
+        variants: clone_variants(&experiment_id, source_variants),
+        experiment_type: source_experiment.experiment_type,
+        flag_key: None,
+        starts_at: None,
+        ends_at: None,
+        rollout_plan,
     };
 
-    let mut inserted_experiments = diesel::insert_into(experiments)
-        .values(&new_experiment)
-        .get_results(&mut conn)?;
+    let inserted_experiment =
+        create_experiment(&state, &clone_request, &tenant, &user, &mut conn).await?;
+    let mut response = ExperimentCreateResponse::from(inserted_experiment);
+    response.warnings = experiment_create_warnings(&clone_request);
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[post("/bulk")]
+async fn bulk_create(
+    state: Data<AppState>,
+    req: web::Json<Vec<ExperimentCreateRequest>>,
+    db_conn: DbConnection,
+    tenant: Tenant,
+    user: User,
+) -> superposition::Result<HttpResponse> {
+    let DbConnection(mut conn) = db_conn;
+
+    if let Some(response) = rate_limited_response(&state, &tenant) {
+        return Ok(response);
+    }
+
+    let mut requests = req.into_inner();
+
+    if requests.is_empty() {
+        return Err(bad_argument!(
+            "No experiments found in request. Provide at least one experiment to create"
+        ));
+    }
 
-    let inserted_experiment: Experiment = inserted_experiments.remove(0);
-    let response = ExperimentCreateResponse::from(inserted_experiment);
+    for (idx, request) in requests.iter_mut().enumerate() {
+        apply_flag_variants(request).map_err(|err| prefix_bulk_error(idx, err))?;
+        validate_variant_count(&request.variants, state.max_variants_per_experiment)
+            .map_err(|err| prefix_bulk_error(idx, err))?;
+    }
+    let default_config_schemas =
+        fetch_default_config_schemas(&state, &tenant, &user).await?;
+    let names = requests
+        .iter()
+        .map(|request| request.name.as_str())
+        .collect::<Vec<&str>>();
+    ensure_experiment_names_available(&names, &mut conn)?;
+    ensure_variant_dimension(&state, &tenant, &user).await?;
+    let flags = &state.experimentation_flags;
 
-    return Ok(Json(response));
+    // each experiment is validated against the active ones and the ones before it in the batch
+    let mut active_experiments = fetch_active_experiments(None, &mut conn)?;
+    let mut new_experiments: Vec<Experiment> = Vec::with_capacity(requests.len());
+    let mut cac_operations: Vec<ContextAction> = vec![];
+    let mut batch_variants: Vec<Vec<Variant>> = Vec::with_capacity(requests.len());
+    let default_traffic_percentages = requests
+        .iter_mut()
+        .map(|request| {
+            apply_default_traffic_percentage(request, state.default_traffic_percentage)
+        })
+        .collect::<Vec<Option<i64>>>();
+    for (idx, request) in requests.iter().enumerate() {
+        let unique_override_keys =
+            validate_create_request(request, &default_config_schemas)
+                .map_err(|err| prefix_bulk_error(idx, err))?;
+        let (valid, reason) = is_valid_experiment(
+            &request.context,
+            &unique_override_keys,
+            flags,
+            &active_experiments,
+        )?;
+        if !valid {
+            return Err(bad_argument!("experiment at index {}: {}", idx, reason));
+        }
+
+        let experiment_id = generate_experiment_id(&state)?;
+        let mut variants = request.variants.to_vec();
+        assign_control_traffic_percentage(&mut variants);
+        cac_operations.extend(build_variant_context_operations(
+            &experiment_id,
+            &request.context,
+            &mut variants,
+        )?);
+
+        let experiment = new_experiment(
+            experiment_id,
+            request,
+            unique_override_keys,
+            variants.clone(),
+            &user,
+        )?;
+        active_experiments.push(experiment.clone());
+        new_experiments.push(experiment);
+        batch_variants.push(variants);
+    }
+
+    // creating every variant context of the batch in a single CAC bulk operation
+    let response = call_cac_bulk_operations(
+        &state,
+        &tenant,
+        &user,
+        &cac_operations,
+        Some(format!("experiment-{}-bulk-create", new_experiments[0].id)),
+    )
+    .await;
+    let responses = process_cac_http_response(response).await?;
+    let created_context_ids = created_context_ids(&responses);
+    // the operations of every experiment were sent back to back, so the
+    // responses are matched across the whole batch
+    let variant_counts = batch_variants.iter().map(Vec::len).collect::<Vec<usize>>();
+    let mut all_variants = batch_variants
+        .into_iter()
+        .flatten()
+        .collect::<Vec<Variant>>();
+    if let Err(err) =
+        created_contexts_of(&cac_operations, responses).and_then(|created_contexts| {
+            assign_created_contexts(&mut all_variants, &created_contexts)
+        })
+    {
+        rollback_created_contexts(&state, &tenant, &user, created_context_ids).await;
+        return Err(err);
+    }
+    let mut all_variants = all_variants.into_iter();
+    for (experiment, variant_count) in new_experiments.iter_mut().zip(variant_counts) {
+        let variants = all_variants
+            .by_ref()
+            .take(variant_count)
+            .collect::<Vec<Variant>>();
+        experiment.variants = serde_json::to_value(variants).inspect_err(|e| {
+            log::error!("failed to serialize variants to json with error: {e}")
+        })?;
+    }
+
+    let inserted_experiments =
+        match insert_experiments(&new_experiments, &user, &mut conn) {
+            Ok(inserted_experiments) => inserted_experiments,
+            Err(err) => {
+                rollback_created_contexts(&state, &tenant, &user, created_context_ids)
+                    .await;
+                return Err(err);
+            }
+        };
+    for experiment in inserted_experiments.iter() {
+        record_experiment_metric(&state, &EXPERIMENTS_CREATED, experiment, &tenant);
+        notify_experiment_event(
+            &state,
+            &tenant,
+            experiment,
+            ExperimentEvent::Created,
+            &user,
+        );
+    }
+
+    let responses = inserted_experiments
+        .into_iter()
+        .zip(requests.iter().zip(default_traffic_percentages))
+        .map(|(experiment, (request, default_traffic_percentage))| {
+            let mut response = ExperimentCreateResponse::from(experiment);
+            response.warnings = experiment_create_warnings(request);
+            response.default_traffic_percentage = default_traffic_percentage;
+            response
+        })
+        .collect::<Vec<ExperimentCreateResponse>>();
+    Ok(HttpResponse::Ok().json(responses))
 }
 
 #[patch("/{experiment_id}/conclude")]
 async fn conclude_handler(
+    http_req: HttpRequest,
     state: Data<AppState>,
-    path: web::Path<i64>,
+    path: web::Path<String>,
     req: web::Json<ConcludeExperimentRequest>,
     db_conn: DbConnection,
     tenant: Tenant,
     user: User,
 ) -> superposition::Result<Json<ExperimentResponse>> {
     let DbConnection(conn) = db_conn;
+    let precondition = experiment_precondition(&http_req)?;
     let response = conclude(
         state,
         path.into_inner(),
         req.into_inner(),
+        precondition,
         conn,
         tenant,
         user,
@@ -272,25 +1273,83 @@ async fn conclude_handler(
 
 pub async fn conclude(
     state: Data<AppState>,
-    experiment_id: i64,
+    experiment_id: String,
     req: ConcludeExperimentRequest,
+    precondition: Option<ExperimentPrecondition>,
     mut conn: PooledConnection<ConnectionManager<PgConnection>>,
     tenant: Tenant,
     user: User,
+) -> superposition::Result<Experiment> {
+    let experiment = lock_experiment(&experiment_id, &mut conn)?;
+    let concluded = conclude_experiment(
+        &state,
+        experiment,
+        req,
+        precondition,
+        &mut conn,
+        &tenant,
+        &user,
+    )
+    .await;
+    let updated_experiment = end_experiment_change(concluded, &mut conn)?;
+    record_experiment_metric(
+        &state,
+        &EXPERIMENTS_CONCLUDED,
+        &updated_experiment,
+        &tenant,
+    );
+    notify_experiment_event(
+        &state,
+        &tenant,
+        &updated_experiment,
+        ExperimentEvent::Concluded,
+        &user,
+    );
+
+    Ok(updated_experiment)
+}
+
+// concludes an experiment locked by `lock_experiment`, moving the context of
+// the winner variant to the experiment's context and deleting the others
+async fn conclude_experiment(
+    state: &Data<AppState>,
+    experiment: Experiment,
+    req: ConcludeExperimentRequest,
+    precondition: Option<ExperimentPrecondition>,
+    conn: &mut PooledConnection<ConnectionManager<PgConnection>>,
+    tenant: &Tenant,
+    user: &User,
 ) -> superposition::Result<Experiment> {
     use crate::db::schema::experiments::dsl;
 
+    let experiment_id = experiment.id.clone();
     let winner_variant_id: String = req.chosen_variant.to_owned();
+    check_experiment_precondition(
+        &experiment_id,
+        experiment.last_modified,
+        precondition,
+    )?;
 
-    let experiment: Experiment = dsl::experiments
-        .find(experiment_id)
-        .get_result::<Experiment>(&mut conn)?;
-
-    if matches!(experiment.status, ExperimentStatusType::CONCLUDED) {
-        return Err(bad_argument!(
-            "experiment with id {} is already concluded",
-            experiment_id
-        ));
+    match experiment.status {
+        ExperimentStatusType::INPROGRESS => (),
+        ExperimentStatusType::CONCLUDED => {
+            return Err(bad_argument!(
+                "experiment with id {} is already concluded",
+                experiment_id
+            ));
+        }
+        ExperimentStatusType::CREATED => {
+            return Err(bad_argument!(
+                "experiment with id {} has not been started yet. Ramp the experiment before concluding it",
+                experiment_id
+            ));
+        }
+        ExperimentStatusType::PAUSED => {
+            return Err(bad_argument!(
+                "experiment with id {} is paused. Resume the experiment before concluding it",
+                experiment_id
+            ));
+        }
     }
 
     let experiment_context = experiment.context.as_object().ok_or_else(|| {
@@ -336,38 +1395,37 @@ pub async fn conclude(
     }
 
     // calling CAC bulk api with operations as payload
-    let http_client = reqwest::Client::new();
-    let url = state.cac_host.clone() + "/context/bulk-operations";
-    let response = http_client
-        .put(&url)
-        .header("x-tenant", tenant.as_str())
-        .header(
-            "Authorization",
-            format!("{} {}", user.get_auth_type(), user.get_auth_token()),
-        )
-        .json(&operations)
-        .send()
-        .await;
+    let response = call_cac_bulk_operations(state, tenant, user, &operations, None).await;
 
     let _ = process_cac_http_response(response).await?;
 
     // updating experiment status in db
-    let updated_experiment = diesel::update(dsl::experiments)
-        .filter(dsl::id.eq(experiment_id))
-        .set((
-            dsl::status.eq(ExperimentStatusType::CONCLUDED),
-            dsl::last_modified.eq(Utc::now()),
-            dsl::last_modified_by.eq(user.get_email()),
-            dsl::chosen_variant.eq(Some(winner_variant_id)),
-        ))
-        .get_result::<Experiment>(&mut conn)?;
-
-    return Ok(updated_experiment);
+    conn.transaction::<_, superposition::AppError, _>(|transaction_conn| {
+        let updated_experiment = diesel::update(dsl::experiments)
+            .filter(dsl::id.eq(&experiment_id))
+            .set((
+                dsl::status.eq(ExperimentStatusType::CONCLUDED),
+                dsl::last_modified.eq(Utc::now()),
+                dsl::last_modified_by.eq(user.get_email()),
+                dsl::chosen_variant.eq(Some(winner_variant_id)),
+            ))
+            .get_result::<Experiment>(transaction_conn)?;
+        record_experiment_audit(
+            &experiment_id,
+            "CONCLUDE",
+            Some(experiment.status),
+            updated_experiment.status,
+            user.get_email(),
+            transaction_conn,
+        )?;
+        Ok(updated_experiment)
+    })
 }
 
 #[get("")]
 async fn list_experiments(
     req: HttpRequest,
+    state: Data<AppState>,
     filters: Query<ListFilters>,
     db_conn: DbConnection,
 ) -> superposition::Result<HttpResponse> {
@@ -394,82 +1452,526 @@ async fn list_experiments(
 
     let query_builder = |filters: &ListFilters| {
         let mut builder = experiments::experiments.into_boxed();
-        if let Some(states) = filters.status.clone() {
+        // an absent or empty status matches every status, `eq_any` on an empty
+        // list would match nothing
+        if let Some(states) = filters.status.clone().filter(|states| !states.0.is_empty())
+        {
             builder = builder.filter(experiments::status.eq_any(states.0.clone()));
         }
-        let now = Utc::now();
-        builder
-            .filter(
-                experiments::last_modified
-                    .ge(filters.from_date.unwrap_or(now - Duration::hours(24))),
-            )
-            .filter(experiments::last_modified.le(filters.to_date.unwrap_or(now)))
+        // archived experiments are hidden unless asked for
+        if !filters.include_archived.unwrap_or(false) {
+            builder = builder.filter(experiments::archived_at.is_null());
+        }
+        // blank values are ignored so that `name=` does not turn into a `%%` scan
+        if let Some(name) = filters.name.as_deref().map(str::trim) {
+            if !name.is_empty() {
+                builder = builder.filter(experiments::name.ilike(format!("%{name}%")));
+            }
+        }
+        if let Some(created_by) = filters.created_by.as_deref().map(str::trim) {
+            if !created_by.is_empty() {
+                builder =
+                    builder.filter(experiments::created_by.eq(created_by.to_string()));
+            }
+        }
+        if let Some(modified_since) = filters.modified_since {
+            builder = builder.filter(experiments::last_modified.gt(modified_since));
+        }
+        let (from_date, to_date) = listing_modified_window(filters, Utc::now());
+        if let Some(from_date) = from_date {
+            builder = builder.filter(experiments::last_modified.ge(from_date));
+        }
+        builder.filter(experiments::last_modified.le(to_date))
     };
     let filters = filters.into_inner();
     let base_query = query_builder(&filters);
+
+    if filters.pagination == Some(PaginationMode::Cursor) {
+        if filters.page.is_some()
+            || filters.sort_by.is_some()
+            || filters.sort_order.is_some()
+        {
+            return Err(bad_argument!(
+                "cursor pagination pages through experiments newest first, page, sort_by and sort_order cannot be used with it"
+            ));
+        }
+        let size = resolve_pagination(None, filters.count, state.max_page_size)?.size;
+        // experiments created while paging sort ahead of the cursor and cannot
+        // shift the pages after it, ids only order experiments created at the
+        // same time as they differ in length across id generators
+        let mut query = base_query
+            .order((experiments::created_at.desc(), experiments::id.desc()))
+            .limit(size + 1);
+        if let Some(cursor) = filters.cursor.as_deref() {
+            let (created_at, experiment_id) = decode_cursor(cursor)?;
+            query = query.filter(
+                experiments::created_at
+                    .lt(created_at)
+                    .or(experiments::created_at
+                        .eq(created_at)
+                        .and(experiments::id.lt(experiment_id))),
+            );
+        }
+        let (experiment_list, next_cursor) =
+            split_cursor_page(query.load::<Experiment>(&mut conn)?, size);
+        return Ok(HttpResponse::Ok().json(ExperimentsCursorResponse {
+            count: size,
+            next_cursor,
+            data: experiment_list
+                .into_iter()
+                .map(ExperimentResponse::from)
+                .collect(),
+        }));
+    }
+    if filters.cursor.is_some() {
+        return Err(bad_argument!(
+            "cursor can only be used with pagination=cursor"
+        ));
+    }
+
     let count_query = query_builder(&filters);
 
-    let limit = filters.count.unwrap_or(10);
-    let offset = (filters.page.unwrap_or(1) - 1) * limit;
+    let pagination =
+        resolve_pagination(filters.page, filters.count, state.max_page_size)?;
+    // unknown sort fields are rejected with a 400 while deserializing the query
+    let sort_order = filters.sort_order.unwrap_or(SortOrder::Desc);
+    let base_query = match (filters.sort_by, sort_order) {
+        (None | Some(ExperimentSortOn::LastModified), SortOrder::Desc) => {
+            base_query.order(experiments::last_modified.desc())
+        }
+        (None | Some(ExperimentSortOn::LastModified), SortOrder::Asc) => {
+            base_query.order(experiments::last_modified.asc())
+        }
+        (Some(ExperimentSortOn::CreatedAt), SortOrder::Desc) => {
+            base_query.order(experiments::created_at.desc())
+        }
+        (Some(ExperimentSortOn::CreatedAt), SortOrder::Asc) => {
+            base_query.order(experiments::created_at.asc())
+        }
+        (Some(ExperimentSortOn::Name), SortOrder::Desc) => {
+            base_query.order(experiments::name.desc())
+        }
+        (Some(ExperimentSortOn::Name), SortOrder::Asc) => {
+            base_query.order(experiments::name.asc())
+        }
+    };
+    // ties are broken on id so that pages stay stable
     let query = base_query
-        .order(experiments::last_modified.desc())
-        .limit(limit)
-        .offset(offset);
+        .then_order_by(experiments::id.desc())
+        .limit(pagination.size)
+        .offset(pagination.offset());
 
     let number_of_experiments = count_query.count().get_result(&mut conn)?;
 
     let experiment_list = query.load::<Experiment>(&mut conn)?;
 
-    let total_pages = (number_of_experiments as f64 / limit as f64).ceil() as i64;
+    let total_pages = calculate_total_pages(number_of_experiments, pagination.size);
+
+    Ok(HttpResponse::Ok().json(ExperimentsResponse {
+        total_pages,
+        total_items: number_of_experiments,
+        page: pagination.page,
+        count: pagination.size,
+        data: experiment_list
+            .into_iter()
+            .map(|entry| ExperimentResponse::from(entry))
+            .collect(),
+    }))
+}
+
+// registered ahead of `/{id}`, which would match it too
+#[get("/schema")]
+async fn get_create_request_schema() -> Json<Value> {
+    Json(ExperimentCreateRequest::json_schema())
+}
+
+#[get("/{id}")]
+async fn get_experiment_handler(
+    params: web::Path<String>,
+    db_conn: DbConnection,
+) -> superposition::Result<Json<ExperimentDetailResponse>> {
+    let DbConnection(mut conn) = db_conn;
+    let response = get_experiment(&params.into_inner(), &mut conn)?;
+    Ok(Json(ExperimentDetailResponse::from(response)))
+}
+
+pub fn get_experiment(
+    experiment_id: &str,
+    conn: &mut PooledConnection<ConnectionManager<PgConnection>>,
+) -> superposition::Result<Experiment> {
+    use crate::db::schema::experiments::dsl::*;
+    let result: Experiment = experiments
+        .find(experiment_id)
+        .get_result::<Experiment>(conn)
+        .map_err(|err| match err {
+            diesel::result::Error::NotFound => {
+                not_found!("experiment with id {} not found", experiment_id)
+            }
+            err => db_error!(err),
+        })?;
+
+    return Ok(result);
+}
+
+#[get("/{id}/audit")]
+async fn get_experiment_audit(
+    params: web::Path<String>,
+    db_conn: DbConnection,
+) -> superposition::Result<Json<Vec<ExperimentAudit>>> {
+    let DbConnection(mut conn) = db_conn;
+    let experiment_id = params.into_inner();
+
+    // surfaces a 404 for unknown experiments instead of an empty trail
+    get_experiment(&experiment_id, &mut conn)?;
+
+    let audit_trail = experiment_audit::experiment_audit
+        .filter(experiment_audit::experiment_id.eq(&experiment_id))
+        .order(experiment_audit::timestamp.asc())
+        .load::<ExperimentAudit>(&mut conn)?;
+
+    Ok(Json(audit_trail))
+}
+
+#[get("/{id}/verify")]
+async fn verify_experiment(
+    state: Data<AppState>,
+    params: web::Path<String>,
+    db_conn: DbConnection,
+    tenant: Tenant,
+    user: User,
+) -> superposition::Result<Json<ExperimentVerifyResponse>> {
+    let DbConnection(mut conn) = db_conn;
+    let experiment = get_experiment(&params.into_inner(), &mut conn)?;
+
+    // concluding discards the variant contexts, there is nothing left to compare
+    if matches!(experiment.status, ExperimentStatusType::CONCLUDED) {
+        return Err(bad_argument!(
+            "experiment with id {} is concluded, its variant contexts no longer exist in CAC",
+            experiment.id
+        ));
+    }
+
+    let variants: Vec<Variant> =
+        serde_json::from_value(experiment.variants).map_err(|e| {
+            log::error!(
+                "failed to parse variants of experiment {}: {e}",
+                experiment.id
+            );
+            unexpected_error!("Something went wrong, failed to verify experiment")
+        })?;
+
+    let mut reconciliations = Vec::with_capacity(variants.len());
+    for variant in variants.iter() {
+        let cac_context = match &variant.context_id {
+            Some(context_id) => {
+                fetch_cac_context(&state, &tenant, &user, context_id).await?
+            }
+            None => None,
+        };
+        reconciliations.push(reconcile_variant(variant, cac_context.as_ref()));
+    }
+
+    let in_sync = reconciliations
+        .iter()
+        .all(|reconciliation| reconciliation.status == ReconciliationStatus::InSync);
+    if !in_sync {
+        log::warn!(
+            "experiment {} has variants out of sync with CAC",
+            experiment.id
+        );
+    }
+
+    Ok(Json(ExperimentVerifyResponse {
+        experiment_id: experiment.id.to_string(),
+        status: experiment.status,
+        in_sync,
+        variants: reconciliations,
+    }))
+}
+
+#[post("/{id}/metrics")]
+async fn record_metrics(
+    params: web::Path<String>,
+    req: web::Json<MetricsIngestRequest>,
+    db_conn: DbConnection,
+) -> superposition::Result<HttpResponse> {
+    let DbConnection(mut conn) = db_conn;
+    let experiment = get_experiment(&params.into_inner(), &mut conn)?;
+    let variants: Vec<Variant> =
+        serde_json::from_value(experiment.variants).map_err(|e| {
+            log::error!(
+                "failed to parse variants of experiment {}: {e}",
+                experiment.id
+            );
+            unexpected_error!("Something went wrong, failed to record metrics")
+        })?;
+
+    let observations = req.into_inner().observations;
+    validate_metric_observations(&variants, &observations)?;
+
+    let recorded_at = Utc::now();
+    let new_metrics = observations
+        .into_iter()
+        .map(|observation| ExperimentMetric {
+            id: uuid::Uuid::new_v4(),
+            experiment_id: experiment.id.clone(),
+            variant_id: observation.variant_id,
+            metric_name: observation.metric_name,
+            value: observation.value,
+            recorded_at,
+        })
+        .collect::<Vec<ExperimentMetric>>();
+    let recorded = diesel::insert_into(experiment_metrics::experiment_metrics)
+        .values(&new_metrics)
+        .execute(&mut conn)?;
+
+    Ok(HttpResponse::Created().json(MetricsIngestResponse {
+        experiment_id: experiment.id,
+        recorded,
+    }))
+}
+
+#[get("/{id}/results")]
+async fn get_results(
+    params: web::Path<String>,
+    query: Query<ResultsQuery>,
+    db_conn: DbConnection,
+) -> superposition::Result<Json<ExperimentResultsResponse>> {
+    let DbConnection(mut conn) = db_conn;
+    let experiment = get_experiment(&params.into_inner(), &mut conn)?;
+
+    let summaries = experiment_metrics::experiment_metrics
+        .filter(experiment_metrics::experiment_id.eq(&experiment.id))
+        .group_by((
+            experiment_metrics::variant_id,
+            experiment_metrics::metric_name,
+        ))
+        .select((
+            experiment_metrics::variant_id,
+            experiment_metrics::metric_name,
+            count(experiment_metrics::value),
+            avg(experiment_metrics::value),
+            sql::<Nullable<Float8>>("var_samp(value)"),
+            sql::<Nullable<Bool>>("bool_and(value IN (0, 1))"),
+        ))
+        .order_by((
+            experiment_metrics::variant_id,
+            experiment_metrics::metric_name,
+        ))
+        .load::<(String, String, i64, Option<f64>, Option<f64>, Option<bool>)>(
+            &mut conn,
+        )?;
+
+    let results = summaries
+        .into_iter()
+        .map(|(variant_id, metric_name, count, mean, variance, binary)| {
+            VariantMetricSummary {
+                variant_id,
+                metric_name,
+                count,
+                mean: mean.unwrap_or_default(),
+                variance,
+                binary: binary.unwrap_or(false),
+            }
+        })
+        .collect::<Vec<VariantMetricSummary>>();
+
+    let comparisons = if query.significance {
+        let variants: Vec<Variant> = serde_json::from_value(experiment.variants)
+            .map_err(|e| {
+                log::error!(
+                    "failed to parse variants of experiment {}: {e}",
+                    experiment.id
+                );
+                unexpected_error!("Something went wrong, failed to compute results")
+            })?;
+        let control_variant = variants
+            .iter()
+            .find(|variant| variant.variant_type == VariantType::CONTROL)
+            .ok_or(unexpected_error!(
+                "experiment {} has no control variant",
+                experiment.id
+            ))?;
+        Some(compare_with_control(&control_variant.id, &results))
+    } else {
+        None
+    };
+
+    Ok(Json(ExperimentResultsResponse {
+        experiment_id: experiment.id,
+        results,
+        comparisons,
+    }))
+}
+
+#[get("/{id}/diff")]
+async fn get_diff(
+    params: web::Path<String>,
+    query: Query<DiffQuery>,
+    state: Data<AppState>,
+    db_conn: DbConnection,
+    tenant: Tenant,
+    user: User,
+) -> superposition::Result<Json<ExperimentDiffResponse>> {
+    let DbConnection(mut conn) = db_conn;
+    let experiment = get_experiment(&params.into_inner(), &mut conn)?;
+
+    let variants: Vec<Variant> =
+        serde_json::from_value(experiment.variants).map_err(|e| {
+            log::error!(
+                "failed to parse variants of experiment {}: {e}",
+                experiment.id
+            );
+            unexpected_error!("Something went wrong, failed to compute the diff")
+        })?;
+    let control_variant = variants
+        .iter()
+        .find(|variant| variant.variant_type == VariantType::CONTROL)
+        .ok_or(unexpected_error!(
+            "experiment {} has no control variant",
+            experiment.id
+        ))?;
+
+    let default_config = if query.default_config {
+        let default_configs = fetch_default_configs(&state, &tenant, &user).await?;
+        Some(
+            default_configs
+                .into_iter()
+                .map(|config| (config.key, config.value))
+                .collect::<Map<String, Value>>(),
+        )
+    } else {
+        None
+    };
+
+    let variant_diffs = variants
+        .iter()
+        .map(|variant| VariantDiff {
+            variant_id: variant.id.clone(),
+            variant_type: variant.variant_type.clone(),
+            against_control: diff_overrides(
+                &experiment.override_keys,
+                &control_variant.overrides,
+                &variant.overrides,
+            ),
+            // only the keys the experiment overrides are of interest here,
+            // not the rest of the default config
+            against_default_config: default_config.as_ref().map(|default_config| {
+                let default_values = experiment
+                    .override_keys
+                    .iter()
+                    .chain(variant.overrides.keys())
+                    .filter_map(|key| {
+                        default_config
+                            .get(key)
+                            .map(|value| (key.clone(), value.clone()))
+                    })
+                    .collect::<Map<String, Value>>();
+                diff_overrides(
+                    &experiment.override_keys,
+                    &default_values,
+                    &variant.overrides,
+                )
+            }),
+        })
+        .collect();
 
-    Ok(HttpResponse::Ok().json(ExperimentsResponse {
-        total_pages,
-        total_items: number_of_experiments,
-        data: experiment_list
-            .into_iter()
-            .map(|entry| ExperimentResponse::from(entry))
-            .collect(),
+    Ok(Json(ExperimentDiffResponse {
+        experiment_id: experiment.id,
+        control_variant_id: control_variant.id.clone(),
+        variants: variant_diffs,
     }))
 }
 
-#[get("/{id}")]
-async fn get_experiment_handler(
-    params: web::Path<i64>,
+#[post("/{id}/resolve")]
+async fn resolve_variant(
+    http_req: HttpRequest,
+    params: web::Path<String>,
+    req: web::Json<ResolveVariantRequest>,
     db_conn: DbConnection,
-) -> superposition::Result<Json<ExperimentResponse>> {
+) -> superposition::Result<Json<ResolveVariantResponse>> {
     let DbConnection(mut conn) = db_conn;
-    let response = get_experiment(params.into_inner(), &mut conn)?;
-    return Ok(Json(ExperimentResponse::from(response)));
-}
+    let experiment_id = params.into_inner();
+    let ResolveVariantRequest {
+        context,
+        toss,
+        toss_key,
+    } = req.into_inner();
+    let pin = variant_pin_of(&http_req)?;
+
+    let experiment = get_experiment(&experiment_id, &mut conn)?;
+    match experiment.status {
+        ExperimentStatusType::CREATED | ExperimentStatusType::INPROGRESS => (),
+        status => {
+            return Err(bad_argument!(
+                "experiment with id {} is {:?}, variants are only served for CREATED or INPROGRESS experiments",
+                experiment_id,
+                status
+            ));
+        }
+    }
 
-pub fn get_experiment(
-    experiment_id: i64,
-    conn: &mut PooledConnection<ConnectionManager<PgConnection>>,
-) -> superposition::Result<Experiment> {
-    use crate::db::schema::experiments::dsl::*;
-    let result: Experiment = experiments
-        .find(experiment_id)
-        .get_result::<Experiment>(conn)?;
+    let applies_to_context =
+        jsonlogic::apply(&experiment.context, &Value::Object(context.clone()))
+            == Ok(Value::Bool(true));
+    if !applies_to_context {
+        return Ok(Json(ResolveVariantResponse {
+            variant_id: None,
+            overrides: None,
+        }));
+    }
 
-    return Ok(result);
+    let variants: Vec<Variant> =
+        serde_json::from_value(experiment.variants).map_err(|err| {
+            log::error!("failed to parse existing variants while resolving {}", err);
+            unexpected_error!("Something went wrong, failed to resolve variant")
+        })?;
+
+    let chosen_variant = pinned_variant(&experiment_id, &variants, pin.as_ref())
+        .or_else(|| {
+            choose_variant(
+                &experiment_id,
+                experiment.traffic_percentage,
+                &variants,
+                &context,
+                toss,
+                &toss_key,
+            )
+        })
+        .cloned();
+
+    Ok(Json(ResolveVariantResponse {
+        variant_id: chosen_variant
+            .as_ref()
+            .map(|variant| variant.id.to_string()),
+        overrides: chosen_variant.map(|variant| variant.overrides),
+    }))
 }
 
 #[patch("/{id}/ramp")]
 async fn ramp(
-    params: web::Path<i64>,
+    http_req: HttpRequest,
+    state: Data<AppState>,
+    params: web::Path<String>,
     req: web::Json<RampRequest>,
     db_conn: DbConnection,
+    tenant: Tenant,
     user: User,
 ) -> superposition::Result<Json<ExperimentResponse>> {
     let DbConnection(mut conn) = db_conn;
     let exp_id = params.into_inner();
 
     let experiment: Experiment = experiments::experiments
-        .find(exp_id)
+        .find(&exp_id)
         .get_result::<Experiment>(&mut conn)?;
+    check_experiment_precondition(
+        &exp_id,
+        experiment.last_modified,
+        experiment_precondition(&http_req)?,
+    )?;
 
-    let old_traffic_percentage = experiment.traffic_percentage as u8;
-    let new_traffic_percentage = req.traffic_percentage as u8;
+    let old_traffic_percentage = experiment.traffic_percentage as i64;
+    let new_traffic_percentage = req.traffic_percentage;
     let experiment_variants: Vec<Variant> = serde_json::from_value(experiment.variants)
         .map_err(|e| {
         log::error!(
@@ -478,38 +1980,446 @@ async fn ramp(
         );
         unexpected_error!("Something went wrong, failed to ramp traffic percentage")
     })?;
-    let variants_count = experiment_variants.len() as u8;
-    let max = 100 / variants_count;
 
-    if matches!(experiment.status, ExperimentStatusType::CONCLUDED) {
+    match experiment.status {
+        ExperimentStatusType::CONCLUDED => {
+            return Err(bad_argument!(
+                "experiment already concluded, cannot ramp a concluded experiment"
+            ));
+        }
+        ExperimentStatusType::PAUSED => {
+            return Err(bad_argument!(
+                "experiment is paused, resume the experiment before ramping it"
+            ));
+        }
+        _ => (),
+    }
+    validate_traffic_percentage(new_traffic_percentage, &experiment_variants)?;
+    if new_traffic_percentage != 0 && new_traffic_percentage == old_traffic_percentage {
+        return Err(bad_argument!("The traffic_percentage is same as provided"))?;
+    }
+
+    // ramping a freshly created experiment starts it
+    let new_status = match experiment.status {
+        ExperimentStatusType::CREATED => ExperimentStatusType::INPROGRESS,
+        status => status,
+    };
+
+    let updated_experiment: Experiment = conn
+        .transaction::<_, superposition::AppError, _>(|transaction_conn| {
+            // the experiment could have changed since it was read above
+            let updated_experiment: Experiment = diesel::update(experiments::experiments)
+                .filter(experiments::id.eq(&exp_id))
+                .filter(experiments::last_modified.eq(experiment.last_modified))
+                .set((
+                    experiments::traffic_percentage.eq(new_traffic_percentage as i32),
+                    experiments::last_modified.eq(Utc::now()),
+                    experiments::last_modified_by.eq(user.get_email()),
+                    experiments::status.eq(new_status),
+                ))
+                .get_result::<Experiment>(transaction_conn)
+                .optional()?
+                .ok_or_else(|| experiment_modified_error(&exp_id))?;
+            record_experiment_audit(
+                &exp_id,
+                "RAMP",
+                Some(experiment.status),
+                new_status,
+                user.get_email(),
+                transaction_conn,
+            )?;
+            Ok(updated_experiment)
+        })?;
+    record_experiment_metric(&state, &EXPERIMENTS_RAMPED, &updated_experiment, &tenant);
+    notify_experiment_event(
+        &state,
+        &tenant,
+        &updated_experiment,
+        ExperimentEvent::Ramped,
+        &user,
+    );
+
+    return Ok(Json(ExperimentResponse::from(updated_experiment)));
+}
+
+#[post("/{id}/advance")]
+async fn advance_handler(
+    http_req: HttpRequest,
+    state: Data<AppState>,
+    params: web::Path<String>,
+    db_conn: DbConnection,
+    tenant: Tenant,
+    user: User,
+) -> superposition::Result<Json<ExperimentResponse>> {
+    let DbConnection(mut conn) = db_conn;
+    let exp_id = params.into_inner();
+
+    let experiment = get_experiment(&exp_id, &mut conn)?;
+    check_experiment_precondition(
+        &exp_id,
+        experiment.last_modified,
+        experiment_precondition(&http_req)?,
+    )?;
+    let updated_experiment =
+        advance_rollout(&state, experiment, "ADVANCE", &tenant, &user, &mut conn)?;
+
+    Ok(Json(ExperimentResponse::from(updated_experiment)))
+}
+
+/// Moves an experiment to the next step of its rollout plan, serving that
+/// step's traffic percentage. Advancing a CREATED experiment starts it, and an
+/// experiment on its last step holds there until it is concluded.
+pub fn advance_rollout(
+    state: &Data<AppState>,
+    experiment: Experiment,
+    action: &str,
+    tenant: &Tenant,
+    user: &User,
+    conn: &mut PgConnection,
+) -> superposition::Result<Experiment> {
+    let exp_id = experiment.id.clone();
+    let rollout_plan = parse_rollout_plan(&experiment)?;
+    if rollout_plan.is_empty() {
         return Err(bad_argument!(
-            "experiment already concluded, cannot ramp a concluded experiment"
+            "experiment with id {} has no rollout plan, ramp it instead",
+            exp_id
         ));
-    } else if new_traffic_percentage > max {
+    }
+    match experiment.status {
+        ExperimentStatusType::CREATED | ExperimentStatusType::INPROGRESS => (),
+        status => {
+            return Err(bad_argument!(
+                "experiment with id {} is {:?}, only CREATED or INPROGRESS experiments can be advanced",
+                exp_id,
+                status
+            ));
+        }
+    }
+    let Some(next_step) = next_rollout_step(&rollout_plan, experiment.rollout_step)
+    else {
         return Err(bad_argument!(
-            "The traffic_percentage cannot exceed {}. Provide a traffic percentage less than {}", max, max
-        ))?;
-    } else if new_traffic_percentage != 0
-        && new_traffic_percentage == old_traffic_percentage
-    {
-        return Err(bad_argument!("The traffic_percentage is same as provided"))?;
+            "experiment with id {} is on the last step of its rollout plan, conclude it to pick a winner",
+            exp_id
+        ));
+    };
+    let new_traffic_percentage = rollout_plan[next_step].traffic_percentage;
+    let new_status = ExperimentStatusType::INPROGRESS;
+
+    let updated_experiment: Experiment = conn
+        .transaction::<_, superposition::AppError, _>(|transaction_conn| {
+            // the experiment could have changed since it was read
+            let updated_experiment: Experiment = diesel::update(experiments::experiments)
+                .filter(experiments::id.eq(&exp_id))
+                .filter(experiments::last_modified.eq(experiment.last_modified))
+                .set((
+                    experiments::traffic_percentage.eq(new_traffic_percentage as i32),
+                    experiments::rollout_step.eq(next_step as i32),
+                    experiments::rollout_step_started_at.eq(Utc::now()),
+                    experiments::last_modified.eq(Utc::now()),
+                    experiments::last_modified_by.eq(user.get_email()),
+                    experiments::status.eq(new_status),
+                ))
+                .get_result::<Experiment>(transaction_conn)
+                .optional()?
+                .ok_or_else(|| experiment_modified_error(&exp_id))?;
+            record_experiment_audit(
+                &exp_id,
+                action,
+                Some(experiment.status),
+                new_status,
+                user.get_email(),
+                transaction_conn,
+            )?;
+            Ok(updated_experiment)
+        })?;
+    record_experiment_metric(state, &EXPERIMENTS_RAMPED, &updated_experiment, tenant);
+    notify_experiment_event(
+        state,
+        tenant,
+        &updated_experiment,
+        ExperimentEvent::Ramped,
+        user,
+    );
+
+    Ok(updated_experiment)
+}
+
+#[post("/{id}/pause")]
+async fn pause(
+    http_req: HttpRequest,
+    params: web::Path<String>,
+    state: Data<AppState>,
+    db_conn: DbConnection,
+    tenant: Tenant,
+    user: User,
+) -> superposition::Result<Json<ExperimentResponse>> {
+    let DbConnection(mut conn) = db_conn;
+    let exp_id = params.into_inner();
+    let experiment = get_experiment(&exp_id, &mut conn)?;
+    check_experiment_precondition(
+        &exp_id,
+        experiment.last_modified,
+        experiment_precondition(&http_req)?,
+    )?;
+    let updated_experiment =
+        pause_experiment(&state, experiment, "PAUSE", &tenant, &user, &mut conn).await?;
+
+    Ok(Json(ExperimentResponse::from(updated_experiment)))
+}
+
+/// Removes the variant contexts of an INPROGRESS experiment from CAC and marks
+/// it PAUSED, recording `action` in its audit log.
+pub(super) async fn pause_experiment(
+    state: &Data<AppState>,
+    experiment: Experiment,
+    action: &str,
+    tenant: &Tenant,
+    user: &User,
+    conn: &mut PgConnection,
+) -> superposition::Result<Experiment> {
+    let exp_id = experiment.id.clone();
+    if experiment.status != ExperimentStatusType::INPROGRESS {
+        return Err(bad_argument!(
+            "experiment with id {} is {:?}, only INPROGRESS experiments can be paused",
+            exp_id,
+            experiment.status
+        ));
     }
-    let updated_experiment: Experiment = diesel::update(experiments::experiments)
-        .filter(experiments::id.eq(exp_id))
-        .set((
-            experiments::traffic_percentage.eq(req.traffic_percentage as i32),
-            experiments::last_modified.eq(Utc::now()),
-            experiments::last_modified_by.eq(user.get_email()),
-            experiments::status.eq(ExperimentStatusType::INPROGRESS),
-        ))
-        .get_result(&mut conn)?;
 
-    return Ok(Json(ExperimentResponse::from(updated_experiment)));
+    let mut experiment_variants: Vec<Variant> =
+        serde_json::from_value(experiment.variants).map_err(|err| {
+            log::error!("failed to parse existing variants while pausing {}", err);
+            unexpected_error!("Something went wrong, failed to pause experiment")
+        })?;
+
+    // removing variant contexts from CAC stops the experiment from being served,
+    // traffic_percentage is left untouched so that resume can restore it
+    let mut cac_operations: Vec<ContextAction> = vec![];
+    for variant in &mut experiment_variants {
+        let context_id = variant.context_id.take().ok_or_else(|| {
+            log::error!("context id not available for variant {:?}", variant.id);
+            unexpected_error!("Something went wrong, failed to pause experiment")
+        })?;
+        variant.override_id = None;
+        cac_operations.push(ContextAction::DELETE(context_id));
+    }
+
+    let response =
+        call_cac_bulk_operations(state, tenant, user, &cac_operations, None).await;
+    let _ = process_cac_http_response(response).await?;
+
+    let variants_json = serde_json::to_value(experiment_variants).inspect_err(|err| {
+        log::error!("failed to serialize variants while pausing {}", err)
+    })?;
+
+    let updated_experiment: Experiment = conn
+        .transaction::<_, superposition::AppError, _>(|transaction_conn| {
+            let updated_experiment: Experiment =
+                diesel::update(experiments::experiments.find(&exp_id))
+                    .set((
+                        experiments::variants.eq(variants_json),
+                        experiments::status.eq(ExperimentStatusType::PAUSED),
+                        experiments::last_modified.eq(Utc::now()),
+                        experiments::last_modified_by.eq(user.get_email()),
+                    ))
+                    .get_result(transaction_conn)?;
+            record_experiment_audit(
+                &exp_id,
+                action,
+                Some(experiment.status),
+                ExperimentStatusType::PAUSED,
+                user.get_email(),
+                transaction_conn,
+            )?;
+            Ok(updated_experiment)
+        })?;
+
+    Ok(updated_experiment)
+}
+
+#[post("/{id}/archive")]
+async fn archive(
+    http_req: HttpRequest,
+    params: web::Path<String>,
+    db_conn: DbConnection,
+    user: User,
+) -> superposition::Result<Json<ExperimentResponse>> {
+    let DbConnection(mut conn) = db_conn;
+    let exp_id = params.into_inner();
+
+    let experiment = get_experiment(&exp_id, &mut conn)?;
+    check_experiment_precondition(
+        &exp_id,
+        experiment.last_modified,
+        experiment_precondition(&http_req)?,
+    )?;
+    if experiment.status != ExperimentStatusType::CONCLUDED {
+        return Err(bad_argument!(
+            "experiment with id {} is {:?}, only CONCLUDED experiments can be archived",
+            exp_id,
+            experiment.status
+        ));
+    }
+    if experiment.archived_at.is_some() {
+        return Err(bad_argument!(
+            "experiment with id {} is already archived",
+            exp_id
+        ));
+    }
+
+    let updated_experiment: Experiment = conn
+        .transaction::<_, superposition::AppError, _>(|transaction_conn| {
+            let updated_experiment: Experiment =
+                diesel::update(experiments::experiments.find(&exp_id))
+                    .filter(experiments::last_modified.eq(experiment.last_modified))
+                    .set((
+                        experiments::archived_at.eq(Some(Utc::now())),
+                        experiments::last_modified.eq(Utc::now()),
+                        experiments::last_modified_by.eq(user.get_email()),
+                    ))
+                    .get_result::<Experiment>(transaction_conn)
+                    .optional()?
+                    .ok_or_else(|| experiment_modified_error(&exp_id))?;
+            record_experiment_audit(
+                &exp_id,
+                "ARCHIVE",
+                Some(experiment.status),
+                experiment.status,
+                user.get_email(),
+                transaction_conn,
+            )?;
+            Ok(updated_experiment)
+        })?;
+
+    Ok(Json(ExperimentResponse::from(updated_experiment)))
+}
+
+#[post("/{id}/resume")]
+async fn resume(
+    http_req: HttpRequest,
+    params: web::Path<String>,
+    state: Data<AppState>,
+    db_conn: DbConnection,
+    tenant: Tenant,
+    user: User,
+) -> superposition::Result<Json<ExperimentResponse>> {
+    let DbConnection(mut conn) = db_conn;
+    let exp_id = params.into_inner();
+
+    let experiment = get_experiment(&exp_id, &mut conn)?;
+    check_experiment_precondition(
+        &exp_id,
+        experiment.last_modified,
+        experiment_precondition(&http_req)?,
+    )?;
+    if experiment.status != ExperimentStatusType::PAUSED {
+        return Err(bad_argument!(
+            "experiment with id {} is {:?}, only PAUSED experiments can be resumed",
+            exp_id,
+            experiment.status
+        ));
+    }
+
+    let mut experiment_variants: Vec<Variant> =
+        serde_json::from_value(experiment.variants).map_err(|err| {
+            log::error!("failed to parse existing variants while resuming {}", err);
+            unexpected_error!("Something went wrong, failed to resume experiment")
+        })?;
+
+    // re-creating the variant contexts removed while pausing
+    let mut cac_operations: Vec<ContextAction> = vec![];
+    for variant in &experiment_variants {
+        let variant_context =
+            add_variant_dimension_to_ctx(&experiment.context, variant.id.to_string())?;
+        let payload = ContextPutReq {
+            context: variant_context
+                .as_object()
+                .ok_or_else(|| {
+                    log::error!("Could not convert updated CAC context to serde Object");
+                    unexpected_error!("Something went wrong, failed to resume experiment")
+                })?
+                .clone(),
+            r#override: json!(variant.overrides),
+        };
+        cac_operations.push(ContextAction::PUT(payload));
+    }
+
+    let response =
+        call_cac_bulk_operations(&state, &tenant, &user, &cac_operations, None).await;
+    let created_contexts =
+        created_contexts_of(&cac_operations, process_cac_http_response(response).await?)?;
+    assign_created_contexts(&mut experiment_variants, &created_contexts)?;
+
+    let variants_json = serde_json::to_value(experiment_variants).inspect_err(|err| {
+        log::error!("failed to serialize variants while resuming {}", err)
+    })?;
+
+    let updated_experiment: Experiment = conn
+        .transaction::<_, superposition::AppError, _>(|transaction_conn| {
+            let updated_experiment: Experiment =
+                diesel::update(experiments::experiments.find(&exp_id))
+                    .set((
+                        experiments::variants.eq(variants_json),
+                        experiments::status.eq(ExperimentStatusType::INPROGRESS),
+                        experiments::last_modified.eq(Utc::now()),
+                        experiments::last_modified_by.eq(user.get_email()),
+                    ))
+                    .get_result(transaction_conn)?;
+            record_experiment_audit(
+                &exp_id,
+                "RESUME",
+                Some(experiment.status),
+                ExperimentStatusType::INPROGRESS,
+                user.get_email(),
+                transaction_conn,
+            )?;
+            Ok(updated_experiment)
+        })?;
+
+    Ok(Json(ExperimentResponse::from(updated_experiment)))
 }
 
 #[put("/{id}/overrides")]
 async fn update_overrides(
-    params: web::Path<i64>,
+    http_req: HttpRequest,
+    params: web::Path<String>,
+    state: Data<AppState>,
+    db_conn: DbConnection,
+    req: web::Json<OverrideKeysUpdateRequest>,
+    tenant: Tenant,
+    user: User,
+) -> superposition::Result<Json<ExperimentResponse>> {
+    let DbConnection(mut conn) = db_conn;
+    let experiment_id = params.into_inner();
+    let precondition = experiment_precondition(&http_req)?;
+
+    // fetch the current variants of the experiment
+    let experiment = lock_experiment(&experiment_id, &mut conn)?;
+    let updated = async {
+        check_experiment_precondition(
+            &experiment_id,
+            experiment.last_modified,
+            precondition,
+        )?;
+        replace_variant_overrides(
+            &state,
+            experiment,
+            req.into_inner().variants,
+            &tenant,
+            &user,
+            &mut conn,
+        )
+        .await
+    }
+    .await;
+    let updated_experiment = end_experiment_change(updated, &mut conn)?;
+    Ok(Json(ExperimentResponse::from(updated_experiment)))
+}
+
+#[patch("/{id}/overrides")]
+async fn patch_overrides(
+    http_req: HttpRequest,
+    params: web::Path<String>,
     state: Data<AppState>,
     db_conn: DbConnection,
     req: web::Json<OverrideKeysUpdateRequest>,
@@ -518,22 +2428,61 @@ async fn update_overrides(
 ) -> superposition::Result<Json<ExperimentResponse>> {
     let DbConnection(mut conn) = db_conn;
     let experiment_id = params.into_inner();
+    let precondition = experiment_precondition(&http_req)?;
+
+    // the overrides are merged onto the variants of the locked version
+    let experiment = lock_experiment(&experiment_id, &mut conn)?;
+    let updated = async {
+        check_experiment_precondition(
+            &experiment_id,
+            experiment.last_modified,
+            precondition,
+        )?;
+
+        let experiment_variants: Vec<Variant> =
+            serde_json::from_value(experiment.variants.clone()).map_err(|err| {
+                log::error!("failed to parse exisiting variants with error {}", err);
+                unexpected_error!("Something went wrong, failed to update experiment")
+            })?;
+        let merged_variants =
+            merge_variant_overrides(&experiment_variants, req.into_inner().variants)?;
+
+        // the merged overrides go through the same checks as a full update
+        replace_variant_overrides(
+            &state,
+            experiment,
+            merged_variants,
+            &tenant,
+            &user,
+            &mut conn,
+        )
+        .await
+    }
+    .await;
+    let updated_experiment = end_experiment_change(updated, &mut conn)?;
+    Ok(Json(ExperimentResponse::from(updated_experiment)))
+}
 
-    let payload = req.into_inner();
-    let variants = payload.variants;
+/// Replaces the overrides of every variant of a CREATED experiment with
+/// `variants`, re-creating their contexts in CAC. The experiment has to be
+/// locked by [`lock_experiment`].
+async fn replace_variant_overrides(
+    state: &Data<AppState>,
+    experiment: Experiment,
+    variants: Vec<VariantUpdateRequest>,
+    tenant: &Tenant,
+    user: &User,
+    conn: &mut PooledConnection<ConnectionManager<PgConnection>>,
+) -> superposition::Result<Experiment> {
+    let experiment_id = experiment.id.clone();
 
-    let first_variant = variants.get(0).ok_or(bad_argument!(
+    let first_variant = variants.first().ok_or(bad_argument!(
         "Variant not found in request. Provide at least one entry in variant's list",
     ))?;
     let override_keys = extract_override_keys(&first_variant.overrides)
         .into_iter()
         .collect();
 
-    // fetch the current variants of the experiment
-    let experiment = experiments::experiments
-        .find(experiment_id)
-        .first::<Experiment>(&mut conn)?;
-
     if experiment.status != ExperimentStatusType::CREATED {
         return Err(bad_argument!(
             "Only experiments in CREATED state can be updated"
@@ -556,6 +2505,10 @@ async fn update_overrides(
     /****************** Validating override_keys and variant overrides *********************/
 
     validate_override_keys(&override_keys)?;
+    let default_config_schemas =
+        fetch_default_config_schemas(state, tenant, user).await?;
+    let default_config_keys = default_config_schemas.keys().cloned().collect();
+    validate_override_keys_exist(&override_keys, &default_config_keys)?;
 
     // checking if variants passed with correct existing variant ids
     let variant_ids: HashSet<String> = HashSet::from_iter(
@@ -571,18 +2524,33 @@ async fn update_overrides(
             ))?;
         }
     }
+    let mut unknown_variant_ids = variant_ids
+        .iter()
+        .filter(|id| !id_to_existing_variant.contains_key(*id))
+        .map(String::as_str)
+        .collect::<Vec<&str>>();
+    if !unknown_variant_ids.is_empty() {
+        unknown_variant_ids.sort();
+        return Err(bad_argument!(
+            "variant ids [{}] do not belong to experiment {}. Provide only existing variants of the experiment",
+            unknown_variant_ids.join(","),
+            experiment_id
+        ));
+    }
+
     // Checking if all the variants are overriding the mentioned keys
     let mut new_variants: Vec<Variant> = variants
         .into_iter()
         .map(|variant| {
-            let existing_variant: &Variant =
-                id_to_existing_variant.get(&variant.id).unwrap();
+            let existing_variant: &Variant = id_to_existing_variant[&variant.id];
             Variant {
                 id: variant.id,
                 variant_type: existing_variant.variant_type.clone(),
                 overrides: variant.overrides,
                 override_id: None,
                 context_id: None,
+                traffic_percentage: existing_variant.traffic_percentage,
+                description: existing_variant.description.clone(),
             }
         })
         .collect();
@@ -601,15 +2569,17 @@ async fn update_overrides(
             )
         )?;
     }
+    check_variant_types(&new_variants, experiment.experiment_type)?;
+    validate_variant_override_types(&new_variants, &default_config_schemas)?;
 
     // validating experiment against other active experiments based on permission flags
     let flags = &state.experimentation_flags;
     let (valid, reason) = validate_experiment(
         &experiment.context,
         &override_keys,
-        Some(experiment_id),
+        Some(&experiment_id),
         &flags,
-        &mut conn,
+        conn,
     )?;
     if !valid {
         return Err(bad_argument!(reason));
@@ -652,58 +2622,159 @@ async fn update_overrides(
         cac_operations.push(ContextAction::PUT(payload));
     }
 
-    let http_client = reqwest::Client::new();
-    let url = state.cac_host.clone() + "/context/bulk-operations";
-
-    let response = http_client
-        .put(&url)
-        .header("x-tenant", tenant.as_str())
-        .header(
-            "Authorization",
-            format!("{} {}", user.get_auth_type(), user.get_auth_token()),
-        )
-        .json(&cac_operations)
-        .send()
-        .await;
+    let response =
+        call_cac_bulk_operations(state, tenant, user, &cac_operations, None).await;
 
     // directly return an error response if not a 200 response
-    let created_contexts = process_cac_http_response(response).await?.into_iter().fold(
-        Vec::new(),
-        |mut acc, item| {
-            if let ContextBulkResponse::PUT(context) = item {
-                acc.push(context);
-            } else {
-                log::error!("Unexpected response item: {:?}", item);
-            }
-            acc
-        },
-    );
-    for i in 0..created_contexts.len() {
-        let created_context = &created_contexts[i];
-
-        new_variants[i].context_id = Some(created_context.context_id.clone());
-        new_variants[i].override_id = Some(created_context.override_id.clone());
-    }
+    let created_contexts =
+        created_contexts_of(&cac_operations, process_cac_http_response(response).await?)?;
+    assign_created_contexts(&mut new_variants, &created_contexts)?;
 
     /*************************** Updating experiment in DB **************************/
     let new_variants_json = serde_json::to_value(new_variants).map_err(|e| {
         log::error!("failed to serialize new variants to json with error: {e}");
         bad_argument!("failed to update experiment, bad variant data")
     })?;
-    let updated_experiment = diesel::update(experiments::experiments.find(experiment_id))
-        .set((
-            experiments::variants.eq(new_variants_json),
-            experiments::override_keys.eq(override_keys),
-            experiments::last_modified.eq(Utc::now()),
-            experiments::last_modified_by.eq(user.get_email()),
-        ))
-        .get_result::<Experiment>(&mut conn)?;
+    let updated_experiment =
+        diesel::update(experiments::experiments.find(&experiment_id))
+            .set((
+                experiments::variants.eq(new_variants_json),
+                experiments::override_keys.eq(override_keys),
+                experiments::last_modified.eq(Utc::now()),
+                experiments::last_modified_by.eq(user.get_email()),
+            ))
+            .get_result::<Experiment>(conn)?;
+
+    Ok(updated_experiment)
+}
 
-    return Ok(Json(ExperimentResponse::from(updated_experiment)));
+#[put("/{id}/context")]
+async fn update_context(
+    http_req: HttpRequest,
+    params: web::Path<String>,
+    state: Data<AppState>,
+    db_conn: DbConnection,
+    req: web::Json<ContextUpdateRequest>,
+    tenant: Tenant,
+    user: User,
+) -> superposition::Result<Json<ExperimentResponse>> {
+    let DbConnection(mut conn) = db_conn;
+    let experiment_id = params.into_inner();
+    let precondition = experiment_precondition(&http_req)?;
+
+    let experiment = lock_experiment(&experiment_id, &mut conn)?;
+    let updated = async {
+        check_experiment_precondition(
+            &experiment_id,
+            experiment.last_modified,
+            precondition,
+        )?;
+        replace_experiment_context(
+            &state,
+            experiment,
+            req.into_inner(),
+            &tenant,
+            &user,
+            &mut conn,
+        )
+        .await
+    }
+    .await;
+    let updated_experiment = end_experiment_change(updated, &mut conn)?;
+    Ok(Json(ExperimentResponse::from(updated_experiment)))
+}
+
+/// Moves a CREATED experiment to a new context, re-creating the contexts of
+/// its variants in CAC. Experiments serving traffic keep their context, as
+/// changing it would move users between audiences mid experiment. The
+/// experiment has to be locked by [`lock_experiment`].
+async fn replace_experiment_context(
+    state: &Data<AppState>,
+    experiment: Experiment,
+    req: ContextUpdateRequest,
+    tenant: &Tenant,
+    user: &User,
+    conn: &mut PooledConnection<ConnectionManager<PgConnection>>,
+) -> superposition::Result<Experiment> {
+    let experiment_id = experiment.id.clone();
+    if experiment.status != ExperimentStatusType::CREATED {
+        return Err(bad_argument!(
+            "the context of experiment {} can only be changed while it is CREATED, it is {:?}",
+            experiment_id,
+            experiment.status
+        ));
+    }
+
+    validate_experiment_context(&req.context, req.allow_global)?;
+    let flags = &state.experimentation_flags;
+    let (valid, reason) = validate_experiment(
+        &req.context,
+        &experiment.override_keys,
+        Some(&experiment_id),
+        flags,
+        conn,
+    )?;
+    if !valid {
+        return Err(bad_argument!(reason));
+    }
+
+    let mut variants: Vec<Variant> = serde_json::from_value(experiment.variants)?;
+    let mut cac_operations: Vec<ContextAction> = vec![];
+    for variant in &variants {
+        let context_id = variant.context_id.clone().ok_or_else(|| {
+            log::error!("context id not available for variant {:?}", variant.id);
+            unexpected_error!("Something went wrong, failed to update experiment")
+        })?;
+        cac_operations.push(ContextAction::DELETE(context_id));
+    }
+    // variant ids already carry the experiment id, only the targeting changes
+    for variant in &variants {
+        let variant_context =
+            add_variant_dimension_to_ctx(&req.context, variant.id.to_string())?;
+        let context = variant_context.as_object().cloned().ok_or_else(|| {
+            log::error!("failed to parse updated context with variant dimension");
+            unexpected_error!("Something went wrong, failed to update experiment")
+        })?;
+        cac_operations.push(ContextAction::PUT(ContextPutReq {
+            context,
+            r#override: json!(variant.overrides),
+        }));
+    }
+
+    let response =
+        call_cac_bulk_operations(state, tenant, user, &cac_operations, None).await;
+    let created_contexts =
+        created_contexts_of(&cac_operations, process_cac_http_response(response).await?)?;
+    assign_created_contexts(&mut variants, &created_contexts)?;
+
+    let updated_experiment =
+        conn.transaction::<_, superposition::AppError, _>(|transaction_conn| {
+            let updated_experiment =
+                diesel::update(experiments::experiments.find(&experiment_id))
+                    .set((
+                        experiments::context.eq(&req.context),
+                        experiments::variants.eq(serde_json::to_value(&variants)?),
+                        experiments::last_modified.eq(Utc::now()),
+                        experiments::last_modified_by.eq(user.get_email()),
+                    ))
+                    .get_result::<Experiment>(transaction_conn)?;
+            record_experiment_audit(
+                &experiment_id,
+                "UPDATE_CONTEXT",
+                Some(ExperimentStatusType::CREATED),
+                ExperimentStatusType::CREATED,
+                user.get_email(),
+                transaction_conn,
+            )?;
+            Ok(updated_experiment)
+        })?;
+
+    Ok(updated_experiment)
 }
 
 #[get("/audit")]
 async fn get_audit_logs(
+    state: Data<AppState>,
     filters: Query<AuditQueryFilters>,
     db_conn: DbConnection,
 ) -> superposition::Result<HttpResponse> {
@@ -732,18 +2803,18 @@ async fn get_audit_logs(
     let base_query = query_builder(&filters);
     let count_query = query_builder(&filters);
 
-    let limit = filters.count.unwrap_or(10);
-    let offset = (filters.page.unwrap_or(1) - 1) * limit;
+    let pagination =
+        resolve_pagination(filters.page, filters.count, state.max_page_size)?;
     let query = base_query
         .order(event_log::timestamp.desc())
-        .limit(limit)
-        .offset(offset);
+        .limit(pagination.size)
+        .offset(pagination.offset());
 
     let log_count: i64 = count_query.count().get_result(&mut conn)?;
 
     let logs: Vec<EventLog> = query.load(&mut conn)?;
 
-    let total_pages = (log_count as f64 / limit as f64).ceil() as i64;
+    let total_pages = calculate_total_pages(log_count, pagination.size);
 
     Ok(HttpResponse::Ok().json(json!({
         "total_items": log_count,
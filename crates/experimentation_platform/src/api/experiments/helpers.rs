@@ -1,15 +1,34 @@
-use super::types::{Variant, VariantType};
-use crate::db::models::{Experiment, ExperimentStatusType};
+use super::types::{
+    ContextAction, ContextBulkResponse, ContextGetResp, ContextPutResp,
+    ExperimentCreateRequest, ExperimentExport, KeyDiff, ListFilters, MetricObservation,
+    ReconciliationStatus, RolloutStep, ServedVariant, Variant, VariantPin,
+    VariantReconciliation, VariantType, VariantUpdateRequest,
+};
+use crate::db::models::{
+    Experiment, ExperimentAudit, ExperimentStatusType, ExperimentType,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use diesel::connection::{AnsiTransactionManager, TransactionManager};
 use diesel::pg::PgConnection;
-use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+};
+use jsonschema::{Draft, JSONSchema};
 use serde_json::{Map, Value};
 use service_utils::helpers::extract_dimensions;
 use service_utils::service::types::ExperimentationFlags;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
 
-use service_utils::{bad_argument, result as superposition};
+use actix_web::{http::StatusCode, HttpRequest};
+use service_utils::{
+    bad_argument, response_error, result as superposition, unexpected_error,
+};
 
-pub fn check_variant_types(variants: &Vec<Variant>) -> superposition::Result<()> {
+pub fn check_variant_types(
+    variants: &Vec<Variant>,
+    experiment_type: ExperimentType,
+) -> superposition::Result<()> {
     let mut experimental_variant_cnt = 0;
     let mut control_variant_cnt = 0;
 
@@ -34,9 +53,139 @@ pub fn check_variant_types(variants: &Vec<Variant>) -> superposition::Result<()>
         ));
     }
 
+    if experiment_type == ExperimentType::FLAG {
+        check_flag_variants(variants)?;
+    }
+
+    Ok(())
+}
+
+// a flag is off for the control variant and on for the experimental one
+fn check_flag_variants(variants: &Vec<Variant>) -> superposition::Result<()> {
+    if variants.len() != 2 {
+        return Err(bad_argument!(
+            "FLAG experiments have exactly 1 control and 1 experimental variant, provided {} variants",
+            variants.len()
+        ));
+    }
+    for variant in variants {
+        let expected = variant.variant_type == VariantType::EXPERIMENTAL;
+        let mut overrides = variant.overrides.values();
+        match (overrides.next(), overrides.next()) {
+            (Some(Value::Bool(value)), None) if *value == expected => (),
+            _ => {
+                return Err(bad_argument!(
+                    "variant {} of a FLAG experiment should override a single boolean key to {}",
+                    variant.id,
+                    expected
+                ))
+            }
+        }
+    }
     Ok(())
 }
 
+pub const FLAG_CONTROL_VARIANT_ID: &str = "off";
+pub const FLAG_EXPERIMENTAL_VARIANT_ID: &str = "on";
+
+/// Generates the variants of a FLAG create request from its `flag_key`, `off`
+/// setting the key to `false` and `on` setting it to `true`. Requests of other
+/// types have to list their variants instead.
+pub fn apply_flag_variants(
+    req: &mut ExperimentCreateRequest,
+) -> superposition::Result<()> {
+    match (req.experiment_type, req.flag_key.as_deref()) {
+        (ExperimentType::DEFAULT, None) => Ok(()),
+        (ExperimentType::DEFAULT, Some(_)) => Err(bad_argument!(
+            "flag_key is only used by FLAG experiments, set experiment_type to FLAG or list the variants"
+        )),
+        (ExperimentType::FLAG, None) => Err(bad_argument!(
+            "FLAG experiments need a flag_key, the boolean config key they turn on"
+        )),
+        (ExperimentType::FLAG, Some(_)) if !req.variants.is_empty() => Err(bad_argument!(
+            "the variants of FLAG experiments are generated from flag_key, leave out variants"
+        )),
+        (ExperimentType::FLAG, Some(flag_key)) => {
+            let flag_variant = |id: &str, variant_type, value: bool| Variant {
+                id: id.to_string(),
+                variant_type,
+                context_id: None,
+                override_id: None,
+                overrides: Map::from_iter([(flag_key.to_string(), Value::Bool(value))]),
+                traffic_percentage: None,
+                description: None,
+            };
+            req.variants = vec![
+                flag_variant(FLAG_CONTROL_VARIANT_ID, VariantType::CONTROL, false),
+                flag_variant(FLAG_EXPERIMENTAL_VARIANT_ID, VariantType::EXPERIMENTAL, true),
+            ];
+            Ok(())
+        }
+    }
+}
+
+/// Separates the experiment id from a variant's own id in the ids of the
+/// variants stored with an experiment, `<experiment_id>-<variant_id>`.
+pub const VARIANT_ID_SEPARATOR: char = '-';
+
+pub fn variant_context_id(experiment_id: &str, variant_id: &str) -> String {
+    format!("{experiment_id}{VARIANT_ID_SEPARATOR}{variant_id}")
+}
+
+/// Experiment and variant ids of an id built by `variant_context_id`.
+pub fn split_variant_context_id(variant_context_id: &str) -> Option<(&str, &str)> {
+    variant_context_id.split_once(VARIANT_ID_SEPARATOR).filter(
+        |(experiment_id, variant_id)| !experiment_id.is_empty() && !variant_id.is_empty(),
+    )
+}
+
+/// Every variant gets its own CAC context and a slice of the traffic, so
+/// experiments are limited to `max_variants` of them.
+pub fn validate_variant_count(
+    variants: &[Variant],
+    max_variants: usize,
+) -> superposition::Result<()> {
+    if variants.len() > max_variants {
+        return Err(bad_argument!(
+            "an experiment can have at most {} variants, provided {}",
+            max_variants,
+            variants.len()
+        ));
+    }
+    Ok(())
+}
+
+// variant ids are restricted to letters, digits and `_`, so that the id stored
+// with an experiment splits back unambiguously at its first separator, and
+// have to be unique as each one gets its own CAC context
+pub fn validate_variant_ids(variants: &[Variant]) -> superposition::Result<()> {
+    let mut seen_ids = HashSet::new();
+    if let Some(variant) = variants
+        .iter()
+        .find(|variant| !seen_ids.insert(&variant.id))
+    {
+        return Err(bad_argument!(
+            "variant id `{}` is used by more than one variant, variant ids have to be unique",
+            variant.id
+        ));
+    }
+
+    let unsafe_id = variants.iter().find(|variant| {
+        variant.id.is_empty()
+            || !variant
+                .id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    });
+    match unsafe_id {
+        Some(variant) => Err(bad_argument!(
+            "variant id `{}` is invalid, variant ids can only contain letters, digits and `_`",
+            variant.id
+        )),
+        None => Ok(()),
+    }
+}
+
 pub fn validate_override_keys(override_keys: &Vec<String>) -> superposition::Result<()> {
     let mut key_set: HashSet<&str> = HashSet::new();
     for key in override_keys {
@@ -50,6 +199,433 @@ pub fn validate_override_keys(override_keys: &Vec<String>) -> superposition::Res
     Ok(())
 }
 
+pub fn validate_override_keys_exist(
+    override_keys: &[String],
+    default_config_keys: &HashSet<String>,
+) -> superposition::Result<()> {
+    let unknown_keys = override_keys
+        .iter()
+        .filter(|key| !default_config_keys.contains(*key))
+        .map(String::as_str)
+        .collect::<Vec<&str>>();
+
+    if !unknown_keys.is_empty() {
+        return Err(bad_argument!(
+            "override_keys [{}] are not defined in default config. Create these keys in default config before overriding them",
+            unknown_keys.join(",")
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks every variant override against the schema of its default config
+/// key, so that a `"5"` is not served for an integer key. Keys without a
+/// schema are left to `validate_override_keys_exist`.
+pub fn validate_variant_override_types(
+    variants: &Vec<Variant>,
+    default_config_schemas: &HashMap<String, Value>,
+) -> superposition::Result<()> {
+    let mut compiled_schemas: HashMap<&str, JSONSchema> = HashMap::new();
+    for variant in variants {
+        for (key, value) in &variant.overrides {
+            let Some(schema) = default_config_schemas.get(key) else {
+                continue;
+            };
+            if !compiled_schemas.contains_key(key.as_str()) {
+                let compiled = JSONSchema::options()
+                    .with_draft(Draft::Draft7)
+                    .compile(schema)
+                    .map_err(|err| {
+                        log::error!(
+                            "failed to compile schema of config key {key}: {err}"
+                        );
+                        bad_argument!("failed to compile ({}) config key schema", key)
+                    })?;
+                compiled_schemas.insert(key, compiled);
+            }
+            if let Err(errors) = compiled_schemas[key.as_str()].validate(value) {
+                let errors = errors.map(|err| err.to_string()).collect::<Vec<String>>();
+                return Err(bad_argument!(
+                    "override of {} in variant {} does not match the schema of the key: {}",
+                    key,
+                    variant.id,
+                    errors.join(", ")
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn validate_global_context(
+    context: &Value,
+    allow_global: bool,
+) -> superposition::Result<()> {
+    let is_global = context.as_object().map_or(false, Map::is_empty);
+    if is_global && !allow_global {
+        return Err(bad_argument!(
+            "context is empty, which would run the experiment for every request. Provide a context, or set allow_global to true to create a global experiment intentionally"
+        ));
+    }
+    Ok(())
+}
+
+/// Conflicts between experiments are checked on the dimensions of their
+/// contexts, which are read as all having to hold. The branches of an `or`
+/// would be flattened into those dimensions and checked as if all of them
+/// held, so experiment contexts may only combine conditions with `and`.
+pub fn validate_context_is_conjunction(context: &Value) -> superposition::Result<()> {
+    fn find_or(condition: &Value, path: &str) -> Option<String> {
+        let condition = condition.as_object()?;
+        if condition.contains_key("or") {
+            return Some(format!("{path}or"));
+        }
+        condition
+            .get("and")
+            .and_then(Value::as_array)?
+            .iter()
+            .enumerate()
+            .find_map(|(idx, nested)| find_or(nested, &format!("{path}and[{idx}].")))
+    }
+
+    match find_or(context, "") {
+        Some(location) => Err(bad_argument!(
+            "experiment contexts cannot use `or` (at `{}`), conflicts with other experiments are checked assuming every condition holds. Create one experiment per branch instead",
+            location
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Fills in the traffic_percentage of a create request that left it out.
+/// `default_traffic` is the total traffic of the experiment, weighted
+/// experiments split it by their weights and the others evenly between their
+/// variants. Returns the traffic_percentage filled in.
+pub fn apply_default_traffic_percentage(
+    req: &mut ExperimentCreateRequest,
+    default_traffic: i64,
+) -> Option<i64> {
+    if req.traffic_percentage.is_some() {
+        return None;
+    }
+    let traffic_percentage = if is_weighted_experiment(&req.variants) {
+        default_traffic
+    } else {
+        default_traffic / (req.variants.len().max(1) as i64)
+    };
+    req.traffic_percentage = Some(traffic_percentage);
+    Some(traffic_percentage)
+}
+
+pub fn validate_traffic_percentage(
+    traffic_percentage: i64,
+    variants: &Vec<Variant>,
+) -> superposition::Result<()> {
+    if traffic_percentage < 0 {
+        return Err(bad_argument!(
+            "traffic_percentage cannot be negative, provided {}",
+            traffic_percentage
+        ));
+    }
+
+    // weighted variants split the experiment's traffic among themselves
+    if is_weighted_experiment(variants) {
+        if traffic_percentage > 100 {
+            return Err(bad_argument!(
+                "traffic_percentage of an experiment with weighted variants cannot exceed 100, provided {}",
+                traffic_percentage
+            ));
+        }
+        return Ok(());
+    }
+
+    // every variant, including control, is served `traffic_percentage` of the traffic
+    let mut total_traffic = 0;
+    for variant in variants {
+        total_traffic += traffic_percentage;
+        if total_traffic > 100 {
+            return Err(bad_argument!(
+                "allocating {}% traffic to variant {} takes the total traffic to {}%. Provide a traffic_percentage of at most {}",
+                traffic_percentage,
+                variant.id,
+                total_traffic,
+                100 / variants.len()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// A rollout plan steps through increasing traffic percentages, each of them
+/// valid for the variants of the experiment.
+pub fn validate_rollout_plan(
+    rollout_plan: &[RolloutStep],
+    variants: &Vec<Variant>,
+) -> superposition::Result<()> {
+    let mut previous_traffic = None;
+    for (idx, step) in rollout_plan.iter().enumerate() {
+        validate_traffic_percentage(step.traffic_percentage, variants).map_err(
+            |err| match err {
+                superposition::AppError::BadArgument(msg) => {
+                    bad_argument!("rollout step {}: {}", idx, msg)
+                }
+                err => err,
+            },
+        )?;
+        if previous_traffic.map_or(false, |previous| step.traffic_percentage <= previous)
+        {
+            return Err(bad_argument!(
+                "rollout step {} does not increase the traffic_percentage, every step has to serve more traffic than the one before",
+                idx
+            ));
+        }
+        if step.advance_after_secs.map_or(false, |secs| secs <= 0) {
+            return Err(bad_argument!(
+                "advance_after_secs of rollout step {} has to be positive",
+                idx
+            ));
+        }
+        previous_traffic = Some(step.traffic_percentage);
+    }
+    Ok(())
+}
+
+pub fn parse_rollout_plan(
+    experiment: &Experiment,
+) -> superposition::Result<Vec<RolloutStep>> {
+    match &experiment.rollout_plan {
+        None => Ok(Vec::new()),
+        Some(rollout_plan) => {
+            serde_json::from_value(rollout_plan.clone()).map_err(|err| {
+                log::error!("failed to parse rollout plan of {}: {err}", experiment.id);
+                unexpected_error!("Something went wrong, failed to read the rollout plan")
+            })
+        }
+    }
+}
+
+/// Index of the step an experiment advances to, `None` once it is on the last
+/// step of its plan.
+pub fn next_rollout_step(
+    rollout_plan: &[RolloutStep],
+    current_step: Option<i32>,
+) -> Option<usize> {
+    let next_step = current_step.map_or(0, |step| step as usize + 1);
+    (next_step < rollout_plan.len()).then_some(next_step)
+}
+
+/// Whether the current step is advanced on time and has run long enough.
+pub fn is_rollout_step_due(
+    rollout_plan: &[RolloutStep],
+    current_step: Option<i32>,
+    step_started_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> bool {
+    let advance_after_secs = current_step
+        .and_then(|step| rollout_plan.get(step as usize))
+        .and_then(|step| step.advance_after_secs);
+    match (advance_after_secs, step_started_at) {
+        (Some(secs), Some(started_at)) => {
+            started_at + chrono::Duration::seconds(secs) <= now
+        }
+        _ => false,
+    }
+}
+
+// variants served less traffic than this take long to gather enough samples
+pub const LOW_VARIANT_TRAFFIC_PERCENTAGE: f64 = 2.0;
+// experiments exposing at least this much traffic on a context of at most one
+// dimension reach most users
+pub const BROAD_AUDIENCE_TRAFFIC_PERCENTAGE: i64 = 50;
+
+/// Warns when some variant of an experiment that is serving traffic gets less
+/// than [`LOW_VARIANT_TRAFFIC_PERCENTAGE`] of it.
+pub fn low_traffic_warning(
+    traffic_percentage: i64,
+    variants: &[Variant],
+) -> Option<String> {
+    if traffic_percentage == 0 {
+        return None;
+    }
+    let smallest_share = if is_weighted_experiment(variants) {
+        variants
+            .iter()
+            .filter_map(|variant| variant.traffic_percentage)
+            .min()
+            .map_or(traffic_percentage as f64, |weight| {
+                traffic_percentage as f64 * weight as f64 / 100.0
+            })
+    } else {
+        traffic_percentage as f64
+    };
+    (smallest_share < LOW_VARIANT_TRAFFIC_PERCENTAGE).then(|| {
+        format!(
+            "a variant is served only {smallest_share}% of the traffic, with {} variants the experiment may take long to reach significant results",
+            variants.len()
+        )
+    })
+}
+
+/// Warns when an experiment exposes at least
+/// [`BROAD_AUDIENCE_TRAFFIC_PERCENTAGE`] of the traffic on a context that
+/// narrows it down by at most one dimension.
+pub fn broad_audience_warning(
+    context: &Value,
+    traffic_percentage: i64,
+    variants: &[Variant],
+) -> Option<String> {
+    let exposed_traffic = if is_weighted_experiment(variants) {
+        traffic_percentage
+    } else {
+        traffic_percentage * variants.len() as i64
+    };
+    let dimension_count = extract_dimensions(context).map_or(0, |dims| dims.len());
+    (dimension_count <= 1 && exposed_traffic >= BROAD_AUDIENCE_TRAFFIC_PERCENTAGE).then(
+        || {
+            format!(
+                "the experiment serves {exposed_traffic}% of the traffic on a context of {dimension_count} dimensions, which may reach a very large audience"
+            )
+        },
+    )
+}
+
+/// Non fatal findings about an experiment being created, reported back to the
+/// client without failing the request.
+pub fn experiment_create_warnings(req: &ExperimentCreateRequest) -> Vec<String> {
+    let traffic_percentage = req.traffic_percentage.unwrap_or_default();
+    [
+        low_traffic_warning(traffic_percentage, &req.variants),
+        broad_audience_warning(&req.context, traffic_percentage, &req.variants),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Points an error of one experiment of a bulk request at its index, so field
+/// errors come back keyed as `[idx].field`.
+pub fn prefix_bulk_error(
+    idx: usize,
+    err: superposition::AppError,
+) -> superposition::AppError {
+    match err {
+        superposition::AppError::BadArgument(msg) => {
+            bad_argument!("experiment at index {}: {}", idx, msg)
+        }
+        superposition::AppError::InvalidFields(field_errors) => {
+            superposition::AppError::InvalidFields(
+                field_errors
+                    .into_iter()
+                    .map(|(field, msg)| (format!("[{idx}].{field}"), msg))
+                    .collect(),
+            )
+        }
+        err => err,
+    }
+}
+
+pub fn is_weighted_experiment(variants: &[Variant]) -> bool {
+    variants
+        .iter()
+        .any(|variant| variant.traffic_percentage.is_some())
+}
+
+pub fn validate_variant_traffic_percentages(
+    variants: &Vec<Variant>,
+) -> superposition::Result<()> {
+    if !is_weighted_experiment(variants) {
+        return Ok(());
+    }
+
+    let mut experimental_traffic = 0;
+    for variant in variants {
+        if variant.variant_type != VariantType::EXPERIMENTAL {
+            continue;
+        }
+        match variant.traffic_percentage {
+            Some(percentage) if percentage > 0 => experimental_traffic += percentage,
+            Some(percentage) => {
+                return Err(bad_argument!(
+                    "traffic_percentage of experimental variant {} should be positive, provided {}",
+                    variant.id,
+                    percentage
+                ))
+            }
+            None => {
+                return Err(bad_argument!(
+                    "traffic_percentage is missing for experimental variant {}. Provide it for all experimental variants or for none of them",
+                    variant.id
+                ))
+            }
+        }
+    }
+
+    if experimental_traffic > 100 {
+        return Err(bad_argument!(
+            "traffic_percentage of experimental variants add up to {}%, which should be at most 100%",
+            experimental_traffic
+        ));
+    }
+
+    let control_traffic = 100 - experimental_traffic;
+    for variant in variants {
+        match (&variant.variant_type, variant.traffic_percentage) {
+            (VariantType::CONTROL, Some(percentage)) if percentage != control_traffic => {
+                return Err(bad_argument!(
+                    "control variant {} gets the remaining {}% of the traffic, provided {}",
+                    variant.id,
+                    control_traffic,
+                    percentage
+                ))
+            }
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+// the control variant of a weighted experiment is served whatever the
+// experimental variants leave over
+pub fn assign_control_traffic_percentage(variants: &mut [Variant]) {
+    if !is_weighted_experiment(variants) {
+        return;
+    }
+
+    let experimental_traffic: i32 = variants
+        .iter()
+        .filter(|variant| variant.variant_type == VariantType::EXPERIMENTAL)
+        .filter_map(|variant| variant.traffic_percentage)
+        .sum();
+    for variant in variants.iter_mut() {
+        if variant.variant_type == VariantType::CONTROL {
+            variant.traffic_percentage = Some(100 - experimental_traffic);
+        }
+    }
+}
+
+// percentage of the overall traffic each variant is served, weighted variants
+// get their share of the experiment's `traffic_percentage`
+pub fn variant_traffic_split(
+    traffic_percentage: i32,
+    variants: &[Variant],
+) -> Vec<(Variant, i32)> {
+    let weighted = is_weighted_experiment(variants);
+    variants
+        .iter()
+        .map(|variant| {
+            let percentage = match variant.traffic_percentage {
+                Some(weight) if weighted => weight * traffic_percentage / 100,
+                _ if weighted => 0,
+                _ => traffic_percentage,
+            };
+            (variant.clone(), percentage)
+        })
+        .collect()
+}
+
 pub fn are_overlapping_contexts(
     context_a: &Value,
     context_b: &Value,
@@ -57,27 +633,42 @@ pub fn are_overlapping_contexts(
     let dimensions_a = extract_dimensions(context_a)?;
     let dimensions_b = extract_dimensions(context_b)?;
 
-    let dim_a_keys = dimensions_a.keys();
-    let dim_b_keys = dimensions_b.keys();
+    Ok(are_overlapping_dimensions(&dimensions_a, &dimensions_b))
+}
 
-    let ref_keys = if dim_a_keys.len() > dim_b_keys.len() {
-        dim_b_keys
+// dimension maps overlap when the dimensions of the smaller one are all set to
+// the same values in the other
+pub fn are_overlapping_dimensions(
+    dimensions_a: &Map<String, Value>,
+    dimensions_b: &Map<String, Value>,
+) -> bool {
+    let ref_keys = if dimensions_a.len() > dimensions_b.len() {
+        dimensions_b.keys()
     } else {
-        dim_a_keys
+        dimensions_a.keys()
     };
 
-    let mut is_overlapping = true;
-    for key in ref_keys {
-        let test = (dimensions_a.contains_key(key) && dimensions_b.contains_key(key))
-            && (dimensions_a[key] == dimensions_b[key]);
-        is_overlapping = is_overlapping && test;
+    ref_keys.into_iter().all(|key| {
+        dimensions_a.contains_key(key)
+            && dimensions_b.contains_key(key)
+            && dimensions_a[key] == dimensions_b[key]
+    })
+}
 
-        if !test {
-            break;
+/// Experiments whose context overlaps with the given dimensions, the same
+/// check `is_valid_experiment` applies against a new experiment's context.
+pub fn matching_experiments(
+    experiments: Vec<Experiment>,
+    dimensions: &Map<String, Value>,
+) -> superposition::Result<Vec<Experiment>> {
+    let mut matching = Vec::new();
+    for experiment in experiments {
+        let experiment_dimensions = extract_dimensions(&experiment.context)?;
+        if are_overlapping_dimensions(dimensions, &experiment_dimensions) {
+            matching.push(experiment);
         }
     }
-
-    Ok(is_overlapping)
+    Ok(matching)
 }
 
 pub fn check_variant_override_coverage(
@@ -93,7 +684,7 @@ pub fn check_variant_override_coverage(
             return false;
         }
     }
-    return true;
+    true
 }
 
 pub fn check_variants_override_coverage(
@@ -106,7 +697,7 @@ pub fn check_variants_override_coverage(
         }
     }
 
-    return true;
+    true
 }
 
 pub fn is_valid_experiment(
@@ -156,7 +747,10 @@ pub fn is_valid_experiment(
             }
 
             if !valid_experiment {
-                invalid_reason.push_str("This current context overlaps with an existing experiment or the keys in the context are overlapping");
+                invalid_reason = format!(
+                    "This current context overlaps with an existing experiment (id: {}) or the keys in the context are overlapping",
+                    active_experiment.id
+                );
                 break;
             }
         }
@@ -165,29 +759,69 @@ pub fn is_valid_experiment(
     Ok((valid_experiment, invalid_reason))
 }
 
-pub fn validate_experiment(
-    context: &Value,
-    override_keys: &Vec<String>,
-    experiment_id: Option<i64>,
-    flags: &ExperimentationFlags,
+pub fn fetch_active_experiments(
+    experiment_id: Option<&str>,
     conn: &mut PgConnection,
-) -> superposition::Result<(bool, String)> {
+) -> superposition::Result<Vec<Experiment>> {
     use crate::db::schema::experiments::dsl as experiments_dsl;
 
     let active_experiments: Vec<Experiment> = experiments_dsl::experiments
         .filter(
             diesel::dsl::not(experiments_dsl::id.eq(experiment_id.unwrap_or_default()))
-                .and(
-                    experiments_dsl::status
-                        .eq(ExperimentStatusType::CREATED)
-                        .or(experiments_dsl::status.eq(ExperimentStatusType::INPROGRESS)),
-                ),
+                .and(experiments_dsl::status.eq_any(vec![
+                    ExperimentStatusType::CREATED,
+                    ExperimentStatusType::INPROGRESS,
+                    ExperimentStatusType::PAUSED,
+                ])),
         )
         .load(conn)?;
 
+    Ok(active_experiments)
+}
+
+pub fn validate_experiment(
+    context: &Value,
+    override_keys: &Vec<String>,
+    experiment_id: Option<&str>,
+    flags: &ExperimentationFlags,
+    conn: &mut PgConnection,
+) -> superposition::Result<(bool, String)> {
+    let active_experiments = fetch_active_experiments(experiment_id, conn)?;
+
     is_valid_experiment(context, override_keys, flags, &active_experiments)
 }
 
+pub fn record_experiment_audit(
+    experiment_id: &str,
+    action: &str,
+    previous_status: Option<ExperimentStatusType>,
+    new_status: ExperimentStatusType,
+    actor_email: String,
+    conn: &mut PgConnection,
+) -> superposition::Result<()> {
+    use crate::db::schema::experiment_audit::dsl as experiment_audit_dsl;
+
+    let audit_entry = ExperimentAudit {
+        id: uuid::Uuid::new_v4(),
+        experiment_id: experiment_id.to_string(),
+        action: action.to_string(),
+        previous_status,
+        new_status,
+        actor_email,
+        timestamp: Utc::now(),
+    };
+
+    diesel::insert_into(experiment_audit_dsl::experiment_audit)
+        .values(&audit_entry)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Dimension holding the variant ids a request was assigned, every variant
+/// context is conditioned on it.
+pub const VARIANT_DIMENSION: &str = "variantIds";
+
 pub fn add_variant_dimension_to_ctx(
     context_json: &Value,
     variant: String,
@@ -209,7 +843,7 @@ pub fn add_variant_dimension_to_ctx(
     let variant_condition = serde_json::json!({
         "in" : [
             variant,
-            { "var": "variantIds" }
+            { "var": VARIANT_DIMENSION }
         ]
     });
     conditions.push(variant_condition);
@@ -225,6 +859,762 @@ pub fn add_variant_dimension_to_ctx(
     }
 }
 
+/// Contexts created by the PUT operations of a CAC bulk call. CAC answers
+/// every operation in order with a response of the same action, so responses
+/// are matched to their operations by action and the DELETE and MOVE ones of
+/// a mixed batch are skipped instead of being taken for created contexts.
+pub fn created_contexts_of(
+    operations: &[ContextAction],
+    responses: Vec<ContextBulkResponse>,
+) -> superposition::Result<Vec<ContextPutResp>> {
+    if operations.len() != responses.len() {
+        log::error!(
+            "CAC returned {} responses for {} bulk operations",
+            responses.len(),
+            operations.len()
+        );
+        return Err(unexpected_error!(
+            "CAC returned {} responses for {} operations, they cannot be matched to the operations",
+            responses.len(),
+            operations.len()
+        ));
+    }
+    let mut created_contexts = Vec::new();
+    for (idx, (operation, response)) in operations.iter().zip(responses).enumerate() {
+        match (operation, response) {
+            (ContextAction::PUT(_), ContextBulkResponse::PUT(created_context)) => {
+                created_contexts.push(created_context)
+            }
+            (ContextAction::DELETE(_), ContextBulkResponse::DELETE(_))
+            | (ContextAction::MOVE(_), ContextBulkResponse::MOVE(_)) => (),
+            (operation, response) => {
+                log::error!(
+                    "CAC answered the {} operation at index {idx} with {:?}",
+                    operation.action(),
+                    response
+                );
+                return Err(unexpected_error!(
+                    "CAC answered the {} operation at index {} with a {} response",
+                    operation.action(),
+                    idx,
+                    response.action()
+                ));
+            }
+        }
+    }
+    Ok(created_contexts)
+}
+
+/// Sets the CAC context and override created for each variant. CAC answers a
+/// bulk operation with one response per operation in the order they were sent,
+/// any other count means the responses cannot be matched to the variants.
+pub fn assign_created_contexts(
+    variants: &mut [Variant],
+    created_contexts: &[ContextPutResp],
+) -> superposition::Result<()> {
+    if created_contexts.len() != variants.len() {
+        log::error!(
+            "CAC returned {} contexts for {} variants",
+            created_contexts.len(),
+            variants.len()
+        );
+        return Err(unexpected_error!(
+            "CAC returned {} contexts for {} variants, they cannot be matched to the variants",
+            created_contexts.len(),
+            variants.len()
+        ));
+    }
+    for (variant, created_context) in variants.iter_mut().zip(created_contexts) {
+        variant.context_id = Some(created_context.context_id.clone());
+        variant.override_id = Some(created_context.override_id.clone());
+    }
+    Ok(())
+}
+
+/// Variants of an existing experiment as they would be sent in a create request,
+/// the experiment id prefix and the CAC context/override ids are dropped.
+pub fn clone_variants(experiment_id: &str, variants: Vec<Variant>) -> Vec<Variant> {
+    let id_prefix = variant_context_id(experiment_id, "");
+    variants
+        .into_iter()
+        .map(|variant| Variant {
+            id: variant
+                .id
+                .strip_prefix(&id_prefix)
+                .map(String::from)
+                .unwrap_or(variant.id.clone()),
+            context_id: None,
+            override_id: None,
+            ..variant
+        })
+        .collect()
+}
+
+/// Create request recreating an exported experiment. The exported
+/// `override_keys` have to be the keys every variant overrides, and the ids of
+/// the source CAC contexts are dropped.
+pub fn experiment_import_request(
+    export: ExperimentExport,
+) -> superposition::Result<ExperimentCreateRequest> {
+    let ExperimentExport {
+        name,
+        context,
+        override_keys,
+        traffic_percentage,
+        mut variants,
+        rollout_plan,
+        experiment_type,
+    } = export;
+    let variant_overrides = variants
+        .iter()
+        .map(|variant| &variant.overrides)
+        .collect::<Vec<&Map<String, Value>>>();
+    if !check_variants_override_coverage(&variant_overrides, &override_keys) {
+        return Err(bad_argument!(
+            "override_keys of the exported experiment do not match the keys its variants override"
+        ));
+    }
+    for variant in variants.iter_mut() {
+        variant.context_id = None;
+        variant.override_id = None;
+    }
+
+    Ok(ExperimentCreateRequest {
+        name,
+        traffic_percentage: Some(traffic_percentage),
+        allow_global: context.as_object().map_or(false, Map::is_empty),
+        context,
+        variants,
+        experiment_type,
+        flag_key: None,
+        starts_at: None,
+        ends_at: None,
+        rollout_plan,
+    })
+}
+
+/// Merges partial overrides into the variants of an experiment. A key set to
+/// `null` is removed from the variant, other keys are added or replaced, and
+/// variants left out of `patches` keep their overrides.
+pub fn merge_variant_overrides(
+    existing_variants: &[Variant],
+    patches: Vec<VariantUpdateRequest>,
+) -> superposition::Result<Vec<VariantUpdateRequest>> {
+    if patches.is_empty() {
+        return Err(bad_argument!(
+            "Variant not found in request. Provide at least one entry in variant's list"
+        ));
+    }
+    let mut patches_by_id: HashMap<String, Map<String, Value>> = HashMap::new();
+    for patch in patches {
+        if !existing_variants
+            .iter()
+            .any(|variant| variant.id == patch.id)
+        {
+            return Err(bad_argument!(
+                "variant id {} does not belong to the experiment",
+                patch.id
+            ));
+        }
+        if patches_by_id
+            .insert(patch.id.clone(), patch.overrides)
+            .is_some()
+        {
+            return Err(bad_argument!(
+                "variant id {} is patched more than once",
+                patch.id
+            ));
+        }
+    }
+
+    Ok(existing_variants
+        .iter()
+        .map(|variant| {
+            let mut overrides = variant.overrides.clone();
+            for (key, value) in patches_by_id.remove(&variant.id).unwrap_or_default() {
+                if value.is_null() {
+                    overrides.remove(&key);
+                } else {
+                    overrides.insert(key, value);
+                }
+            }
+            VariantUpdateRequest {
+                id: variant.id.clone(),
+                overrides,
+            }
+        })
+        .collect())
+}
+
+/// Key by key comparison of two override maps over `override_keys` and every
+/// key either map sets, sorted by key.
+pub fn diff_overrides(
+    override_keys: &[String],
+    before: &Map<String, Value>,
+    after: &Map<String, Value>,
+) -> Vec<KeyDiff> {
+    let keys = override_keys
+        .iter()
+        .chain(before.keys())
+        .chain(after.keys())
+        .collect::<BTreeSet<&String>>();
+    keys.into_iter()
+        .map(|key| {
+            let before = before.get(key).cloned();
+            let after = after.get(key).cloned();
+            KeyDiff {
+                key: key.clone(),
+                changed: before != after,
+                before,
+                after,
+            }
+        })
+        .collect()
+}
+
+/// Version of an experiment a client last read, sent back with a change so
+/// that it does not overwrite changes made since.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExperimentPrecondition {
+    // If-Match carrying the experiment's last_modified
+    Version(DateTime<Utc>),
+    // If-Unmodified-Since, HTTP dates only go down to seconds
+    UnmodifiedSince(DateTime<Utc>),
+}
+
+/// Reads the precondition of a change from the values of its `If-Match` and
+/// `If-Unmodified-Since` headers, `If-Match` wins when both are sent.
+pub fn parse_experiment_precondition(
+    if_match: Option<&str>,
+    if_unmodified_since: Option<&str>,
+) -> superposition::Result<Option<ExperimentPrecondition>> {
+    if let Some(version) = if_match {
+        let version = version.trim().trim_start_matches("W/").trim_matches('"');
+        return DateTime::parse_from_rfc3339(version)
+            .map(|version| {
+                Some(ExperimentPrecondition::Version(version.with_timezone(&Utc)))
+            })
+            .map_err(|_| {
+                bad_argument!(
+                    "If-Match should be the last_modified of the experiment, got {}",
+                    version
+                )
+            });
+    }
+    if let Some(since) = if_unmodified_since {
+        return DateTime::parse_from_rfc2822(since.trim())
+            .map(|since| {
+                Some(ExperimentPrecondition::UnmodifiedSince(
+                    since.with_timezone(&Utc),
+                ))
+            })
+            .map_err(|_| {
+                bad_argument!("If-Unmodified-Since should be an HTTP date, got {}", since)
+            });
+    }
+    Ok(None)
+}
+
+pub fn experiment_modified_error(experiment_id: &str) -> superposition::AppError {
+    response_error!(
+        StatusCode::CONFLICT,
+        format!(
+            "experiment with id {experiment_id} was modified after the version this change was made on. Fetch it again and retry"
+        )
+    )
+}
+
+// partial unique index keeping names unique among experiments not archived
+pub const EXPERIMENT_NAME_INDEX: &str = "experiments_active_name_key";
+
+pub fn experiment_name_taken_error(name: &str, holder: &str) -> superposition::AppError {
+    response_error!(
+        StatusCode::CONFLICT,
+        format!(
+            "an experiment named {name} already exists ({holder}). Pick another name, or archive that experiment to reuse it"
+        )
+    )
+}
+
+/// First name given to more than one of `names`.
+pub fn duplicate_experiment_name<'a>(names: &[&'a str]) -> Option<&'a str> {
+    let mut seen = HashSet::new();
+    names.iter().copied().find(|name| !seen.insert(*name))
+}
+
+/// Fails with a 409 when any of `names` repeats or is already used by an
+/// experiment that is not archived.
+pub fn ensure_experiment_names_available(
+    names: &[&str],
+    conn: &mut PgConnection,
+) -> superposition::Result<()> {
+    use crate::db::schema::experiments::dsl as experiments;
+
+    if let Some(name) = duplicate_experiment_name(names) {
+        return Err(experiment_name_taken_error(
+            name,
+            "given to another experiment of this request",
+        ));
+    }
+    let taken = experiments::experiments
+        .filter(experiments::name.eq_any(names))
+        .filter(experiments::archived_at.is_null())
+        .select((experiments::name, experiments::id))
+        .first::<(String, String)>(conn)
+        .optional()?;
+    match taken {
+        Some((name, id)) => Err(experiment_name_taken_error(&name, &format!("id {id}"))),
+        None => Ok(()),
+    }
+}
+
+/// Fails with a 409 when the experiment changed after the version the client
+/// read, no precondition always passes.
+pub fn check_experiment_precondition(
+    experiment_id: &str,
+    last_modified: DateTime<Utc>,
+    precondition: Option<ExperimentPrecondition>,
+) -> superposition::Result<()> {
+    let is_stale = match precondition {
+        None => false,
+        Some(ExperimentPrecondition::Version(version)) => version != last_modified,
+        Some(ExperimentPrecondition::UnmodifiedSince(since)) => {
+            last_modified.timestamp() > since.timestamp()
+        }
+    };
+    if is_stale {
+        return Err(experiment_modified_error(experiment_id));
+    }
+    Ok(())
+}
+
+/// Reads an experiment locking its row, in a transaction left open on `conn`
+/// until [`end_experiment_change`] ends it. Changes calling CAC take the lock
+/// before calling it, so that concurrent changes are applied one at a time,
+/// each checked against the version left by the previous one.
+pub fn lock_experiment(
+    experiment_id: &str,
+    conn: &mut PgConnection,
+) -> superposition::Result<Experiment> {
+    use crate::db::schema::experiments::dsl as experiments;
+
+    AnsiTransactionManager::begin_transaction(conn)?;
+    let experiment = experiments::experiments
+        .find(experiment_id)
+        .for_update()
+        .get_result::<Experiment>(conn)
+        .map_err(superposition::AppError::from);
+    match experiment {
+        Ok(experiment) => Ok(experiment),
+        Err(err) => end_experiment_change(Err(err), conn),
+    }
+}
+
+/// Commits the change to an experiment locked by [`lock_experiment`], or rolls
+/// it back when it failed.
+pub fn end_experiment_change<T>(
+    change: superposition::Result<T>,
+    conn: &mut PgConnection,
+) -> superposition::Result<T> {
+    match change {
+        Ok(value) => {
+            AnsiTransactionManager::commit_transaction(conn)?;
+            Ok(value)
+        }
+        Err(err) => {
+            if let Err(rollback_err) = AnsiTransactionManager::rollback_transaction(conn)
+            {
+                log::error!(
+                    "failed to roll back a change to an experiment: {rollback_err}"
+                );
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Checks the optional start and end of an experiment's schedule, both have
+/// to be in the future and the experiment has to end after it starts.
+pub fn validate_schedule(
+    starts_at: Option<DateTime<Utc>>,
+    ends_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> superposition::Result<()> {
+    if starts_at.map_or(false, |starts_at| starts_at <= now) {
+        return Err(bad_argument!("starts_at should be in the future"));
+    }
+    if ends_at.map_or(false, |ends_at| ends_at <= now) {
+        return Err(bad_argument!("ends_at should be in the future"));
+    }
+    if let (Some(starts_at), Some(ends_at)) = (starts_at, ends_at) {
+        if ends_at <= starts_at {
+            return Err(bad_argument!("ends_at should be after starts_at"));
+        }
+    }
+    Ok(())
+}
+
+// keeps a single ingestion request within one bulk insert
+pub const MAX_METRIC_OBSERVATIONS: usize = 1000;
+
+/// Checks a batch of metric observations before it is recorded, every
+/// observation must name one of the experiment's variants.
+pub fn validate_metric_observations(
+    variants: &[Variant],
+    observations: &[MetricObservation],
+) -> superposition::Result<()> {
+    if observations.is_empty() {
+        return Err(bad_argument!("No metric observations found in request"));
+    }
+    if observations.len() > MAX_METRIC_OBSERVATIONS {
+        return Err(bad_argument!(
+            "Too many metric observations, send at most {} per request",
+            MAX_METRIC_OBSERVATIONS
+        ));
+    }
+    if observations
+        .iter()
+        .any(|observation| observation.metric_name.trim().is_empty())
+    {
+        return Err(bad_argument!("metric_name cannot be empty"));
+    }
+    if observations
+        .iter()
+        .any(|observation| !observation.value.is_finite())
+    {
+        return Err(bad_argument!("metric values should be finite numbers"));
+    }
+
+    let variant_ids: HashSet<&str> =
+        variants.iter().map(|variant| variant.id.as_str()).collect();
+    let mut seen = HashSet::new();
+    let unknown_variant_ids = observations
+        .iter()
+        .map(|observation| observation.variant_id.as_str())
+        .filter(|variant_id| {
+            !variant_ids.contains(variant_id) && seen.insert(*variant_id)
+        })
+        .collect::<Vec<&str>>();
+    if !unknown_variant_ids.is_empty() {
+        return Err(bad_argument!(
+            "Unknown variant ids in metric observations: {}",
+            unknown_variant_ids.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Compares a variant with the CAC context it points to, `cac_context` is
+/// `None` when CAC has no context with the variant's context_id.
+pub fn reconcile_variant(
+    variant: &Variant,
+    cac_context: Option<&ContextGetResp>,
+) -> VariantReconciliation {
+    let status = match (&variant.context_id, cac_context) {
+        (None, _) => ReconciliationStatus::MissingContextId,
+        (Some(_), None) => ReconciliationStatus::ContextNotFound,
+        (Some(_), Some(context))
+            if variant.override_id.as_deref() != Some(context.override_id.as_str()) =>
+        {
+            ReconciliationStatus::OverrideIdMismatch
+        }
+        (Some(_), Some(context))
+            if context.override_.as_object() != Some(&variant.overrides) =>
+        {
+            ReconciliationStatus::OverridesMismatch
+        }
+        (Some(_), Some(_)) => ReconciliationStatus::InSync,
+    };
+
+    VariantReconciliation {
+        variant_id: variant.id.clone(),
+        context_id: variant.context_id.clone(),
+        override_id: variant.override_id.clone(),
+        cac_override_id: cac_context.map(|context| context.override_id.clone()),
+        status,
+    }
+}
+
 pub fn extract_override_keys(overrides: &Map<String, Value>) -> HashSet<String> {
     overrides.keys().map(String::from).collect()
 }
+
+/// Range of `last_modified` an experiment listing is limited to, the lower
+/// bound being inclusive. Without `from_date` only the last day is listed,
+/// unless `modified_since` already bounds it, as incremental syncs need every
+/// change after their last one however long ago that was.
+pub fn listing_modified_window(
+    filters: &ListFilters,
+    now: DateTime<Utc>,
+) -> (Option<DateTime<Utc>>, DateTime<Utc>) {
+    let from_date = match (filters.from_date, filters.modified_since) {
+        (Some(from_date), _) => Some(from_date),
+        (None, Some(_)) => None,
+        (None, None) => Some(now - chrono::Duration::hours(24)),
+    };
+    (from_date, filters.to_date.unwrap_or(now))
+}
+
+pub fn calculate_total_pages(total_items: i64, count: i64) -> i64 {
+    if count <= 0 {
+        return 0;
+    }
+    (total_items + count - 1) / count
+}
+
+/// Opaque cursor resuming a listing after the experiment created at
+/// `created_at` with id `experiment_id`.
+pub fn encode_cursor(created_at: &DateTime<Utc>, experiment_id: &str) -> String {
+    let key = format!(
+        "{}|{experiment_id}",
+        created_at.to_rfc3339_opts(SecondsFormat::Micros, true)
+    );
+    key.bytes().fold(String::new(), |mut cursor, byte| {
+        let _ = write!(cursor, "{byte:02x}");
+        cursor
+    })
+}
+
+/// `created_at` and id of the experiment a cursor resumes after.
+pub fn decode_cursor(cursor: &str) -> superposition::Result<(DateTime<Utc>, String)> {
+    let invalid = || {
+        bad_argument!(
+            "invalid cursor {}, pass the next_cursor of the previous page",
+            cursor
+        )
+    };
+    if cursor.is_empty() || cursor.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|idx| {
+            cursor
+                .get(idx..idx + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(invalid)?;
+    let key = String::from_utf8(bytes).map_err(|_| invalid())?;
+    let (created_at, experiment_id) = key.split_once('|').ok_or_else(invalid)?;
+    let created_at = DateTime::parse_from_rfc3339(created_at).map_err(|_| invalid())?;
+    if experiment_id.is_empty() {
+        return Err(invalid());
+    }
+    Ok((created_at.with_timezone(&Utc), experiment_id.to_string()))
+}
+
+/// Splits the `size + 1` experiments fetched for a cursor page into the page
+/// and the cursor of the next one, the extra experiment only tells that there
+/// is a next page.
+pub fn split_cursor_page(
+    mut experiments: Vec<Experiment>,
+    size: i64,
+) -> (Vec<Experiment>, Option<String>) {
+    let size = size.max(0) as usize;
+    if experiments.len() <= size {
+        return (experiments, None);
+    }
+    experiments.truncate(size);
+    let next_cursor = experiments
+        .last()
+        .map(|experiment| encode_cursor(&experiment.created_at, &experiment.id));
+    (experiments, next_cursor)
+}
+
+// 64-bit FNV-1a, unlike std's DefaultHasher its output does not change across
+// rust releases or processes, which bucketing relies on
+pub fn stable_hash(input: &str) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    input.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+// derives a toss in [0, 100) from the experiment id and the context, keys are
+// sorted so that the same context always lands in the same bucket
+pub fn toss_for_context(experiment_id: &str, context: &Map<String, Value>) -> i8 {
+    let mut dimensions = context.iter().collect::<Vec<(&String, &Value)>>();
+    dimensions.sort_by_key(|(dimension, _)| *dimension);
+
+    let hash_input = dimensions.into_iter().fold(
+        experiment_id.to_string(),
+        |mut acc, (dimension, value)| {
+            acc.push_str(&format!(";{dimension}={value}"));
+            acc
+        },
+    );
+
+    (stable_hash(&hash_input) % 100) as i8
+}
+
+// hashes experiment_id + toss key onto the cumulative percentage ranges of the
+// variants, the same key always gets the same variant of an experiment
+pub fn assign_variant<'a>(
+    experiment_id: &str,
+    toss_key_value: &str,
+    variants_with_percentages: &'a [(Variant, i32)],
+) -> Option<&'a Variant> {
+    let bucket = (stable_hash(&format!("{experiment_id}-{toss_key_value}")) % 100) as i32;
+
+    let mut cumulative_percentage = 0;
+    for (variant, percentage) in variants_with_percentages {
+        cumulative_percentage += percentage;
+        if bucket < cumulative_percentage {
+            return Some(variant);
+        }
+    }
+    None
+}
+
+/// Variant of an experiment served to `context`. An explicit toss wins,
+/// otherwise the bucket is derived from the `toss_key` dimension of the
+/// context (or the whole context when it is absent), so a given context keeps
+/// resolving to the same variant.
+pub fn choose_variant<'a>(
+    experiment_id: &str,
+    traffic_percentage: i32,
+    variants: &'a Vec<Variant>,
+    context: &Map<String, Value>,
+    toss: Option<i8>,
+    toss_key: &str,
+) -> Option<&'a Variant> {
+    match (toss, context.get(toss_key)) {
+        (Some(toss), _) => decide_variant(traffic_percentage, variants, toss),
+        (None, Some(toss_key_value)) => {
+            let toss_key_value = match toss_key_value {
+                Value::String(value) => value.to_string(),
+                value => value.to_string(),
+            };
+            let variants_with_percentages =
+                variant_traffic_split(traffic_percentage, variants);
+            assign_variant(experiment_id, &toss_key_value, &variants_with_percentages)
+                .and_then(|chosen| {
+                    variants.iter().find(|variant| variant.id == chosen.id)
+                })
+        }
+        (None, None) => decide_variant(
+            traffic_percentage,
+            variants,
+            toss_for_context(experiment_id, context),
+        ),
+    }
+}
+
+pub const EXPERIMENT_OVERRIDE_HEADER: &str = "x-experiment-override";
+
+/// Parses an `x-experiment-override` header of the form
+/// `<experiment_id>:<variant_id>`.
+pub fn parse_variant_pin(header_value: &str) -> superposition::Result<VariantPin> {
+    match header_value.trim().split_once(':') {
+        Some((experiment_id, variant_id))
+            if !experiment_id.trim().is_empty() && !variant_id.trim().is_empty() =>
+        {
+            Ok(VariantPin {
+                experiment_id: experiment_id.trim().to_string(),
+                variant_id: variant_id.trim().to_string(),
+            })
+        }
+        _ => Err(bad_argument!(
+            "{} must be of the form <experiment_id>:<variant_id>",
+            EXPERIMENT_OVERRIDE_HEADER
+        )),
+    }
+}
+
+// variant pinned by the request's x-experiment-override header, if any
+pub fn variant_pin_of(req: &HttpRequest) -> superposition::Result<Option<VariantPin>> {
+    req.headers()
+        .get(EXPERIMENT_OVERRIDE_HEADER)
+        .map(|header_value| {
+            header_value
+                .to_str()
+                .map_err(|_| {
+                    bad_argument!("{} must be valid ASCII", EXPERIMENT_OVERRIDE_HEADER)
+                })
+                .and_then(parse_variant_pin)
+        })
+        .transpose()
+}
+
+/// Variant of `experiment_id` forced by `pin`, a pin for another experiment or
+/// naming a variant outside of `variants` is ignored.
+pub fn pinned_variant<'a>(
+    experiment_id: &str,
+    variants: &'a [Variant],
+    pin: Option<&VariantPin>,
+) -> Option<&'a Variant> {
+    pin.filter(|pin| pin.experiment_id == experiment_id)
+        .and_then(|pin| variants.iter().find(|variant| variant.id == pin.variant_id))
+}
+
+/// Variants served to `context` by the given experiments, those whose context
+/// does not match it or whose traffic does not reach its toss are left out.
+pub fn assign_experiment_variants(
+    experiments: &[Experiment],
+    context: &Map<String, Value>,
+    toss: Option<i8>,
+    toss_key: &str,
+    pin: Option<&VariantPin>,
+) -> superposition::Result<Vec<ServedVariant>> {
+    let context_value = Value::Object(context.clone());
+    let mut served = Vec::new();
+    for experiment in experiments {
+        if jsonlogic::apply(&experiment.context, &context_value) != Ok(Value::Bool(true))
+        {
+            continue;
+        }
+        let variants: Vec<Variant> = serde_json::from_value(experiment.variants.clone())
+            .map_err(|err| {
+                log::error!("failed to parse variants of {}: {err}", experiment.id);
+                unexpected_error!("Something went wrong, failed to resolve variants")
+            })?;
+        let chosen = pinned_variant(&experiment.id, &variants, pin).or_else(|| {
+            choose_variant(
+                &experiment.id,
+                experiment.traffic_percentage,
+                &variants,
+                context,
+                toss,
+                toss_key,
+            )
+        });
+        if let Some(variant) = chosen {
+            served.push(ServedVariant {
+                experiment_id: experiment.id.clone(),
+                variant_id: variant.id.clone(),
+                variant_type: variant.variant_type.clone(),
+            });
+        }
+    }
+    Ok(served)
+}
+
+// mirrors the bucketing done by experimentation_client, every variant gets
+// its share of `traffic_percentage` as buckets and tosses beyond them fall
+// outside the experiment
+pub fn decide_variant(
+    traffic_percentage: i32,
+    variants: &Vec<Variant>,
+    toss: i8,
+) -> Option<&Variant> {
+    if toss < 0 {
+        return variants
+            .iter()
+            .find(|variant| variant.variant_type == VariantType::EXPERIMENTAL);
+    }
+
+    let mut cumulative_percentage = 0;
+    for (index, (_, percentage)) in variant_traffic_split(traffic_percentage, variants)
+        .into_iter()
+        .enumerate()
+    {
+        cumulative_percentage += percentage;
+        if (toss as i32) < cumulative_percentage {
+            return variants.get(index);
+        }
+    }
+    None
+}
@@ -1,4 +1,7 @@
 pub mod handlers;
 pub mod helpers;
+pub mod orphan_contexts;
+pub mod scheduler;
+pub mod statistics;
 pub mod types;
 pub use handlers::endpoints;
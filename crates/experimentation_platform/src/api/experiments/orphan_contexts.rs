@@ -0,0 +1,173 @@
+use std::{collections::HashMap, time::Duration};
+
+use actix_web::{
+    rt::{
+        spawn,
+        time::{interval_at, Instant},
+    },
+    web::Data,
+};
+use chrono::{DateTime, Utc};
+use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use serde_json::Value;
+use service_utils::{
+    helpers::extract_dimensions,
+    result as superposition,
+    service::types::{AppState, Tenant},
+};
+use superposition_types::User;
+
+use super::{
+    handlers::{
+        call_cac_bulk_operations, fetch_variant_contexts, process_cac_http_response,
+    },
+    helpers::{split_variant_context_id, VARIANT_DIMENSION},
+    scheduler::scheduler_targets,
+    types::{CacContext, ContextAction},
+};
+use crate::db::{models::ExperimentStatusType, schema::experiments::dsl as experiments};
+
+// variant contexts are created in CAC before their experiment is stored, younger
+// ones may belong to an experiment that is still being created
+const ORPHAN_GRACE_PERIOD_MINS: i64 = 10;
+
+/// A variant context left in CAC by an experiment that no longer needs it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrphanContext {
+    pub context_id: String,
+    pub experiment_id: String,
+    pub variant_id: String,
+}
+
+/// Deletes, or with `dry_run` only logs, the variant contexts of experiments
+/// that are missing or concluded, checking every `interval`.
+pub fn spawn_orphan_context_cleanup(
+    state: Data<AppState>,
+    interval: Duration,
+    dry_run: bool,
+) {
+    spawn(async move {
+        let mut ticker = interval_at(Instant::now() + interval, interval);
+        loop {
+            ticker.tick().await;
+            cleanup_orphan_contexts(&state, dry_run).await;
+        }
+    });
+}
+
+pub async fn cleanup_orphan_contexts(state: &Data<AppState>, dry_run: bool) {
+    let user = User::default();
+    for (tenant, namespace) in scheduler_targets(state) {
+        let mut conn = match state.db_pool.get_conn(namespace) {
+            Ok(conn) => conn,
+            Err(err) => {
+                log::error!(
+                    "orphan context cleanup could not get a db connection: {err}"
+                );
+                continue;
+            }
+        };
+        if let Err(err) =
+            cleanup_tenant_orphans(state, &tenant, &user, dry_run, &mut conn).await
+        {
+            log::error!(
+                "orphan context cleanup failed for {}: {err}",
+                tenant.as_str()
+            );
+        }
+    }
+}
+
+async fn cleanup_tenant_orphans(
+    state: &Data<AppState>,
+    tenant: &Tenant,
+    user: &User,
+    dry_run: bool,
+    conn: &mut PgConnection,
+) -> superposition::Result<()> {
+    let contexts = fetch_variant_contexts(state, tenant, user).await?;
+    let experiment_ids = contexts
+        .iter()
+        .filter_map(|context| variant_context_of(&context.value))
+        .filter_map(|variant_context_id| {
+            split_variant_context_id(&variant_context_id)
+                .map(|(experiment_id, _)| experiment_id.to_string())
+        })
+        .collect::<Vec<String>>();
+    let experiment_statuses = experiments::experiments
+        .filter(experiments::id.eq_any(&experiment_ids))
+        .select((experiments::id, experiments::status))
+        .load::<(String, ExperimentStatusType)>(conn)?
+        .into_iter()
+        .collect::<HashMap<String, ExperimentStatusType>>();
+
+    let created_before = Utc::now() - chrono::Duration::minutes(ORPHAN_GRACE_PERIOD_MINS);
+    let orphans = find_orphan_contexts(&contexts, &experiment_statuses, created_before);
+    if orphans.is_empty() {
+        return Ok(());
+    }
+    let orphan_list = orphans
+        .iter()
+        .map(|orphan| format!("{} ({})", orphan.context_id, orphan.variant_id))
+        .collect::<Vec<String>>()
+        .join(",");
+    if dry_run {
+        log::info!(
+            "orphan context cleanup would delete {} contexts of {}: {orphan_list}",
+            orphans.len(),
+            tenant.as_str()
+        );
+        return Ok(());
+    }
+
+    let operations = orphans
+        .into_iter()
+        .map(|orphan| ContextAction::DELETE(orphan.context_id))
+        .collect::<Vec<ContextAction>>();
+    let response = call_cac_bulk_operations(state, tenant, user, &operations, None).await;
+    process_cac_http_response(response).await?;
+    log::info!(
+        "orphan context cleanup deleted {} contexts of {}: {orphan_list}",
+        operations.len(),
+        tenant.as_str()
+    );
+    Ok(())
+}
+
+// id of the variant a context is conditioned on, contexts not conditioned on a
+// single variant are not the experiments' to clean up
+fn variant_context_of(condition: &Value) -> Option<String> {
+    match extract_dimensions(condition)
+        .ok()?
+        .remove(VARIANT_DIMENSION)?
+    {
+        Value::String(variant_context_id) => Some(variant_context_id),
+        _ => None,
+    }
+}
+
+/// Variant contexts created before `created_before` whose experiment is
+/// missing from `experiment_statuses` or has been concluded.
+pub fn find_orphan_contexts(
+    contexts: &[CacContext],
+    experiment_statuses: &HashMap<String, ExperimentStatusType>,
+    created_before: DateTime<Utc>,
+) -> Vec<OrphanContext> {
+    contexts
+        .iter()
+        .filter(|context| context.created_at < created_before)
+        .filter_map(|context| {
+            let variant_context_id = variant_context_of(&context.value)?;
+            let (experiment_id, _) = split_variant_context_id(&variant_context_id)?;
+            let is_orphan = match experiment_statuses.get(experiment_id) {
+                None | Some(ExperimentStatusType::CONCLUDED) => true,
+                Some(_) => false,
+            };
+            is_orphan.then(|| OrphanContext {
+                context_id: context.id.clone(),
+                experiment_id: experiment_id.to_string(),
+                variant_id: variant_context_id.clone(),
+            })
+        })
+        .collect()
+}
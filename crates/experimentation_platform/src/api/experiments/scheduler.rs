@@ -0,0 +1,219 @@
+use std::time::Duration;
+
+use actix_web::{
+    rt::{
+        spawn,
+        time::{interval_at, Instant},
+    },
+    web::Data,
+};
+use chrono::{DateTime, Utc};
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use service_utils::{
+    result as superposition,
+    service::types::{execution_namespace, AppScope, AppState, Tenant},
+};
+use superposition_types::{SuperpositionUser, User};
+
+use super::{
+    handlers::{advance_rollout, pause_experiment},
+    helpers::{
+        is_rollout_step_due, next_rollout_step, parse_rollout_plan,
+        record_experiment_audit,
+    },
+};
+use crate::db::{
+    models::{Experiment, ExperimentStatusType},
+    schema::experiments::dsl as experiments,
+};
+
+// tenant used by requests when tenants are disabled, see the Tenant extractor
+const DEFAULT_TENANT: &str = "mjos";
+
+/// Starts CREATED experiments once their `starts_at` has passed, advances the
+/// rollout steps that run on time and pauses INPROGRESS experiments once their
+/// `ends_at` has passed, checking every `interval`.
+/// Transitions are picked from the stored timestamps on every run, so the ones
+/// that fell due while the server was down happen on the first run after it.
+pub fn spawn_experiment_scheduler(state: Data<AppState>, interval: Duration) {
+    spawn(async move {
+        // the first run waits a whole interval for the server to start serving
+        let mut ticker = interval_at(Instant::now() + interval, interval);
+        loop {
+            ticker.tick().await;
+            run_scheduled_transitions(&state).await;
+        }
+    });
+}
+
+// tenant and db namespace of every experimentation schema being served
+pub fn scheduler_targets(state: &AppState) -> Vec<(Tenant, String)> {
+    let tenants = if state.enable_tenant_and_scope {
+        state.tenant_list().into_iter().map(Tenant).collect()
+    } else {
+        vec![Tenant(DEFAULT_TENANT.to_string())]
+    };
+    tenants
+        .into_iter()
+        .filter_map(|tenant| {
+            execution_namespace(
+                state.enable_tenant_and_scope,
+                Some(&tenant),
+                Some(AppScope::EXPERIMENTATION),
+            )
+            .ok()
+            .map(|namespace| (tenant, namespace))
+        })
+        .collect()
+}
+
+pub async fn run_scheduled_transitions(state: &Data<AppState>) {
+    let user = User::default();
+    for (tenant, namespace) in scheduler_targets(state) {
+        let mut conn = match state.db_pool.get_conn(namespace) {
+            Ok(conn) => conn,
+            Err(err) => {
+                log::error!("experiment scheduler could not get a db connection: {err}");
+                continue;
+            }
+        };
+        let now = Utc::now();
+        if let Err(err) = start_due_experiments(now, &user, &mut conn) {
+            log::error!(
+                "experiment scheduler failed to start experiments of {}: {err}",
+                tenant.as_str()
+            );
+        }
+        if let Err(err) = advance_due_rollouts(state, now, &tenant, &user, &mut conn) {
+            log::error!(
+                "experiment scheduler failed to advance rollouts of {}: {err}",
+                tenant.as_str()
+            );
+        }
+        if let Err(err) =
+            pause_ended_experiments(state, now, &tenant, &user, &mut conn).await
+        {
+            log::error!(
+                "experiment scheduler failed to pause experiments of {}: {err}",
+                tenant.as_str()
+            );
+        }
+    }
+}
+
+fn start_due_experiments(
+    now: DateTime<Utc>,
+    user: &User,
+    conn: &mut PgConnection,
+) -> superposition::Result<()> {
+    let due_experiments = experiments::experiments
+        .filter(experiments::status.eq(ExperimentStatusType::CREATED))
+        .filter(experiments::starts_at.le(now))
+        .load::<Experiment>(conn)?;
+
+    for experiment in due_experiments {
+        if experiment.ends_at.map_or(false, |ends_at| ends_at <= now) {
+            log::warn!(
+                "experiment {} ended before it could be started, leaving it CREATED",
+                experiment.id
+            );
+            continue;
+        }
+        conn.transaction::<_, superposition::AppError, _>(|transaction_conn| {
+            // only the run that moves it out of CREATED records the transition
+            let updated = diesel::update(experiments::experiments.find(&experiment.id))
+                .filter(experiments::status.eq(ExperimentStatusType::CREATED))
+                .set((
+                    experiments::status.eq(ExperimentStatusType::INPROGRESS),
+                    experiments::last_modified.eq(Utc::now()),
+                    experiments::last_modified_by.eq(user.get_email()),
+                ))
+                .execute(transaction_conn)?;
+            if updated == 1 {
+                record_experiment_audit(
+                    &experiment.id,
+                    "SCHEDULED_START",
+                    Some(ExperimentStatusType::CREATED),
+                    ExperimentStatusType::INPROGRESS,
+                    user.get_email(),
+                    transaction_conn,
+                )?;
+                log::info!("started scheduled experiment {}", experiment.id);
+            }
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
+async fn pause_ended_experiments(
+    state: &Data<AppState>,
+    now: DateTime<Utc>,
+    tenant: &Tenant,
+    user: &User,
+    conn: &mut PgConnection,
+) -> superposition::Result<()> {
+    let ended_experiments = experiments::experiments
+        .filter(experiments::status.eq(ExperimentStatusType::INPROGRESS))
+        .filter(experiments::ends_at.le(now))
+        .load::<Experiment>(conn)?;
+
+    for experiment in ended_experiments {
+        let experiment_id = experiment.id.clone();
+        // one failing experiment should not hold back the others
+        match pause_experiment(state, experiment, "SCHEDULED_END", tenant, user, conn)
+            .await
+        {
+            Ok(_) => log::info!("paused experiment {experiment_id} at its end"),
+            Err(err) => log::error!(
+                "experiment scheduler failed to pause experiment {experiment_id}: {err}"
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn advance_due_rollouts(
+    state: &Data<AppState>,
+    now: DateTime<Utc>,
+    tenant: &Tenant,
+    user: &User,
+    conn: &mut PgConnection,
+) -> superposition::Result<()> {
+    let rolling_out = experiments::experiments
+        .filter(experiments::status.eq(ExperimentStatusType::INPROGRESS))
+        .filter(experiments::rollout_step.is_not_null())
+        .load::<Experiment>(conn)?;
+
+    for experiment in rolling_out {
+        let experiment_id = experiment.id.clone();
+        let rollout_plan = match parse_rollout_plan(&experiment) {
+            Ok(rollout_plan) => rollout_plan,
+            Err(err) => {
+                log::error!("experiment scheduler skipped {experiment_id}: {err}");
+                continue;
+            }
+        };
+        let is_due = is_rollout_step_due(
+            &rollout_plan,
+            experiment.rollout_step,
+            experiment.rollout_step_started_at,
+            now,
+        ) && next_rollout_step(&rollout_plan, experiment.rollout_step)
+            .is_some();
+        if !is_due {
+            continue;
+        }
+        match advance_rollout(state, experiment, "SCHEDULED_ADVANCE", tenant, user, conn)
+        {
+            Ok(advanced) => log::info!(
+                "advanced experiment {experiment_id} to {}% traffic",
+                advanced.traffic_percentage
+            ),
+            Err(err) => log::error!(
+                "experiment scheduler failed to advance experiment {experiment_id}: {err}"
+            ),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use super::types::{
+    SignificanceResult, SignificanceTest, VariantComparison, VariantMetricSummary,
+};
+
+/// Confidence level of the reported intervals, a difference is significant
+/// when its p-value is below `1 - CONFIDENCE_LEVEL`.
+pub const CONFIDENCE_LEVEL: f64 = 0.95;
+
+// two sided critical value of the standard normal distribution at 95%
+const Z_CRITICAL: f64 = 1.959963984540054;
+
+/// Observations of one metric for one variant, `variance` is the sample
+/// variance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MetricSample {
+    pub count: i64,
+    pub mean: f64,
+    pub variance: f64,
+}
+
+/// Two-proportion z-test of a 0/1 metric, `mean` of each sample being the
+/// proportion of ones. `None` when there is not enough data to compare.
+pub fn two_proportion_z_test(
+    control: &MetricSample,
+    treatment: &MetricSample,
+) -> Option<SignificanceResult> {
+    if control.count < 1 || treatment.count < 1 {
+        return None;
+    }
+    let (n1, n2) = (control.count as f64, treatment.count as f64);
+    let (p1, p2) = (control.mean, treatment.mean);
+    let pooled = (p1 * n1 + p2 * n2) / (n1 + n2);
+    let pooled_se = (pooled * (1.0 - pooled) * (1.0 / n1 + 1.0 / n2)).sqrt();
+    if !pooled_se.is_normal() {
+        return None;
+    }
+
+    let difference = p2 - p1;
+    let statistic = difference / pooled_se;
+    let p_value = 2.0 * (1.0 - normal_cdf(statistic.abs()));
+    let se = (p1 * (1.0 - p1) / n1 + p2 * (1.0 - p2) / n2).sqrt();
+    Some(significance_result(
+        SignificanceTest::TwoProportionZTest,
+        statistic,
+        p_value,
+        difference,
+        Z_CRITICAL * se,
+    ))
+}
+
+/// Welch's t-test of a continuous metric, which does not assume equal
+/// variances. `None` when there is not enough data to compare.
+pub fn welch_t_test(
+    control: &MetricSample,
+    treatment: &MetricSample,
+) -> Option<SignificanceResult> {
+    if control.count < 2 || treatment.count < 2 {
+        return None;
+    }
+    let v1 = control.variance / control.count as f64;
+    let v2 = treatment.variance / treatment.count as f64;
+    let se = (v1 + v2).sqrt();
+    if !se.is_normal() {
+        return None;
+    }
+
+    let difference = treatment.mean - control.mean;
+    let statistic = difference / se;
+    let degrees_of_freedom = (v1 + v2).powi(2)
+        / (v1.powi(2) / (control.count - 1) as f64
+            + v2.powi(2) / (treatment.count - 1) as f64);
+    let p_value = 2.0 * (1.0 - student_t_cdf(statistic.abs(), degrees_of_freedom));
+    let t_critical =
+        student_t_quantile(1.0 - (1.0 - CONFIDENCE_LEVEL) / 2.0, degrees_of_freedom);
+    Some(significance_result(
+        SignificanceTest::WelchTTest,
+        statistic,
+        p_value,
+        difference,
+        t_critical * se,
+    ))
+}
+
+fn significance_result(
+    test: SignificanceTest,
+    statistic: f64,
+    p_value: f64,
+    difference: f64,
+    margin: f64,
+) -> SignificanceResult {
+    let p_value = p_value.clamp(0.0, 1.0);
+    SignificanceResult {
+        test,
+        statistic,
+        p_value,
+        difference,
+        confidence_interval: [difference - margin, difference + margin],
+        significant: p_value < 1.0 - CONFIDENCE_LEVEL,
+    }
+}
+
+/// Compares every experimental variant against control on each metric both
+/// have observations for. Metrics where every observed value was 0 or 1 are
+/// compared as proportions, the rest as continuous values.
+pub fn compare_with_control(
+    control_variant_id: &str,
+    summaries: &[VariantMetricSummary],
+) -> Vec<VariantComparison> {
+    let control_summaries = summaries
+        .iter()
+        .filter(|summary| summary.variant_id == control_variant_id)
+        .map(|summary| (summary.metric_name.as_str(), summary))
+        .collect::<HashMap<&str, &VariantMetricSummary>>();
+
+    summaries
+        .iter()
+        .filter(|summary| summary.variant_id != control_variant_id)
+        .filter_map(|summary| {
+            let control = control_summaries.get(summary.metric_name.as_str())?;
+            let result = if control.binary && summary.binary {
+                two_proportion_z_test(&sample_of(control), &sample_of(summary))
+            } else {
+                welch_t_test(&sample_of(control), &sample_of(summary))
+            };
+            Some(VariantComparison {
+                variant_id: summary.variant_id.clone(),
+                control_variant_id: control_variant_id.to_string(),
+                metric_name: summary.metric_name.clone(),
+                result,
+            })
+        })
+        .collect()
+}
+
+fn sample_of(summary: &VariantMetricSummary) -> MetricSample {
+    MetricSample {
+        count: summary.count,
+        mean: summary.mean,
+        variance: summary.variance.unwrap_or_default(),
+    }
+}
+
+pub fn normal_cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / std::f64::consts::SQRT_2)
+}
+
+// complementary error function, fractional error below 1.2e-7 (Numerical Recipes)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587
+                                        + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+pub fn student_t_cdf(t: f64, degrees_of_freedom: f64) -> f64 {
+    let x = degrees_of_freedom / (degrees_of_freedom + t * t);
+    let tail = 0.5 * regularized_incomplete_beta(degrees_of_freedom / 2.0, 0.5, x);
+    if t > 0.0 {
+        1.0 - tail
+    } else {
+        tail
+    }
+}
+
+// inverts the cdf by bisection, the cdf is monotonic so this always converges
+fn student_t_quantile(probability: f64, degrees_of_freedom: f64) -> f64 {
+    let (mut low, mut high) = (-1000.0, 1000.0);
+    for _ in 0..200 {
+        let mid = (low + high) / 2.0;
+        if student_t_cdf(mid, degrees_of_freedom) < probability {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
+// Lanczos approximation, g = 7
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.5203681218851,
+        -1259.1392167224028,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507343278686905,
+        -0.13857109526572012,
+        9.984_369_578_019_572e-6,
+        1.5056327351493116e-7,
+    ];
+    if x < 0.5 {
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .skip(1)
+        .fold(COEFFICIENTS[0], |acc, (idx, coefficient)| {
+            acc + coefficient / (x + idx as f64)
+        });
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+fn regularized_incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln())
+            .exp();
+    // the continued fraction converges quickly only on this side of the mean
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_continued_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+// modified Lentz's method (Numerical Recipes betacf)
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 300;
+    const EPSILON: f64 = 1e-15;
+    const TINY: f64 = 1e-300;
+
+    let clamp_tiny = |value: f64| if value.abs() < TINY { TINY } else { value };
+    let mut c = 1.0;
+    let mut d = 1.0 / clamp_tiny(1.0 - (a + b) * x / (a + 1.0));
+    let mut h = d;
+    for m in 1..=MAX_ITERATIONS {
+        let m = m as f64;
+        let m2 = 2.0 * m;
+
+        let even = m * (b - m) * x / ((a + m2 - 1.0) * (a + m2));
+        d = 1.0 / clamp_tiny(1.0 + even * d);
+        c = clamp_tiny(1.0 + even / c);
+        h *= d * c;
+
+        let odd = -(a + m) * (a + b + m) * x / ((a + m2) * (a + m2 + 1.0));
+        d = 1.0 / clamp_tiny(1.0 + odd * d);
+        c = clamp_tiny(1.0 + odd / c);
+        let delta = d * c;
+        h *= delta;
+
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    h
+}
@@ -1,9 +1,9 @@
 use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
-use serde_json::{Map, Value};
+use serde_json::{json, Map, Value};
 use service_utils::helpers::deserialize_stringified_list;
 
-use crate::db::models::{self, ExperimentStatusType};
+use crate::db::models::{self, ExperimentStatusType, ExperimentType};
 
 #[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
 pub enum VariantType {
@@ -18,6 +18,12 @@ pub struct Variant {
     pub context_id: Option<String>,
     pub override_id: Option<String>,
     pub overrides: Map<String, Value>,
+    // share of the experiment's traffic served by this variant, when absent
+    // every variant is served the experiment's traffic_percentage
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traffic_percentage: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 }
 
 /********** Experiment Create Req Types ************/
@@ -25,25 +31,229 @@ pub struct Variant {
 #[derive(Deserialize)]
 pub struct ExperimentCreateRequest {
     pub name: String,
+    // left out, it is filled in from EXPERIMENT_DEFAULT_TRAFFIC_PERCENTAGE
+    #[serde(default)]
+    pub traffic_percentage: Option<i64>,
 
     pub context: Value,
+    // generated from flag_key for FLAG experiments
+    #[serde(default)]
     pub variants: Vec<Variant>,
+    #[serde(default)]
+    pub experiment_type: ExperimentType,
+    // boolean config key a FLAG experiment turns on for its experimental variant
+    #[serde(default)]
+    pub flag_key: Option<String>,
+    // an empty context matches every request, it has to be asked for explicitly
+    #[serde(default)]
+    pub allow_global: bool,
+    // the experiment is started at starts_at and paused at ends_at
+    #[serde(default)]
+    pub starts_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub ends_at: Option<DateTime<Utc>>,
+    // traffic percentages the experiment is advanced through, in order
+    #[serde(default)]
+    pub rollout_plan: Vec<RolloutStep>,
+}
+
+/// A step of an experiment's staged rollout. Steps with `advance_after_secs`
+/// are advanced by the scheduler once they have run that long, the others wait
+/// for `POST /experiments/{id}/advance`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct RolloutStep {
+    pub traffic_percentage: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub advance_after_secs: Option<i64>,
+}
+
+impl ExperimentCreateRequest {
+    /// JSON Schema of the request body, served at `GET /experiments/schema`.
+    /// Checks spanning several fields, like the traffic split or override key
+    /// coverage of variants, are left to the server.
+    pub fn json_schema() -> Value {
+        json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "title": "ExperimentCreateRequest",
+            "type": "object",
+            "required": ["name", "context"],
+            "if": {
+                "properties": { "experiment_type": { "const": "FLAG" } },
+                "required": ["experiment_type"]
+            },
+            "then": { "required": ["flag_key"] },
+            "else": { "required": ["variants"] },
+            "properties": {
+                "name": { "type": "string" },
+                "traffic_percentage": {
+                    "type": ["integer", "null"],
+                    "minimum": 0,
+                    "description": "traffic served by each variant, or by the whole experiment when variants set their own traffic_percentage. Left out, the server's default traffic is split evenly between the variants"
+                },
+                "context": {
+                    "type": "object",
+                    "description": "jsonlogic condition on dimensions selecting the requests the experiment runs on"
+                },
+                "variants": {
+                    "type": "array",
+                    "minItems": 2,
+                    "items": { "$ref": "#/definitions/Variant" },
+                    "description": "exactly one CONTROL and at least one EXPERIMENTAL variant, all overriding the same keys. Those keys become the experiment's override_keys"
+                },
+                "experiment_type": {
+                    "enum": ["DEFAULT", "FLAG"],
+                    "default": "DEFAULT",
+                    "description": "FLAG experiments leave out variants, a control and an experimental variant are generated from flag_key"
+                },
+                "flag_key": {
+                    "type": ["string", "null"],
+                    "description": "boolean default config key of a FLAG experiment, false for the control variant and true for the experimental one"
+                },
+                "allow_global": {
+                    "type": "boolean",
+                    "default": false,
+                    "description": "has to be true for an empty context, which matches every request"
+                },
+                "starts_at": { "type": ["string", "null"], "format": "date-time" },
+                "ends_at": { "type": ["string", "null"], "format": "date-time" },
+                "rollout_plan": {
+                    "type": "array",
+                    "items": { "$ref": "#/definitions/RolloutStep" },
+                    "default": [],
+                    "description": "increasing traffic percentages the experiment is advanced through"
+                }
+            },
+            "definitions": {
+                "RolloutStep": {
+                    "type": "object",
+                    "required": ["traffic_percentage"],
+                    "properties": {
+                        "traffic_percentage": { "type": "integer", "minimum": 0 },
+                        "advance_after_secs": {
+                            "type": ["integer", "null"],
+                            "minimum": 1,
+                            "description": "advance to the next step once this one has run for as long, otherwise the step waits to be advanced"
+                        }
+                    }
+                },
+                "Variant": {
+                    "type": "object",
+                    "required": ["id", "variant_type", "overrides"],
+                    "properties": {
+                        "id": { "type": "string" },
+                        "variant_type": { "enum": ["CONTROL", "EXPERIMENTAL"] },
+                        "overrides": {
+                            "type": "object",
+                            "description": "values of default config keys served to the variant"
+                        },
+                        "traffic_percentage": {
+                            "type": ["integer", "null"],
+                            "minimum": 0,
+                            "maximum": 100,
+                            "description": "share of the experiment's traffic, set on every EXPERIMENTAL variant or on none"
+                        },
+                        "description": { "type": ["string", "null"] },
+                        "context_id": {
+                            "type": ["string", "null"],
+                            "description": "set by the server"
+                        },
+                        "override_id": {
+                            "type": ["string", "null"],
+                            "description": "set by the server"
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ExperimentCloneRequest {
+    pub name: String,
+}
+
+// self-contained copy of an experiment for recreating it in another
+// environment, ids, timestamps and status are left behind
+#[derive(Serialize, Deserialize)]
+pub struct ExperimentExport {
+    pub name: String,
+    pub context: Value,
+    pub override_keys: Vec<String>,
+    pub traffic_percentage: i64,
+    pub variants: Vec<Variant>,
+    #[serde(default)]
+    pub rollout_plan: Vec<RolloutStep>,
+    #[serde(default)]
+    pub experiment_type: ExperimentType,
+}
+
+// ties an experiment variant to the CAC context and override created for it
+#[derive(Serialize, Deserialize)]
+pub struct VariantContextMapping {
+    pub variant_id: String,
+    pub context_id: Option<String>,
+    pub override_id: Option<String>,
+}
+
+impl VariantContextMapping {
+    pub fn from_variants(variants: &Value) -> Vec<Self> {
+        let variants = Vec::<Variant>::deserialize(variants).unwrap_or_else(|e| {
+            log::error!("failed to parse experiment variants with error: {e}");
+            Vec::new()
+        });
+        variants
+            .into_iter()
+            .map(|variant| VariantContextMapping {
+                variant_id: variant.id,
+                context_id: variant.context_id,
+                override_id: variant.override_id,
+            })
+            .collect()
+    }
 }
 
 #[derive(Serialize)]
 pub struct ExperimentCreateResponse {
     pub experiment_id: String,
+    pub variant_contexts: Vec<VariantContextMapping>,
+    // non fatal findings, the experiment was created regardless
+    pub warnings: Vec<String>,
+    // traffic_percentage the experiment got from the server's default, when
+    // the request left it out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_traffic_percentage: Option<i64>,
 }
 
 impl From<models::Experiment> for ExperimentCreateResponse {
     fn from(experiment: models::Experiment) -> Self {
         ExperimentCreateResponse {
             experiment_id: experiment.id.to_string(),
+            variant_contexts: VariantContextMapping::from_variants(&experiment.variants),
+            warnings: Vec::new(),
+            default_traffic_percentage: None,
         }
     }
 }
 
 /********** Experiment Response Type **************/
+
+#[derive(Serialize)]
+pub struct ExperimentDetailResponse {
+    #[serde(flatten)]
+    pub experiment: ExperimentResponse,
+    pub variant_contexts: Vec<VariantContextMapping>,
+}
+
+impl From<models::Experiment> for ExperimentDetailResponse {
+    fn from(experiment: models::Experiment) -> Self {
+        ExperimentDetailResponse {
+            variant_contexts: VariantContextMapping::from_variants(&experiment.variants),
+            experiment: ExperimentResponse::from(experiment),
+        }
+    }
+}
+
 // Same as models::Experiments but `id` field is String
 // JS have limitation of 53-bit integers, so on
 // deserializing from JSON to JS Object will lead incorrect `id` values
@@ -62,6 +272,13 @@ pub struct ExperimentResponse {
     pub context: Value,
     pub variants: Value,
     pub chosen_variant: Option<String>,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub rollout_plan: Option<Value>,
+    pub rollout_step: Option<i32>,
+    pub rollout_step_started_at: Option<DateTime<Utc>>,
+    pub experiment_type: ExperimentType,
 }
 
 impl From<models::Experiment> for ExperimentResponse {
@@ -80,6 +297,13 @@ impl From<models::Experiment> for ExperimentResponse {
             context: experiment.context,
             variants: experiment.variants,
             chosen_variant: experiment.chosen_variant,
+            archived_at: experiment.archived_at,
+            starts_at: experiment.starts_at,
+            ends_at: experiment.ends_at,
+            rollout_plan: experiment.rollout_plan,
+            rollout_step: experiment.rollout_step,
+            rollout_step_started_at: experiment.rollout_step_started_at,
+            experiment_type: experiment.experiment_type,
         }
     }
 }
@@ -88,6 +312,16 @@ impl From<models::Experiment> for ExperimentResponse {
 pub struct ExperimentsResponse {
     pub total_items: i64,
     pub total_pages: i64,
+    pub page: i64,
+    pub count: i64,
+    pub data: Vec<ExperimentResponse>,
+}
+
+#[derive(Serialize)]
+pub struct ExperimentsCursorResponse {
+    pub count: i64,
+    // absent on the last page
+    pub next_cursor: Option<String>,
     pub data: Vec<ExperimentResponse>,
 }
 
@@ -95,6 +329,7 @@ pub struct ExperimentsResponse {
 
 #[derive(Deserialize, Debug)]
 pub struct ConcludeExperimentRequest {
+    #[serde(alias = "winner_variant_id")]
     pub chosen_variant: String,
 }
 
@@ -113,6 +348,16 @@ pub enum ContextAction {
     MOVE((String, ContextMoveReq)),
 }
 
+impl ContextAction {
+    pub fn action(&self) -> &'static str {
+        match self {
+            ContextAction::PUT(_) => "PUT",
+            ContextAction::DELETE(_) => "DELETE",
+            ContextAction::MOVE(_) => "MOVE",
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct ContextPutResp {
     pub context_id: String,
@@ -127,6 +372,257 @@ pub enum ContextBulkResponse {
     MOVE(ContextPutResp),
 }
 
+impl ContextBulkResponse {
+    pub fn action(&self) -> &'static str {
+        match self {
+            ContextBulkResponse::PUT(_) => "PUT",
+            ContextBulkResponse::DELETE(_) => "DELETE",
+            ContextBulkResponse::MOVE(_) => "MOVE",
+        }
+    }
+}
+
+/********** Context Get API Type *************/
+
+#[derive(Deserialize, Debug)]
+pub struct ContextGetResp {
+    pub id: String,
+    pub override_id: String,
+    #[serde(rename = "override")]
+    pub override_: Value,
+}
+
+/********** Context List API Type *************/
+
+// context as listed by CAC's `GET /context`
+#[derive(Deserialize, Debug, Clone)]
+pub struct CacContext {
+    pub id: String,
+    pub value: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CacContextPage {
+    pub total_pages: i64,
+    pub data: Vec<CacContext>,
+}
+
+/********** Experiment Verify Response Types *************/
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReconciliationStatus {
+    InSync,
+    MissingContextId,
+    ContextNotFound,
+    OverrideIdMismatch,
+    OverridesMismatch,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VariantReconciliation {
+    pub variant_id: String,
+    pub context_id: Option<String>,
+    pub override_id: Option<String>,
+    pub cac_override_id: Option<String>,
+    pub status: ReconciliationStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExperimentVerifyResponse {
+    pub experiment_id: String,
+    pub status: ExperimentStatusType,
+    pub in_sync: bool,
+    pub variants: Vec<VariantReconciliation>,
+}
+
+/********** Experiment Webhook Types *************/
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExperimentEvent {
+    Created,
+    Ramped,
+    Concluded,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExperimentWebhookPayload {
+    pub experiment_id: String,
+    pub name: String,
+    pub event: ExperimentEvent,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/********** Experiment Metrics Types *************/
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MetricObservation {
+    pub variant_id: String,
+    pub metric_name: String,
+    pub value: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MetricsIngestRequest {
+    pub observations: Vec<MetricObservation>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MetricsIngestResponse {
+    pub experiment_id: String,
+    pub recorded: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VariantMetricSummary {
+    pub variant_id: String,
+    pub metric_name: String,
+    pub count: i64,
+    pub mean: f64,
+    // sample variance, absent with a single observation
+    pub variance: Option<f64>,
+    // every observed value was 0 or 1
+    pub binary: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ResultsQuery {
+    #[serde(default)]
+    pub significance: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SignificanceTest {
+    TwoProportionZTest,
+    WelchTTest,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignificanceResult {
+    pub test: SignificanceTest,
+    pub statistic: f64,
+    pub p_value: f64,
+    // treatment minus control
+    pub difference: f64,
+    pub confidence_interval: [f64; 2],
+    pub significant: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VariantComparison {
+    pub variant_id: String,
+    pub control_variant_id: String,
+    pub metric_name: String,
+    // absent when either side has too few observations to compare
+    pub result: Option<SignificanceResult>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExperimentResultsResponse {
+    pub experiment_id: String,
+    pub results: Vec<VariantMetricSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comparisons: Option<Vec<VariantComparison>>,
+}
+
+/********** Experiment Diff Types ************/
+
+#[derive(Deserialize, Debug)]
+pub struct DiffQuery {
+    #[serde(default)]
+    pub default_config: bool,
+}
+
+/// Value of a key on either side of a comparison, `None` when that side does
+/// not set it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeyDiff {
+    pub key: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub changed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VariantDiff {
+    pub variant_id: String,
+    pub variant_type: VariantType,
+    // control is compared against itself too, every key shows up unchanged
+    pub against_control: Vec<KeyDiff>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub against_default_config: Option<Vec<KeyDiff>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExperimentDiffResponse {
+    pub experiment_id: String,
+    pub control_variant_id: String,
+    pub variants: Vec<VariantDiff>,
+}
+
+/********** Default Config API Type *************/
+
+#[derive(Deserialize, Debug)]
+pub struct DefaultConfigKey {
+    pub key: String,
+    pub value: Value,
+    pub schema: Value,
+}
+
+/********** Dimension API Type *************/
+
+#[derive(Deserialize, Debug)]
+pub struct DimensionName {
+    pub dimension: String,
+}
+
+/********** Match API Type *************/
+
+#[derive(Deserialize, Debug)]
+pub struct MatchExperimentsRequest {
+    pub context: Map<String, Value>,
+}
+
+/********** Resolve API Type *************/
+
+#[derive(Deserialize, Debug)]
+pub struct ResolveVariantRequest {
+    pub context: Map<String, Value>,
+    pub toss: Option<i8>,
+    #[serde(default = "default_toss_key")]
+    pub toss_key: String,
+}
+
+pub fn default_toss_key() -> String {
+    String::from("user_id")
+}
+
+#[derive(Serialize, Debug)]
+pub struct ResolveVariantResponse {
+    pub variant_id: Option<String>,
+    pub overrides: Option<Map<String, Value>>,
+}
+
+// variant of an experiment served to a context when resolving config
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ServedVariant {
+    pub experiment_id: String,
+    pub variant_id: String,
+    pub variant_type: VariantType,
+}
+
+// variant QA pinned for an experiment through the x-experiment-override
+// header, in place of the bucketed one
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantPin {
+    pub experiment_id: String,
+    pub variant_id: String,
+}
+
 /********** List API Filter Type *************/
 
 #[derive(Deserialize, Debug, Clone)]
@@ -135,6 +631,30 @@ pub struct StatusTypes(
     pub  Vec<ExperimentStatusType>,
 );
 
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ExperimentSortOn {
+    CreatedAt,
+    LastModified,
+    Name,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// `offset` pages through `page` and `count`, `cursor` resumes after the last
+/// experiment of the previous page and is not thrown off by concurrent inserts.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PaginationMode {
+    Offset,
+    Cursor,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct ListFilters {
     pub status: Option<StatusTypes>,
@@ -142,12 +662,22 @@ pub struct ListFilters {
     pub to_date: Option<DateTime<Utc>>,
     pub page: Option<i64>,
     pub count: Option<i64>,
+    pub name: Option<String>,
+    pub created_by: Option<String>,
+    pub sort_by: Option<ExperimentSortOn>,
+    pub sort_order: Option<SortOrder>,
+    pub include_archived: Option<bool>,
+    pub pagination: Option<PaginationMode>,
+    // experiments modified strictly after it, for incremental syncs
+    pub modified_since: Option<DateTime<Utc>>,
+    // next_cursor of the previous page, in cursor pagination
+    pub cursor: Option<String>,
 }
 
 /********** Ramp API type **********/
 #[derive(Deserialize, Debug)]
 pub struct RampRequest {
-    pub traffic_percentage: u64,
+    pub traffic_percentage: i64,
 }
 
 /********** Update API type ********/
@@ -163,6 +693,14 @@ pub struct OverrideKeysUpdateRequest {
     pub variants: Vec<VariantUpdateRequest>,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct ContextUpdateRequest {
+    pub context: Value,
+    // an empty context matches every request, it has to be asked for explicitly
+    #[serde(default)]
+    pub allow_global: bool,
+}
+
 #[derive(Deserialize, Serialize, Clone)]
 pub struct ContextMoveReq {
     pub context: serde_json::Map<String, Value>,
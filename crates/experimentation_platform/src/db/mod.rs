@@ -1,2 +1,20 @@
 pub mod models;
 pub mod schema;
+
+/// `up.sql` of every experimentation migration in the order they are applied,
+/// setting up the `public` schema. Tenants are provisioned by running them
+/// with `public` replaced by the tenant's schema, like
+/// `scripts/create-tenant.sh`.
+pub const TENANT_MIGRATIONS: &[&str] = &[
+    include_str!("../../migrations/2023-10-16-134612_experimentation-init/up.sql"),
+    include_str!("../../migrations/2024-01-18-063937_audit_log_partitions/up.sql"),
+    include_str!("../../migrations/2024-03-12-101500_experiment_audit/up.sql"),
+    include_str!("../../migrations/2024-03-14-083000_experiment_paused_status/up.sql"),
+    include_str!("../../migrations/2024-03-20-071500_experiment_archived_at/up.sql"),
+    include_str!("../../migrations/2024-03-26-090000_experiment_text_id/up.sql"),
+    include_str!("../../migrations/2024-03-28-101500_experiment_metrics/up.sql"),
+    include_str!("../../migrations/2024-04-02-083000_experiment_schedule/up.sql"),
+    include_str!("../../migrations/2024-04-08-090000_experiment_rollout_plan/up.sql"),
+    include_str!("../../migrations/2024-04-10-083000_experiment_unique_name/up.sql"),
+    include_str!("../../migrations/2024-04-12-090000_experiment_type/up.sql"),
+];
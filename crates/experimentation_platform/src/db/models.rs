@@ -14,13 +14,34 @@ pub enum ExperimentStatusType {
     CREATED,
     CONCLUDED,
     INPROGRESS,
+    PAUSED,
+}
+
+/// `FLAG` experiments turn a single boolean config key off for their control
+/// variant and on for their experimental one.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Deserialize,
+    Serialize,
+    diesel_derive_enum::DbEnum,
+)]
+#[DbValueStyle = "UPPERCASE"]
+#[ExistingTypePath = "crate::db::schema::sql_types::ExperimentType"]
+pub enum ExperimentType {
+    #[default]
+    DEFAULT,
+    FLAG,
 }
 
 #[derive(QueryableByName, Queryable, Selectable, Insertable, Serialize, Clone, Debug)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 #[diesel(primary_key(id))]
 pub struct Experiment {
-    pub id: i64,
+    pub id: String,
     pub created_at: DateTime<Utc>,
     pub created_by: String,
     pub last_modified: DateTime<Utc>,
@@ -34,6 +55,13 @@ pub struct Experiment {
     pub variants: Value,
     pub last_modified_by: String,
     pub chosen_variant: Option<String>,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub rollout_plan: Option<Value>,
+    pub rollout_step: Option<i32>,
+    pub rollout_step_started_at: Option<DateTime<Utc>>,
+    pub experiment_type: ExperimentType,
 }
 
 pub type Experiments = Vec<Experiment>;
@@ -52,3 +80,30 @@ pub struct EventLog {
     pub new_data: Option<Value>,
     pub query: String,
 }
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Clone, Debug)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = experiment_audit)]
+#[diesel(primary_key(id))]
+pub struct ExperimentAudit {
+    pub id: uuid::Uuid,
+    pub experiment_id: String,
+    pub action: String,
+    pub previous_status: Option<ExperimentStatusType>,
+    pub new_status: ExperimentStatusType,
+    pub actor_email: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Insertable, Serialize, Clone, Debug)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(table_name = experiment_metrics)]
+#[diesel(primary_key(id))]
+pub struct ExperimentMetric {
+    pub id: uuid::Uuid,
+    pub experiment_id: String,
+    pub variant_id: String,
+    pub metric_name: String,
+    pub value: f64,
+    pub recorded_at: DateTime<Utc>,
+}
@@ -4,6 +4,10 @@ pub mod sql_types {
     #[derive(diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "experiment_status_type"))]
     pub struct ExperimentStatusType;
+
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "experiment_type"))]
+    pub struct ExperimentType;
 }
 
 diesel::table! {
@@ -556,8 +560,35 @@ diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::ExperimentStatusType;
 
+    experiment_audit (id) {
+        id -> Uuid,
+        experiment_id -> Text,
+        action -> Text,
+        previous_status -> Nullable<ExperimentStatusType>,
+        new_status -> ExperimentStatusType,
+        actor_email -> Text,
+        timestamp -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    experiment_metrics (id) {
+        id -> Uuid,
+        experiment_id -> Text,
+        variant_id -> Text,
+        metric_name -> Text,
+        value -> Float8,
+        recorded_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::ExperimentStatusType;
+    use super::sql_types::ExperimentType;
+
     experiments (id) {
-        id -> Int8,
+        id -> Text,
         created_at -> Timestamptz,
         created_by -> Text,
         last_modified -> Timestamptz,
@@ -569,9 +600,19 @@ diesel::table! {
         variants -> Json,
         last_modified_by -> Text,
         chosen_variant -> Nullable<Text>,
+        archived_at -> Nullable<Timestamptz>,
+        starts_at -> Nullable<Timestamptz>,
+        ends_at -> Nullable<Timestamptz>,
+        rollout_plan -> Nullable<Json>,
+        rollout_step -> Nullable<Int4>,
+        rollout_step_started_at -> Nullable<Timestamptz>,
+        experiment_type -> ExperimentType,
     }
 }
 
+diesel::joinable!(experiment_audit -> experiments (experiment_id));
+diesel::joinable!(experiment_metrics -> experiments (experiment_id));
+
 diesel::allow_tables_to_appear_in_same_query!(
     event_log,
     event_log_y2023m08,
@@ -615,5 +656,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     event_log_y2026m10,
     event_log_y2026m11,
     event_log_y2026m12,
+    experiment_audit,
+    experiment_metrics,
     experiments,
 );
@@ -1,10 +1,45 @@
-use chrono::Utc;
-use experimentation_platform::api::experiments::helpers;
-use experimentation_platform::db::models::{Experiment, ExperimentStatusType};
+use actix_web::http::StatusCode;
+use actix_web::web::{Data, Query};
+use chrono::{DateTime, Duration as ChronoDuration, TimeZone, Utc};
+use diesel::connection::SimpleConnection;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::{PgConnection, QueryDsl, RunQueryDsl};
+use experimentation_platform::api::experiments::handlers::conclude;
+use experimentation_platform::api::experiments::helpers::{self, ExperimentPrecondition};
+use experimentation_platform::api::experiments::orphan_contexts::find_orphan_contexts;
+use experimentation_platform::api::experiments::statistics::{self, MetricSample};
+use experimentation_platform::api::experiments::types::{
+    CacContext, ConcludeExperimentRequest, ContextAction, ContextBulkResponse,
+    ContextGetResp, ContextPutReq, ContextPutResp, ExperimentCreateRequest,
+    ExperimentExport, ExperimentSortOn, KeyDiff, ListFilters, MetricObservation,
+    ReconciliationStatus, RolloutStep, SignificanceTest, SortOrder, Variant,
+    VariantContextMapping, VariantMetricSummary, VariantType, VariantUpdateRequest,
+};
+use experimentation_platform::db::models::{
+    Experiment, ExperimentStatusType, ExperimentType,
+};
+use experimentation_platform::db::schema::experiments::dsl as experiments;
+use experimentation_platform::db::TENANT_MIGRATIONS;
+use jsonschema::JSONSchema;
+use reqwest::Url;
 use serde_json::{json, Map, Value};
-use service_utils::helpers::extract_dimensions;
-use service_utils::result::AppError;
-use service_utils::service::types::ExperimentationFlags;
+use service_utils::admin::AdminToken;
+use service_utils::config_watch::ConfigChangeNotifier;
+use service_utils::db::pgschema_manager::PgSchemaManager;
+use service_utils::helpers::{extract_dimensions, get_variable_name_and_value};
+use service_utils::id_generator::{new_id_generator, IdGeneratorKind};
+use service_utils::metrics::Metrics;
+use service_utils::rate_limiter::RateLimiter;
+use service_utils::resolve_cache::ResolveCache;
+use service_utils::result::{AppError, FieldErrors, ResponseError};
+use service_utils::retry::RetryPolicy;
+use service_utils::service::types::{AppEnv, AppState, ExperimentationFlags, Tenant};
+use service_utils::webhook::Webhooks;
+use std::collections::{HashMap, HashSet};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use superposition_types::User;
 
 enum Dimensions {
     OS(String),
@@ -44,7 +79,7 @@ fn experiment_gen(
     variants: &Value,
 ) -> Experiment {
     Experiment {
-        id: 123456789,
+        id: String::from("123456789"),
         created_at: Utc::now(),
         created_by: "test".to_string(),
         last_modified: Utc::now(),
@@ -57,9 +92,34 @@ fn experiment_gen(
         context: context.clone(),
         variants: variants.clone(),
         chosen_variant: None,
+        archived_at: None,
+        starts_at: None,
+        ends_at: None,
+        rollout_plan: None,
+        rollout_step: None,
+        rollout_step_started_at: None,
+        experiment_type: ExperimentType::DEFAULT,
     }
 }
 
+fn variants_gen(count: usize) -> Vec<Variant> {
+    (0..count)
+        .map(|idx| Variant {
+            id: format!("variant-{idx}"),
+            variant_type: if idx == 0 {
+                VariantType::CONTROL
+            } else {
+                VariantType::EXPERIMENTAL
+            },
+            context_id: None,
+            override_id: None,
+            overrides: Map::new(),
+            traffic_percentage: None,
+            description: None,
+        })
+        .collect()
+}
+
 #[test]
 fn test_duplicate_override_key_entries() {
     let override_keys = vec!["key1".to_string(), "key2".to_string(), "key1".to_string()];
@@ -101,6 +161,113 @@ fn test_extract_dimensions() -> Result<(), AppError> {
     Ok(())
 }
 
+#[test]
+fn test_extract_dimensions_nested_logic() -> Result<(), AppError> {
+    let context = json!({
+        "and": [
+            { "==": [{ "var": "os" }, "os1"] },
+            {
+                "or": [
+                    { "==": [{ "var": "clientId" }, "testclient1"] },
+                    { "and": [{ "==": [{ "var": "city" }, "bangalore"] }] }
+                ]
+            }
+        ]
+    });
+
+    let expected_dimensions = serde_json::Map::from_iter(vec![
+        ("os".to_string(), json!("os1")),
+        ("clientId".to_string(), json!("testclient1")),
+        ("city".to_string(), json!("bangalore")),
+    ]);
+
+    assert_eq!(extract_dimensions(&context)?, expected_dimensions);
+    Ok(())
+}
+
+#[test]
+fn test_extract_dimensions_error_path() {
+    match extract_dimensions(&json!({ "and": [{ "or": { "var": "os" } }] })) {
+        Err(AppError::BadArgument(msg)) => assert!(msg.contains("(at `and[0].or`)")),
+        _ => panic!("expected malformed operands to be rejected"),
+    }
+
+    let context = json!({
+        "and": [
+            { "==": [{ "var": "os" }, "os1"] },
+            { "or": [{ "==": [{ "var": "clientId" }, "a"] }, { "==": [{ "var": "city" }] }] }
+        ]
+    });
+    match extract_dimensions(&context) {
+        Err(AppError::BadArgument(msg)) => {
+            assert!(msg.contains("(at `and[1].or[1].==`)"))
+        }
+        _ => panic!("expected a condition without a value to be rejected"),
+    }
+}
+
+#[test]
+fn test_validate_context_is_conjunction() {
+    let conjunction = multiple_dimension_ctx_gen(vec![
+        Dimensions::OS("os1".to_string()),
+        Dimensions::CLIENT("testclient1".to_string()),
+    ]);
+    assert!(helpers::validate_context_is_conjunction(&conjunction).is_ok());
+    assert!(helpers::validate_context_is_conjunction(&json!({})).is_ok());
+
+    let top_level_or = json!({
+        "or": [
+            { "==": [{ "var": "os" }, "os1"] },
+            { "==": [{ "var": "os" }, "os2"] }
+        ]
+    });
+    match helpers::validate_context_is_conjunction(&top_level_or) {
+        Err(AppError::BadArgument(msg)) => assert!(msg.contains("(at `or`)")),
+        _ => panic!("expected a top level or to be rejected"),
+    }
+
+    let nested_or = json!({
+        "and": [
+            { "==": [{ "var": "os" }, "os1"] },
+            { "or": [{ "==": [{ "var": "clientId" }, "a"] }] }
+        ]
+    });
+    match helpers::validate_context_is_conjunction(&nested_or) {
+        Err(AppError::BadArgument(msg)) => assert!(msg.contains("(at `and[1].or`)")),
+        _ => panic!("expected a nested or to be rejected"),
+    }
+}
+
+#[test]
+fn test_get_variable_name_and_value() -> Result<(), AppError> {
+    // two operand equality
+    let operands = vec![json!({ "var": "os" }), json!("os1")];
+    assert_eq!(
+        get_variable_name_and_value(&operands)?,
+        ("os", json!("os1"))
+    );
+
+    // `in` with an array of values
+    let operands = vec![json!({ "var": "city" }), json!(["A", "B"])];
+    assert_eq!(
+        get_variable_name_and_value(&operands)?,
+        ("city", json!(["A", "B"]))
+    );
+
+    // range comparison with both bounds around the variable
+    let operands = vec![json!(10), json!({ "var": "version" }), json!(20)];
+    assert_eq!(
+        get_variable_name_and_value(&operands)?,
+        ("version", json!([10, 20]))
+    );
+
+    assert!(matches!(
+        get_variable_name_and_value(&vec![json!({ "var": "os" })]),
+        Err(AppError::BadArgument(_))
+    ));
+    Ok(())
+}
+
 #[test]
 fn test_are_overlapping_contexts() -> Result<(), AppError> {
     let context_a = multiple_dimension_ctx_gen(vec![
@@ -142,6 +309,52 @@ fn test_are_overlapping_contexts() -> Result<(), AppError> {
     Ok(())
 }
 
+#[test]
+fn test_matching_experiments() -> Result<(), AppError> {
+    let variants = json!(variants_gen(2));
+    let os_experiment = experiment_gen(
+        &vec![],
+        &single_dimension_ctx_gen(Dimensions::OS("os1".to_string())),
+        ExperimentStatusType::INPROGRESS,
+        &variants,
+    );
+    let mut client_experiment = experiment_gen(
+        &vec![],
+        &multiple_dimension_ctx_gen(vec![
+            Dimensions::OS("os1".to_string()),
+            Dimensions::CLIENT("testclient1".to_string()),
+        ]),
+        ExperimentStatusType::CREATED,
+        &variants,
+    );
+    client_experiment.id = String::from("987654321");
+    let experiments = vec![os_experiment, client_experiment];
+    let matching_ids = |dimensions: Map<String, Value>| -> Result<Vec<String>, AppError> {
+        Ok(
+            helpers::matching_experiments(experiments.clone(), &dimensions)?
+                .into_iter()
+                .map(|experiment| experiment.id)
+                .collect(),
+        )
+    };
+
+    assert_eq!(
+        matching_ids(Map::from_iter([(String::from("os"), json!("os1"))]))?,
+        vec!["123456789", "987654321"]
+    );
+    assert_eq!(
+        matching_ids(Map::from_iter([
+            (String::from("os"), json!("os1")),
+            (String::from("clientId"), json!("testclient2")),
+        ]))?,
+        vec!["123456789"]
+    );
+    assert!(
+        matching_ids(Map::from_iter([(String::from("os"), json!("os2"))]))?.is_empty()
+    );
+    Ok(())
+}
+
 #[test]
 fn test_check_variants_override_coverage() {
     let override_keys = vec!["key1".to_string(), "key2".to_string()];
@@ -181,6 +394,772 @@ fn test_check_variants_override_coverage() {
     );
 }
 
+#[test]
+fn test_validate_variant_ids() {
+    let mut variants = variants_gen(2);
+    variants[0].id = String::from("control");
+    variants[1].id = String::from("test_1");
+    assert!(helpers::validate_variant_ids(&variants).is_ok());
+    assert_eq!(
+        helpers::variant_context_id("7172348901236981760", &variants[1].id),
+        "7172348901236981760-test_1"
+    );
+
+    for unsafe_id in ["test-1", "", "test 1", "tést"] {
+        variants[1].id = String::from(unsafe_id);
+        assert!(helpers::validate_variant_ids(&variants).is_err());
+    }
+}
+
+#[test]
+fn test_validate_variant_ids_rejects_duplicates() {
+    let mut variants = variants_gen(3);
+    variants[0].id = String::from("control");
+    variants[1].id = String::from("test");
+    variants[2].id = String::from("test");
+    match helpers::validate_variant_ids(&variants) {
+        Err(AppError::BadArgument(msg)) => assert!(msg.contains("`test`")),
+        _ => panic!("expected variants sharing an id to be rejected"),
+    }
+}
+
+#[test]
+fn test_find_orphan_contexts() {
+    let now = Utc::now();
+    let variant_context = |id: &str, variant_id: &str, age_mins: i64| CacContext {
+        id: String::from(id),
+        value: json!({ "and": [
+            { "==": [{ "var": "os" }, "ios"] },
+            { "in": [variant_id, { "var": "variantIds" }] }
+        ]}),
+        created_at: now - ChronoDuration::minutes(age_mins),
+    };
+    let contexts = vec![
+        variant_context("running", "1-control", 60),
+        variant_context("concluded", "2-control", 60),
+        variant_context("missing", "3-control", 60),
+        variant_context("recent", "4-control", 1),
+        CacContext {
+            id: String::from("plain"),
+            value: single_dimension_ctx_gen(Dimensions::OS(String::from("ios"))),
+            created_at: now - ChronoDuration::minutes(60),
+        },
+    ];
+    let statuses = HashMap::from([
+        (String::from("1"), ExperimentStatusType::INPROGRESS),
+        (String::from("2"), ExperimentStatusType::CONCLUDED),
+    ]);
+
+    let orphans =
+        find_orphan_contexts(&contexts, &statuses, now - ChronoDuration::minutes(10));
+    let orphan_ids = orphans
+        .iter()
+        .map(|orphan| orphan.context_id.as_str())
+        .collect::<Vec<&str>>();
+    assert_eq!(orphan_ids, vec!["concluded", "missing"]);
+    assert_eq!(orphans[1].experiment_id, "3");
+    assert_eq!(orphans[1].variant_id, "3-control");
+
+    assert_eq!(
+        helpers::split_variant_context_id("7172348901236981760-test_1"),
+        Some(("7172348901236981760", "test_1"))
+    );
+    assert_eq!(helpers::split_variant_context_id("test"), None);
+}
+
+#[test]
+fn test_validate_override_keys_exist() {
+    let default_config_keys: HashSet<String> =
+        HashSet::from(["key1".to_string(), "key2".to_string()]);
+
+    assert!(matches!(
+        helpers::validate_override_keys_exist(
+            &vec!["key1".to_string(), "key2".to_string()],
+            &default_config_keys
+        ),
+        Ok(())
+    ));
+    match helpers::validate_override_keys_exist(
+        &vec!["key1".to_string(), "key3".to_string(), "key4".to_string()],
+        &default_config_keys,
+    ) {
+        Err(AppError::BadArgument(msg)) => assert!(msg.contains("[key3,key4]")),
+        _ => panic!("expected unknown override keys to be rejected"),
+    }
+}
+
+#[test]
+fn test_validate_variant_override_types() {
+    let schemas = HashMap::from([
+        (String::from("door_count"), json!({"type": "integer"})),
+        (String::from("color"), json!({"type": "string"})),
+    ]);
+    let mut variants = variants_gen(2);
+    for (variant, doors) in variants.iter_mut().zip([4, 2]) {
+        variant.overrides = Map::from_iter([
+            (String::from("door_count"), json!(doors)),
+            (String::from("color"), json!("red")),
+        ]);
+    }
+    assert!(helpers::validate_variant_override_types(&variants, &schemas).is_ok());
+
+    variants[1]
+        .overrides
+        .insert(String::from("door_count"), json!("5"));
+    match helpers::validate_variant_override_types(&variants, &schemas) {
+        Err(AppError::BadArgument(message)) => {
+            assert!(message.contains("door_count"), "{message}");
+            assert!(message.contains("variant-1"), "{message}");
+        }
+        other => panic!("expected a bad argument error, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_validate_traffic_percentage_at_boundary() {
+    // total traffic is exactly 100
+    assert!(matches!(
+        helpers::validate_traffic_percentage(50, &variants_gen(2)),
+        Ok(())
+    ));
+    assert!(matches!(
+        helpers::validate_traffic_percentage(25, &variants_gen(4)),
+        Ok(())
+    ));
+    assert!(matches!(
+        helpers::validate_traffic_percentage(0, &variants_gen(4)),
+        Ok(())
+    ));
+}
+
+#[test]
+fn test_validate_traffic_percentage_off_by_one() {
+    match helpers::validate_traffic_percentage(51, &variants_gen(2)) {
+        Err(AppError::BadArgument(msg)) => {
+            assert!(msg.contains("variant-1"));
+            assert!(msg.contains("at most 50"));
+        }
+        _ => panic!("expected traffic of 51% across 2 variants to be rejected"),
+    }
+    // 100 / 3 rounds down, so 34% across 3 variants takes the total to 102%
+    assert!(matches!(
+        helpers::validate_traffic_percentage(33, &variants_gen(3)),
+        Ok(())
+    ));
+    assert!(matches!(
+        helpers::validate_traffic_percentage(34, &variants_gen(3)),
+        Err(AppError::BadArgument(_))
+    ));
+}
+
+#[test]
+fn test_validate_traffic_percentage_negative() {
+    assert!(matches!(
+        helpers::validate_traffic_percentage(-1, &variants_gen(2)),
+        Err(AppError::BadArgument(_))
+    ));
+}
+
+#[test]
+fn test_validate_rollout_plan() {
+    let step = |traffic_percentage: i64, advance_after_secs: Option<i64>| RolloutStep {
+        traffic_percentage,
+        advance_after_secs,
+    };
+    let variants = variants_gen(2);
+
+    assert!(helpers::validate_rollout_plan(&[], &variants).is_ok());
+    assert!(helpers::validate_rollout_plan(
+        &[
+            step(1, Some(3600)),
+            step(5, None),
+            step(25, None),
+            step(50, None)
+        ],
+        &variants
+    )
+    .is_ok());
+    // two variants cannot be served more than 50% each
+    assert!(
+        helpers::validate_rollout_plan(&[step(1, None), step(100, None)], &variants)
+            .is_err()
+    );
+    assert!(
+        helpers::validate_rollout_plan(&[step(5, None), step(5, None)], &variants)
+            .is_err()
+    );
+    assert!(helpers::validate_rollout_plan(&[step(5, Some(0))], &variants).is_err());
+}
+
+#[test]
+fn test_experiment_create_warnings() {
+    let narrow_context = json!({"and": [
+        {"==": [{"var": "os"}, "android"]},
+        {"==": [{"var": "city"}, "Bangalore"]}
+    ]});
+    let broad_context = json!({"==": [{"var": "os"}, "android"]});
+
+    assert!(helpers::low_traffic_warning(1, &variants_gen(5)).is_some());
+    assert!(helpers::low_traffic_warning(5, &variants_gen(5)).is_none());
+    // experiments not yet ramped up are not warned about
+    assert!(helpers::low_traffic_warning(0, &variants_gen(5)).is_none());
+    let mut weighted = variants_gen(2);
+    weighted[0].traffic_percentage = Some(99);
+    weighted[1].traffic_percentage = Some(1);
+    assert!(helpers::low_traffic_warning(100, &weighted).is_some());
+
+    assert!(
+        helpers::broad_audience_warning(&broad_context, 25, &variants_gen(2)).is_some()
+    );
+    assert!(helpers::broad_audience_warning(&json!({}), 60, &weighted).is_some());
+    assert!(
+        helpers::broad_audience_warning(&narrow_context, 25, &variants_gen(2)).is_none()
+    );
+    assert!(
+        helpers::broad_audience_warning(&broad_context, 10, &variants_gen(2)).is_none()
+    );
+
+    let request = |context: Value, traffic_percentage: i64, variants: Vec<Variant>| {
+        ExperimentCreateRequest {
+            name: "experiment".to_string(),
+            context,
+            variants,
+            experiment_type: ExperimentType::DEFAULT,
+            flag_key: None,
+            traffic_percentage: Some(traffic_percentage),
+            allow_global: false,
+            starts_at: None,
+            ends_at: None,
+            rollout_plan: Vec::new(),
+        }
+    };
+    assert!(helpers::experiment_create_warnings(&request(
+        narrow_context.clone(),
+        10,
+        variants_gen(2)
+    ))
+    .is_empty());
+    assert_eq!(
+        helpers::experiment_create_warnings(&request(broad_context, 1, variants_gen(50)))
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn test_apply_default_traffic_percentage() {
+    let request = |traffic_percentage: Option<i64>, variants: Vec<Variant>| {
+        ExperimentCreateRequest {
+            name: "experiment".to_string(),
+            context: json!({"==": [{"var": "os"}, "android"]}),
+            variants,
+            experiment_type: ExperimentType::DEFAULT,
+            flag_key: None,
+            traffic_percentage,
+            allow_global: false,
+            starts_at: None,
+            ends_at: None,
+            rollout_plan: Vec::new(),
+        }
+    };
+
+    // the default total is split evenly, every variant serving its share
+    let mut even = request(None, variants_gen(4));
+    assert_eq!(
+        helpers::apply_default_traffic_percentage(&mut even, 100),
+        Some(25)
+    );
+    assert_eq!(even.traffic_percentage, Some(25));
+    let mut uneven = request(None, variants_gen(3));
+    assert_eq!(
+        helpers::apply_default_traffic_percentage(&mut uneven, 100),
+        Some(33)
+    );
+
+    let mut weighted_variants = variants_gen(2);
+    weighted_variants[1].traffic_percentage = Some(30);
+    let mut weighted = request(None, weighted_variants);
+    assert_eq!(
+        helpers::apply_default_traffic_percentage(&mut weighted, 80),
+        Some(80)
+    );
+
+    // an explicit traffic_percentage, even 0, is kept
+    let mut explicit = request(Some(0), variants_gen(2));
+    assert_eq!(
+        helpers::apply_default_traffic_percentage(&mut explicit, 100),
+        None
+    );
+    assert_eq!(explicit.traffic_percentage, Some(0));
+}
+
+#[test]
+fn test_rollout_steps() {
+    let plan = vec![
+        RolloutStep {
+            traffic_percentage: 1,
+            advance_after_secs: Some(3600),
+        },
+        RolloutStep {
+            traffic_percentage: 5,
+            advance_after_secs: None,
+        },
+    ];
+    assert_eq!(helpers::next_rollout_step(&plan, None), Some(0));
+    assert_eq!(helpers::next_rollout_step(&plan, Some(0)), Some(1));
+    // the last step holds
+    assert_eq!(helpers::next_rollout_step(&plan, Some(1)), None);
+
+    let now = Utc::now();
+    let started_at = Some(now - ChronoDuration::minutes(90));
+    assert!(helpers::is_rollout_step_due(
+        &plan,
+        Some(0),
+        started_at,
+        now
+    ));
+    assert!(!helpers::is_rollout_step_due(
+        &plan,
+        Some(0),
+        Some(now - ChronoDuration::minutes(30)),
+        now
+    ));
+    // steps without advance_after_secs wait to be advanced
+    assert!(!helpers::is_rollout_step_due(
+        &plan,
+        Some(1),
+        started_at,
+        now
+    ));
+    assert!(!helpers::is_rollout_step_due(&plan, None, started_at, now));
+}
+
+#[test]
+fn test_decide_variant_buckets() {
+    let variants = variants_gen(2);
+
+    // each variant owns `traffic_percentage` consecutive buckets
+    assert_eq!(
+        helpers::decide_variant(30, &variants, 0).map(|v| v.id.as_str()),
+        Some("variant-0")
+    );
+    assert_eq!(
+        helpers::decide_variant(30, &variants, 29).map(|v| v.id.as_str()),
+        Some("variant-0")
+    );
+    assert_eq!(
+        helpers::decide_variant(30, &variants, 30).map(|v| v.id.as_str()),
+        Some("variant-1")
+    );
+    assert!(helpers::decide_variant(30, &variants, 60).is_none());
+    assert!(helpers::decide_variant(0, &variants, 0).is_none());
+
+    // negative tosses force the experimental variant
+    assert_eq!(
+        helpers::decide_variant(0, &variants, -1).map(|v| v.id.as_str()),
+        Some("variant-1")
+    );
+}
+
+#[test]
+fn test_assign_experiment_variants() {
+    let variants = json!(variants_gen(2));
+    let mut ios_experiment = experiment_gen(
+        &vec![],
+        &single_dimension_ctx_gen(Dimensions::OS(String::from("ios"))),
+        ExperimentStatusType::INPROGRESS,
+        &variants,
+    );
+    ios_experiment.traffic_percentage = 50;
+    let mut android_experiment = ios_experiment.clone();
+    android_experiment.id = String::from("987654321");
+    android_experiment.context =
+        single_dimension_ctx_gen(Dimensions::OS(String::from("android")));
+    let experiments = [ios_experiment, android_experiment];
+
+    let context = Map::from_iter([(String::from("os"), json!("ios"))]);
+    let served = helpers::assign_experiment_variants(
+        &experiments,
+        &context,
+        Some(70),
+        "user_id",
+        None,
+    )
+    .unwrap();
+    assert_eq!(served.len(), 1);
+    assert_eq!(served[0].experiment_id, "123456789");
+    assert_eq!(served[0].variant_id, "variant-1");
+    assert_eq!(served[0].variant_type, VariantType::EXPERIMENTAL);
+
+    let served = helpers::assign_experiment_variants(
+        &experiments,
+        &context,
+        Some(10),
+        "user_id",
+        None,
+    )
+    .unwrap();
+    assert_eq!(served[0].variant_type, VariantType::CONTROL);
+
+    // without a toss the same toss key keeps getting the same variant
+    let mut keyed_context = context.clone();
+    keyed_context.insert(String::from("user_id"), json!("user-42"));
+    let first = helpers::assign_experiment_variants(
+        &experiments,
+        &keyed_context,
+        None,
+        "user_id",
+        None,
+    )
+    .unwrap();
+    let second = helpers::assign_experiment_variants(
+        &experiments,
+        &keyed_context,
+        None,
+        "user_id",
+        None,
+    )
+    .unwrap();
+    assert_eq!(first, second);
+
+    let windows = Map::from_iter([(String::from("os"), json!("windows"))]);
+    assert!(helpers::assign_experiment_variants(
+        &experiments,
+        &windows,
+        Some(0),
+        "user_id",
+        None
+    )
+    .unwrap()
+    .is_empty());
+
+    // a pin forces its variant on the matching experiment only
+    let pin = helpers::parse_variant_pin("123456789:variant-0").unwrap();
+    let served = helpers::assign_experiment_variants(
+        &experiments,
+        &context,
+        Some(70),
+        "user_id",
+        Some(&pin),
+    )
+    .unwrap();
+    assert_eq!(served[0].variant_id, "variant-0");
+    let served = helpers::assign_experiment_variants(
+        &experiments,
+        &windows,
+        Some(0),
+        "user_id",
+        Some(&pin),
+    )
+    .unwrap();
+    assert!(served.is_empty());
+
+    // a variant id outside the experiment leaves the bucketing in place
+    let foreign_pin = helpers::parse_variant_pin("123456789:variant-7").unwrap();
+    let served = helpers::assign_experiment_variants(
+        &experiments,
+        &context,
+        Some(70),
+        "user_id",
+        Some(&foreign_pin),
+    )
+    .unwrap();
+    assert_eq!(served[0].variant_id, "variant-1");
+
+    assert!(helpers::parse_variant_pin("123456789").is_err());
+    assert!(helpers::parse_variant_pin(":variant-0").is_err());
+}
+
+fn weighted_variants_gen(weights: &[i32]) -> Vec<Variant> {
+    let mut variants = variants_gen(weights.len() + 1);
+    for (variant, weight) in variants.iter_mut().skip(1).zip(weights) {
+        variant.traffic_percentage = Some(*weight);
+    }
+    variants
+}
+
+#[test]
+fn test_validate_variant_traffic_percentages() {
+    // unweighted experiments keep the equal split
+    assert!(helpers::validate_variant_traffic_percentages(&variants_gen(3)).is_ok());
+    assert!(
+        helpers::validate_variant_traffic_percentages(&weighted_variants_gen(&[10, 10]))
+            .is_ok()
+    );
+    assert!(
+        helpers::validate_variant_traffic_percentages(&weighted_variants_gen(&[60, 40]))
+            .is_ok()
+    );
+
+    assert!(matches!(
+        helpers::validate_variant_traffic_percentages(&weighted_variants_gen(&[60, 50])),
+        Err(AppError::BadArgument(_))
+    ));
+    assert!(matches!(
+        helpers::validate_variant_traffic_percentages(&weighted_variants_gen(&[10, 0])),
+        Err(AppError::BadArgument(_))
+    ));
+    assert!(matches!(
+        helpers::validate_variant_traffic_percentages(&weighted_variants_gen(&[10, -5])),
+        Err(AppError::BadArgument(_))
+    ));
+
+    let mut partially_weighted = variants_gen(3);
+    partially_weighted[1].traffic_percentage = Some(10);
+    assert!(matches!(
+        helpers::validate_variant_traffic_percentages(&partially_weighted),
+        Err(AppError::BadArgument(_))
+    ));
+
+    // the control variant can only be given the remainder
+    let mut control_weighted = weighted_variants_gen(&[10, 10]);
+    control_weighted[0].traffic_percentage = Some(50);
+    assert!(matches!(
+        helpers::validate_variant_traffic_percentages(&control_weighted),
+        Err(AppError::BadArgument(_))
+    ));
+    control_weighted[0].traffic_percentage = Some(80);
+    assert!(helpers::validate_variant_traffic_percentages(&control_weighted).is_ok());
+}
+
+#[test]
+fn test_weighted_variants_bucketing() {
+    let mut variants = weighted_variants_gen(&[10, 10]);
+    helpers::assign_control_traffic_percentage(&mut variants);
+    assert_eq!(variants[0].traffic_percentage, Some(80));
+
+    let split: Vec<i32> = helpers::variant_traffic_split(100, &variants)
+        .into_iter()
+        .map(|(_, percentage)| percentage)
+        .collect();
+    assert_eq!(split, vec![80, 10, 10]);
+
+    let split: Vec<i32> = helpers::variant_traffic_split(50, &variants)
+        .into_iter()
+        .map(|(_, percentage)| percentage)
+        .collect();
+    assert_eq!(split, vec![40, 5, 5]);
+
+    let decide =
+        |toss| helpers::decide_variant(100, &variants, toss).map(|v| v.id.clone());
+    assert_eq!(decide(79), Some("variant-0".to_string()));
+    assert_eq!(decide(80), Some("variant-1".to_string()));
+    assert_eq!(decide(90), Some("variant-2".to_string()));
+    assert_eq!(decide(99), Some("variant-2".to_string()));
+    assert!(helpers::decide_variant(50, &variants, 50).is_none());
+
+    // weighted experiments can be ramped up to the whole traffic
+    assert!(helpers::validate_traffic_percentage(100, &variants).is_ok());
+    assert!(matches!(
+        helpers::validate_traffic_percentage(101, &variants),
+        Err(AppError::BadArgument(_))
+    ));
+}
+
+#[test]
+fn test_toss_for_context_is_deterministic() {
+    let mut context = Map::new();
+    context.insert("os".to_string(), json!("android"));
+    context.insert("clientId".to_string(), json!("testclient1"));
+
+    let mut reordered_context = Map::new();
+    reordered_context.insert("clientId".to_string(), json!("testclient1"));
+    reordered_context.insert("os".to_string(), json!("android"));
+
+    let toss = helpers::toss_for_context("123456789", &context);
+    assert!((0..100).contains(&toss));
+    assert_eq!(
+        toss,
+        helpers::toss_for_context("123456789", &reordered_context)
+    );
+    assert_eq!(helpers::stable_hash(""), 0xcbf29ce484222325);
+    assert_eq!(helpers::stable_hash("a"), 0xaf63dc4c8601ec8c);
+}
+
+#[test]
+fn test_assign_variant_is_deterministic() {
+    let variants_with_percentages: Vec<(Variant, i32)> =
+        variants_gen(2).into_iter().map(|v| (v, 50)).collect();
+
+    let assigned =
+        helpers::assign_variant("123456789", "user-42", &variants_with_percentages)
+            .map(|v| v.id.clone());
+    for _ in 0..10 {
+        assert_eq!(
+            helpers::assign_variant("123456789", "user-42", &variants_with_percentages)
+                .map(|v| v.id.clone()),
+            assigned
+        );
+    }
+}
+
+#[test]
+fn test_assign_variant_distribution() {
+    let total_keys = 10_000;
+    let variants_with_percentages: Vec<(Variant, i32)> =
+        variants_gen(3).into_iter().zip([20, 30, 40]).collect();
+
+    let mut counts: HashMap<String, i32> = HashMap::new();
+    let mut unassigned = 0;
+    for key in 0..total_keys {
+        match helpers::assign_variant(
+            "123456789",
+            &format!("user-{key}"),
+            &variants_with_percentages,
+        ) {
+            Some(variant) => *counts.entry(variant.id.clone()).or_default() += 1,
+            None => unassigned += 1,
+        }
+    }
+
+    // every bucket should be within 2% of its expected share
+    let tolerance = total_keys / 50;
+    for (variant, percentage) in &variants_with_percentages {
+        let expected = total_keys * percentage / 100;
+        let actual = counts.get(&variant.id).copied().unwrap_or_default();
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} got {} keys, expected around {}",
+            variant.id,
+            actual,
+            expected
+        );
+    }
+    assert!((unassigned - total_keys / 10).abs() <= tolerance);
+}
+
+#[test]
+fn test_calculate_total_pages() {
+    assert_eq!(helpers::calculate_total_pages(0, 10), 0);
+    assert_eq!(helpers::calculate_total_pages(10, 10), 1);
+    // a partially filled last page still counts as a page
+    assert_eq!(helpers::calculate_total_pages(11, 10), 2);
+    assert_eq!(helpers::calculate_total_pages(1, 10), 1);
+    assert_eq!(helpers::calculate_total_pages(25, 7), 4);
+}
+
+#[test]
+fn test_cursor_encoding() {
+    let created_at = Utc.with_ymd_and_hms(2024, 4, 1, 10, 30, 0).unwrap()
+        + ChronoDuration::microseconds(123456);
+    let cursor = helpers::encode_cursor(&created_at, "7182735129856122880");
+    assert!(!cursor.contains("7182735129856122880"));
+    assert_eq!(
+        helpers::decode_cursor(&cursor).unwrap(),
+        (created_at, "7182735129856122880".to_string())
+    );
+    // hex of a key without an id, and of one without a creation time
+    let missing_id = "2024-04-01T10:30:00Z|"
+        .bytes()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    let missing_created_at = "7182735129856122880"
+        .bytes()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    for invalid in ["", "abc", "zz", "ff", &missing_id, &missing_created_at] {
+        assert!(matches!(
+            helpers::decode_cursor(invalid),
+            Err(AppError::BadArgument(_))
+        ));
+    }
+}
+
+#[test]
+fn test_cursor_pages_are_stable_under_inserts() {
+    let start = Utc.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap();
+    let experiment = |(created_at, id): (DateTime<Utc>, String)| {
+        let mut experiment = experiment_gen(
+            &vec![],
+            &json!({}),
+            ExperimentStatusType::CREATED,
+            &json!([]),
+        );
+        experiment.created_at = created_at;
+        experiment.id = id;
+        experiment
+    };
+    // ids of different lengths and generators, which do not sort by creation
+    // as text, and pairs of experiments created at the same time
+    let id_of = |idx: i64| match idx % 3 {
+        0 => format!("{}", 90 + idx),
+        1 => format!("{}", 7182735129856122880_i64 + idx),
+        _ => format!("01HTDG7{idx:019}"),
+    };
+    // runs the listing query against an in memory table: newest first by
+    // creation and then id, after the cursor, one more than the page size
+    let fetch_page =
+        |table: &Vec<(DateTime<Utc>, String)>, cursor: &Option<String>, size: i64| {
+            let after = cursor
+                .as_deref()
+                .map(|cursor| helpers::decode_cursor(cursor).unwrap());
+            let mut rows = table
+                .iter()
+                .filter(|row| after.as_ref().map_or(true, |after| *row < after))
+                .cloned()
+                .collect::<Vec<(DateTime<Utc>, String)>>();
+            rows.sort_unstable_by(|a, b| b.cmp(a));
+            let rows = rows
+                .into_iter()
+                .take(size as usize + 1)
+                .map(experiment)
+                .collect::<Vec<Experiment>>();
+            helpers::split_cursor_page(rows, size)
+        };
+
+    let mut table = (0..23)
+        .map(|idx| (start + ChronoDuration::seconds(idx / 2), id_of(idx)))
+        .collect::<Vec<(DateTime<Utc>, String)>>();
+    let mut expected = table.clone();
+    expected.sort_unstable_by(|a, b| b.cmp(a));
+    let expected = expected
+        .into_iter()
+        .map(|(_, id)| id)
+        .collect::<Vec<String>>();
+
+    let mut next_idx = 23;
+    let mut seen = Vec::new();
+    let mut cursor = None;
+    loop {
+        let (page, next_cursor) = fetch_page(&table, &cursor, 5);
+        assert!(page.len() <= 5);
+        seen.extend(page.into_iter().map(|experiment| experiment.id));
+        // experiments created between pages are newer, whatever their ids
+        for _ in 0..2 {
+            table.push((start + ChronoDuration::hours(1 + next_idx), id_of(next_idx)));
+            next_idx += 1;
+        }
+        match next_cursor {
+            Some(next_cursor) => cursor = Some(next_cursor),
+            None => break,
+        }
+    }
+    assert_eq!(seen, expected);
+
+    let (page, next_cursor) =
+        helpers::split_cursor_page(vec![experiment((start, id_of(1)))], 5);
+    assert_eq!(page.len(), 1);
+    assert!(next_cursor.is_none());
+}
+
+#[test]
+fn test_duplicate_experiment_name() {
+    assert_eq!(
+        helpers::duplicate_experiment_name(&["checkout test", "search", "checkout test"]),
+        Some("checkout test")
+    );
+    // names are compared as given
+    assert_eq!(
+        helpers::duplicate_experiment_name(&["checkout test", "Checkout test"]),
+        None
+    );
+    assert_eq!(helpers::duplicate_experiment_name(&[]), None);
+
+    let err = helpers::experiment_name_taken_error("checkout test", "id 123");
+    let res = actix_web::ResponseError::error_response(&err);
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+}
+
 /************************* No Restrictions *****************************************/
 
 #[test]
@@ -248,16 +1227,50 @@ fn test_is_valid_experiment_no_restrictions_non_overlapping_experiment(
             &flags,
             &active_experiments
         )?,
-        (true, "".to_string())
+        (true, "".to_string())
+    );
+
+    Ok(())
+}
+
+/************************* Restrict Same Keys Overlapping Context *****************************************/
+
+#[test]
+fn test_is_valid_experiment_restrict_same_keys_overlapping_ctx_overlapping_experiment_same_keys(
+) -> Result<(), AppError> {
+    let experiment_context = multiple_dimension_ctx_gen(vec![
+        Dimensions::OS("os1".to_string()),
+        Dimensions::CLIENT("testclient1".to_string()),
+    ]);
+    let experiment_override_keys = vec!["key1".to_string(), "key2".to_string()];
+    let flags = ExperimentationFlags {
+        allow_same_keys_overlapping_ctx: false,
+        allow_diff_keys_overlapping_ctx: true,
+        allow_same_keys_non_overlapping_ctx: true,
+    };
+
+    let active_experiments = vec![experiment_gen(
+        &experiment_override_keys,
+        &experiment_context,
+        ExperimentStatusType::CREATED,
+        &json!(""),
+    )];
+
+    assert_eq!(
+        helpers::is_valid_experiment(
+            &experiment_context,
+            &experiment_override_keys,
+            &flags,
+            &active_experiments
+        )?,
+        (false, "This current context overlaps with an existing experiment (id: 123456789) or the keys in the context are overlapping".to_string())
     );
 
     Ok(())
 }
 
-/************************* Restrict Same Keys Overlapping Context *****************************************/
-
 #[test]
-fn test_is_valid_experiment_restrict_same_keys_overlapping_ctx_overlapping_experiment_same_keys(
+fn test_is_valid_experiment_restrict_same_keys_overlapping_ctx_overlapping_experiment_one_same_key(
 ) -> Result<(), AppError> {
     let experiment_context = multiple_dimension_ctx_gen(vec![
         Dimensions::OS("os1".to_string()),
@@ -271,7 +1284,7 @@ fn test_is_valid_experiment_restrict_same_keys_overlapping_ctx_overlapping_exper
     };
 
     let active_experiments = vec![experiment_gen(
-        &experiment_override_keys,
+        &vec!["key1".to_string(), "key3".to_string()],
         &experiment_context,
         ExperimentStatusType::CREATED,
         &json!(""),
@@ -284,14 +1297,14 @@ fn test_is_valid_experiment_restrict_same_keys_overlapping_ctx_overlapping_exper
             &flags,
             &active_experiments
         )?,
-        (false, "This current context overlaps with an existing experiment or the keys in the context are overlapping".to_string())
+        (false, "This current context overlaps with an existing experiment (id: 123456789) or the keys in the context are overlapping".to_string())
     );
 
     Ok(())
 }
 
 #[test]
-fn test_is_valid_experiment_restrict_same_keys_overlapping_ctx_overlapping_experiment_one_same_key(
+fn test_is_valid_experiment_restrict_same_keys_overlapping_ctx_overlapping_experiment_diff_keys(
 ) -> Result<(), AppError> {
     let experiment_context = multiple_dimension_ctx_gen(vec![
         Dimensions::OS("os1".to_string()),
@@ -305,7 +1318,7 @@ fn test_is_valid_experiment_restrict_same_keys_overlapping_ctx_overlapping_exper
     };
 
     let active_experiments = vec![experiment_gen(
-        &vec!["key1".to_string(), "key3".to_string()],
+        &vec!["key3".to_string(), "key4".to_string()],
         &experiment_context,
         ExperimentStatusType::CREATED,
         &json!(""),
@@ -318,43 +1331,61 @@ fn test_is_valid_experiment_restrict_same_keys_overlapping_ctx_overlapping_exper
             &flags,
             &active_experiments
         )?,
-        (false, "This current context overlaps with an existing experiment or the keys in the context are overlapping".to_string())
+        (true, "".to_string())
     );
 
     Ok(())
 }
 
 #[test]
-fn test_is_valid_experiment_restrict_same_keys_overlapping_ctx_overlapping_experiment_diff_keys(
+fn test_is_valid_experiment_restrict_same_keys_overlapping_ctx_names_conflicting_experiment(
 ) -> Result<(), AppError> {
     let experiment_context = multiple_dimension_ctx_gen(vec![
         Dimensions::OS("os1".to_string()),
         Dimensions::CLIENT("testclient1".to_string()),
     ]);
-    let experiment_override_keys = vec!["key1".to_string(), "key2".to_string()];
     let flags = ExperimentationFlags {
         allow_same_keys_overlapping_ctx: false,
         allow_diff_keys_overlapping_ctx: true,
         allow_same_keys_non_overlapping_ctx: true,
     };
 
-    let active_experiments = vec![experiment_gen(
-        &vec!["key3".to_string(), "key4".to_string()],
+    let mut disjoint_experiment = experiment_gen(
+        &vec!["key3".to_string()],
         &experiment_context,
-        ExperimentStatusType::CREATED,
+        ExperimentStatusType::INPROGRESS,
         &json!(""),
-    )];
+    );
+    disjoint_experiment.id = String::from("111");
+    let mut overlapping_experiment = experiment_gen(
+        &vec!["key2".to_string()],
+        &experiment_context,
+        ExperimentStatusType::INPROGRESS,
+        &json!(""),
+    );
+    overlapping_experiment.id = String::from("222");
 
+    // disjoint override keys on the same context are allowed
     assert_eq!(
         helpers::is_valid_experiment(
             &experiment_context,
-            &experiment_override_keys,
+            &vec!["key1".to_string()],
             &flags,
-            &active_experiments
+            &vec![disjoint_experiment.clone()]
         )?,
         (true, "".to_string())
     );
 
+    // a shared override key is rejected and the error points at the clashing experiment
+    let (valid, reason) = helpers::is_valid_experiment(
+        &experiment_context,
+        &vec!["key1".to_string(), "key2".to_string()],
+        &flags,
+        &vec![disjoint_experiment, overlapping_experiment],
+    )?;
+    assert!(!valid);
+    assert!(reason.contains("(id: 222)"));
+
     Ok(())
 }
 
@@ -422,7 +1453,7 @@ fn test_is_valid_experiment_restrict_diff_keys_overlapping_ctx_overlapping_exper
             &flags,
             &active_experiments
         )?,
-        (false, "This current context overlaps with an existing experiment or the keys in the context are overlapping".to_string())
+        (false, "This current context overlaps with an existing experiment (id: 123456789) or the keys in the context are overlapping".to_string())
     );
 
     Ok(())
@@ -456,7 +1487,7 @@ fn test_is_valid_experiment_restrict_diff_keys_overlapping_ctx_overlapping_exper
             &flags,
             &active_experiments
         )?,
-        (false, "This current context overlaps with an existing experiment or the keys in the context are overlapping".to_string())
+        (false, "This current context overlaps with an existing experiment (id: 123456789) or the keys in the context are overlapping".to_string())
     );
 
     Ok(())
@@ -495,7 +1526,7 @@ fn test_is_valid_experiment_restrict_same_keys_non_overlapping_ctx_non_overlappi
             &flags,
             &active_experiments
         )?,
-        (false, "This current context overlaps with an existing experiment or the keys in the context are overlapping".to_string())
+        (false, "This current context overlaps with an existing experiment (id: 123456789) or the keys in the context are overlapping".to_string())
     );
 
     Ok(())
@@ -532,7 +1563,7 @@ fn test_is_valid_experiment_restrict_same_keys_non_overlapping_ctx_non_overlappi
             &flags,
             &active_experiments
         )?,
-        (false, "This current context overlaps with an existing experiment or the keys in the context are overlapping".to_string())
+        (false, "This current context overlaps with an existing experiment (id: 123456789) or the keys in the context are overlapping".to_string())
     );
 
     Ok(())
@@ -574,3 +1605,958 @@ fn test_is_valid_experiment_restrict_same_keys_non_overlapping_ctx_non_overlappi
 
     Ok(())
 }
+
+#[test]
+fn test_list_filters_sorting() {
+    let filters = Query::<ListFilters>::from_query(
+        "status=CONCLUDED&sort_by=last_modified&sort_order=asc",
+    )
+    .expect("valid list filters")
+    .into_inner();
+    assert_eq!(
+        filters.status.map(|statuses| statuses.0),
+        Some(vec![ExperimentStatusType::CONCLUDED])
+    );
+    assert!(matches!(
+        filters.sort_by,
+        Some(ExperimentSortOn::LastModified)
+    ));
+    assert!(matches!(filters.sort_order, Some(SortOrder::Asc)));
+
+    let filters = Query::<ListFilters>::from_query("").expect("empty list filters");
+    assert!(filters.sort_by.is_none() && filters.sort_order.is_none());
+    assert!(filters.include_archived.is_none());
+
+    let filters = Query::<ListFilters>::from_query("include_archived=true")
+        .expect("list filters including archived experiments");
+    assert_eq!(filters.include_archived, Some(true));
+
+    assert!(Query::<ListFilters>::from_query("sort_by=traffic_percentage").is_err());
+    assert!(Query::<ListFilters>::from_query("sort_order=sideways").is_err());
+}
+
+#[test]
+fn test_listing_modified_window() {
+    let now = "2024-04-10T09:00:00Z"
+        .parse::<chrono::DateTime<Utc>>()
+        .unwrap();
+    let window = |query: &str| {
+        let filters = Query::<ListFilters>::from_query(query)
+            .expect("valid list filters")
+            .into_inner();
+        helpers::listing_modified_window(&filters, now)
+    };
+
+    // the last day by default
+    assert_eq!(window(""), (Some(now - ChronoDuration::hours(24)), now));
+    // syncs reach back to their last sync, however long ago
+    let filters = Query::<ListFilters>::from_query("modified_since=2024-01-01T00:00:00Z")
+        .expect("list filters modified since a timestamp")
+        .into_inner();
+    assert_eq!(
+        filters.modified_since,
+        Some("2024-01-01T00:00:00Z".parse().unwrap())
+    );
+    assert_eq!(window("modified_since=2024-01-01T00:00:00Z"), (None, now));
+    let from_date = "2024-04-01T00:00:00Z".parse().unwrap();
+    assert_eq!(
+        window("modified_since=2024-01-01T00:00:00Z&from_date=2024-04-01T00:00:00Z"),
+        (Some(from_date), now)
+    );
+    assert!(Query::<ListFilters>::from_query("modified_since=yesterday").is_err());
+}
+
+#[test]
+fn test_list_filters_absent_status() {
+    let filters = Query::<ListFilters>::from_query("").expect("absent status");
+    assert!(filters.status.is_none());
+
+    let filters = Query::<ListFilters>::from_query("status=CREATED,INPROGRESS")
+        .expect("status list")
+        .into_inner();
+    assert_eq!(
+        filters.status.map(|statuses| statuses.0),
+        Some(vec![
+            ExperimentStatusType::CREATED,
+            ExperimentStatusType::INPROGRESS
+        ])
+    );
+
+    assert!(Query::<ListFilters>::from_query("status=UNKNOWN").is_err());
+}
+
+#[test]
+fn test_list_filters_status_whitespace_and_trailing_comma() {
+    let expected = Some(vec![
+        ExperimentStatusType::CREATED,
+        ExperimentStatusType::INPROGRESS,
+    ]);
+    for query in [
+        "status=CREATED,%20INPROGRESS",
+        "status=%20CREATED%20,INPROGRESS%20",
+        "status=CREATED,INPROGRESS,",
+        "status=,CREATED,%20,INPROGRESS,%20",
+        "status=CREATED,,INPROGRESS",
+    ] {
+        let filters = Query::<ListFilters>::from_query(query)
+            .unwrap_or_else(|err| panic!("{query} should parse: {err}"))
+            .into_inner();
+        assert_eq!(
+            filters.status.map(|statuses| statuses.0),
+            expected,
+            "{query}"
+        );
+    }
+
+    // a blank status is an empty list, which matches every status
+    let filters = Query::<ListFilters>::from_query("status=")
+        .expect("blank status")
+        .into_inner();
+    assert_eq!(filters.status.map(|statuses| statuses.0), Some(vec![]));
+}
+
+#[test]
+fn test_validate_global_context() {
+    let context = single_dimension_ctx_gen(Dimensions::OS("os1".to_string()));
+    assert!(helpers::validate_global_context(&context, false).is_ok());
+
+    assert!(matches!(
+        helpers::validate_global_context(&json!({}), false),
+        Err(AppError::BadArgument(_))
+    ));
+    assert!(helpers::validate_global_context(&json!({}), true).is_ok());
+}
+
+#[test]
+fn test_clone_variants() {
+    let experiment_id = "7165430744645431296";
+    let mut variants = weighted_variants_gen(&[30, 20]);
+    for variant in variants.iter_mut() {
+        variant.id = format!("{experiment_id}-{}", variant.id);
+        variant.context_id = Some(format!("ctx-{}", variant.id));
+        variant.override_id = Some(format!("ovr-{}", variant.id));
+        variant.description = Some(String::from("description"));
+    }
+
+    let cloned_variants = helpers::clone_variants(experiment_id, variants);
+    let cloned_ids = cloned_variants
+        .iter()
+        .map(|variant| variant.id.as_str())
+        .collect::<Vec<&str>>();
+    assert_eq!(cloned_ids, vec!["variant-0", "variant-1", "variant-2"]);
+    for variant in cloned_variants.iter() {
+        assert!(variant.context_id.is_none());
+        assert!(variant.override_id.is_none());
+        assert_eq!(variant.description.as_deref(), Some("description"));
+    }
+    assert_eq!(cloned_variants[1].traffic_percentage, Some(30));
+    assert_eq!(cloned_variants[1].variant_type, VariantType::EXPERIMENTAL);
+}
+
+#[test]
+fn test_experiment_import_request() {
+    let mut variants = variants_gen(2);
+    for variant in variants.iter_mut() {
+        variant.overrides = Map::from_iter([(String::from("color"), json!("red"))]);
+        variant.context_id = Some(format!("ctx-{}", variant.id));
+        variant.override_id = Some(format!("ovr-{}", variant.id));
+    }
+    let export = json!({
+        "name": "exported",
+        "context": single_dimension_ctx_gen(Dimensions::OS(String::from("ios"))),
+        "override_keys": ["color"],
+        "traffic_percentage": 10,
+        "variants": variants,
+    });
+
+    let request = helpers::experiment_import_request(
+        serde_json::from_value::<ExperimentExport>(export.clone()).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(request.name, "exported");
+    assert_eq!(request.traffic_percentage, Some(10));
+    assert!(!request.allow_global);
+    assert!(request.starts_at.is_none() && request.ends_at.is_none());
+    for variant in request.variants.iter() {
+        assert!(variant.context_id.is_none());
+        assert!(variant.override_id.is_none());
+    }
+
+    let mut mismatched = export;
+    mismatched["override_keys"] = json!(["color", "size"]);
+    assert!(helpers::experiment_import_request(
+        serde_json::from_value::<ExperimentExport>(mismatched).unwrap()
+    )
+    .is_err());
+}
+
+#[test]
+fn test_assign_created_contexts() {
+    // a CAC bulk operations response, as returned for the PUT of every variant
+    let cac_response = |count: usize| -> Vec<ContextPutResp> {
+        serde_json::from_value(json!((0..count)
+            .map(|idx| json!({
+                "context_id": format!("context-{idx}"),
+                "override_id": format!("override-{idx}"),
+                "priority": 1
+            }))
+            .collect::<Vec<Value>>()))
+        .unwrap()
+    };
+
+    let mut variants = variants_gen(2);
+    helpers::assign_created_contexts(&mut variants, &cac_response(2)).unwrap();
+    assert_eq!(variants[1].context_id.as_deref(), Some("context-1"));
+    assert_eq!(variants[1].override_id.as_deref(), Some("override-1"));
+
+    for wrong_count in [1, 3] {
+        let mut variants = variants_gen(2);
+        assert!(helpers::assign_created_contexts(
+            &mut variants,
+            &cac_response(wrong_count)
+        )
+        .is_err());
+        assert!(variants.iter().all(|variant| variant.context_id.is_none()));
+    }
+}
+
+#[test]
+fn test_variant_context_mapping_from_variants() {
+    let mut variants = variants_gen(2);
+    variants[0].context_id = Some(String::from("context-0"));
+    variants[0].override_id = Some(String::from("override-0"));
+
+    let mappings = VariantContextMapping::from_variants(&json!(variants));
+    assert_eq!(mappings.len(), 2);
+    assert_eq!(mappings[0].variant_id, "variant-0");
+    assert_eq!(mappings[0].context_id.as_deref(), Some("context-0"));
+    assert_eq!(mappings[0].override_id.as_deref(), Some("override-0"));
+    assert!(mappings[1].context_id.is_none());
+
+    assert!(VariantContextMapping::from_variants(&json!("not variants")).is_empty());
+}
+
+#[test]
+fn test_reconcile_variant() {
+    let mut variant = variants_gen(1).remove(0);
+    variant
+        .overrides
+        .insert(String::from("key1"), json!("value1"));
+    let cac_context = ContextGetResp {
+        id: String::from("context-0"),
+        override_id: String::from("override-0"),
+        override_: json!({"key1": "value1"}),
+    };
+    let status = |variant: &Variant, cac_context: Option<&ContextGetResp>| {
+        helpers::reconcile_variant(variant, cac_context).status
+    };
+
+    assert_eq!(
+        status(&variant, Some(&cac_context)),
+        ReconciliationStatus::MissingContextId
+    );
+
+    variant.context_id = Some(String::from("context-0"));
+    assert_eq!(
+        status(&variant, None),
+        ReconciliationStatus::ContextNotFound
+    );
+    assert_eq!(
+        status(&variant, Some(&cac_context)),
+        ReconciliationStatus::OverrideIdMismatch
+    );
+
+    variant.override_id = Some(String::from("override-0"));
+    assert_eq!(
+        status(&variant, Some(&cac_context)),
+        ReconciliationStatus::InSync
+    );
+
+    variant
+        .overrides
+        .insert(String::from("key1"), json!("value2"));
+    assert_eq!(
+        status(&variant, Some(&cac_context)),
+        ReconciliationStatus::OverridesMismatch
+    );
+}
+
+#[test]
+fn test_prefix_bulk_error() {
+    let mut field_errors = FieldErrors::new();
+    field_errors.insert(
+        "traffic_percentage".to_string(),
+        "too much traffic".to_string(),
+    );
+    match helpers::prefix_bulk_error(2, AppError::InvalidFields(field_errors)) {
+        AppError::InvalidFields(field_errors) => assert_eq!(
+            field_errors.into_iter().collect::<Vec<(String, String)>>(),
+            vec![(
+                "[2].traffic_percentage".to_string(),
+                "too much traffic".to_string()
+            )]
+        ),
+        err => panic!("expected invalid fields, got {err:?}"),
+    }
+    match helpers::prefix_bulk_error(1, AppError::BadArgument("no name".to_string())) {
+        AppError::BadArgument(msg) => assert_eq!(msg, "experiment at index 1: no name"),
+        err => panic!("expected a bad argument, got {err:?}"),
+    }
+}
+
+#[test]
+fn test_validate_metric_observations() {
+    let variants = variants_gen(2);
+    let observation =
+        |variant_id: &str, metric_name: &str, value: f64| MetricObservation {
+            variant_id: variant_id.to_string(),
+            metric_name: metric_name.to_string(),
+            value,
+        };
+
+    let valid = vec![
+        observation("variant-0", "conversion", 1.0),
+        observation("variant-1", "conversion", 0.0),
+        observation("variant-1", "latency_ms", 120.5),
+    ];
+    assert!(helpers::validate_metric_observations(&variants, &valid).is_ok());
+
+    let unknown_variant = vec![
+        observation("variant-0", "conversion", 1.0),
+        observation("variant-7", "conversion", 1.0),
+        observation("variant-7", "latency_ms", 80.0),
+    ];
+    match helpers::validate_metric_observations(&variants, &unknown_variant) {
+        Err(AppError::BadArgument(msg)) => assert!(msg.ends_with(": variant-7")),
+        other => panic!("expected a bad argument error, got {other:?}"),
+    }
+
+    for invalid in [
+        vec![],
+        vec![observation("variant-0", " ", 1.0)],
+        vec![observation("variant-0", "conversion", f64::NAN)],
+    ] {
+        assert!(matches!(
+            helpers::validate_metric_observations(&variants, &invalid),
+            Err(AppError::BadArgument(_))
+        ));
+    }
+}
+
+fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+    assert!(
+        (actual - expected).abs() < tolerance,
+        "expected {expected}, got {actual}"
+    );
+}
+
+#[test]
+fn test_student_t_cdf() {
+    // two sided p-value of t = 2 with 10 degrees of freedom
+    assert_close(
+        2.0 * (1.0 - statistics::student_t_cdf(2.0, 10.0)),
+        0.0733880347707403,
+        1e-9,
+    );
+    // 97.5th percentile of t with 10 degrees of freedom
+    assert_close(statistics::student_t_cdf(2.228138851986, 10.0), 0.975, 1e-9);
+    assert_close(statistics::student_t_cdf(0.0, 5.0), 0.5, 1e-12);
+    assert_close(statistics::normal_cdf(1.959963984540054), 0.975, 1e-7);
+}
+
+#[test]
+fn test_two_proportion_z_test() {
+    // 100 of 1000 converting on control against 130 of 1000 on the treatment
+    let control = MetricSample {
+        count: 1000,
+        mean: 0.10,
+        variance: 0.0,
+    };
+    let treatment = MetricSample {
+        count: 1000,
+        mean: 0.13,
+        variance: 0.0,
+    };
+    let result = statistics::two_proportion_z_test(&control, &treatment)
+        .expect("enough data to compare");
+    assert_eq!(result.test, SignificanceTest::TwoProportionZTest);
+    assert_close(result.statistic, 2.10274060562211, 1e-9);
+    assert_close(result.p_value, 0.0354884504664748, 1e-6);
+    assert_close(result.confidence_interval[0], 0.00206793443937637, 1e-9);
+    assert_close(result.confidence_interval[1], 0.0579320655606236, 1e-9);
+    assert!(result.significant);
+
+    // nothing converted anywhere, there is no variance to test against
+    let none_converted = MetricSample {
+        count: 1000,
+        mean: 0.0,
+        variance: 0.0,
+    };
+    assert!(
+        statistics::two_proportion_z_test(&none_converted, &none_converted).is_none()
+    );
+}
+
+#[test]
+fn test_welch_t_test() {
+    let control = MetricSample {
+        count: 30,
+        mean: 10.0,
+        variance: 4.0,
+    };
+    let treatment = MetricSample {
+        count: 25,
+        mean: 11.5,
+        variance: 9.0,
+    };
+    let result =
+        statistics::welch_t_test(&control, &treatment).expect("enough data to compare");
+    assert_eq!(result.test, SignificanceTest::WelchTTest);
+    assert_close(result.statistic, 2.13560549044312, 1e-9);
+    assert_close(result.p_value, 0.0388119716663102, 1e-9);
+    assert_close(result.confidence_interval[0], 0.0809621553833393, 1e-8);
+    assert_close(result.confidence_interval[1], 2.91903784461666, 1e-8);
+    assert!(result.significant);
+
+    let single_observation = MetricSample {
+        count: 1,
+        mean: 10.0,
+        variance: 0.0,
+    };
+    assert!(statistics::welch_t_test(&single_observation, &treatment).is_none());
+}
+
+#[test]
+fn test_compare_with_control() {
+    let summary = |variant_id: &str, metric_name: &str, mean: f64, binary: bool| {
+        VariantMetricSummary {
+            variant_id: variant_id.to_string(),
+            metric_name: metric_name.to_string(),
+            count: 1000,
+            mean,
+            variance: Some(if binary { mean * (1.0 - mean) } else { 4.0 }),
+            binary,
+        }
+    };
+    let summaries = vec![
+        summary("variant-0", "conversion", 0.10, true),
+        summary("variant-0", "latency_ms", 120.0, false),
+        summary("variant-1", "conversion", 0.13, true),
+        summary("variant-1", "latency_ms", 119.9, false),
+        summary("variant-1", "revenue", 12.0, false),
+    ];
+
+    let comparisons = statistics::compare_with_control("variant-0", &summaries);
+    // revenue has no control observations to compare against
+    assert_eq!(comparisons.len(), 2);
+    assert!(comparisons
+        .iter()
+        .all(|comparison| comparison.variant_id == "variant-1"
+            && comparison.control_variant_id == "variant-0"));
+
+    let test_of = |metric_name: &str| {
+        comparisons
+            .iter()
+            .find(|comparison| comparison.metric_name == metric_name)
+            .and_then(|comparison| comparison.result.as_ref())
+            .map(|result| (result.test, result.significant))
+    };
+    assert_eq!(
+        test_of("conversion"),
+        Some((SignificanceTest::TwoProportionZTest, true))
+    );
+    assert_eq!(
+        test_of("latency_ms"),
+        Some((SignificanceTest::WelchTTest, false))
+    );
+}
+
+#[test]
+fn test_validate_schedule() {
+    let now = Utc::now();
+    let later = now + ChronoDuration::hours(1);
+    let much_later = now + ChronoDuration::days(7);
+    let earlier = now - ChronoDuration::hours(1);
+
+    assert!(helpers::validate_schedule(None, None, now).is_ok());
+    assert!(helpers::validate_schedule(Some(later), None, now).is_ok());
+    assert!(helpers::validate_schedule(None, Some(later), now).is_ok());
+    assert!(helpers::validate_schedule(Some(later), Some(much_later), now).is_ok());
+
+    for (starts_at, ends_at) in [
+        (Some(earlier), None),
+        (Some(now), None),
+        (None, Some(earlier)),
+        (Some(much_later), Some(later)),
+        (Some(later), Some(later)),
+    ] {
+        assert!(matches!(
+            helpers::validate_schedule(starts_at, ends_at, now),
+            Err(AppError::BadArgument(_))
+        ));
+    }
+}
+
+#[test]
+fn test_conflicting_experiment_writes() {
+    let read_version = Utc::now() - ChronoDuration::minutes(5);
+    let is_conflict = |result: Result<(), AppError>| {
+        matches!(
+            result,
+            Err(AppError::ResponseError(err)) if err.status_code == StatusCode::CONFLICT
+        )
+    };
+
+    // both clients read the experiment at `read_version`, the first one to
+    // write moves last_modified ahead
+    let first_write = helpers::parse_experiment_precondition(
+        Some(&format!("\"{}\"", read_version.to_rfc3339())),
+        None,
+    )
+    .unwrap();
+    assert!(
+        helpers::check_experiment_precondition("7", read_version, first_write).is_ok()
+    );
+    let last_modified = read_version + ChronoDuration::seconds(3);
+
+    // the second write still carries the version it read
+    assert!(is_conflict(helpers::check_experiment_precondition(
+        "7",
+        last_modified,
+        first_write
+    )));
+    let since =
+        helpers::parse_experiment_precondition(None, Some(&read_version.to_rfc2822()))
+            .unwrap();
+    assert!(is_conflict(helpers::check_experiment_precondition(
+        "7",
+        last_modified,
+        since
+    )));
+
+    // re-reading the experiment before writing succeeds, as do writes without
+    // a precondition
+    let reread = helpers::parse_experiment_precondition(
+        Some(&last_modified.to_rfc3339()),
+        Some(&read_version.to_rfc2822()),
+    )
+    .unwrap();
+    assert!(helpers::check_experiment_precondition("7", last_modified, reread).is_ok());
+    assert!(helpers::check_experiment_precondition("7", last_modified, None).is_ok());
+
+    assert!(matches!(
+        helpers::parse_experiment_precondition(Some("yesterday"), None),
+        Err(AppError::BadArgument(_))
+    ));
+    assert!(matches!(
+        helpers::parse_experiment_precondition(None, Some("yesterday")),
+        Err(AppError::BadArgument(_))
+    ));
+}
+
+#[test]
+fn test_diff_overrides() {
+    let to_map = |value: Value| value.as_object().unwrap().clone();
+    let control = to_map(json!({"pmTestKey1": "value1", "pmTestKey2": 2}));
+    let variant =
+        to_map(json!({"pmTestKey1": "value1", "pmTestKey2": 3, "pmTestKey3": true}));
+    let override_keys = vec!["pmTestKey1".to_string(), "pmTestKey4".to_string()];
+
+    let key_diff = |key: &str, before: Option<Value>, after: Option<Value>| KeyDiff {
+        key: key.to_string(),
+        changed: before != after,
+        before,
+        after,
+    };
+    assert_eq!(
+        helpers::diff_overrides(&override_keys, &control, &variant),
+        vec![
+            key_diff("pmTestKey1", Some(json!("value1")), Some(json!("value1"))),
+            key_diff("pmTestKey2", Some(json!(2)), Some(json!(3))),
+            key_diff("pmTestKey3", None, Some(json!(true))),
+            key_diff("pmTestKey4", None, None),
+        ]
+    );
+    assert!(helpers::diff_overrides(&override_keys, &control, &control)
+        .iter()
+        .all(|diff| !diff.changed));
+}
+
+#[test]
+fn test_merge_variant_overrides() {
+    let to_map = |value: Value| value.as_object().unwrap().clone();
+    let mut variants = variants_gen(2);
+    variants[0].overrides = to_map(json!({"pmTestKey1": "value1", "pmTestKey2": 1}));
+    variants[1].overrides = to_map(json!({"pmTestKey1": "value2", "pmTestKey2": 2}));
+    let patch = |id: &str, overrides: Value| VariantUpdateRequest {
+        id: id.to_string(),
+        overrides: to_map(overrides),
+    };
+
+    let merged = helpers::merge_variant_overrides(
+        &variants,
+        vec![patch(
+            "variant-1",
+            json!({"pmTestKey2": 3, "pmTestKey1": null, "pmTestKey3": true}),
+        )],
+    )
+    .unwrap();
+    let merged = merged
+        .into_iter()
+        .map(|variant| (variant.id, Value::Object(variant.overrides)))
+        .collect::<Vec<(String, Value)>>();
+    assert_eq!(
+        merged,
+        vec![
+            (
+                "variant-0".to_string(),
+                json!({"pmTestKey1": "value1", "pmTestKey2": 1})
+            ),
+            (
+                "variant-1".to_string(),
+                json!({"pmTestKey2": 3, "pmTestKey3": true})
+            ),
+        ]
+    );
+
+    for patches in [
+        vec![],
+        vec![patch("variant-7", json!({"pmTestKey1": "value3"}))],
+        vec![
+            patch("variant-1", json!({"pmTestKey1": "value3"})),
+            patch("variant-1", json!({"pmTestKey2": 4})),
+        ],
+    ] {
+        assert!(matches!(
+            helpers::merge_variant_overrides(&variants, patches),
+            Err(AppError::BadArgument(_))
+        ));
+    }
+}
+
+#[test]
+fn test_create_request_schema() {
+    let schema = ExperimentCreateRequest::json_schema();
+    let validator = JSONSchema::compile(&schema).unwrap();
+    let valid_request = json!({
+        "name": "experiment-test",
+        "context": {"==": [{"var": "os"}, "ios"]},
+        "traffic_percentage": 10,
+        "starts_at": "2024-04-10T09:00:00Z",
+        "variants": [
+            {"id": "control", "variant_type": "CONTROL", "overrides": {"pmTestKey1": "value1"}},
+            {"id": "test", "variant_type": "EXPERIMENTAL", "overrides": {"pmTestKey1": "value2"}}
+        ]
+    });
+    assert!(validator.is_valid(&valid_request));
+    assert!(
+        serde_json::from_value::<ExperimentCreateRequest>(valid_request.clone()).is_ok()
+    );
+
+    // payloads the schema rejects do not deserialize either
+    let with = |key: &str, value: Value| {
+        let mut request = valid_request.clone();
+        request[key] = value;
+        request
+    };
+    let mut without_name = valid_request.clone();
+    without_name.as_object_mut().unwrap().remove("name");
+    for invalid_request in [
+        without_name,
+        with(
+            "variants",
+            json!([{"id": "control", "variant_type": "BASELINE", "overrides": {}}]),
+        ),
+        with("traffic_percentage", json!("10")),
+        with("starts_at", json!("tomorrow")),
+    ] {
+        assert!(!validator.is_valid(&invalid_request));
+        assert!(
+            serde_json::from_value::<ExperimentCreateRequest>(invalid_request).is_err()
+        );
+    }
+}
+
+#[test]
+fn test_tenant_migrations_are_embedded() {
+    let migrations_dir =
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+    let migration_count = std::fs::read_dir(migrations_dir)
+        .unwrap()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name() != "00000000000000_diesel_initial_setup")
+        .count();
+    assert_eq!(
+        experimentation_platform::db::TENANT_MIGRATIONS.len(),
+        migration_count
+    );
+}
+
+#[test]
+fn test_validate_variant_count() {
+    assert!(helpers::validate_variant_count(&variants_gen(3), 3).is_ok());
+    assert!(helpers::validate_variant_count(&variants_gen(2), 10).is_ok());
+    match helpers::validate_variant_count(&variants_gen(4), 3) {
+        Err(AppError::BadArgument(message)) => {
+            assert!(message.contains("at most 3 variants"), "{message}");
+            assert!(message.contains("provided 4"), "{message}");
+        }
+        other => panic!("expected a bad argument, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_flag_experiment_variants() {
+    let flag_request = |extra: Value| {
+        let mut request = json!({
+            "name": "dark-mode",
+            "context": {"==": [{"var": "os"}, "ios"]},
+            "experiment_type": "FLAG",
+            "flag_key": "darkMode"
+        });
+        request
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        serde_json::from_value::<ExperimentCreateRequest>(request).unwrap()
+    };
+
+    let mut request = flag_request(json!({}));
+    helpers::apply_flag_variants(&mut request).unwrap();
+    let overrides = request
+        .variants
+        .iter()
+        .map(|variant| (variant.variant_type.clone(), json!(variant.overrides)))
+        .collect::<Vec<(VariantType, Value)>>();
+    assert_eq!(
+        overrides,
+        vec![
+            (VariantType::CONTROL, json!({"darkMode": false})),
+            (VariantType::EXPERIMENTAL, json!({"darkMode": true})),
+        ]
+    );
+    assert!(
+        helpers::check_variant_types(&request.variants, ExperimentType::FLAG).is_ok()
+    );
+
+    // a flag key without variants is the whole payload, and the other way round
+    for invalid_request in [
+        flag_request(json!({"flag_key": null})),
+        flag_request(json!({"variants": variants_gen(2)})),
+        flag_request(json!({"experiment_type": "DEFAULT", "variants": variants_gen(2)})),
+    ] {
+        let mut invalid_request = invalid_request;
+        assert!(matches!(
+            helpers::apply_flag_variants(&mut invalid_request),
+            Err(AppError::BadArgument(_))
+        ));
+    }
+
+    // updated overrides have to keep the shape of a flag
+    let mut variants = request.variants.clone();
+    variants[1]
+        .overrides
+        .insert("darkMode".to_string(), json!("on"));
+    assert!(helpers::check_variant_types(&variants, ExperimentType::FLAG).is_err());
+    assert!(helpers::check_variant_types(&variants, ExperimentType::DEFAULT).is_ok());
+    assert!(
+        helpers::check_variant_types(&variants_gen(3), ExperimentType::FLAG).is_err()
+    );
+}
+
+#[test]
+fn test_created_contexts_of() {
+    let put = || {
+        ContextAction::PUT(ContextPutReq {
+            context: Map::new(),
+            r#override: json!({}),
+        })
+    };
+    let created = |context_id: &str| {
+        ContextBulkResponse::PUT(ContextPutResp {
+            context_id: context_id.to_string(),
+            override_id: format!("{context_id}-override"),
+            priority: 1,
+        })
+    };
+
+    // replacing variant contexts deletes the old ones in the same batch
+    let operations = vec![
+        ContextAction::DELETE("old-1".to_string()),
+        ContextAction::DELETE("old-2".to_string()),
+        put(),
+        put(),
+    ];
+    let responses = vec![
+        ContextBulkResponse::DELETE("old-1".to_string()),
+        ContextBulkResponse::DELETE("old-2".to_string()),
+        created("new-1"),
+        created("new-2"),
+    ];
+    let created_contexts = helpers::created_contexts_of(&operations, responses)
+        .unwrap()
+        .into_iter()
+        .map(|created_context| created_context.context_id)
+        .collect::<Vec<String>>();
+    assert_eq!(created_contexts, vec!["new-1", "new-2"]);
+
+    // responses not answering their operation are not taken for created contexts
+    for responses in [
+        vec![
+            created("new-1"),
+            ContextBulkResponse::DELETE("old-2".to_string()),
+            created("new-2"),
+            ContextBulkResponse::DELETE("old-1".to_string()),
+        ],
+        vec![created("new-1"), created("new-2")],
+    ] {
+        assert!(matches!(
+            helpers::created_contexts_of(&operations, responses),
+            Err(AppError::UnexpectedError(_))
+        ));
+    }
+}
+
+// state of a server whose CAC is at `cac_host`
+fn test_app_state(cac_host: &str, database_url: &str) -> AppState {
+    let empty_schema = || JSONSchema::compile(&json!({})).unwrap();
+    AppState {
+        cac_host: Url::parse(cac_host).unwrap(),
+        app_env: AppEnv::TEST,
+        tenants: Arc::new(RwLock::new(HashSet::from(["test".to_string()]))),
+        cac_version: "test".to_string(),
+        db_pool: PgSchemaManager::new(
+            database_url.to_string(),
+            1,
+            Duration::from_secs(1),
+        ),
+        default_config_validation_schema: empty_schema(),
+        meta_schema: empty_schema(),
+        experimentation_flags: ExperimentationFlags {
+            allow_same_keys_overlapping_ctx: true,
+            allow_diff_keys_overlapping_ctx: true,
+            allow_same_keys_non_overlapping_ctx: true,
+        },
+        id_generator: new_id_generator(IdGeneratorKind::Snowflake),
+        http_client: reqwest::Client::builder()
+            .timeout(Duration::from_secs(1))
+            .build()
+            .unwrap(),
+        cac_retry_policy: RetryPolicy::new(1, Duration::ZERO),
+        metrics: Arc::new(Metrics::default()),
+        experiment_create_rate_limiter: Arc::new(RateLimiter::new(60, HashMap::new())),
+        variant_dimension_tenants: Arc::new(Mutex::new(HashSet::new())),
+        webhooks: Arc::new(Webhooks::new(None, HashMap::new(), Duration::from_secs(1))),
+        config_change_notifier: Arc::new(ConfigChangeNotifier::new(1)),
+        resolve_cache: Arc::new(ResolveCache::new(0, Duration::ZERO)),
+        max_page_size: 100,
+        default_traffic_percentage: 0,
+        max_variants_per_experiment: 10,
+        admin_token: Arc::new(AdminToken::new(None)),
+        enable_tenant_and_scope: false,
+        tenant_middleware_exclusion_list: HashSet::new(),
+        service_prefix: String::new(),
+    }
+}
+
+#[actix_web::test]
+#[ignore = "needs a database, run with DATABASE_URL set and --ignored"]
+async fn test_conclude_waits_for_concurrent_changes_before_calling_cac() {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL is not set");
+    let schema = "exp_test_conclude_lock";
+    let pool = Pool::builder()
+        .max_size(2)
+        .build(ConnectionManager::<PgConnection>::new(&database_url))
+        .expect("failed to connect to DATABASE_URL");
+    let mut setup_conn = pool.get().unwrap();
+    setup_conn
+        .batch_execute(&format!(
+            "DROP SCHEMA IF EXISTS {schema} CASCADE; CREATE SCHEMA {schema};"
+        ))
+        .unwrap();
+    for migration in TENANT_MIGRATIONS {
+        setup_conn
+            .batch_execute(&migration.replace("public", schema))
+            .expect("failed to run the experimentation migrations");
+    }
+    let variants = json!([
+        { "id": "7-control", "variant_type": "CONTROL", "context_id": "ctx-control",
+            "override_id": "o-control", "overrides": { "key": 1 } },
+        { "id": "7-test", "variant_type": "EXPERIMENTAL", "context_id": "ctx-test",
+            "override_id": "o-test", "overrides": { "key": 2 } }
+    ]);
+    setup_conn
+        .batch_execute(&format!(
+            "CREATE TABLE {schema}.event_log_default PARTITION OF {schema}.event_log DEFAULT;
+            SET search_path TO {schema};
+            INSERT INTO experiments (id, created_at, created_by, last_modified, name,
+                override_keys, status, traffic_percentage, context, variants, last_modified_by)
+            VALUES ('7', now(), 'test', now(), 'locked', ARRAY['key'], 'INPROGRESS', 10,
+                '{{\"==\": [{{\"var\": \"os\"}}, \"ios\"]}}', '{variants}', 'test');"
+        ))
+        .unwrap();
+    let read_version = |conn: &mut PgConnection| {
+        experiments::experiments
+            .find("7")
+            .select(experiments::last_modified)
+            .first::<chrono::DateTime<Utc>>(conn)
+            .unwrap()
+    };
+    let version = read_version(&mut setup_conn);
+
+    // a CAC that never answers, so that any call to it shows up as a
+    // connection waiting on the listener
+    let cac = TcpListener::bind("127.0.0.1:0").unwrap();
+    cac.set_nonblocking(true).unwrap();
+    let state = Data::new(test_app_state(
+        &format!("http://{}", cac.local_addr().unwrap()),
+        &database_url,
+    ));
+
+    // another change holds the experiment while conclude starts
+    setup_conn
+        .batch_execute(
+            "BEGIN;
+            SELECT id FROM experiments WHERE id = '7' FOR UPDATE;
+            UPDATE experiments SET last_modified = now() + interval '1 second' WHERE id = '7';",
+        )
+        .unwrap();
+    let concurrent_change = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(300));
+        setup_conn.batch_execute("COMMIT").unwrap();
+        setup_conn
+    });
+
+    let mut conclude_conn = pool.get().unwrap();
+    conclude_conn
+        .batch_execute(&format!("SET search_path TO {schema};"))
+        .unwrap();
+    let concluded = conclude(
+        state,
+        "7".to_string(),
+        ConcludeExperimentRequest {
+            chosen_variant: "7-test".to_string(),
+        },
+        Some(ExperimentPrecondition::Version(version)),
+        conclude_conn,
+        Tenant("test".to_string()),
+        User::default(),
+    )
+    .await;
+    let mut setup_conn = concurrent_change.join().unwrap();
+
+    // the conflict is found before calling CAC, so its contexts are untouched
+    assert!(matches!(
+        concluded,
+        Err(AppError::ResponseError(ResponseError { status_code, .. }))
+            if status_code == StatusCode::CONFLICT
+    ));
+    assert!(matches!(
+        cac.accept(),
+        Err(err) if err.kind() == std::io::ErrorKind::WouldBlock
+    ));
+    assert_ne!(read_version(&mut setup_conn), version);
+    setup_conn
+        .batch_execute(&format!("DROP SCHEMA {schema} CASCADE;"))
+        .unwrap();
+}
@@ -42,6 +42,9 @@ where
                         ExperimentStatusType::CONCLUDED => {
                             "badge text-white ml-3 mb-1 badge-xl badge-success"
                         }
+                        ExperimentStatusType::PAUSED => {
+                            "badge text-white ml-3 mb-1 badge-xl badge-neutral"
+                        }
                     };
                     view! {
                         <h1 class="text-2xl pt-4 font-extrabold">
@@ -119,6 +122,7 @@ where
                                 }
                                     .into_view()
                             }
+                            ExperimentStatusType::PAUSED => ().into_view(),
                         }
                     }
                 }
@@ -34,6 +34,22 @@ pub fn gen_variant_table(
             }
         }
     }
-    let rows = row_map.into_values().collect();
+    let mut rows: Vec<Map<String, Value>> = Vec::new();
+    if variants.iter().any(|variant| variant.description.is_some()) {
+        let mut description_row = Map::new();
+        description_row.insert("Config Key".into(), Value::String("Description".into()));
+        for (i, variant) in variants.iter().enumerate() {
+            let name = match variant.variant_type {
+                VariantType::CONTROL => format!("{}", variant.variant_type),
+                VariantType::EXPERIMENTAL => format!("Variant-{}", i),
+            };
+            description_row.insert(
+                name,
+                Value::String(variant.description.clone().unwrap_or_default()),
+            );
+        }
+        rows.push(description_row);
+    }
+    rows.extend(row_map.into_values());
     Ok((rows, columns))
 }
@@ -1,4 +1,4 @@
-use super::utils::{create_experiment, update_experiment};
+use super::utils::{create_experiment, update_context, update_experiment};
 use crate::components::button::button::Button;
 use crate::components::context_form::context_form::ContextForm;
 use crate::components::variant_form::variant_form::VariantForm;
@@ -17,6 +17,7 @@ fn default_variants_for_form() -> Vec<(String, Variant)> {
                 context_id: None,
                 override_id: None,
                 overrides: Map::new(),
+                description: None,
             },
         ),
         (
@@ -27,6 +28,7 @@ fn default_variants_for_form() -> Vec<(String, Variant)> {
                 context_id: None,
                 override_id: None,
                 overrides: Map::new(),
+                description: None,
             },
         ),
     ]
@@ -64,8 +66,10 @@ where
     let tenant_rs = use_context::<ReadSignal<String>>().unwrap();
 
     let (experiment_name, set_experiment_name) = create_signal(name);
+    let initial_context = StoredValue::new(context.clone());
     let (f_context, set_context) = create_signal(context.clone());
     let (f_variants, set_variants) = create_signal(init_variants);
+    let (error_message, set_error_message) = create_signal(String::new());
 
     let handle_context_form_change = move |updated_ctx: Vec<(String, String, String)>| {
         set_context.set_untracked(updated_ctx);
@@ -91,6 +95,7 @@ where
         let tenant = tenant_rs.get();
         let experiment_id = id.clone();
         let handle_submit_clone = handle_submit.clone();
+        let context_changed = f_context != initial_context.get_value();
 
         logging::log!("{:?}", f_experiment_name);
         logging::log!("{:?}", f_context);
@@ -98,7 +103,26 @@ where
         spawn_local({
             async move {
                 let result = if edit {
-                    update_experiment(experiment_id, f_variants, tenant).await
+                    // experiments are only editable while CREATED, when the
+                    // targeting can still change
+                    let context_result = if context_changed {
+                        update_context(
+                            experiment_id.clone(),
+                            f_context,
+                            tenant.clone(),
+                            dimensions.get_value(),
+                        )
+                        .await
+                        .map(|_| ())
+                    } else {
+                        Ok(())
+                    };
+                    match context_result {
+                        Ok(()) => {
+                            update_experiment(experiment_id, f_variants, tenant).await
+                        }
+                        Err(e) => Err(e),
+                    }
                 } else {
                     create_experiment(
                         f_context,
@@ -114,9 +138,8 @@ where
                     Ok(_) => {
                         handle_submit_clone();
                     }
-                    Err(_) => {
-                        // Handle error
-                        // We can consider logging or displaying the error
+                    Err(e) => {
+                        set_error_message.set(e);
                     }
                 }
             }
@@ -152,7 +175,6 @@ where
                             context=context
                             handle_change=handle_context_form_change
                             is_standalone=false
-                            disabled=edit
                             heading_sub_text=String::from(
                                 "Define rules under which this experiment would run",
                             )
@@ -179,6 +201,9 @@ where
             <div class="flex justify-end mt-8">
                 <Button text="Submit".to_string() on_click=on_submit/>
             </div>
+            <div>
+                <p class="text-red-500">{move || error_message.get()}</p>
+            </div>
         </div>
     }
 }
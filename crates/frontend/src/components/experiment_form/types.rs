@@ -20,3 +20,8 @@ pub struct VariantUpdateRequest {
 pub struct ExperimentUpdateRequest {
     pub variants: Vec<VariantUpdateRequest>,
 }
+
+#[derive(Serialize, Debug)]
+pub struct ExperimentContextUpdateRequest {
+    pub context: Value,
+}
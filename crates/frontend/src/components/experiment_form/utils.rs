@@ -1,16 +1,59 @@
 use super::types::{
-    ExperimentCreateRequest, ExperimentUpdateRequest, VariantUpdateRequest,
+    ExperimentContextUpdateRequest, ExperimentCreateRequest, ExperimentUpdateRequest,
+    VariantUpdateRequest,
 };
 use crate::components::context_form::utils::construct_context;
 use crate::types::{Dimension, Variant};
 use crate::utils::get_host;
 use reqwest::StatusCode;
-use serde_json::json;
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+
+/// Keys of `override_keys` that `overrides` does not override, sorted.
+pub fn get_missing_override_keys(
+    overrides: &Map<String, Value>,
+    override_keys: &HashSet<String>,
+) -> Vec<String> {
+    let mut missing_keys = override_keys
+        .iter()
+        .filter(|key| !overrides.contains_key(*key))
+        .map(String::from)
+        .collect::<Vec<String>>();
+    missing_keys.sort();
+    missing_keys
+}
+
+// mirrors check_variants_override_coverage on the backend, keys overridden by
+// any variant have to be overridden by all of them
+pub fn validate_variants_override_coverage(variants: &[Variant]) -> Result<(), String> {
+    let override_keys = variants
+        .iter()
+        .flat_map(|variant| variant.overrides.keys().cloned())
+        .collect::<HashSet<String>>();
+    let reasons = variants
+        .iter()
+        .filter_map(|variant| {
+            let missing_keys =
+                get_missing_override_keys(&variant.overrides, &override_keys);
+            (!missing_keys.is_empty()).then(|| {
+                format!("{} is missing [{}]", variant.id, missing_keys.join(", "))
+            })
+        })
+        .collect::<Vec<String>>();
+    if reasons.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "all variants should override the same keys: {}",
+        reasons.join("; ")
+    ))
+}
 
 pub fn validate_experiment(experiment: &ExperimentCreateRequest) -> Result<bool, String> {
     if experiment.name.is_empty() {
         return Err(String::from("experiment name should not be empty"));
     }
+    validate_variants_override_coverage(&experiment.variants)?;
     Ok(true)
 }
 
@@ -42,7 +85,10 @@ pub async fn create_experiment(
         .map_err(|e| e.to_string())?;
     match response.status() {
         StatusCode::OK => response.text().await.map_err(|e| e.to_string()),
-        StatusCode::BAD_REQUEST => Err("epxeriment data corrupt".to_string()),
+        StatusCode::BAD_REQUEST => Err(response
+            .text()
+            .await
+            .unwrap_or("experiment data is invalid, but the error could not be understood by the system. Contact an admin for help if this persists".to_string())),
         _ => Err("Internal Server Error".to_string()),
     }
 }
@@ -52,6 +98,8 @@ pub async fn update_experiment(
     variants: Vec<Variant>,
     tenant: String,
 ) -> Result<String, String> {
+    validate_variants_override_coverage(&variants)?;
+
     let payload = ExperimentUpdateRequest {
         variants: variants
             .into_iter()
@@ -69,7 +117,6 @@ pub async fn update_experiment(
     let response = client
         .put(url)
         .header("x-tenant", tenant)
-        .header("Authorization", "Bearer 12345678")
         .json(&request_payload)
         .send()
         .await
@@ -77,7 +124,44 @@ pub async fn update_experiment(
 
     match response.status() {
         StatusCode::OK => response.text().await.map_err(|e| e.to_string()),
-        StatusCode::BAD_REQUEST => Err("epxeriment data corrupt".to_string()),
+        StatusCode::BAD_REQUEST => Err(response
+            .text()
+            .await
+            .unwrap_or("experiment data is invalid, but the error could not be understood by the system. Contact an admin for help if this persists".to_string())),
+        _ => Err("Internal Server Error".to_string()),
+    }
+}
+
+// only allowed while the experiment is CREATED, the variants' contexts are
+// re-created with the new targeting
+pub async fn update_context(
+    experiment_id: String,
+    conditions: Vec<(String, String, String)>,
+    tenant: String,
+    dimensions: Vec<Dimension>,
+) -> Result<String, String> {
+    let payload = ExperimentContextUpdateRequest {
+        context: construct_context(conditions, dimensions),
+    };
+
+    let client = reqwest::Client::new();
+    let host = get_host();
+    let url = format!("{}/experiments/{}/context", host, experiment_id);
+    let request_payload = json!(payload);
+    let response = client
+        .put(url)
+        .header("x-tenant", tenant)
+        .json(&request_payload)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match response.status() {
+        StatusCode::OK => response.text().await.map_err(|e| e.to_string()),
+        StatusCode::BAD_REQUEST | StatusCode::CONFLICT => Err(response
+            .text()
+            .await
+            .unwrap_or("experiment context is invalid, but the error could not be understood by the system. Contact an admin for help if this persists".to_string())),
         _ => Err("Internal Server Error".to_string()),
     }
 }
@@ -7,6 +7,7 @@ use serde_json::{json, Map, Value};
 use crate::{
     components::{
         dropdown::dropdown::{Dropdown, DropdownBtnType, DropdownDirection},
+        experiment_form::utils::get_missing_override_keys,
         override_form::override_form::OverrideForm,
     },
     types::{DefaultConfig, Variant, VariantType},
@@ -124,6 +125,7 @@ where
                         VariantType::CONTROL => "Control".to_string(),
                         VariantType::EXPERIMENTAL => format!("Variant {idx}"),
                     };
+                    let variant_description = variant.description.clone().unwrap_or_default();
                     view! {
                         <div class="my-2 p-4 rounded bg-gray-50">
                             <div class="flex items-center justify-between">
@@ -179,7 +181,54 @@ where
                                     />
 
                                 </div>
+                                <div class="form-control">
+                                    <label class="label">
+                                        <span class="label-text">Description</span>
+                                    </label>
+                                </div>
+                                <div class="form-control w-2/5">
+                                    <input
+                                        name="variantDescription"
+                                        value=variant_description
+                                        type="text"
+                                        placeholder="What this variant changes"
+                                        class="input input-bordered w-full max-w-xs h-10"
+                                        on:input=move |event| {
+                                            let description = event_target_value(&event);
+                                            set_variants
+                                                .update(|current_variants: &mut Vec<(String, Variant)>| {
+                                                    if let Some((_, ref mut variant)) = current_variants
+                                                        .get_mut(idx)
+                                                    {
+                                                        variant.description = Some(description)
+                                                            .filter(|description| !description.is_empty());
+                                                    }
+                                                });
+                                        }
+                                    />
+
+                                </div>
                             </div>
+                            {move || {
+                                let missing_keys = f_variants
+                                    .with(|current_variants| {
+                                        current_variants
+                                            .get(idx)
+                                            .map(|(_, variant)| {
+                                                get_missing_override_keys(&variant.overrides, &override_keys.get())
+                                            })
+                                    })
+                                    .unwrap_or_default();
+                                (!missing_keys.is_empty())
+                                    .then(|| {
+                                        view! {
+                                            <p class="text-red-500 text-xs">
+                                                {format!("Missing overrides for: {}", missing_keys.join(", "))}
+                                            </p>
+                                        }
+                                    })
+                            }}
+
                             <div class="mt-2">
                                 <Show when=move || {
                                     is_control_variant && override_keys.get().len() == 0
@@ -269,11 +318,12 @@ where
                                     .push((
                                         key,
                                         Variant {
-                                            id: format!("variant-{}", total_variants),
+                                            id: format!("variant_{}", total_variants),
                                             variant_type: VariantType::EXPERIMENTAL,
                                             context_id: None,
                                             override_id: None,
                                             overrides: overrides,
+                                            description: None,
                                         },
                                     ))
                             });
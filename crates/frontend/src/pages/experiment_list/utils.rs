@@ -72,6 +72,7 @@ pub fn experiment_table_columns() -> Vec<Column> {
                     "CREATED" => "badge-info",
                     "INPROGRESS" => "badge-warning",
                     "CONCLUDED" => "badge-success",
+                    "PAUSED" => "badge-neutral",
                     &_ => "info",
                 };
                 let class = format!("badge {}", badge_color);
@@ -73,6 +73,7 @@ pub enum ExperimentStatusType {
     CREATED,
     CONCLUDED,
     INPROGRESS,
+    PAUSED,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -125,6 +126,8 @@ pub struct Variant {
     pub context_id: Option<String>,
     pub override_id: Option<String>,
     pub overrides: Map<String, Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 }
 
 pub type Variants = Vec<Variant>;
@@ -0,0 +1,192 @@
+use actix_web::{
+    http::header,
+    web::{Data, Json},
+    HttpRequest, HttpResponse,
+};
+use diesel::{ConnectionResult, PgConnection, QueryResult};
+use serde::Deserialize;
+use std::sync::{PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::db::admin_token::{load_admin_token, store_admin_token};
+use crate::helpers::authorize_admin;
+use crate::result::{self, AppError};
+use crate::service::types::AppState;
+
+// short tokens are easy to guess
+pub const MIN_ADMIN_TOKEN_LENGTH: usize = 16;
+// how long a server trusts the admin token it last read, rotations on other
+// servers take at most this long to reach it
+pub const ADMIN_TOKEN_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Bearer token of admin endpoints. `ADMIN_TOKEN` is the token until one is
+/// rotated, rotated tokens are stored in the database and shared by every
+/// server. Admin endpoints are disabled when `ADMIN_TOKEN` is not set.
+pub struct AdminToken {
+    initial: Option<String>,
+    // token as last read from the database, and when it was read
+    cached: RwLock<Option<(String, Instant)>>,
+}
+
+impl AdminToken {
+    pub fn new(initial: Option<String>) -> Self {
+        AdminToken {
+            initial,
+            cached: RwLock::new(None),
+        }
+    }
+
+    /// The current token, re-read from the database once the cached one is
+    /// older than [`ADMIN_TOKEN_CACHE_TTL`]. The last known token is kept when
+    /// the database cannot be reached.
+    pub fn current(
+        &self,
+        connect: impl FnOnce() -> ConnectionResult<PgConnection>,
+    ) -> Option<String> {
+        let initial = self.initial.as_ref()?;
+        let cached = self
+            .cached
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        if let Some((token, read_at)) = &cached {
+            if read_at.elapsed() < ADMIN_TOKEN_CACHE_TTL {
+                return Some(token.clone());
+            }
+        }
+        let stored = connect()
+            .map_err(|err| err.to_string())
+            .and_then(|mut conn| {
+                load_admin_token(&mut conn).map_err(|err| err.to_string())
+            });
+        match stored {
+            Ok(stored) => {
+                let token = stored.unwrap_or_else(|| initial.clone());
+                self.cache(token.clone());
+                Some(token)
+            }
+            Err(err) => {
+                log::error!(
+                    "failed to read the admin token, using the last known one: {err}"
+                );
+                Some(cached.map_or_else(|| initial.clone(), |(token, _)| token))
+            }
+        }
+    }
+
+    /// Stores `token` for every server, this one uses it right away.
+    pub fn rotate(&self, conn: &mut PgConnection, token: String) -> QueryResult<()> {
+        store_admin_token(conn, &token)?;
+        self.cache(token);
+        Ok(())
+    }
+
+    fn cache(&self, token: String) {
+        *self.cached.write().unwrap_or_else(PoisonError::into_inner) =
+            Some((token, Instant::now()));
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RotateAdminTokenRequest {
+    pub token: String,
+}
+
+/// Checks the `Authorization` header of a request to an admin endpoint
+/// against the current admin token.
+pub fn authorize_admin_request(
+    state: &AppState,
+    req: &HttpRequest,
+) -> result::Result<()> {
+    let authorization = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    authorize_admin(state.admin_token().as_deref(), authorization)
+}
+
+pub fn validate_admin_token(token: &str) -> result::Result<()> {
+    if token.trim() != token || token.len() < MIN_ADMIN_TOKEN_LENGTH {
+        return Err(AppError::BadArgument(format!(
+            "admin token should be at least {MIN_ADMIN_TOKEN_LENGTH} characters long, without leading or trailing whitespace"
+        )));
+    }
+    Ok(())
+}
+
+/// Replaces the admin token of every server, requests bearing the previous
+/// one are rejected from then on, on other servers once their cached token
+/// expires.
+pub async fn rotate_admin_token_handler(
+    req: HttpRequest,
+    state: Data<AppState>,
+    body: Json<RotateAdminTokenRequest>,
+) -> result::Result<HttpResponse> {
+    authorize_admin_request(&state, &req)?;
+    let token = body.into_inner().token;
+    validate_admin_token(&token)?;
+    let mut conn = state.db_pool.establish().map_err(|err| {
+        log::error!("failed to connect to the database to rotate the admin token: {err}");
+        AppError::UnexpectedError(anyhow::anyhow!(
+            "Something went wrong, failed to rotate the admin token"
+        ))
+    })?;
+    state.admin_token.rotate(&mut conn, token).map_err(|err| {
+        log::error!("failed to store the rotated admin token: {err}");
+        AppError::UnexpectedError(anyhow::anyhow!(
+            "Something went wrong, failed to rotate the admin token"
+        ))
+    })?;
+    log::info!("admin token rotated");
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_admin_token() {
+        assert!(validate_admin_token("0123456789abcdef").is_ok());
+        assert!(validate_admin_token("too-short").is_err());
+        assert!(validate_admin_token(" 0123456789abcdef").is_err());
+        assert!(validate_admin_token("0123456789abcdef\n").is_err());
+    }
+
+    #[test]
+    fn test_admin_token_without_a_database() {
+        let unreachable = || {
+            Err(diesel::ConnectionError::BadConnection(
+                "no database in tests".to_string(),
+            ))
+        };
+        // admin endpoints stay disabled without ADMIN_TOKEN, whatever is stored
+        let disabled = AdminToken::new(None);
+        assert_eq!(disabled.current(|| panic!("should not connect")), None);
+
+        let admin_token = AdminToken::new(Some("0123456789abcdef".to_string()));
+        assert_eq!(
+            admin_token.current(unreachable).as_deref(),
+            Some("0123456789abcdef")
+        );
+
+        // a token read within the TTL is used without connecting
+        admin_token.cache("fedcba9876543210".to_string());
+        assert_eq!(
+            admin_token
+                .current(|| panic!("should not connect"))
+                .as_deref(),
+            Some("fedcba9876543210")
+        );
+
+        // an expired one is still used when the database is unreachable
+        *admin_token.cached.write().unwrap() = Some((
+            "fedcba9876543210".to_string(),
+            Instant::now() - ADMIN_TOKEN_CACHE_TTL,
+        ));
+        assert_eq!(
+            admin_token.current(unreachable).as_deref(),
+            Some("fedcba9876543210")
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use tokio::sync::{futures::Notified, Notify};
+
+/// Wakes requests long-polling for config changes, keyed on the db namespace
+/// of the config. At most `max_waiters` requests wait at a time, across all
+/// namespaces.
+pub struct ConfigChangeNotifier {
+    namespaces: Mutex<HashMap<String, Arc<Notify>>>,
+    waiters: Arc<AtomicUsize>,
+    max_waiters: usize,
+}
+
+impl ConfigChangeNotifier {
+    pub fn new(max_waiters: usize) -> Self {
+        ConfigChangeNotifier {
+            namespaces: Mutex::new(HashMap::new()),
+            waiters: Arc::new(AtomicUsize::new(0)),
+            max_waiters,
+        }
+    }
+
+    fn namespace_notify(&self, namespace: &str) -> Arc<Notify> {
+        let mut namespaces = self
+            .namespaces
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        namespaces.entry(namespace.to_string()).or_default().clone()
+    }
+
+    /// Wakes every request waiting on a change to the config of `namespace`.
+    pub fn notify(&self, namespace: &str) {
+        self.namespace_notify(namespace).notify_waiters();
+    }
+
+    /// `None` when `max_waiters` requests are already waiting.
+    pub fn subscribe(&self, namespace: &str) -> Option<ConfigSubscription> {
+        self.waiters
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |waiters| {
+                (waiters < self.max_waiters).then_some(waiters + 1)
+            })
+            .ok()?;
+        Some(ConfigSubscription {
+            notify: self.namespace_notify(namespace),
+            waiters: self.waiters.clone(),
+        })
+    }
+
+    pub fn waiters(&self) -> usize {
+        self.waiters.load(Ordering::SeqCst)
+    }
+}
+
+/// A request waiting on config changes, it counts against the limit of
+/// waiters until dropped.
+pub struct ConfigSubscription {
+    notify: Arc<Notify>,
+    waiters: Arc<AtomicUsize>,
+}
+
+impl ConfigSubscription {
+    /// Resolves on the next change. Changes made after this call wake it even
+    /// before it is awaited, so checking the config version after calling it
+    /// does not race with changes.
+    pub fn next_change(&self) -> Pin<Box<Notified<'_>>> {
+        let mut notified = Box::pin(self.notify.notified());
+        notified.as_mut().enable();
+        notified
+    }
+}
+
+impl Drop for ConfigSubscription {
+    fn drop(&mut self) {
+        self.waiters.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::rt::time::timeout;
+    use std::time::Duration;
+
+    #[actix_web::test]
+    async fn test_config_change_notifier() {
+        let notifier = ConfigChangeNotifier::new(2);
+        let first = notifier.subscribe("dev_cac").unwrap();
+        let other_tenant = notifier.subscribe("test_cac").unwrap();
+        assert!(notifier.subscribe("dev_cac").is_none());
+
+        // a change made before the wait is awaited still wakes it
+        let change = first.next_change();
+        let unrelated = other_tenant.next_change();
+        notifier.notify("dev_cac");
+        assert!(timeout(Duration::from_secs(1), change).await.is_ok());
+        assert!(timeout(Duration::from_millis(50), unrelated).await.is_err());
+
+        drop(other_tenant);
+        assert_eq!(notifier.waiters(), 1);
+        assert!(notifier.subscribe("test_cac").is_some());
+    }
+}
@@ -0,0 +1,41 @@
+use diesel::{
+    connection::SimpleConnection, sql_query, sql_types::Text, OptionalExtension,
+    PgConnection, QueryResult, QueryableByName, RunQueryDsl,
+};
+
+// the admin token rotated through `PUT /admin-token`, a single row in the
+// `public` schema so that every server and restart picks up the rotation
+const CREATE_ADMIN_TOKEN_TABLE: &str = "CREATE TABLE IF NOT EXISTS public.admin_token (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    token TEXT NOT NULL,
+    rotated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);";
+
+#[derive(QueryableByName)]
+struct StoredAdminToken {
+    #[diesel(sql_type = Text)]
+    token: String,
+}
+
+/// Creates the admin token table when it does not exist yet.
+pub fn ensure_admin_token_table(conn: &mut PgConnection) -> QueryResult<()> {
+    conn.batch_execute(CREATE_ADMIN_TOKEN_TABLE)
+}
+
+/// The last rotated admin token, `None` when it was never rotated.
+pub fn load_admin_token(conn: &mut PgConnection) -> QueryResult<Option<String>> {
+    let stored = sql_query("SELECT token FROM public.admin_token")
+        .get_result::<StoredAdminToken>(conn)
+        .optional()?;
+    Ok(stored.map(|stored| stored.token))
+}
+
+pub fn store_admin_token(conn: &mut PgConnection, token: &str) -> QueryResult<()> {
+    sql_query(
+        "INSERT INTO public.admin_token (id, token) VALUES (TRUE, $1)
+        ON CONFLICT (id) DO UPDATE SET token = EXCLUDED.token, rotated_at = CURRENT_TIMESTAMP",
+    )
+    .bind::<Text, _>(token)
+    .execute(conn)?;
+    Ok(())
+}
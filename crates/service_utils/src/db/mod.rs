@@ -1,2 +1,4 @@
+pub mod admin_token;
 pub mod pgschema_manager;
+pub mod tenants;
 pub mod utils;
@@ -1,11 +1,12 @@
 extern crate derive_more;
-use derive_more::{Deref, DerefMut, Display};
+use derive_more::Display;
 use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard};
+use std::time::Duration;
 
-use anyhow::anyhow;
 use diesel::{
-    r2d2::{ConnectionManager, Pool, PooledConnection},
-    PgConnection,
+    r2d2::{ConnectionManager, Pool, PoolError, PooledConnection},
+    Connection, ConnectionResult, PgConnection,
 };
 
 pub type PgSchemaConnectionPool = Pool<ConnectionManager<PgConnection>>;
@@ -18,15 +19,23 @@ pub struct ConnectionConfig {
     database_url: String,
     schema: String,
     count: u32,
+    connection_timeout: Duration,
 }
 
 impl ConnectionConfig {
-    pub fn new(name: String, database_url: String, schema: String, count: u32) -> Self {
+    pub fn new(
+        name: String,
+        database_url: String,
+        schema: String,
+        count: u32,
+        connection_timeout: Duration,
+    ) -> Self {
         ConnectionConfig {
             name,
             database_url,
             schema,
             count,
+            connection_timeout,
         }
     }
 
@@ -45,32 +54,101 @@ impl ConnectionConfig {
     }
 }
 
-#[derive(Deref, DerefMut, Clone)]
-pub struct PgSchemaManager(HashMap<String, PgSchemaConnectionPool>);
+/// Connection pools keyed on the namespace (db schema) they serve. Clones
+/// share their pools, so a namespace added at runtime, like the schemas of a
+/// newly provisioned tenant, is served by every worker.
+#[derive(Clone)]
+pub struct PgSchemaManager {
+    pools: Arc<RwLock<HashMap<String, PgSchemaConnectionPool>>>,
+    // pools of namespaces added later are built like the initial ones
+    database_url: String,
+    count: u32,
+    connection_timeout: Duration,
+}
+#[derive(Debug, thiserror::Error)]
+pub enum PoolCheckoutError {
+    #[error("Invalid connection name provided: {0}")]
+    InvalidName(String),
+    // every connection is checked out, more capacity is needed, not a db outage
+    #[error("connection pool {0} exhausted: {1}")]
+    Exhausted(String, PoolError),
+    #[error("failed to connect to the database for {0}: {1}")]
+    Connection(String, PoolError),
+}
 
-impl From<Vec<ConnectionConfig>> for PgSchemaManager {
-    fn from(value: Vec<ConnectionConfig>) -> Self {
-        let mut schema_manager: PgSchemaManager = PgSchemaManager(HashMap::new());
-        for config in value.into_iter() {
-            let manager = ConnectionManager::<PgConnection>::new(config.conn_url());
-            schema_manager.insert(
-                config.name.clone(),
-                Pool::builder()
-                    .max_size(config.count)
-                    .build(manager)
-                    .expect(format!("Invalid config provided, {}", config.name).as_str()),
-            );
+impl PgSchemaManager {
+    pub fn new(database_url: String, count: u32, connection_timeout: Duration) -> Self {
+        PgSchemaManager {
+            pools: Arc::new(RwLock::new(HashMap::new())),
+            database_url,
+            count,
+            connection_timeout,
         }
-        schema_manager
     }
-}
 
-impl PgSchemaManager {
-    pub fn get_conn(&self, name: String) -> anyhow::Result<PgSchemaConnection> {
-        let conn = self
-            .get(&name) // gets the pool for the given namespace
-            .ok_or_else(|| anyhow!("Invalid connection name provided: {}", name))?
-            .get()?; // fetches the connection from the pool
-        Ok(conn)
+    fn read_pools(&self) -> RwLockReadGuard<'_, HashMap<String, PgSchemaConnectionPool>> {
+        // pools stay usable even if a thread panicked while adding one
+        self.pools.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Builds a pool for `namespace`, connecting with its schema first on the
+    /// search path. Fails when no connection can be made within the timeout.
+    pub fn add_namespace(&self, namespace: &str) -> Result<(), PoolError> {
+        let config = ConnectionConfig::new(
+            namespace.to_string(),
+            self.database_url.clone(),
+            namespace.to_string(),
+            self.count,
+            self.connection_timeout,
+        );
+        let pool = Pool::builder()
+            .max_size(config.count)
+            .connection_timeout(config.connection_timeout)
+            .build(ConnectionManager::<PgConnection>::new(config.conn_url()))?;
+        self.pools
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(config.name, pool);
+        Ok(())
+    }
+
+    pub fn has_namespace(&self, namespace: &str) -> bool {
+        self.read_pools().contains_key(namespace)
+    }
+
+    pub fn pools(&self) -> Vec<(String, PgSchemaConnectionPool)> {
+        self.read_pools()
+            .iter()
+            .map(|(namespace, pool)| (namespace.clone(), pool.clone()))
+            .collect()
+    }
+
+    /// A connection outside of every pool, on the default search path. Meant
+    /// for setting up schemas, not for serving requests.
+    pub fn establish(&self) -> ConnectionResult<PgConnection> {
+        PgConnection::establish(&self.database_url)
+    }
+
+    pub fn get_conn(
+        &self,
+        name: String,
+    ) -> Result<PgSchemaConnection, PoolCheckoutError> {
+        // gets the pool for the given namespace
+        let pool = self
+            .read_pools()
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| PoolCheckoutError::InvalidName(name.clone()))?;
+        // fetches the connection from the pool
+        pool.get().map_err(|err| {
+            // r2d2 only reports timeouts, a pool at its max size with nothing
+            // idle timed out waiting on other requests rather than on postgres
+            let state = pool.state();
+            if state.connections >= pool.max_size() && state.idle_connections == 0 {
+                PoolCheckoutError::Exhausted(name, err)
+            } else {
+                PoolCheckoutError::Connection(name, err)
+            }
+        })
     }
 }
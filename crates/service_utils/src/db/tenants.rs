@@ -0,0 +1,54 @@
+use std::collections::HashSet;
+
+use diesel::{
+    connection::SimpleConnection, sql_query, sql_types::Text, PgConnection, QueryResult,
+    QueryableByName, RunQueryDsl,
+};
+
+// tenants provisioned through `POST /tenants`, in the `public` schema shared
+// by every server so that they are served after a restart and by every
+// replica, not only by the server that provisioned them
+const CREATE_TENANTS_TABLE: &str = "CREATE TABLE IF NOT EXISTS public.tenants (
+    tenant TEXT PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_by TEXT NOT NULL
+);";
+
+#[derive(QueryableByName)]
+struct ProvisionedTenant {
+    #[diesel(sql_type = Text)]
+    tenant: String,
+}
+
+/// Creates the table of provisioned tenants when it does not exist yet.
+pub fn ensure_tenants_table(conn: &mut PgConnection) -> QueryResult<()> {
+    conn.batch_execute(CREATE_TENANTS_TABLE)
+}
+
+pub fn load_provisioned_tenants(conn: &mut PgConnection) -> QueryResult<HashSet<String>> {
+    let tenants = sql_query("SELECT tenant FROM public.tenants")
+        .load::<ProvisionedTenant>(conn)?
+        .into_iter()
+        .map(|provisioned| provisioned.tenant)
+        .collect();
+    Ok(tenants)
+}
+
+pub fn is_provisioned_tenant(conn: &mut PgConnection, tenant: &str) -> QueryResult<bool> {
+    let provisioned = sql_query("SELECT tenant FROM public.tenants WHERE tenant = $1")
+        .bind::<Text, _>(tenant)
+        .load::<ProvisionedTenant>(conn)?;
+    Ok(!provisioned.is_empty())
+}
+
+pub fn record_provisioned_tenant(
+    conn: &mut PgConnection,
+    tenant: &str,
+    created_by: &str,
+) -> QueryResult<()> {
+    sql_query("INSERT INTO public.tenants (tenant, created_by) VALUES ($1, $2)")
+        .bind::<Text, _>(tenant)
+        .bind::<Text, _>(created_by)
+        .execute(conn)?;
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use crate::aws::kms;
-use crate::db::pgschema_manager::{ConnectionConfig, PgSchemaManager};
+use crate::db::pgschema_manager::PgSchemaManager;
 use crate::helpers::{get_from_env_or_default, get_from_env_unsafe};
 use crate::service::types::AppEnv;
 use diesel::{
@@ -7,6 +7,7 @@ use diesel::{
     PgConnection,
 };
 use std::collections::HashSet;
+use std::time::Duration;
 use urlencoding::encode;
 
 pub async fn get_database_url() -> String {
@@ -39,6 +40,7 @@ pub async fn init_pool_manager(
     enable_tenant_and_scope: bool,
     app_env: AppEnv,
     max_pool_size: u32,
+    connection_timeout: Duration,
 ) -> PgSchemaManager {
     let database_url = get_database_url().await;
     let namespaces = match (enable_tenant_and_scope, app_env) {
@@ -54,17 +56,12 @@ pub async fn init_pool_manager(
         (false, _) => vec!["cac_v1".to_string()],
     };
 
-    let connection_configs = namespaces
-        .iter()
-        .map(|namespace| {
-            ConnectionConfig::new(
-                namespace.to_string(),
-                database_url.clone(),
-                namespace.to_string(),
-                max_pool_size,
-            )
-        })
-        .collect::<Vec<ConnectionConfig>>();
-
-    PgSchemaManager::from(connection_configs)
+    let schema_manager =
+        PgSchemaManager::new(database_url, max_pool_size, connection_timeout);
+    for namespace in namespaces {
+        schema_manager
+            .add_namespace(&namespace)
+            .unwrap_or_else(|err| panic!("Invalid config provided, {namespace}: {err}"));
+    }
+    schema_manager
 }
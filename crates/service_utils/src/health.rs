@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use actix_web::{web::Data, HttpResponse};
+use serde::Serialize;
+
+use crate::service::types::AppState;
+
+// probes are retried by the orchestrator, fail fast instead of waiting on the pool
+const DB_CHECKOUT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+pub struct DependencyCheck {
+    pub name: String,
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DependencyCheck {
+    fn new(name: String, result: Result<(), String>) -> Self {
+        DependencyCheck {
+            name,
+            healthy: result.is_ok(),
+            error: result.err(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub dependencies: Vec<DependencyCheck>,
+}
+
+fn check_db(state: &AppState) -> Vec<DependencyCheck> {
+    let mut checks = state
+        .db_pool
+        .pools()
+        .into_iter()
+        .map(|(schema, pool)| {
+            let result = pool
+                .get_timeout(DB_CHECKOUT_TIMEOUT)
+                .map(|_| ())
+                .map_err(|err| format!("failed to get a connection: {err}"));
+            DependencyCheck::new(format!("database:{schema}"), result)
+        })
+        .collect::<Vec<DependencyCheck>>();
+    checks.sort_by(|a, b| a.name.cmp(&b.name));
+    checks
+}
+
+async fn check_cac(state: &AppState) -> DependencyCheck {
+    let url = state.cac_url("health");
+    let result = match state.http_client.get(url).send().await {
+        Ok(res) if res.status().is_success() => Ok(()),
+        Ok(res) => Err(format!("responded with status {}", res.status())),
+        Err(err) => Err(format!("failed to reach CAC: {err}")),
+    };
+    DependencyCheck::new(String::from("cac"), result)
+}
+
+pub async fn liveness_handler() -> HttpResponse {
+    HttpResponse::Ok().body("Health is good :D")
+}
+
+/// Ready only when a connection can be checked out of every db pool and CAC
+/// answers its health check, responds with a 503 naming the failed ones otherwise.
+pub async fn readiness_handler(state: Data<AppState>) -> HttpResponse {
+    let mut dependencies = check_db(&state);
+    dependencies.push(check_cac(&state).await);
+
+    let ready = dependencies.iter().all(|dependency| dependency.healthy);
+    let response = ReadinessResponse {
+        ready,
+        dependencies,
+    };
+    if ready {
+        HttpResponse::Ok().json(response)
+    } else {
+        let failed = response
+            .dependencies
+            .iter()
+            .filter(|dependency| !dependency.healthy)
+            .map(|dependency| dependency.name.as_str())
+            .collect::<Vec<&str>>();
+        log::error!("readiness check failed for {}", failed.join(", "));
+        HttpResponse::ServiceUnavailable().json(response)
+    }
+}
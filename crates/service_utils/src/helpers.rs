@@ -1,4 +1,9 @@
-use actix_web::{error::ErrorInternalServerError, Error};
+use actix_web::{
+    error::{ErrorInternalServerError, JsonPayloadError},
+    http::StatusCode,
+    web::JsonConfig,
+    Error,
+};
 use log::info;
 use serde::de::{self, IntoDeserializer};
 use std::{
@@ -8,6 +13,7 @@ use std::{
 };
 
 use super::result;
+use reqwest::Url;
 use serde_json::{Map, Value};
 
 //WARN Do NOT use this fxn inside api requests, instead add the required
@@ -32,7 +38,12 @@ where
     <F as FromStr>::Err: std::fmt::Debug,
 {
     match std::env::var(name) {
-        Ok(env) => env.parse().unwrap(),
+        Ok(env) => env.parse().unwrap_or_else(|err| {
+            log::warn!(
+                "{name} ENV has an invalid value {env} ({err:?}), using default value {default}"
+            );
+            default
+        }),
         Err(err) => {
             info!(
                 "{name} ENV failed to load due to {err}, using default value {default}"
@@ -42,6 +53,13 @@ where
     }
 }
 
+/// Experiment creations per minute allowed for `tenant`, from
+/// `EXPERIMENT_CREATE_RATE_LIMIT_<TENANT>` when it is set.
+pub fn experiment_create_rate_limit_of(tenant: &str, default_limit: u32) -> u32 {
+    let env_name = format!("EXPERIMENT_CREATE_RATE_LIMIT_{}", tenant.to_uppercase());
+    get_from_env_or_default(&env_name, default_limit)
+}
+
 pub trait ToActixErr<T> {
     fn map_err_to_internal_server<B>(
         self,
@@ -97,7 +115,8 @@ where
             E: de::Error,
         {
             let mut query_vector = Vec::new();
-            for param in v.split(",") {
+            // blank entries, as in `status=` or `a,,b`, are skipped
+            for param in v.split(",").map(str::trim).filter(|p| !p.is_empty()) {
                 let p: I = I::deserialize(param.into_deserializer())?;
                 query_vector.push(p);
             }
@@ -108,65 +127,257 @@ where
     deserializer.deserialize_any(StringVecVisitor(std::marker::PhantomData::<I>))
 }
 
-pub fn get_pod_info() -> (String, String) {
-    let hostname: String = get_from_env_unsafe("HOSTNAME").expect("HOSTNAME is not set");
-    let tokens = hostname
-        .split("-")
-        .map(str::to_string)
-        .collect::<Vec<String>>();
-    let mut tokens = tokens.iter().rev();
-    let (pod_id, _replica_set, deployment_id) = (
-        tokens.next().unwrap().to_owned(),
-        tokens.next().unwrap().to_owned(),
-        tokens.next().unwrap().to_owned(),
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+
+/// Page, counted from 1, and page size of a listing request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pagination {
+    pub page: i64,
+    pub size: i64,
+}
+
+impl Pagination {
+    pub fn offset(&self) -> i64 {
+        (self.page - 1) * self.size
+    }
+}
+
+/// Defaults an absent `page` to 1 and `size` to [`DEFAULT_PAGE_SIZE`], and
+/// caps `size` at `max_size` so that no request reads a whole table. Pages
+/// and sizes below 1 are rejected.
+pub fn resolve_pagination(
+    page: Option<i64>,
+    size: Option<i64>,
+    max_size: i64,
+) -> result::Result<Pagination> {
+    let page = page.unwrap_or(1);
+    if page < 1 {
+        return Err(result::AppError::BadArgument(format!(
+            "page has to be at least 1, provided {page}"
+        )));
+    }
+    let size = size.unwrap_or(DEFAULT_PAGE_SIZE);
+    if size < 1 {
+        return Err(result::AppError::BadArgument(format!(
+            "page size has to be at least 1, provided {size}"
+        )));
+    }
+    Ok(Pagination {
+        page,
+        size: size.min(max_size),
+    })
+}
+
+pub const DEFAULT_MAX_BULK_PAYLOAD_BYTES: usize = 2 * 1024 * 1024;
+
+/// Error of a request body over `limit` bytes, `length` being its size when
+/// the request declared it.
+pub fn payload_too_large_error(length: Option<usize>, limit: usize) -> result::AppError {
+    let size = length.map_or_else(
+        || String::from("request body"),
+        |length| format!("request body of {length} bytes"),
     );
-    (pod_id, deployment_id)
+    result::AppError::ResponseError(result::ResponseError {
+        status_code: StatusCode::PAYLOAD_TOO_LARGE,
+        message: format!(
+            "{size} exceeds the limit of {limit} bytes. Split it into smaller batches, for instance fewer bulk operations or variants per request"
+        ),
+    })
 }
 
-pub fn extract_dimensions(context_json: &Value) -> result::Result<Map<String, Value>> {
-    // Assuming max 2-level nesting in context json logic
-    let context = context_json
-        .as_object()
-        .ok_or(
-            result::AppError::BadArgument("Error extracting dimensions, contect not a valid JSON object. Provide a valid JSON context".into())
-            )?;
-
-    let conditions = match context.get("and") {
-        Some(conditions_json) => conditions_json
-            .as_array()
-            .ok_or(result::AppError::BadArgument("Error extracting dimensions, failed parsing conditions as an array. Ensure the context provided obeys the rules of JSON logic".into()))?
-            .clone(),
-        None => vec![context_json.clone()],
+/// Limits JSON request bodies to `limit` bytes. Larger bodies are rejected
+/// with a `413 Payload Too Large` before being read whole, so a single huge
+/// request cannot exhaust the memory of a worker.
+pub fn json_payload_config(limit: usize) -> JsonConfig {
+    JsonConfig::default()
+        .limit(limit)
+        .error_handler(|err, _| match err {
+            JsonPayloadError::OverflowKnownLength { length, limit } => {
+                payload_too_large_error(Some(length), limit).into()
+            }
+            JsonPayloadError::Overflow { limit } => {
+                payload_too_large_error(None, limit).into()
+            }
+            err => err.into(),
+        })
+}
+
+/// Checks the `Authorization: Bearer <token>` header of a request to an admin
+/// endpoint against the admin token. Admin endpoints are forbidden when no
+/// admin token is configured.
+pub fn authorize_admin(
+    admin_token: Option<&str>,
+    authorization: Option<&str>,
+) -> result::Result<()> {
+    let error = |status_code, message: &str| {
+        result::AppError::ResponseError(result::ResponseError {
+            status_code,
+            message: message.to_string(),
+        })
     };
+    let Some(admin_token) = admin_token else {
+        return Err(error(
+            StatusCode::FORBIDDEN,
+            "admin endpoints are disabled, set ADMIN_TOKEN to enable them",
+        ));
+    };
+    let provided = authorization
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .map(str::trim);
+    match provided {
+        Some(token) if constant_time_eq(token.as_bytes(), admin_token.as_bytes()) => {
+            Ok(())
+        }
+        _ => Err(error(
+            StatusCode::UNAUTHORIZED,
+            "a valid admin token is required, pass it as `Authorization: Bearer <token>`",
+        )),
+    }
+}
+
+// compares every byte, so the time taken does not tell how much of a guessed
+// token was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Splits a kubernetes pod hostname, `<application>-<deployment_id>-<replicaset>-<pod_id>`,
+/// into the pod and deployment ids. Hostnames that don't follow the pattern, as
+/// when running locally, are used whole for both ids.
+pub fn parse_pod_info(hostname: &str) -> (String, String) {
+    let mut tokens = hostname.rsplit('-');
+    match (tokens.next(), tokens.next(), tokens.next()) {
+        (Some(pod_id), Some(_replica_set), Some(deployment_id))
+            if !pod_id.is_empty() && !deployment_id.is_empty() =>
+        {
+            (pod_id.to_string(), deployment_id.to_string())
+        }
+        _ => {
+            log::warn!(
+                "HOSTNAME {hostname} is not a kubernetes pod name, using it as the pod and deployment id"
+            );
+            (hostname.to_string(), hostname.to_string())
+        }
+    }
+}
+
+/// Parses the CAC host, an http(s) URL that may carry a path prefix like
+/// `http://localhost:8080/superposition`. Its path is made to end in a single
+/// `/`, so that endpoint paths joined onto it keep the prefix whether or not
+/// the host was given with a trailing slash.
+pub fn parse_cac_host(host: &str) -> Result<Url, String> {
+    let mut url = Url::parse(host.trim()).map_err(|err| {
+        format!("CAC host {host} is not a valid URL, like http://localhost:8080: {err}")
+    })?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(format!(
+            "CAC host {host} has to be an http or https URL with a host, like http://localhost:8080"
+        ));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(format!("CAC host {host} cannot have a query or fragment"));
+    }
+    let path = format!("{}/", url.path().trim_end_matches('/'));
+    url.set_path(&path);
+    Ok(url)
+}
+
+/// Joins `path`, with or without a leading `/`, onto a host parsed by
+/// [`parse_cac_host`].
+pub fn cac_url(cac_host: &Url, path: &str) -> Url {
+    cac_host
+        .join(path.trim_start_matches('/'))
+        .expect("a relative path always joins onto the parsed CAC host")
+}
+
+pub fn get_pod_info() -> Result<(String, String), String> {
+    let hostname: String = get_from_env_unsafe("HOSTNAME")
+        .map_err(|err| format!("HOSTNAME is not set: {err}"))?;
+    if hostname.trim().is_empty() {
+        return Err(String::from("HOSTNAME is empty"));
+    }
+    Ok(parse_pod_info(hostname.trim()))
+}
+
+pub fn extract_dimensions(context_json: &Value) -> result::Result<Map<String, Value>> {
+    if !context_json.is_object() {
+        return Err(result::AppError::BadArgument("Error extracting dimensions, contect not a valid JSON object. Provide a valid JSON context".into()));
+    }
 
     let mut dimension_tuples = Vec::new();
-    for condition in &conditions {
-        let condition_obj =
-            condition
-                .as_object()
-                .ok_or(result::AppError::BadArgument(
-                    "Failed to parse condition as an object. Ensure the context provided obeys the rules of JSON logic".to_string()
-                ))?;
-        let operators = condition_obj.keys();
-
-        for operator in operators {
-            let operands = condition_obj[operator].as_array().ok_or(result::AppError::BadArgument(
-                    "Failed to parse operands as an arrays. Ensure the context provided obeys the rules of JSON logic"
-                            .into()
-            ))?;
+    collect_dimensions(context_json, "", &mut dimension_tuples)?;
 
-            let (variable_name, variable_value) = get_variable_name_and_value(operands)?;
+    Ok(Map::from_iter(dimension_tuples))
+}
 
-            dimension_tuples.push((String::from(variable_name), variable_value.clone()));
+// points the error at the failing node, paths look like `and[2].==`
+fn dimension_error_at(path: &str, err: result::AppError) -> result::AppError {
+    let location = if path.is_empty() { "<root>" } else { path };
+    match err {
+        result::AppError::BadArgument(msg) => {
+            result::AppError::BadArgument(format!("{msg} (at `{location}`)"))
         }
+        err => err,
     }
+}
 
-    Ok(Map::from_iter(dimension_tuples))
+// walks nested `and`/`or` conditions to any depth, every other operator is
+// expected to compare a `{"var": ...}` against a value
+fn collect_dimensions(
+    condition: &Value,
+    path: &str,
+    dimension_tuples: &mut Vec<(String, Value)>,
+) -> result::Result<()> {
+    let condition_obj = condition.as_object().ok_or_else(|| {
+        dimension_error_at(
+            path,
+            result::AppError::BadArgument(
+                "Failed to parse condition as an object. Ensure the context provided obeys the rules of JSON logic".to_string()
+            ),
+        )
+    })?;
+
+    for (operator, operands) in condition_obj {
+        let operator_path = if path.is_empty() {
+            operator.to_string()
+        } else {
+            format!("{path}.{operator}")
+        };
+        let operands = operands.as_array().ok_or_else(|| {
+            dimension_error_at(
+                &operator_path,
+                result::AppError::BadArgument(
+                    "Failed to parse operands as an arrays. Ensure the context provided obeys the rules of JSON logic"
+                        .into()
+                ),
+            )
+        })?;
+
+        match operator.as_str() {
+            "and" | "or" => {
+                for (idx, nested_condition) in operands.iter().enumerate() {
+                    collect_dimensions(
+                        nested_condition,
+                        &format!("{operator_path}[{idx}]"),
+                        dimension_tuples,
+                    )?;
+                }
+            }
+            _ => {
+                let (variable_name, variable_value) =
+                    get_variable_name_and_value(operands)
+                        .map_err(|err| dimension_error_at(&operator_path, err))?;
+                dimension_tuples.push((String::from(variable_name), variable_value));
+            }
+        }
+    }
+
+    Ok(())
 }
 
 pub fn get_variable_name_and_value(
     operands: &Vec<Value>,
-) -> result::Result<(&str, &Value)> {
+) -> result::Result<(&str, Value)> {
     let (obj_pos, variable_obj) = operands
         .iter()
         .enumerate()
@@ -187,14 +398,174 @@ pub fn get_variable_name_and_value(
                 .into()
         ))?;
 
-    let value_pos = (obj_pos + 1) % 2;
-    let variable_value =
-        operands
-            .get(value_pos)
-            .ok_or(result::AppError::BadArgument(
-                "Failed to get variable value from operands list. Ensure the context provided obeys the rules of JSON logic"
-                    .into()
-            ))?;
+    // `in` compares against an array literal and range operators like
+    // `<=` carry two bounds, so every non-variable operand is kept
+    let mut values = operands
+        .iter()
+        .enumerate()
+        .filter(|(pos, _)| *pos != obj_pos)
+        .map(|(_, operand)| operand.clone())
+        .collect::<Vec<Value>>();
+    let variable_value = match values.len() {
+        0 => Err(result::AppError::BadArgument(
+            "Failed to get variable value from operands list. Ensure the context provided obeys the rules of JSON logic"
+                .into()
+        ))?,
+        1 => values.remove(0),
+        _ => Value::Array(values),
+    };
 
     Ok((variable_name, variable_value))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result::AppError;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_pod_info() {
+        assert_eq!(
+            parse_pod_info("superposition-7c9d5b-5f8d9-x2k4p"),
+            (String::from("x2k4p"), String::from("7c9d5b"))
+        );
+
+        // local and other non kubernetes hostnames are used whole
+        for hostname in ["laptop", "my-laptop", "app-deployment-"] {
+            assert_eq!(
+                parse_pod_info(hostname),
+                (hostname.to_string(), hostname.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_pagination() {
+        assert_eq!(
+            resolve_pagination(None, None, 100).unwrap(),
+            Pagination {
+                page: 1,
+                size: DEFAULT_PAGE_SIZE
+            }
+        );
+        let pagination = resolve_pagination(Some(3), Some(1_000_000), 100).unwrap();
+        assert_eq!(pagination, Pagination { page: 3, size: 100 });
+        assert_eq!(pagination.offset(), 200);
+        assert_eq!(
+            resolve_pagination(None, None, 5).unwrap(),
+            Pagination { page: 1, size: 5 }
+        );
+
+        for (page, size) in [(Some(0), None), (Some(-1), None), (None, Some(0))] {
+            assert!(matches!(
+                resolve_pagination(page, size, 100),
+                Err(AppError::BadArgument(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_authorize_admin() {
+        let status_of = |result: Result<(), AppError>| match result {
+            Err(AppError::ResponseError(err)) => Some(err.status_code),
+            Err(other) => panic!("expected a response error, got {other:?}"),
+            Ok(()) => None,
+        };
+        assert_eq!(
+            status_of(authorize_admin(None, Some("Bearer secret"))),
+            Some(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            status_of(authorize_admin(Some("secret"), Some("Bearer secret"))),
+            None
+        );
+        assert_eq!(
+            status_of(authorize_admin(Some("secret"), Some("Bearer secrets"))),
+            Some(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            status_of(authorize_admin(Some("secret"), Some("secret"))),
+            Some(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            status_of(authorize_admin(Some("secret"), None)),
+            Some(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_json_payload_limit() {
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .app_data(json_payload_config(32))
+                .route(
+                    "/bulk",
+                    actix_web::web::put().to(
+                        |body: actix_web::web::Json<Value>| async move {
+                            actix_web::HttpResponse::Ok().json(body.into_inner())
+                        },
+                    ),
+                ),
+        )
+        .await;
+
+        let req = actix_web::test::TestRequest::put()
+            .uri("/bulk")
+            .set_json(json!([1, 2, 3]))
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let req = actix_web::test::TestRequest::put()
+            .uri("/bulk")
+            .set_json(json!(["an operation that does not fit in 32 bytes"]))
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body: Value = actix_web::test::read_body_json(res).await;
+        let message = body["message"].as_str().unwrap();
+        assert!(message.contains("limit of 32 bytes"), "{message}");
+    }
+
+    #[test]
+    fn test_cac_host_urls() {
+        // trailing slashes make no difference, and a path prefix is kept
+        for host in [
+            "http://localhost:8080",
+            "http://localhost:8080/",
+            " http://localhost:8080// ",
+        ] {
+            let cac_host = parse_cac_host(host).unwrap();
+            assert_eq!(
+                cac_url(&cac_host, "context/bulk-operations").as_str(),
+                "http://localhost:8080/context/bulk-operations"
+            );
+            assert_eq!(
+                cac_url(&cac_host, "/dimension").as_str(),
+                "http://localhost:8080/dimension"
+            );
+        }
+        for host in [
+            "https://cac.example.com/superposition",
+            "https://cac.example.com/superposition/",
+        ] {
+            let cac_host = parse_cac_host(host).unwrap();
+            assert_eq!(
+                cac_url(&cac_host, "context/abc").as_str(),
+                "https://cac.example.com/superposition/context/abc"
+            );
+        }
+
+        for host in [
+            "localhost:8080",
+            "cac.example.com",
+            "ftp://localhost",
+            "http://",
+            "http://localhost:8080?tenant=dev",
+            "",
+        ] {
+            assert!(parse_cac_host(host).is_err(), "{host}");
+        }
+    }
+}
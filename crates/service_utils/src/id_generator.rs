@@ -0,0 +1,262 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use snowflake::SnowflakeIdGenerator;
+
+/// Generates experiment ids, ids of a generator are unique and increase with
+/// time.
+pub trait IdGenerator: Send + Sync {
+    fn generate_id(&self) -> Result<String, String>;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, strum_macros::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum IdGeneratorKind {
+    Snowflake,
+    Ulid,
+}
+
+impl FromStr for IdGeneratorKind {
+    type Err = String;
+    fn from_str(val: &str) -> Result<IdGeneratorKind, Self::Err> {
+        match val.to_lowercase().as_str() {
+            "snowflake" => Ok(IdGeneratorKind::Snowflake),
+            "ulid" => Ok(IdGeneratorKind::Ulid),
+            _ => Err(format!("invalid id generator {val}, use snowflake or ulid")),
+        }
+    }
+}
+
+pub fn new_id_generator(kind: IdGeneratorKind) -> Arc<dyn IdGenerator> {
+    match kind {
+        IdGeneratorKind::Snowflake => Arc::new(Snowflake::new(
+            Mutex::new(SnowflakeIdGenerator::new(1, 1)),
+            Arc::new(AtomicI64::new(0)),
+        )),
+        IdGeneratorKind::Ulid => Arc::new(Ulid::default()),
+    }
+}
+
+// bits below the millisecond timestamp in a snowflake id
+const SNOWFLAKE_TIMESTAMP_SHIFT: u32 = 22;
+// how far behind the last id the clock may be, in milliseconds, before
+// generating fails instead of waiting for the clock to catch up
+const MAX_CLOCK_DRIFT_MS: i64 = 5;
+
+/// 64 bit ids, numerically sortable, written out in decimal.
+pub struct Snowflake {
+    pub generator: Mutex<SnowflakeIdGenerator>,
+    // last id handed out by any generator sharing it
+    pub last_id: Arc<AtomicI64>,
+}
+
+impl Snowflake {
+    pub fn new(generator: Mutex<SnowflakeIdGenerator>, last_id: Arc<AtomicI64>) -> Self {
+        Snowflake { generator, last_id }
+    }
+
+    // ids are handed out strictly increasing across every generator sharing
+    // `last_id`, an id that is not ahead of the last one (same millisecond on
+    // another worker, or the clock moving backwards) is generated again.
+    // The clock moving back by up to `MAX_CLOCK_DRIFT_MS` is waited out without
+    // holding the generator, further than that fails.
+    // A panic while the generator was held only poisons the lock, the generator
+    // itself is still consistent so id generation carries on with it
+    pub fn generate(&self) -> Result<i64, String> {
+        let started = Instant::now();
+        let mut warned_clock_drift = false;
+        loop {
+            let id = self
+                .generator
+                .lock()
+                .unwrap_or_else(|poisoned| {
+                    log::warn!("snowflake generator lock was poisoned, recovering it");
+                    poisoned.into_inner()
+                })
+                .real_time_generate();
+            let last = self.last_id.load(Ordering::SeqCst);
+            if id > last {
+                if self
+                    .last_id
+                    .compare_exchange(last, id, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+                {
+                    return Ok(id);
+                }
+                continue;
+            }
+            let drift_ms =
+                (last >> SNOWFLAKE_TIMESTAMP_SHIFT) - (id >> SNOWFLAKE_TIMESTAMP_SHIFT);
+            if drift_ms > 0 {
+                if drift_ms > MAX_CLOCK_DRIFT_MS
+                    || started.elapsed()
+                        > Duration::from_millis(MAX_CLOCK_DRIFT_MS as u64)
+                {
+                    return Err(format!(
+                        "clock moved backwards, snowflake id {id} is {drift_ms}ms behind the last id {last}"
+                    ));
+                }
+                if !warned_clock_drift {
+                    log::warn!(
+                        "clock moved backwards, snowflake id {id} is behind the last id {last}. Waiting for the clock to catch up"
+                    );
+                    warned_clock_drift = true;
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+}
+
+impl IdGenerator for Snowflake {
+    fn generate_id(&self) -> Result<String, String> {
+        self.generate().map(|id| id.to_string())
+    }
+}
+
+const ULID_RANDOM_BITS: u32 = 80;
+const ULID_LENGTH: usize = 26;
+const CROCKFORD_BASE32: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// 128 bit ids, a 48 bit millisecond timestamp followed by 80 random bits,
+/// written out as 26 character Crockford base32 strings that sort
+/// lexicographically. Ids within the same millisecond increment the last
+/// one, as in the monotonic ULID spec.
+#[derive(Default)]
+pub struct Ulid {
+    last_id: Mutex<u128>,
+}
+
+impl Ulid {
+    pub fn generate(&self) -> u128 {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+            & ((1 << 48) - 1);
+        let random = rand::random::<u128>() & ((1 << ULID_RANDOM_BITS) - 1);
+        let id = (timestamp_ms << ULID_RANDOM_BITS) | random;
+
+        let mut last_id = self.last_id.lock().unwrap_or_else(|poisoned| {
+            log::warn!("ulid generator lock was poisoned, recovering it");
+            poisoned.into_inner()
+        });
+        *last_id = if (id >> ULID_RANDOM_BITS) > (*last_id >> ULID_RANDOM_BITS) {
+            id
+        } else {
+            *last_id + 1
+        };
+        *last_id
+    }
+}
+
+pub fn encode_ulid(id: u128) -> String {
+    (0..ULID_LENGTH)
+        .map(|idx| {
+            let shift = 5 * (ULID_LENGTH - 1 - idx);
+            CROCKFORD_BASE32[((id >> shift) & 0x1f) as usize] as char
+        })
+        .collect()
+}
+
+impl IdGenerator for Ulid {
+    fn generate_id(&self) -> Result<String, String> {
+        Ok(encode_ulid(self.generate()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_snowflake_id_recovers_poisoned_lock() {
+        let generator = Arc::new(Snowflake::new(
+            Mutex::new(SnowflakeIdGenerator::new(1, 1)),
+            Arc::new(AtomicI64::new(0)),
+        ));
+        let first_id = generator.generate().unwrap();
+
+        let poisoning_generator = Arc::clone(&generator);
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoning_generator.generator.lock().unwrap();
+            panic!("poisoning the snowflake generator lock");
+        })
+        .join();
+        assert!(generator.generator.is_poisoned());
+
+        let second_id = generator.generate().unwrap();
+        assert!(second_id > first_id);
+    }
+
+    #[test]
+    fn test_generate_snowflake_id_fails_on_clock_drift() {
+        let generator = Snowflake::new(
+            Mutex::new(SnowflakeIdGenerator::new(1, 1)),
+            Arc::new(AtomicI64::new(0)),
+        );
+        let id = generator.generate().unwrap();
+
+        // a last id a second ahead, as left by a clock that moved back since
+        let ahead = id + (1000 << SNOWFLAKE_TIMESTAMP_SHIFT);
+        generator.last_id.store(ahead, Ordering::SeqCst);
+        let started = Instant::now();
+        assert!(generator.generate().is_err());
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert!(!generator.generator.is_poisoned());
+        assert!(generator.generator.try_lock().is_ok());
+        assert_eq!(generator.last_id.load(Ordering::SeqCst), ahead);
+
+        assert!(generator
+            .generate_id()
+            .unwrap_err()
+            .contains("clock moved backwards"));
+
+        // a drift of a couple of milliseconds is waited out
+        let id = SnowflakeIdGenerator::new(1, 1).real_time_generate();
+        let ahead = id + (2 << SNOWFLAKE_TIMESTAMP_SHIFT);
+        generator.last_id.store(ahead, Ordering::SeqCst);
+        assert!(generator.generate().unwrap() > ahead);
+    }
+
+    #[test]
+    fn test_generate_snowflake_id_is_monotonic() {
+        // generators with the same machine and node ids, as every worker has,
+        // collide within a millisecond unless the last id is shared
+        let last_id = Arc::new(AtomicI64::new(0));
+        let generators = [
+            Snowflake::new(Mutex::new(SnowflakeIdGenerator::new(1, 1)), last_id.clone()),
+            Snowflake::new(Mutex::new(SnowflakeIdGenerator::new(1, 1)), last_id.clone()),
+        ];
+
+        let mut previous_id = 0;
+        for idx in 0..10_000 {
+            let id = generators[idx % 2].generate().unwrap();
+            assert!(id > previous_id);
+            previous_id = id;
+        }
+        assert_eq!(last_id.load(Ordering::SeqCst), previous_id);
+        assert_eq!(
+            generators[0].generate_id().unwrap(),
+            last_id.load(Ordering::SeqCst).to_string()
+        );
+    }
+
+    #[test]
+    fn test_generate_ulid_is_monotonic() {
+        assert_eq!(encode_ulid(0), "00000000000000000000000000");
+        assert_eq!(encode_ulid(u128::MAX), "7ZZZZZZZZZZZZZZZZZZZZZZZZZ");
+
+        let generator = Ulid::default();
+        let mut previous_id = generator.generate_id().unwrap();
+        assert_eq!(previous_id.len(), 26);
+        for _ in 0..10_000 {
+            // ids within the same millisecond increment the random bits
+            let id = generator.generate_id().unwrap();
+            assert!(id > previous_id);
+            previous_id = id;
+        }
+    }
+}
@@ -1,7 +1,17 @@
+pub mod admin;
 pub mod aws;
+pub mod config_watch;
 pub mod db;
+pub mod health;
 pub mod helpers;
+pub mod id_generator;
+pub mod logger;
 pub mod macros;
+pub mod metrics;
 pub mod middlewares;
+pub mod rate_limiter;
+pub mod resolve_cache;
 pub mod result;
+pub mod retry;
 pub mod service;
+pub mod webhook;
@@ -0,0 +1,24 @@
+use std::io::Write;
+
+use serde_json::json;
+
+use crate::middlewares::request_id::current_request_id;
+
+/// Initialises the `log` facade to write one JSON object per line, tagged with
+/// the id of the request being handled. Levels are picked from RUST_LOG as before.
+pub fn init_json_logger() {
+    env_logger::Builder::from_default_env()
+        .format(|buf, record| {
+            let mut line = json!({
+                "timestamp": buf.timestamp_millis().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            if let Some(request_id) = current_request_id() {
+                line["request_id"] = json!(request_id);
+            }
+            writeln!(buf, "{line}")
+        })
+        .init();
+}
@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+use actix_web::{web::Data, HttpResponse};
+
+use crate::service::types::AppState;
+
+pub struct MetricDesc {
+    pub name: &'static str,
+    pub help: &'static str,
+}
+
+pub const EXPERIMENTS_CREATED: MetricDesc = MetricDesc {
+    name: "superposition_experiments_created_total",
+    help: "Number of experiments created",
+};
+
+pub const EXPERIMENTS_RAMPED: MetricDesc = MetricDesc {
+    name: "superposition_experiments_ramped_total",
+    help: "Number of experiment ramps",
+};
+
+pub const EXPERIMENTS_CONCLUDED: MetricDesc = MetricDesc {
+    name: "superposition_experiments_concluded_total",
+    help: "Number of experiments concluded",
+};
+
+pub const CAC_BULK_OPERATIONS_DURATION: MetricDesc = MetricDesc {
+    name: "superposition_cac_bulk_operations_duration_seconds",
+    help: "Latency of the context bulk-operations calls made to CAC",
+};
+
+pub const CAC_BULK_OPERATIONS_RETRIES: MetricDesc = MetricDesc {
+    name: "superposition_cac_bulk_operations_retries_total",
+    help: "Number of retried context bulk-operations calls to CAC",
+};
+
+pub const RESOLVE_CACHE_HITS: MetricDesc = MetricDesc {
+    name: "superposition_resolve_cache_hits_total",
+    help: "Number of resolved configs served from the resolve cache",
+};
+
+pub const RESOLVE_CACHE_MISSES: MetricDesc = MetricDesc {
+    name: "superposition_resolve_cache_misses_total",
+    help: "Number of resolved configs evaluated for lack of a cached one",
+};
+
+const LATENCY_BUCKETS_SECONDS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+// keep label values bounded (status, tenant), never ids
+pub type Labels<'a> = [(&'static str, &'a str)];
+
+struct Histogram {
+    bucket_counts: [u64; LATENCY_BUCKETS_SECONDS.len()],
+    sum: f64,
+    count: u64,
+}
+
+struct Family<T> {
+    help: &'static str,
+    series: BTreeMap<String, T>,
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    counters: Mutex<BTreeMap<&'static str, Family<u64>>>,
+    histograms: Mutex<BTreeMap<&'static str, Family<Histogram>>>,
+}
+
+fn render_labels(labels: &Labels) -> String {
+    labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{name}=\"{value}\"")
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn with_label(labels: &str, label: &str) -> String {
+    if labels.is_empty() {
+        label.to_string()
+    } else {
+        format!("{labels},{label}")
+    }
+}
+
+impl Metrics {
+    pub fn inc_counter(&self, desc: &MetricDesc, labels: &Labels) {
+        let mut counters = self.counters.lock().unwrap();
+        let family = counters.entry(desc.name).or_insert_with(|| Family {
+            help: desc.help,
+            series: BTreeMap::new(),
+        });
+        *family.series.entry(render_labels(labels)).or_insert(0) += 1;
+    }
+
+    pub fn observe(&self, desc: &MetricDesc, labels: &Labels, value: f64) {
+        let mut histograms = self.histograms.lock().unwrap();
+        let family = histograms.entry(desc.name).or_insert_with(|| Family {
+            help: desc.help,
+            series: BTreeMap::new(),
+        });
+        let histogram = family
+            .series
+            .entry(render_labels(labels))
+            .or_insert_with(|| Histogram {
+                bucket_counts: [0; LATENCY_BUCKETS_SECONDS.len()],
+                sum: 0.0,
+                count: 0,
+            });
+        for (bucket, upper_bound) in LATENCY_BUCKETS_SECONDS.iter().enumerate() {
+            if value <= *upper_bound {
+                histogram.bucket_counts[bucket] += 1;
+            }
+        }
+        histogram.sum += value;
+        histogram.count += 1;
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+
+        for (name, family) in self.counters.lock().unwrap().iter() {
+            let _ = writeln!(output, "# HELP {name} {}", family.help);
+            let _ = writeln!(output, "# TYPE {name} counter");
+            for (labels, value) in family.series.iter() {
+                let _ = writeln!(output, "{name}{{{labels}}} {value}");
+            }
+        }
+
+        for (name, family) in self.histograms.lock().unwrap().iter() {
+            let _ = writeln!(output, "# HELP {name} {}", family.help);
+            let _ = writeln!(output, "# TYPE {name} histogram");
+            for (labels, histogram) in family.series.iter() {
+                for (bucket, upper_bound) in LATENCY_BUCKETS_SECONDS.iter().enumerate() {
+                    let le = with_label(labels, &format!("le=\"{upper_bound}\""));
+                    let _ = writeln!(
+                        output,
+                        "{name}_bucket{{{le}}} {}",
+                        histogram.bucket_counts[bucket]
+                    );
+                }
+                let le = with_label(labels, "le=\"+Inf\"");
+                let _ = writeln!(output, "{name}_bucket{{{le}}} {}", histogram.count);
+                let _ = writeln!(output, "{name}_sum{{{labels}}} {}", histogram.sum);
+                let _ = writeln!(output, "{name}_count{{{labels}}} {}", histogram.count);
+            }
+        }
+
+        output
+    }
+}
+
+pub async fn metrics_handler(state: Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(state.metrics.render())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_render_exposition_format() {
+        let metrics = Metrics::default();
+        let labels = [("status", "CREATED"), ("tenant", "test")];
+        metrics.inc_counter(&EXPERIMENTS_CREATED, &labels);
+        metrics.inc_counter(&EXPERIMENTS_CREATED, &labels);
+        metrics.observe(
+            &CAC_BULK_OPERATIONS_DURATION,
+            &[("status", "200"), ("tenant", "test")],
+            0.2,
+        );
+
+        let rendered = metrics.render();
+        assert!(
+            rendered.contains("# TYPE superposition_experiments_created_total counter")
+        );
+        assert!(rendered.contains(
+            "superposition_experiments_created_total{status=\"CREATED\",tenant=\"test\"} 2"
+        ));
+        assert!(rendered.contains(
+            "# TYPE superposition_cac_bulk_operations_duration_seconds histogram"
+        ));
+        assert!(rendered.contains(
+            "superposition_cac_bulk_operations_duration_seconds_bucket{status=\"200\",tenant=\"test\",le=\"0.1\"} 0"
+        ));
+        assert!(rendered.contains(
+            "superposition_cac_bulk_operations_duration_seconds_bucket{status=\"200\",tenant=\"test\",le=\"0.25\"} 1"
+        ));
+        assert!(rendered.contains(
+            "superposition_cac_bulk_operations_duration_seconds_bucket{status=\"200\",tenant=\"test\",le=\"+Inf\"} 1"
+        ));
+        assert!(rendered.contains(
+            "superposition_cac_bulk_operations_duration_seconds_count{status=\"200\",tenant=\"test\"} 1"
+        ));
+    }
+}
@@ -0,0 +1,82 @@
+use std::future::{ready, Ready};
+
+use crate::service::types::{AppExecutionNamespace, AppState};
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    web::Data,
+    Error, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+
+use std::rc::Rc;
+
+/// Response extension of handlers that committed a change to the config.
+/// Validations, dry runs and replays leave the config as it was, and their
+/// responses do not carry it.
+#[derive(Clone, Copy, Debug)]
+pub struct ConfigChanged;
+
+/// Marks `response` as the response of a committed change to the config.
+pub fn config_changed(mut response: HttpResponse) -> HttpResponse {
+    response.extensions_mut().insert(ConfigChanged);
+    response
+}
+
+/// Wakes requests long-polling for config changes, and drops the resolved
+/// configs cached for the namespace, once a handler commits a change to the
+/// config, as told by the [`ConfigChanged`] extension of its response. Has to
+/// be wrapped inside the app scope middleware, which sets the scope that the
+/// db namespace is derived from.
+pub struct ConfigChangeMiddlewareFactory;
+
+impl<S, B> Transform<S, ServiceRequest> for ConfigChangeMiddlewareFactory
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ConfigChangeMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ConfigChangeMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct ConfigChangeMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for ConfigChangeMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let srv = self.service.clone();
+        let namespace = AppExecutionNamespace::from_request_sync(req.request()).ok();
+        let state = req.app_data::<Data<AppState>>().cloned();
+
+        Box::pin(async move {
+            let res = srv.call(req).await?;
+            let changed = res.response().extensions().contains::<ConfigChanged>();
+            if let (true, Some(namespace), Some(state)) = (changed, namespace, state) {
+                state.config_change_notifier.notify(namespace.as_str());
+                state.resolve_cache.invalidate(namespace.as_str());
+            }
+            Ok(res)
+        })
+    }
+}
@@ -1,2 +1,4 @@
 pub mod app_scope;
+pub mod config_change;
+pub mod request_id;
 pub mod tenant;
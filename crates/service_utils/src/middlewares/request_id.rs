@@ -0,0 +1,136 @@
+use std::future::{ready, Ready};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderName, HeaderValue},
+    Error, HttpMessage,
+};
+use futures_util::future::LocalBoxFuture;
+use std::rc::Rc;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// longer ids sent by clients are replaced, they end up in every log line
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+/// Correlation id of the request being handled, `None` outside of a request.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|request_id| request_id.clone()).ok()
+}
+
+fn request_id_from_header(req: &ServiceRequest) -> Option<String> {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty() && value.len() <= MAX_REQUEST_ID_LENGTH)
+        .map(String::from)
+}
+
+/// Tags every request with a correlation id, taken from the x-request-id header
+/// when the caller sent one. The id is available to log lines and error bodies
+/// while the request is handled and is echoed back in the x-request-id header.
+pub struct RequestIdMiddlewareFactory;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdMiddlewareFactory
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestIdMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestIdMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let srv = self.service.clone();
+        let request_id = request_id_from_header(&req)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        Box::pin(REQUEST_ID.scope(request_id.clone(), async move {
+            req.extensions_mut().insert(RequestId(request_id.clone()));
+            // handler errors are already responses here, only errors raised by
+            // inner middlewares go out without the header
+            let mut res = srv.call(req).await?;
+            if let Ok(header_value) = HeaderValue::from_str(&request_id) {
+                res.headers_mut()
+                    .insert(HeaderName::from_static(REQUEST_ID_HEADER), header_value);
+            }
+            Ok(res)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_request_id_middleware() {
+        let app = actix_web::test::init_service(
+            actix_web::App::new()
+                .wrap(RequestIdMiddlewareFactory)
+                .route(
+                    "/",
+                    actix_web::web::get()
+                        .to(|| async { current_request_id().unwrap_or_default() }),
+                ),
+        )
+        .await;
+
+        // ids sent by the caller are kept for the whole request
+        let req = actix_web::test::TestRequest::get()
+            .uri("/")
+            .insert_header((REQUEST_ID_HEADER, "request-1"))
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        assert_eq!(res.headers().get(REQUEST_ID_HEADER).unwrap(), "request-1");
+        assert_eq!(actix_web::test::read_body(res).await, "request-1");
+
+        let req = actix_web::test::TestRequest::get().uri("/").to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        let generated_id = res
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(!generated_id.is_empty());
+        assert_eq!(actix_web::test::read_body(res).await, generated_id);
+
+        assert!(current_request_id().is_none());
+    }
+}
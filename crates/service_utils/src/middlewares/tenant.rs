@@ -1,6 +1,9 @@
 use std::future::{ready, Ready};
 
-use crate::service::types::{AppState, Tenant};
+use crate::{
+    result::{self, AppError},
+    service::types::{AppState, Tenant},
+};
 use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
     error,
@@ -12,6 +15,8 @@ use futures_util::future::LocalBoxFuture;
 use log::debug;
 use std::rc::Rc;
 
+pub const TENANT_HEADER: &str = "x-tenant";
+
 pub struct TenantMiddlewareFactory;
 impl<S, B> Transform<S, ServiceRequest> for TenantMiddlewareFactory
 where
@@ -36,11 +41,18 @@ pub struct TenantMiddleware<S> {
     service: Rc<S>,
 }
 
-fn extract_tenant_from_header(headers: &HeaderMap) -> Option<&str> {
+// a header that is present but unreadable is an error rather than a miss, so
+// that it never silently falls back to the tenant in the url or query
+fn extract_tenant_from_header(headers: &HeaderMap) -> result::Result<Option<&str>> {
     headers
-        .get("x-tenant")
-        .map(|header_value: &HeaderValue| header_value.to_str().ok())
-        .flatten()
+        .get(TENANT_HEADER)
+        .map(|header_value: &HeaderValue| header_value.to_str())
+        .transpose()
+        .map_err(|_| {
+            AppError::BadArgument(format!(
+                "{TENANT_HEADER} header should be a valid ASCII string"
+            ))
+        })
 }
 
 fn extract_tenant_from_url<'a>(
@@ -65,9 +77,33 @@ fn extract_tenant_from_url<'a>(
 fn extract_tenant_from_query_params(query_str: &str) -> Option<&str> {
     query_str
         .split("&")
-        .find(|segment| segment.contains("tenant="))
-        .map(|tenant_query_param| tenant_query_param.split("=").nth(1))
-        .flatten()
+        .find_map(|segment| segment.strip_prefix("tenant="))
+}
+
+/// Tenant of a request, taken from the `x-tenant` header, else from the
+/// `{tenant}` segment of the matched route, else from the `tenant` query
+/// parameter. Tenants for which `is_served` is false are rejected.
+pub fn resolve_tenant(
+    headers: &HeaderMap,
+    path: &str,
+    match_pattern: Option<String>,
+    query_str: &str,
+    is_served: impl Fn(&str) -> bool,
+) -> result::Result<Tenant> {
+    let tenant = extract_tenant_from_header(headers)?
+        .or_else(|| extract_tenant_from_url(path, match_pattern))
+        .or_else(|| extract_tenant_from_query_params(query_str));
+
+    match tenant {
+        Some(val) if is_served(val) => Ok(Tenant(String::from(val))),
+        Some(val) => {
+            log::error!("request for unknown tenant {val}");
+            Err(AppError::BadArgument(format!(
+                "invalid {TENANT_HEADER} value {val}"
+            )))
+        }
+        None => Err(AppError::BadArgument(format!("{TENANT_HEADER} not set"))),
+    }
 }
 
 impl<S, B> Service<ServiceRequest> for TenantMiddleware<S>
@@ -118,21 +154,14 @@ where
                     extract_tenant_from_query_params(req.query_string())
                 );
 
-                let tenant = extract_tenant_from_header(req.headers())
-                    .or_else(|| extract_tenant_from_url(req.path(), req.match_pattern()))
-                    .or_else(|| extract_tenant_from_query_params(req.query_string()));
-
-                let validated_tenant: Tenant = match tenant {
-                    Some(val) if app_state.tenants.contains(val) => {
-                        Tenant(String::from(val))
-                    }
-                    Some(_) => {
-                        return Err(error::ErrorBadRequest("invalid x-tenant value"));
-                    }
-                    None => {
-                        return Err(error::ErrorBadRequest("x-tenant not set"));
-                    }
-                };
+                // rejected before any handler gets to touch a tenant schema
+                let validated_tenant = resolve_tenant(
+                    req.headers(),
+                    req.path(),
+                    req.match_pattern(),
+                    req.query_string(),
+                    |tenant| app_state.serves_tenant(tenant),
+                )?;
 
                 req.extensions_mut().insert(validated_tenant);
             }
@@ -143,3 +172,78 @@ where
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::HeaderName;
+
+    #[test]
+    fn test_resolve_tenant() {
+        let served = |tenant: &str| ["dev", "test"].contains(&tenant);
+        let headers = |tenant: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(tenant) = tenant {
+                headers.insert(
+                    HeaderName::from_static(TENANT_HEADER),
+                    HeaderValue::from_str(tenant).unwrap(),
+                );
+            }
+            headers
+        };
+        let resolve =
+            |tenant: Option<&str>, path: &str, pattern: Option<&str>, query: &str| {
+                resolve_tenant(
+                    &headers(tenant),
+                    path,
+                    pattern.map(String::from),
+                    query,
+                    served,
+                )
+                .map(|tenant| tenant.0)
+            };
+
+        assert_eq!(
+            resolve(Some("dev"), "/experiments", None, "").unwrap(),
+            "dev"
+        );
+        // the header wins over the url and the query
+        assert_eq!(
+            resolve(
+                Some("dev"),
+                "/test/config",
+                Some("/{tenant}/config"),
+                "tenant=test"
+            )
+            .unwrap(),
+            "dev"
+        );
+        assert_eq!(
+            resolve(None, "/test/config", Some("/{tenant}/config"), "").unwrap(),
+            "test"
+        );
+        assert_eq!(
+            resolve(None, "/config", None, "page=1&tenant=test").unwrap(),
+            "test"
+        );
+        // only the `tenant` parameter itself is read from the query
+        assert!(matches!(
+            resolve(None, "/config", None, "x_tenant=dev"),
+            Err(AppError::BadArgument(msg)) if msg == "x-tenant not set"
+        ));
+        assert!(matches!(
+            resolve(Some("prod"), "/experiments", None, ""),
+            Err(AppError::BadArgument(msg)) if msg.contains("invalid x-tenant value prod")
+        ));
+
+        let mut invalid = HeaderMap::new();
+        invalid.insert(
+            HeaderName::from_static(TENANT_HEADER),
+            HeaderValue::from_bytes(b"d\xe9v").unwrap(),
+        );
+        assert!(matches!(
+            resolve_tenant(&invalid, "/experiments", None, "tenant=dev", served),
+            Err(AppError::BadArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token bucket rate limiter keyed on the tenant. Limits are requests per
+/// minute and also the burst size, a limit of 0 disables rate limiting.
+pub struct RateLimiter {
+    default_limit: u32,
+    // grows as tenants are provisioned at runtime
+    tenant_limits: RwLock<HashMap<String, u32>>,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(default_limit: u32, tenant_limits: HashMap<String, u32>) -> Self {
+        RateLimiter {
+            default_limit,
+            tenant_limits: RwLock::new(tenant_limits),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn default_limit(&self) -> u32 {
+        self.default_limit
+    }
+
+    pub fn limit_for(&self, tenant: &str) -> u32 {
+        self.tenant_limits
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(tenant)
+            .copied()
+            .unwrap_or(self.default_limit)
+    }
+
+    pub fn set_tenant_limit(&self, tenant: &str, limit: u32) {
+        self.tenant_limits
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(tenant.to_string(), limit);
+    }
+
+    /// Takes a token for the tenant, on exhaustion returns how long to wait
+    /// before the next token is available.
+    pub fn try_acquire(&self, tenant: &str) -> Result<(), Duration> {
+        self.try_acquire_at(tenant, Instant::now())
+    }
+
+    pub fn try_acquire_at(&self, tenant: &str, now: Instant) -> Result<(), Duration> {
+        let limit = self.limit_for(tenant);
+        if limit == 0 {
+            return Ok(());
+        }
+        let capacity = limit as f64;
+        let tokens_per_sec = capacity / 60.0;
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry(tenant.to_string())
+            .or_insert_with(|| TokenBucket {
+                tokens: capacity,
+                last_refill: now,
+            });
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens =
+            (bucket.tokens + elapsed.as_secs_f64() * tokens_per_sec).min(capacity);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / tokens_per_sec,
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_per_tenant_token_bucket() {
+        let limiter = RateLimiter::new(2, HashMap::from([("busy".to_string(), 60)]));
+        let start = Instant::now();
+
+        // unconfigured tenants fall back to the default limit
+        assert_eq!(limiter.limit_for("unknown"), 2);
+        assert!(limiter.try_acquire_at("unknown", start).is_ok());
+        assert!(limiter.try_acquire_at("unknown", start).is_ok());
+        let retry_after = limiter
+            .try_acquire_at("unknown", start)
+            .expect_err("bucket should be exhausted");
+        assert!(
+            retry_after > Duration::from_secs(29)
+                && retry_after <= Duration::from_secs(30)
+        );
+
+        // buckets are independent per tenant
+        assert!(limiter.try_acquire_at("busy", start).is_ok());
+
+        // a token is refilled every 30 seconds at 2 per minute
+        assert!(limiter
+            .try_acquire_at("unknown", start + Duration::from_secs(30))
+            .is_ok());
+        assert!(limiter
+            .try_acquire_at("unknown", start + Duration::from_secs(31))
+            .is_err());
+
+        // tenants provisioned at runtime get their own limit
+        limiter.set_tenant_limit("provisioned", 1);
+        assert_eq!(limiter.limit_for("provisioned"), 1);
+        assert!(limiter.try_acquire_at("provisioned", start).is_ok());
+        assert!(limiter.try_acquire_at("provisioned", start).is_err());
+
+        let unlimited = RateLimiter::new(0, HashMap::new());
+        for _ in 0..100 {
+            assert!(unlimited.try_acquire_at("any", start).is_ok());
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::{Map, Value};
+
+/// What a resolved config is cached on: the db namespace of the config, its
+/// version and the request resolved against it. A change to the config moves
+/// its version, so entries of older versions are never served.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResolveCacheKey {
+    pub namespace: String,
+    pub version: String,
+    pub request: String,
+}
+
+impl ResolveCacheKey {
+    /// `variant` tells apart resolutions of the same dimensions that differ
+    /// otherwise, like their merge strategy. Dimensions are canonicalized, so
+    /// the order their keys were sent in does not matter.
+    pub fn new(
+        namespace: &str,
+        version: &str,
+        variant: &str,
+        dimensions: &Map<String, Value>,
+    ) -> Self {
+        ResolveCacheKey {
+            namespace: namespace.to_string(),
+            version: version.to_string(),
+            request: format!(
+                "{variant}|{}",
+                canonical_json(&Value::Object(dimensions.clone()))
+            ),
+        }
+    }
+}
+
+fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(object) => {
+            let fields = object
+                .iter()
+                .collect::<BTreeMap<&String, &Value>>()
+                .into_iter()
+                .map(|(key, value)| {
+                    format!("{}:{}", Value::from(key.as_str()), canonical_json(value))
+                })
+                .collect::<Vec<String>>();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(values) => {
+            let values = values.iter().map(canonical_json).collect::<Vec<String>>();
+            format!("[{}]", values.join(","))
+        }
+        value => value.to_string(),
+    }
+}
+
+struct CacheEntry {
+    value: Map<String, Value>,
+    inserted_at: Instant,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    entries: HashMap<ResolveCacheKey, CacheEntry>,
+    // bumped on every use, the entry with the lowest last_used is evicted
+    clock: u64,
+}
+
+/// Least recently used cache of resolved configs, holding at most `capacity`
+/// of them for up to `ttl` each. A capacity of 0 turns it off.
+pub struct ResolveCache {
+    entries: Mutex<Entries>,
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl ResolveCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        ResolveCache {
+            entries: Mutex::new(Entries::default()),
+            capacity,
+            ttl,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn get(&self, key: &ResolveCacheKey) -> Option<Map<String, Value>> {
+        let mut entries = self.lock();
+        entries.clock += 1;
+        let clock = entries.clock;
+        let entry = entries.entries.get_mut(key)?;
+        if entry.inserted_at.elapsed() >= self.ttl {
+            entries.entries.remove(key);
+            return None;
+        }
+        entry.last_used = clock;
+        Some(entry.value.clone())
+    }
+
+    pub fn insert(&self, key: ResolveCacheKey, value: Map<String, Value>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.lock();
+        if entries.entries.len() >= self.capacity && !entries.entries.contains_key(&key) {
+            let least_recently_used = entries
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(least_recently_used) = least_recently_used {
+                entries.entries.remove(&least_recently_used);
+            }
+        }
+        entries.clock += 1;
+        let last_used = entries.clock;
+        entries.entries.insert(
+            key,
+            CacheEntry {
+                value,
+                inserted_at: Instant::now(),
+                last_used,
+            },
+        );
+    }
+
+    /// Drops every config cached for `namespace`, once its config changed.
+    pub fn invalidate(&self, namespace: &str) {
+        self.lock()
+            .entries
+            .retain(|key, _| key.namespace != namespace);
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_resolve_cache() {
+        let dimensions = |value: Value| value.as_object().unwrap().clone();
+        let key = |namespace: &str, version: &str, value: Value| {
+            ResolveCacheKey::new(namespace, version, "merge|false", &dimensions(value))
+        };
+        // the order dimensions were sent in does not matter
+        assert_eq!(
+            key(
+                "dev_cac",
+                "v1",
+                json!({"os": "ios", "city": {"in": ["a", "b"]}})
+            ),
+            key(
+                "dev_cac",
+                "v1",
+                json!({"city": {"in": ["a", "b"]}, "os": "ios"})
+            )
+        );
+        assert_ne!(
+            key("dev_cac", "v1", json!({"os": "ios"})),
+            key("dev_cac", "v2", json!({"os": "ios"}))
+        );
+
+        let cache = ResolveCache::new(2, Duration::from_secs(60));
+        let resolved = dimensions(json!({"darkMode": true}));
+        cache.insert(key("dev_cac", "v1", json!({"os": "ios"})), resolved.clone());
+        cache.insert(
+            key("dev_cac", "v1", json!({"os": "android"})),
+            resolved.clone(),
+        );
+        assert_eq!(
+            cache.get(&key("dev_cac", "v1", json!({"os": "ios"}))),
+            Some(resolved.clone())
+        );
+        assert_eq!(cache.get(&key("dev_cac", "v2", json!({"os": "ios"}))), None);
+
+        // android is the least recently used, so it makes room for web
+        cache.insert(
+            key("test_cac", "v1", json!({"os": "web"})),
+            resolved.clone(),
+        );
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache.get(&key("dev_cac", "v1", json!({"os": "android"}))),
+            None
+        );
+
+        // a config change drops the entries of its namespace only
+        cache.invalidate("dev_cac");
+        assert_eq!(cache.get(&key("dev_cac", "v1", json!({"os": "ios"}))), None);
+        assert!(cache
+            .get(&key("test_cac", "v1", json!({"os": "web"})))
+            .is_some());
+
+        let expired = ResolveCache::new(2, Duration::ZERO);
+        expired.insert(key("dev_cac", "v1", json!({})), resolved.clone());
+        assert_eq!(expired.get(&key("dev_cac", "v1", json!({}))), None);
+
+        let disabled = ResolveCache::new(0, Duration::from_secs(60));
+        disabled.insert(key("dev_cac", "v1", json!({})), resolved);
+        assert!(disabled.is_empty());
+    }
+}
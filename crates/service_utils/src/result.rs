@@ -5,12 +5,17 @@ use actix_web::{
 };
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use thiserror::Error as this_error;
 
+use crate::middlewares::request_id::current_request_id;
+
 #[derive(this_error)]
 pub enum AppError {
     #[error("validation failed ( `{0}` )")]
     ValidationError(String),
+    #[error("validation failed ( `{}` )", field_errors_message(.0))]
+    InvalidFields(FieldErrors),
     #[error("bad arguments ( `{0}` )")]
     BadArgument(String),
     #[error("not found ( `{0}` )")]
@@ -23,6 +28,45 @@ pub enum AppError {
     UnexpectedError(anyhow::Error),
 }
 
+/// Messages of the fields of a request that failed validation, keyed by the
+/// field name.
+pub type FieldErrors = BTreeMap<String, String>;
+
+fn field_errors_message(field_errors: &FieldErrors) -> String {
+    field_errors
+        .iter()
+        .map(|(field, message)| format!("{field}: {message}"))
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+/// Records the validation error of `field` in `field_errors`, returning the
+/// checked value when there was none. Only the first error of a field is kept
+/// and errors not caused by the request, like database failures, are passed on.
+pub fn check_field<T>(
+    field_errors: &mut FieldErrors,
+    field: &str,
+    checked: Result<T>,
+) -> Result<Option<T>> {
+    match checked {
+        Ok(value) => Ok(Some(value)),
+        Err(AppError::BadArgument(message) | AppError::ValidationError(message)) => {
+            field_errors.entry(field.to_string()).or_insert(message);
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Fails with [`AppError::InvalidFields`] when any field failed validation.
+pub fn field_errors_result(field_errors: FieldErrors) -> Result<()> {
+    if field_errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::InvalidFields(field_errors))
+    }
+}
+
 #[derive(Debug, this_error, Display, Clone)]
 #[display(
     fmt = "server returned an error: {} with status code {}",
@@ -35,17 +79,32 @@ pub struct ResponseError {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Display)]
+#[display(fmt = "{}", message)]
 pub struct ErrorResponse {
     pub message: String,
+    // correlation id of the failed request, to look it up in the logs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    // per field messages of a request that failed validation
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub errors: FieldErrors,
+}
+
+impl ErrorResponse {
+    pub fn new(message: impl Into<String>) -> Self {
+        ErrorResponse {
+            message: message.into(),
+            request_id: current_request_id(),
+            errors: FieldErrors::new(),
+        }
+    }
 }
 
 pub type Result<T> = core::result::Result<T, AppError>;
 
 impl AppError {
     fn generate_err_response(code: StatusCode, msg: &str) -> HttpResponse {
-        let response = ErrorResponse {
-            message: msg.into(),
-        };
+        let response = ErrorResponse::new(msg);
         HttpResponse::build(code)
             .insert_header(ContentType::json())
             .json(response)
@@ -62,6 +121,15 @@ impl error::ResponseError for AppError {
             AppError::ValidationError(msg) | AppError::BadArgument(msg) => {
                 Self::generate_err_response(StatusCode::BAD_REQUEST, msg)
             }
+            AppError::InvalidFields(field_errors) => {
+                let response = ErrorResponse {
+                    errors: field_errors.clone(),
+                    ..ErrorResponse::new(field_errors_message(field_errors))
+                };
+                HttpResponse::build(StatusCode::BAD_REQUEST)
+                    .insert_header(ContentType::json())
+                    .json(response)
+            }
             AppError::NotFound(msg) => {
                 Self::generate_err_response(StatusCode::NOT_FOUND, msg)
             }
@@ -101,6 +169,37 @@ impl error::ResponseError for AppError {
     }
 }
 
+// upstream failures are the fault of the service called, not of the request
+impl From<reqwest::Error> for AppError {
+    fn from(err: reqwest::Error) -> Self {
+        log::error!("http request failed: {err}");
+        let (status_code, message) = if err.is_timeout() {
+            (StatusCode::GATEWAY_TIMEOUT, "upstream service timed out")
+        } else if err.is_builder() {
+            return AppError::UnexpectedError(err.into());
+        } else if err.is_decode() || err.is_body() {
+            (
+                StatusCode::BAD_GATEWAY,
+                "upstream service returned an unreadable response",
+            )
+        } else {
+            (StatusCode::BAD_GATEWAY, "failed to reach upstream service")
+        };
+        AppError::ResponseError(ResponseError {
+            status_code,
+            message: message.to_string(),
+        })
+    }
+}
+
+// request bodies are rejected by the `Json` extractor before handlers run, so
+// json errors in handlers come from stored data and are server errors
+impl From<serde_json::Error> for AppError {
+    fn from(err: serde_json::Error) -> Self {
+        AppError::UnexpectedError(err.into())
+    }
+}
+
 fn error_chain_fmt(
     e: &dyn std::error::Error,
     f: &mut std::fmt::Formatter<'_>,
@@ -119,3 +218,103 @@ impl std::fmt::Debug for AppError {
         error_chain_fmt(self, f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use serde_json::{json, Value};
+
+    #[actix_web::test]
+    async fn test_invalid_fields_response() {
+        let mut field_errors = FieldErrors::new();
+        let checked = check_field(&mut field_errors, "variants", Ok(vec!["key"]));
+        assert_eq!(checked.unwrap(), Some(vec!["key"]));
+        let checked = check_field::<()>(
+            &mut field_errors,
+            "traffic_percentage",
+            Err(AppError::BadArgument("too much traffic".to_string())),
+        );
+        assert_eq!(checked.unwrap(), None);
+        let checked = check_field::<()>(
+            &mut field_errors,
+            "traffic_percentage",
+            Err(AppError::BadArgument("a later error".to_string())),
+        );
+        assert_eq!(checked.unwrap(), None);
+        assert!(matches!(
+            check_field::<()>(
+                &mut field_errors,
+                "context",
+                Err(AppError::DbError(diesel::result::Error::NotFound))
+            ),
+            Err(AppError::DbError(_))
+        ));
+
+        assert!(field_errors_result(FieldErrors::new()).is_ok());
+        let err = field_errors_result(field_errors).unwrap_err();
+        let res = actix_web::ResponseError::error_response(&err);
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body["message"],
+            json!("traffic_percentage: too much traffic")
+        );
+        assert_eq!(
+            body["errors"],
+            json!({"traffic_percentage": "too much traffic"})
+        );
+
+        let err = AppError::BadArgument("single message".to_string());
+        let res = actix_web::ResponseError::error_response(&err);
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert!(body.get("errors").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_app_error_conversions() {
+        let status_of =
+            |err: AppError| actix_web::ResponseError::error_response(&err).status();
+
+        assert_eq!(
+            status_of(diesel::result::Error::NotFound.into()),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status_of(diesel::result::Error::RollbackTransaction.into()),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+
+        let json_err = serde_json::from_str::<Value>("{").unwrap_err();
+        assert!(matches!(
+            AppError::from(json_err),
+            AppError::UnexpectedError(_)
+        ));
+
+        let client = reqwest::Client::new();
+        let builder_err = client.get("not a url").send().await.unwrap_err();
+        assert_eq!(
+            status_of(builder_err.into()),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+
+        let unreadable = reqwest::Response::from(http::Response::new("not json"))
+            .json::<Value>()
+            .await
+            .unwrap_err();
+        assert_eq!(status_of(unreadable.into()), StatusCode::BAD_GATEWAY);
+
+        // the listener queues the connection but never answers it, so the
+        // request can only end by timing out
+        let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let timeout_err = client
+            .get(format!("http://{}", silent.local_addr().unwrap()))
+            .timeout(std::time::Duration::ZERO)
+            .send()
+            .await
+            .unwrap_err();
+        assert_eq!(status_of(timeout_err.into()), StatusCode::GATEWAY_TIMEOUT);
+    }
+}
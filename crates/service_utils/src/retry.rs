@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+// keeps the backoff bounded however many attempts are configured
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Exponential backoff for calls to other services, `max_attempts` includes the
+/// first attempt so 1 disables retries.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, base_delay: Duration) -> Self {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            base_delay,
+        }
+    }
+
+    pub fn no_retry() -> Self {
+        RetryPolicy::new(1, Duration::ZERO)
+    }
+
+    /// Delay before the retry following the given (1 based) failed attempt.
+    pub fn delay_after(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(MAX_RETRY_DELAY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy_backoff() {
+        let policy = RetryPolicy::new(4, Duration::from_millis(100));
+        assert_eq!(policy.max_attempts, 4);
+        assert_eq!(policy.delay_after(1), Duration::from_millis(100));
+        assert_eq!(policy.delay_after(2), Duration::from_millis(200));
+        assert_eq!(policy.delay_after(3), Duration::from_millis(400));
+        // the backoff is capped
+        assert_eq!(policy.delay_after(30), Duration::from_secs(5));
+
+        assert_eq!(RetryPolicy::new(0, Duration::ZERO).max_attempts, 1);
+        assert_eq!(RetryPolicy::no_retry().max_attempts, 1);
+    }
+}
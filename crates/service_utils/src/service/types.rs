@@ -1,6 +1,20 @@
-use crate::db::pgschema_manager::{PgSchemaConnection, PgSchemaManager};
+use crate::admin::AdminToken;
+use crate::config_watch::ConfigChangeNotifier;
+use crate::db::pgschema_manager::{
+    PgSchemaConnection, PgSchemaManager, PoolCheckoutError,
+};
+use crate::db::tenants::is_provisioned_tenant;
+use crate::helpers::{cac_url, experiment_create_rate_limit_of};
+use crate::id_generator::IdGenerator;
+use crate::metrics::Metrics;
+use crate::rate_limiter::RateLimiter;
+use crate::resolve_cache::ResolveCache;
+use crate::retry::RetryPolicy;
+use crate::webhook::{WebhookConfig, Webhooks};
 use derive_more::{Deref, DerefMut};
+use diesel::r2d2::PoolError;
 use jsonschema::JSONSchema;
+use reqwest::Url;
 use serde_json::json;
 
 use std::{
@@ -9,10 +23,10 @@ use std::{
     str::FromStr,
 };
 
-use actix_web::{error, web::Data, Error, FromRequest, HttpMessage};
+use crate::result::{self, AppError, ErrorResponse};
+use actix_web::{error, web::Data, Error, FromRequest, HttpMessage, HttpResponse};
 
-use snowflake::SnowflakeIdGenerator;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
 
 pub struct ExperimentationFlags {
     pub allow_same_keys_overlapping_ctx: bool,
@@ -29,20 +43,129 @@ pub enum AppEnv {
 }
 
 pub struct AppState {
-    pub cac_host: String,
+    // always ends with a `/`, endpoints are joined onto it with `cac_url`
+    pub cac_host: Url,
     pub app_env: AppEnv,
-    pub tenants: HashSet<String>,
+    // tenants being served, grows as tenants are provisioned, shared by all workers
+    pub tenants: Arc<RwLock<HashSet<String>>>,
     pub cac_version: String,
     pub db_pool: PgSchemaManager,
     pub default_config_validation_schema: JSONSchema,
     pub meta_schema: JSONSchema,
     pub experimentation_flags: ExperimentationFlags,
-    pub snowflake_generator: Mutex<SnowflakeIdGenerator>,
+    // experiment ids, shared by all workers
+    pub id_generator: Arc<dyn IdGenerator>,
+    pub http_client: reqwest::Client,
+    // retries of idempotent CAC bulk operations
+    pub cac_retry_policy: RetryPolicy,
+    pub metrics: Arc<Metrics>,
+    pub experiment_create_rate_limiter: Arc<RateLimiter>,
+    // tenants whose CAC is known to have the experiment variant dimension
+    pub variant_dimension_tenants: Arc<Mutex<HashSet<String>>>,
+    // notified of experiment lifecycle events
+    pub webhooks: Arc<Webhooks>,
+    // wakes requests long-polling for config changes, shared by all workers
+    pub config_change_notifier: Arc<ConfigChangeNotifier>,
+    // configs resolved for a set of dimensions, shared by all workers
+    pub resolve_cache: Arc<ResolveCache>,
+    // upper bound of the page size of listing endpoints
+    pub max_page_size: i64,
+    // total traffic of experiments created without a traffic_percentage
+    pub default_traffic_percentage: i64,
+    // every variant of an experiment gets its own CAC context
+    pub max_variants_per_experiment: usize,
+    // bearer token of admin endpoints, rotated at runtime and shared by
+    // every server through the database
+    pub admin_token: Arc<AdminToken>,
     pub enable_tenant_and_scope: bool,
     pub tenant_middleware_exclusion_list: HashSet<String>,
     pub service_prefix: String,
 }
 
+impl AppState {
+    pub fn has_tenant(&self, tenant: &str) -> bool {
+        self.tenants
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(tenant)
+    }
+
+    pub fn admin_token(&self) -> Option<String> {
+        self.admin_token.current(|| self.db_pool.establish())
+    }
+
+    /// URL of the CAC endpoint at `path`, like `context/bulk-operations`,
+    /// under the path prefix of the CAC host.
+    pub fn cac_url(&self, path: &str) -> Url {
+        cac_url(&self.cac_host, path)
+    }
+
+    /// Starts serving `tenant`, connecting to its schemas and applying its
+    /// settings from the environment. Tenants provisioned at runtime, by this
+    /// server or another one, are served like the ones in `TENANTS`.
+    pub fn register_tenant(&self, tenant: &str) -> Result<(), PoolError> {
+        for scope in [AppScope::CAC, AppScope::EXPERIMENTATION] {
+            let namespace = format!("{tenant}_{scope}");
+            if !self.db_pool.has_namespace(&namespace) {
+                self.db_pool.add_namespace(&namespace)?;
+            }
+        }
+        let rate_limit = experiment_create_rate_limit_of(
+            tenant,
+            self.experiment_create_rate_limiter.default_limit(),
+        );
+        self.experiment_create_rate_limiter
+            .set_tenant_limit(tenant, rate_limit);
+        if let Some(webhook) =
+            WebhookConfig::from_env(&format!("_{}", tenant.to_uppercase()))
+        {
+            self.webhooks.set_tenant_webhook(tenant, webhook);
+        }
+        self.tenants
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(tenant.to_string());
+        Ok(())
+    }
+
+    /// Whether `tenant` is served. A tenant this server does not know of yet
+    /// is looked up among the provisioned tenants, and registered when found.
+    pub fn serves_tenant(&self, tenant: &str) -> bool {
+        if self.has_tenant(tenant) {
+            return true;
+        }
+        let provisioned = self
+            .db_pool
+            .establish()
+            .map_err(|err| err.to_string())
+            .and_then(|mut conn| {
+                is_provisioned_tenant(&mut conn, tenant).map_err(|err| err.to_string())
+            });
+        match provisioned {
+            Ok(true) => match self.register_tenant(tenant) {
+                Ok(()) => {
+                    log::info!("serving tenant {tenant} provisioned by another server");
+                    true
+                }
+                Err(err) => {
+                    log::error!("failed to connect to the schemas of {tenant}: {err}");
+                    false
+                }
+            },
+            Ok(false) => false,
+            Err(err) => {
+                log::error!("failed to look up tenant {tenant}: {err}");
+                false
+            }
+        }
+    }
+
+    pub fn tenant_list(&self) -> Vec<String> {
+        let tenants = self.tenants.read().unwrap_or_else(PoisonError::into_inner);
+        tenants.iter().cloned().collect()
+    }
+}
+
 impl FromStr for AppEnv {
     type Err = String;
     fn from_str(val: &str) -> Result<AppEnv, Self::Err> {
@@ -94,30 +217,38 @@ impl AppExecutionNamespace {
         let tenant = req.extensions().get::<Tenant>().cloned();
         let scope = req.extensions().get::<AppScope>().cloned();
 
-        match (
-            app_state.enable_tenant_and_scope,
-            app_state.app_env,
-            tenant,
-            scope,
-        ) {
-            (false, _, _, _) => Ok(AppExecutionNamespace("cac_v1".to_string())),
-            (true, _, Some(t), Some(s)) => Ok(AppExecutionNamespace(format!(
-                "{}_{}",
-                t.as_str(),
-                s.to_string()
-            ))),
-            (true, _, None, _) => {
-                log::error!(
-                    "get_app_execution_namespace: Tenant not set in request extensions"
-                );
-                Err(error::ErrorInternalServerError(""))
-            }
-            (true, _, _, None) => {
-                log::error!(
-                    "get_app_execution_namespace: AppScope not set in request extensions"
-                );
-                Err(error::ErrorInternalServerError(""))
-            }
+        execution_namespace(app_state.enable_tenant_and_scope, tenant.as_ref(), scope)
+            .map(AppExecutionNamespace)
+            .map_err(Error::from)
+    }
+}
+
+/// Schema that the queries of a request run in, `<tenant>_<scope>` so that
+/// tenants never read each other's data. A request without a tenant is
+/// rejected rather than served from a shared schema.
+pub fn execution_namespace(
+    enable_tenant_and_scope: bool,
+    tenant: Option<&Tenant>,
+    scope: Option<AppScope>,
+) -> result::Result<String> {
+    match (enable_tenant_and_scope, tenant, scope) {
+        (false, _, _) => Ok("cac_v1".to_string()),
+        (true, Some(t), Some(s)) => Ok(format!("{}_{}", t.as_str(), s)),
+        (true, None, _) => {
+            log::error!(
+                "get_app_execution_namespace: Tenant not set in request extensions"
+            );
+            Err(AppError::BadArgument(String::from(
+                "tenant was not set. Please ensure you are passing in the x-tenant header",
+            )))
+        }
+        (true, _, None) => {
+            log::error!(
+                "get_app_execution_namespace: AppScope not set in request extensions"
+            );
+            Err(AppError::UnexpectedError(anyhow::anyhow!(
+                "app scope not set in request extensions"
+            )))
         }
     }
 }
@@ -158,9 +289,10 @@ impl FromRequest for Tenant {
                     }
                 };
                 if app_state.enable_tenant_and_scope {
-                    Err(error::ErrorInternalServerError(json!({
-                        "message": "tenant was not set. Please ensure you are passing in the x-tenant header"
-                    })))
+                    Err(AppError::BadArgument(String::from(
+                        "tenant was not set. Please ensure you are passing in the x-tenant header",
+                    ))
+                    .into())
                 } else {
                     Ok(Tenant("mjos".into()))
                 }
@@ -199,12 +331,60 @@ impl FromRequest for DbConnection {
 
         let result = match app_state.db_pool.get_conn(namespace) {
             Ok(conn) => Ok(DbConnection(conn)),
+            Err(e @ PoolCheckoutError::Exhausted(..)) => {
+                log::error!("{e}");
+                let response = HttpResponse::ServiceUnavailable()
+                    .insert_header(("Retry-After", "1"))
+                    .json(ErrorResponse::new(
+                        "Too many requests in flight, retry after some time",
+                    ));
+                Err(error::InternalError::from_response(e, response).into())
+            }
             Err(e) => {
-                log::info!("Unable to get db connection from pool, error: {e}");
-                Err(error::ErrorInternalServerError(""))
+                log::error!("Unable to get db connection from pool, error: {e}");
+                let response = HttpResponse::InternalServerError()
+                    .json(ErrorResponse::new("Could not connect to the database"));
+                Err(error::InternalError::from_response(e, response).into())
             }
         };
 
         ready(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+
+    #[test]
+    fn test_execution_namespace_per_tenant() {
+        let namespace = |tenant: Option<&str>| {
+            execution_namespace(
+                true,
+                tenant.map(|tenant| Tenant(tenant.to_string())).as_ref(),
+                Some(AppScope::EXPERIMENTATION),
+            )
+        };
+        let dev = namespace(Some("dev")).unwrap();
+        let test = namespace(Some("test")).unwrap();
+        assert_eq!(dev, "dev_experimentation");
+        assert_eq!(test, "test_experimentation");
+        assert_ne!(dev, test);
+
+        let missing = namespace(None).unwrap_err();
+        assert!(matches!(missing, AppError::BadArgument(_)));
+        assert_eq!(
+            actix_web::ResponseError::error_response(&missing).status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert!(matches!(
+            execution_namespace(true, Some(&Tenant("dev".to_string())), None),
+            Err(AppError::UnexpectedError(_))
+        ));
+        assert_eq!(
+            execution_namespace(false, None, None).unwrap(),
+            "cac_v1".to_string()
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::sync::{PoisonError, RwLock};
+use std::time::Duration;
+
+use hmac::{Hmac, Mac, NewMac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::helpers::get_from_env_unsafe;
+
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "x-superposition-signature";
+
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    pub url: String,
+    // deliveries are signed only when a secret is configured
+    pub secret: Option<String>,
+}
+
+impl WebhookConfig {
+    /// Webhook of `EXPERIMENT_WEBHOOK_URL<suffix>`, signed with
+    /// `EXPERIMENT_WEBHOOK_SECRET<suffix>` when it is set. The suffix is empty
+    /// for the default webhook and `_<TENANT>` for the one of a tenant.
+    pub fn from_env(suffix: &str) -> Option<WebhookConfig> {
+        let non_empty_env = |name: String| {
+            get_from_env_unsafe::<String>(&name)
+                .ok()
+                .filter(|value| !value.is_empty())
+        };
+        non_empty_env(format!("EXPERIMENT_WEBHOOK_URL{suffix}")).map(|url| {
+            WebhookConfig {
+                url,
+                secret: non_empty_env(format!("EXPERIMENT_WEBHOOK_SECRET{suffix}")),
+            }
+        })
+    }
+}
+
+/// Webhook endpoints keyed on the tenant, tenants without one of their own
+/// use the default, no webhook is called when neither is configured.
+pub struct Webhooks {
+    default: Option<WebhookConfig>,
+    // grows as tenants are provisioned at runtime
+    tenant_webhooks: RwLock<HashMap<String, WebhookConfig>>,
+    timeout: Duration,
+}
+
+impl Webhooks {
+    pub fn new(
+        default: Option<WebhookConfig>,
+        tenant_webhooks: HashMap<String, WebhookConfig>,
+        timeout: Duration,
+    ) -> Self {
+        Webhooks {
+            default,
+            tenant_webhooks: RwLock::new(tenant_webhooks),
+            timeout,
+        }
+    }
+
+    pub fn webhook_for(&self, tenant: &str) -> Option<WebhookConfig> {
+        self.tenant_webhooks
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(tenant)
+            .or(self.default.as_ref())
+            .cloned()
+    }
+
+    pub fn set_tenant_webhook(&self, tenant: &str, webhook: WebhookConfig) {
+        self.tenant_webhooks
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(tenant.to_string(), webhook);
+    }
+
+    /// Posts `payload` to the tenant's webhook in the background. Delivery is
+    /// best effort: it is not retried, and failures are only logged.
+    pub fn notify<T: Serialize>(
+        &self,
+        client: &reqwest::Client,
+        tenant: &str,
+        payload: &T,
+    ) {
+        let Some(webhook) = self.webhook_for(tenant) else {
+            return;
+        };
+        let body = match serde_json::to_vec(payload) {
+            Ok(body) => body,
+            Err(err) => {
+                log::error!("failed to serialize webhook payload: {err}");
+                return;
+            }
+        };
+
+        let mut request = client
+            .post(&webhook.url)
+            .timeout(self.timeout)
+            .header("content-type", "application/json");
+        if let Some(secret) = &webhook.secret {
+            request =
+                request.header(WEBHOOK_SIGNATURE_HEADER, sign_payload(secret, &body));
+        }
+        let url = webhook.url.clone();
+        let tenant = tenant.to_string();
+        actix_web::rt::spawn(async move {
+            match request.body(body).send().await {
+                Ok(res) if res.status().is_success() => (),
+                Ok(res) => log::error!(
+                    "webhook {url} of tenant {tenant} responded with status {}",
+                    res.status()
+                ),
+                Err(err) => {
+                    log::error!("failed to call webhook {url} of tenant {tenant}: {err}")
+                }
+            }
+        });
+    }
+}
+
+/// HMAC-SHA256 of the request body keyed on the webhook secret, as
+/// `sha256=<hex digest>`. Receivers recompute it over the raw body to check
+/// that a delivery came from this server.
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("hmac accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_signature() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign_payload("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_webhook_for_tenant() {
+        let webhook = |url: &str| WebhookConfig {
+            url: url.to_string(),
+            secret: None,
+        };
+        let webhooks = Webhooks::new(
+            Some(webhook("https://hooks.example.com/default")),
+            HashMap::from([(
+                "dev".to_string(),
+                webhook("https://hooks.example.com/dev"),
+            )]),
+            Duration::from_secs(2),
+        );
+        let url_for = |tenant: &str| webhooks.webhook_for(tenant).map(|w| w.url.clone());
+        assert_eq!(
+            url_for("dev").as_deref(),
+            Some("https://hooks.example.com/dev")
+        );
+        assert_eq!(
+            url_for("test").as_deref(),
+            Some("https://hooks.example.com/default")
+        );
+
+        let no_default = Webhooks::new(None, HashMap::new(), Duration::from_secs(2));
+        assert!(no_default.webhook_for("dev").is_none());
+    }
+}
@@ -1,17 +1,28 @@
 use actix_web::dev::Service;
 use actix_web::HttpMessage;
-use actix_web::{web, web::get, web::scope, web::Data, App, HttpResponse, HttpServer};
+use actix_web::{
+    web, web::get, web::put, web::scope, web::Data, App, HttpResponse, HttpServer,
+};
 use context_aware_config::api::*;
 use context_aware_config::helpers::{
     get_default_config_validation_schema, get_meta_schema,
 };
+use context_aware_config::middlewares::cookie_to_header::CookieToHeader;
 use dotenv;
-use experimentation_platform::api::*;
-use std::{collections::HashSet, io::Result};
+use experimentation_platform::api::{
+    experiments::orphan_contexts::spawn_orphan_context_cleanup,
+    experiments::scheduler::spawn_experiment_scheduler, *,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Result,
+};
 use superposition_types::User;
 
-use snowflake::SnowflakeIdGenerator;
-use std::{sync::Mutex, time::Duration};
+use std::{
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
 
 use actix_files::Files;
 use frontend::app::*;
@@ -19,13 +30,30 @@ use frontend::types::Envs as UIEnvs;
 use leptos::*;
 use leptos_actix::{generate_route_list, LeptosRoutes};
 use service_utils::{
+    admin::{rotate_admin_token_handler, AdminToken},
+    config_watch::ConfigChangeNotifier,
+    db::admin_token::ensure_admin_token_table,
     db::pgschema_manager::PgSchemaManager,
+    db::tenants::{ensure_tenants_table, load_provisioned_tenants},
     db::utils::init_pool_manager,
-    helpers::{get_from_env_or_default, get_from_env_unsafe},
+    health::{liveness_handler, readiness_handler},
+    helpers::{
+        experiment_create_rate_limit_of, get_from_env_or_default, get_from_env_unsafe,
+        json_payload_config, parse_cac_host, DEFAULT_MAX_BULK_PAYLOAD_BYTES,
+    },
+    id_generator::{new_id_generator, IdGeneratorKind},
+    logger::init_json_logger,
+    metrics::{metrics_handler, Metrics},
     middlewares::{
-        app_scope::AppExecutionScopeMiddlewareFactory, tenant::TenantMiddlewareFactory,
+        app_scope::AppExecutionScopeMiddlewareFactory,
+        config_change::ConfigChangeMiddlewareFactory,
+        request_id::RequestIdMiddlewareFactory, tenant::TenantMiddlewareFactory,
     },
+    rate_limiter::RateLimiter,
+    resolve_cache::ResolveCache,
+    retry::RetryPolicy,
     service::types::{AppEnv, AppScope, AppState, ExperimentationFlags},
+    webhook::{WebhookConfig, Webhooks},
 };
 
 #[actix_web::get("favicon.ico")]
@@ -42,7 +70,7 @@ async fn favicon(
 #[actix_web::main]
 async fn main() -> Result<()> {
     dotenv::dotenv().ok();
-    env_logger::init();
+    init_json_logger();
     let service_prefix: String =
         get_from_env_unsafe("SERVICE_PREFIX").expect("SERVICE_PREFIX is not set");
 
@@ -64,27 +92,37 @@ async fn main() -> Result<()> {
     };
 
     let cac_host: String = get_from_env_unsafe("CAC_HOST").expect("CAC host is not set");
-    let cac_port: u16 = get_from_env_unsafe("PORT").unwrap_or(8080);
+    // CAC is served by this same server, under the service prefix. Whether it
+    // is up is left to the readiness check, it cannot answer before this starts
+    let cac_host = parse_cac_host(&format!("{}{}", cac_host.trim_end_matches('/'), base))
+        .unwrap_or_else(|err| panic!("CAC_HOST is invalid: {err}"));
+    let cac_port: u16 = get_from_env_or_default("PORT", 8080);
     let cac_version: String = get_from_env_unsafe("CONTEXT_AWARE_CONFIG_VERSION")
         .expect("CONTEXT_AWARE_CONFIG_VERSION is not set");
     let max_pool_size = get_from_env_or_default("MAX_DB_CONNECTION_POOL_SIZE", 2);
+    // how long a request waits for a free db connection before getting a 503
+    let db_pool_timeout =
+        Duration::from_secs(get_from_env_or_default("DB_POOL_TIMEOUT_SECS", 30));
 
     let api_host: String =
         get_from_env_unsafe("API_HOSTNAME").expect("API_HOSTNAME is not set");
     let app_env: AppEnv = get_from_env_unsafe("APP_ENV").expect("APP_ENV is not set");
     let enable_tenant_and_scope: bool = get_from_env_unsafe("ENABLE_TENANT_AND_SCOPE")
         .expect("ENABLE_TENANT_AND_SCOPE is not set");
-    let tenants: HashSet<String> = get_from_env_unsafe::<String>("TENANTS")
+    let mut tenants: HashSet<String> = get_from_env_unsafe::<String>("TENANTS")
         .expect("TENANTS is not set")
         .split(",")
         .map(|tenant| tenant.to_string())
         .collect::<HashSet<String>>();
-    let tenant_middleware_exclusion_list =
+    let mut tenant_middleware_exclusion_list =
         get_from_env_unsafe::<String>("TENANT_MIDDLEWARE_EXCLUSION_LIST")
             .expect("TENANT_MIDDLEWARE_EXCLUSION_LIST is not set")
             .split(",")
             .map(String::from)
             .collect::<HashSet<String>>();
+    // tenants are provisioned before they exist
+    tenant_middleware_exclusion_list.insert(String::from("/tenants"));
+    tenant_middleware_exclusion_list.insert(String::from("/admin-token"));
 
     let string_to_int = |s: &String| -> i32 {
         s.chars()
@@ -97,9 +135,34 @@ async fn main() -> Result<()> {
         enable_tenant_and_scope,
         app_env,
         max_pool_size,
+        db_pool_timeout,
     )
     .await;
 
+    let mut conn = schema_manager
+        .establish()
+        .expect("failed to connect to the database to set up shared tables");
+    ensure_admin_token_table(&mut conn).expect("failed to create the admin token table");
+    // tenants provisioned through `POST /tenants` are served next to TENANTS
+    if enable_tenant_and_scope {
+        ensure_tenants_table(&mut conn).expect("failed to create the tenants table");
+        let provisioned = load_provisioned_tenants(&mut conn)
+            .expect("failed to load provisioned tenants");
+        for tenant in provisioned.difference(&tenants) {
+            for scope in [AppScope::CAC, AppScope::EXPERIMENTATION] {
+                let namespace = format!("{tenant}_{scope}");
+                schema_manager
+                    .add_namespace(&namespace)
+                    .unwrap_or_else(|err| {
+                        panic!(
+                            "failed to connect to provisioned tenant {namespace}: {err}"
+                        )
+                    });
+            }
+        }
+        tenants.extend(provisioned);
+    }
+
     /****** EXPERIMENTATION PLATFORM ENVs *********/
 
     let allow_same_keys_overlapping_ctx: bool =
@@ -112,6 +175,89 @@ async fn main() -> Result<()> {
         get_from_env_unsafe("ALLOW_SAME_KEYS_NON_OVERLAPPING_CTX")
             .expect("ALLOW_SAME_KEYS_NON_OVERLAPPING_CTX not set");
 
+    let cac_request_timeout_secs: u64 =
+        get_from_env_or_default("CAC_REQUEST_TIMEOUT_SECS", 10);
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(cac_request_timeout_secs))
+        .build()
+        .expect("failed to build http client for CAC requests");
+    let cac_retry_policy = RetryPolicy::new(
+        get_from_env_or_default("CAC_RETRY_MAX_ATTEMPTS", 3),
+        Duration::from_millis(get_from_env_or_default("CAC_RETRY_BASE_DELAY_MS", 100)),
+    );
+
+    // experiment creations per minute, overridable per tenant through
+    // EXPERIMENT_CREATE_RATE_LIMIT_<TENANT>
+    let experiment_create_rate_limit: u32 =
+        get_from_env_or_default("EXPERIMENT_CREATE_RATE_LIMIT", 60);
+    let experiment_create_tenant_limits = tenants
+        .iter()
+        .map(|tenant| {
+            (
+                tenant.to_owned(),
+                experiment_create_rate_limit_of(tenant, experiment_create_rate_limit),
+            )
+        })
+        .collect::<HashMap<String, u32>>();
+    let experiment_create_rate_limiter = Arc::new(RateLimiter::new(
+        experiment_create_rate_limit,
+        experiment_create_tenant_limits,
+    ));
+
+    // snowflake (default) or ulid, see docs/experimentation.md before switching
+    let id_generator_kind: IdGeneratorKind =
+        get_from_env_or_default("EXPERIMENT_ID_GENERATOR", IdGeneratorKind::Snowflake);
+    let id_generator = new_id_generator(id_generator_kind);
+
+    // experiment lifecycle webhooks, EXPERIMENT_WEBHOOK_URL_<TENANT> and
+    // EXPERIMENT_WEBHOOK_SECRET_<TENANT> override the defaults for a tenant
+    let tenant_webhooks = tenants
+        .iter()
+        .filter_map(|tenant| {
+            WebhookConfig::from_env(&format!("_{}", tenant.to_uppercase()))
+                .map(|webhook| (tenant.to_string(), webhook))
+        })
+        .collect::<HashMap<String, WebhookConfig>>();
+    let webhooks = Arc::new(Webhooks::new(
+        WebhookConfig::from_env(""),
+        tenant_webhooks,
+        Duration::from_millis(get_from_env_or_default(
+            "EXPERIMENT_WEBHOOK_TIMEOUT_MS",
+            2000,
+        )),
+    ));
+
+    let max_page_size: i64 = get_from_env_or_default("MAX_PAGE_SIZE", 100);
+    let max_variants_per_experiment: usize =
+        get_from_env_or_default("MAX_VARIANTS_PER_EXPERIMENT", 10);
+    let default_traffic_percentage: i64 =
+        get_from_env_or_default("EXPERIMENT_DEFAULT_TRAFFIC_PERCENTAGE", 0);
+    let default_traffic_percentage = if (0..=100).contains(&default_traffic_percentage) {
+        default_traffic_percentage
+    } else {
+        log::warn!(
+            "EXPERIMENT_DEFAULT_TRAFFIC_PERCENTAGE has to be between 0 and 100, provided {default_traffic_percentage}, using 0"
+        );
+        0
+    };
+    // bulk operations of large experiments make for large bodies
+    let max_bulk_payload_bytes: usize =
+        get_from_env_or_default("MAX_BULK_PAYLOAD_BYTES", DEFAULT_MAX_BULK_PAYLOAD_BYTES);
+    let admin_token = Arc::new(AdminToken::new(get_from_env_unsafe("ADMIN_TOKEN").ok()));
+    // grows as tenants are provisioned at runtime
+    let tenant_registry = Arc::new(RwLock::new(tenants.clone()));
+    let config_change_notifier = Arc::new(ConfigChangeNotifier::new(
+        get_from_env_or_default("CONFIG_LONG_POLL_MAX_WAITERS", 1000),
+    ));
+    // resolve is the hot path of SDKs, 0 turns the cache off
+    let resolve_cache = Arc::new(ResolveCache::new(
+        get_from_env_or_default("RESOLVE_CACHE_SIZE", 1000),
+        Duration::from_secs(get_from_env_or_default("RESOLVE_CACHE_TTL_SECS", 300)),
+    ));
+
+    // filled lazily on the first experiment created for a tenant
+    let variant_dimension_tenants = Arc::new(Mutex::new(HashSet::new()));
+
     /****** EXPERIMENTATION PLATFORM ENVs *********/
 
     /* Frontend configurations */
@@ -134,11 +280,77 @@ async fn main() -> Result<()> {
         return view! { <App app_envs=routes_ui_envs.clone()/> };
     });
 
+    // shared by all workers so that /metrics reports the whole server
+    let metrics = Arc::new(Metrics::default());
+
+    // every worker gets its own state, anything shared between them is an Arc
+    let new_app_state = {
+        let cac_version = cac_version.clone();
+        move || AppState {
+            db_pool: schema_manager.clone(),
+            default_config_validation_schema: get_default_config_validation_schema(),
+            cac_host: cac_host.to_owned(),
+            cac_version: cac_version.to_owned(),
+
+            experimentation_flags: ExperimentationFlags {
+                allow_same_keys_overlapping_ctx: allow_same_keys_overlapping_ctx
+                    .to_owned(),
+                allow_diff_keys_overlapping_ctx: allow_diff_keys_overlapping_ctx
+                    .to_owned(),
+                allow_same_keys_non_overlapping_ctx: allow_same_keys_non_overlapping_ctx
+                    .to_owned(),
+            },
+
+            id_generator: id_generator.clone(),
+            http_client: http_client.clone(),
+            cac_retry_policy,
+            metrics: metrics.clone(),
+            experiment_create_rate_limiter: experiment_create_rate_limiter.clone(),
+            variant_dimension_tenants: variant_dimension_tenants.clone(),
+            webhooks: webhooks.clone(),
+            max_page_size,
+            default_traffic_percentage,
+            max_variants_per_experiment,
+            admin_token: admin_token.clone(),
+            config_change_notifier: config_change_notifier.clone(),
+            resolve_cache: resolve_cache.clone(),
+            meta_schema: get_meta_schema(),
+            app_env: app_env.to_owned(),
+            enable_tenant_and_scope: enable_tenant_and_scope.to_owned(),
+            tenants: tenant_registry.clone(),
+            tenant_middleware_exclusion_list: tenant_middleware_exclusion_list.to_owned(),
+            service_prefix: service_prefix_str.to_owned(),
+        }
+    };
+
+    // starts and pauses experiments on their schedule, 0 turns it off
+    let scheduler_interval_secs: u64 =
+        get_from_env_or_default("EXPERIMENT_SCHEDULER_INTERVAL_SECS", 60);
+    if scheduler_interval_secs > 0 {
+        spawn_experiment_scheduler(
+            Data::new(new_app_state()),
+            Duration::from_secs(scheduler_interval_secs),
+        );
+    }
+
+    // removes variant contexts of missing or concluded experiments from CAC,
+    // 0 turns it off and dry run only logs what would be removed
+    let orphan_cleanup_interval_secs: u64 =
+        get_from_env_or_default("ORPHAN_CONTEXT_CLEANUP_INTERVAL_SECS", 3600);
+    let orphan_cleanup_dry_run: bool =
+        get_from_env_or_default("ORPHAN_CONTEXT_CLEANUP_DRY_RUN", true);
+    if orphan_cleanup_interval_secs > 0 {
+        spawn_orphan_context_cleanup(
+            Data::new(new_app_state()),
+            Duration::from_secs(orphan_cleanup_interval_secs),
+            orphan_cleanup_dry_run,
+        );
+    }
+
     HttpServer::new(move || {
         let leptos_options = &conf.leptos_options;
         let site_root = &leptos_options.site_root;
         let leptos_envs = ui_envs.clone();
-        let cac_host = cac_host.to_owned() + base.as_str();
         App::new()
             .wrap_fn(|req, srv| {
                 let user = User::default();
@@ -146,34 +358,12 @@ async fn main() -> Result<()> {
                 srv.call(req)
             })
             .wrap(TenantMiddlewareFactory)
-            .app_data(Data::new(AppState {
-                db_pool: schema_manager.clone(),
-                default_config_validation_schema: get_default_config_validation_schema(),
-                cac_host: cac_host.to_owned(),
-                cac_version: cac_version.to_owned(),
-
-                experimentation_flags: ExperimentationFlags {
-                    allow_same_keys_overlapping_ctx: allow_same_keys_overlapping_ctx
-                        .to_owned(),
-                    allow_diff_keys_overlapping_ctx: allow_diff_keys_overlapping_ctx
-                        .to_owned(),
-                    allow_same_keys_non_overlapping_ctx:
-                        allow_same_keys_non_overlapping_ctx.to_owned(),
-                },
-
-                snowflake_generator: Mutex::new(SnowflakeIdGenerator::new(1,1)),
-                meta_schema: get_meta_schema(),
-                app_env: app_env.to_owned(),
-                enable_tenant_and_scope: enable_tenant_and_scope.to_owned(),
-                tenants: tenants.to_owned(),
-                tenant_middleware_exclusion_list: tenant_middleware_exclusion_list
-                    .to_owned(),
-                service_prefix: service_prefix_str.to_owned(),
-            }))
+            .app_data(Data::new(new_app_state()))
             .wrap(
                 actix_web::middleware::DefaultHeaders::new()
                     .add(("X-SERVER-VERSION", cac_version.to_string()))
             )
+            .wrap(RequestIdMiddlewareFactory)
             .service(web::redirect("/", ui_redirect_path.to_string()))
             .leptos_routes(
                 leptos_options.to_owned(),
@@ -182,23 +372,31 @@ async fn main() -> Result<()> {
             )
             .service(
                 scope(&base)
+                    // the UI calls the API with the `token` cookie of the signed in user
+                    .wrap(CookieToHeader)
                     .route(
                         "/health",
                         get().to(|| async { HttpResponse::Ok().body("Health is good :D") }),
                     )
+                    .route("/health/live", get().to(liveness_handler))
+                    .route("/health/ready", get().to(readiness_handler))
                     /***************************** V1 Routes *****************************/
                     .service(
                         scope("/context")
+                            .app_data(json_payload_config(max_bulk_payload_bytes))
+                            .wrap(ConfigChangeMiddlewareFactory)
                             .wrap(AppExecutionScopeMiddlewareFactory::new(AppScope::CAC))
                             .service(context::endpoints()),
                     )
                     .service(
                         scope("/dimension")
+                            .wrap(ConfigChangeMiddlewareFactory)
                             .wrap(AppExecutionScopeMiddlewareFactory::new(AppScope::CAC))
                             .service(dimension::endpoints()),
                     )
                     .service(
                         scope("/default-config")
+                            .wrap(ConfigChangeMiddlewareFactory)
                             .wrap(AppExecutionScopeMiddlewareFactory::new(AppScope::CAC))
                             .service(default_config::endpoints()),
                     )
@@ -217,8 +415,14 @@ async fn main() -> Result<()> {
                             .wrap(AppExecutionScopeMiddlewareFactory::new(AppScope::CAC))
                             .service(functions::endpoints()),
                     )
+                    .service(scope("/tenants").service(tenant::endpoints()))
+                    .route("/admin-token", put().to(rotate_admin_token_handler))
                     .service(
-                        experiments::endpoints(scope("/experiments")).wrap(
+                        experiments::endpoints(
+                            scope("/experiments")
+                                .app_data(json_payload_config(max_bulk_payload_bytes)),
+                        )
+                        .wrap(
                             AppExecutionScopeMiddlewareFactory::new(AppScope::EXPERIMENTATION),
                         ),
                     )
@@ -234,6 +438,9 @@ async fn main() -> Result<()> {
                 "/health",
                 get().to(|| async { HttpResponse::Ok().body("Health is good :D") }),
             )
+            .route("/health/live", get().to(liveness_handler))
+            .route("/health/ready", get().to(readiness_handler))
+            .route("/metrics", get().to(metrics_handler))
             .app_data(Data::new(leptos_options.to_owned()))
     })
     .bind(("0.0.0.0", cac_port))?